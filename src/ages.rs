@@ -0,0 +1,84 @@
+use crate::providers::github::Snapshot;
+
+/// BUCKETS are the upper bounds in days of the age buckets but the last one,
+/// which holds the older issues.
+pub const BUCKETS: [i64; 3] = [7, 30, 90];
+
+/// AgeHistogram is the open issues of a repo by age, pull requests are not
+/// counted.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AgeHistogram {
+    pub repo: String,
+    /// under 7 days, 7 to 30 days, 30 to 90 days and over 90 days
+    pub counts: [usize; 4],
+}
+
+/// histogram counts the open issues of each repo in the snapshot by their age
+/// at the snapshot time.
+pub fn histogram(snapshot: &Snapshot) -> Vec<AgeHistogram> {
+    snapshot
+        .repo_issues()
+        .iter()
+        .map(|repo| {
+            let mut counts = [0; 4];
+            for issue in repo.issues().iter().filter(|i| !i.is_pull()) {
+                let age = (*snapshot.time() - *issue.created_at()).num_days();
+                let bucket = BUCKETS.iter().position(|days| age < *days);
+                counts[bucket.unwrap_or(BUCKETS.len())] += 1;
+            }
+            AgeHistogram {
+                repo: repo.name(),
+                counts,
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn count_ages() {
+        let issues: Vec<String> = [
+            "2020-04-30T00:00:00Z",
+            "2020-04-24T00:00:00Z",
+            "2020-04-01T00:00:00Z",
+            "2020-03-01T00:00:00Z",
+            "2019-05-01T00:00:00Z",
+            "2019-01-01T00:00:00Z",
+        ]
+        .iter()
+        .enumerate()
+        .map(|(i, created_at)| {
+            format!(
+                r#"{{"number": {}, "title": "issue", "created_at": "{}",
+                    "author_association": "NONE", "labels": []}}"#,
+                i, created_at
+            )
+        })
+        .collect();
+        let snapshot: Snapshot = serde_json::from_str(&format!(
+            r#"{{"time": "2020-05-01T00:00:00Z", "repo_issues": [{{
+                "repo": {{"owner": "pingcap", "repo": "parser"}},
+                "issues": [{}, {{
+                    "number": 10,
+                    "title": "pull",
+                    "pull_request": {{"html_url": "https://github.com/pingcap/parser/pull/10"}},
+                    "created_at": "2019-01-01T00:00:00Z",
+                    "author_association": "MEMBER",
+                    "labels": []
+                }}]
+            }}], "project_issues": []}}"#,
+            issues.join(",")
+        ))
+        .unwrap();
+        assert_eq!(
+            histogram(&snapshot),
+            vec![AgeHistogram {
+                repo: "pingcap/parser".to_owned(),
+                counts: [1, 1, 2, 2],
+            }]
+        );
+    }
+}
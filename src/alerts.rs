@@ -0,0 +1,140 @@
+use chrono::Duration;
+use tracing::info;
+
+use crate::config::Config;
+use crate::providers::github::Issue;
+use crate::providers::opsgenie::{self, Alert, Opsgenie};
+use crate::report::Report;
+use crate::storage;
+use crate::Result;
+
+/// breaches returns the stale issues without reply for more than `sla-days`
+/// by the time of the snapshot, with their repos, none if it's not set.
+pub fn breaches<'a>(conf: &Config, report: &'a Report) -> Vec<(&'a str, &'a Issue)> {
+    let days = match conf.sla_days {
+        Some(days) => Duration::days(days),
+        None => return vec![],
+    };
+    let until = *report.snapshot.time();
+    report
+        .stale
+        .iter()
+        .flat_map(|stale| {
+            stale
+                .issues
+                .iter()
+                .map(move |issue| (&stale.repo[..], issue))
+        })
+        .filter(|(_, issue)| until - *issue.created_at() > days)
+        .collect()
+}
+
+/// sync opens an Opsgenie alert for each new SLA breach, and closes the alerts
+/// of the issues which got a reply or are closed since. The open alerts are
+/// stored in `github-data` as each is opened or closed, and nothing is sent or
+/// stored in a dry run.
+pub async fn sync(
+    conf: &Config,
+    client: &reqwest::Client,
+    report: &Report,
+    dry_run: bool,
+) -> Result<()> {
+    if conf.opsgenie_api_key.is_empty() {
+        return Ok(());
+    }
+    let breaches = breaches(conf, report);
+    let mut open = storage::alerts(&conf.github_data)?;
+    let opsgenie = Opsgenie::new(client.clone(), &conf.opsgenie_url, &conf.opsgenie_api_key)?;
+    for (repo, issue) in &breaches {
+        let alias = opsgenie::alias(repo, issue.number());
+        if open.contains(&alias) {
+            continue;
+        }
+        let alert = Alert::new(
+            repo,
+            issue.number(),
+            issue.title(),
+            &issue.to_string(),
+            &conf.opsgenie_priority,
+        );
+        if dry_run {
+            println!("[dry-run] open Opsgenie alert {}: {}", alias, alert.message);
+            continue;
+        }
+        opsgenie.create_alert(&alert).await?;
+        info!("opened Opsgenie alert {}", alias);
+        open.push(alias);
+        // stored at once, so it's closed later even if the run fails
+        storage::save_alerts(&conf.github_data, &open)?;
+    }
+    let breached: Vec<String> = breaches
+        .iter()
+        .map(|(repo, issue)| opsgenie::alias(repo, issue.number()))
+        .collect();
+    let resolved: Vec<String> = open
+        .iter()
+        .filter(|alias| !breached.contains(alias))
+        .cloned()
+        .collect();
+    for alias in resolved {
+        if dry_run {
+            println!("[dry-run] close Opsgenie alert {}", alias);
+            continue;
+        }
+        opsgenie
+            .close_alert(&alias, "the issue got a reply or is closed")
+            .await?;
+        info!("closed Opsgenie alert {}", alias);
+        open.retain(|a| *a != alias);
+        storage::save_alerts(&conf.github_data, &open)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::providers::github::{Snapshot, StaleIssues};
+
+    #[test]
+    fn find_breaches() {
+        let conf: Config = toml::from_str("slack-channel = \"\"\nsla-days = 7").unwrap();
+        let snapshot: Snapshot = serde_json::from_str(
+            r#"{"time": "2020-05-01T00:00:00Z", "repo_issues": [], "project_issues": []}"#,
+        )
+        .unwrap();
+        let issues: Vec<Issue> = serde_json::from_str(
+            r#"[{
+                "number": 1,
+                "title": "panic in the lexer",
+                "html_url": "https://github.com/pingcap/parser/issues/1",
+                "created_at": "2020-04-01T00:00:00Z",
+                "author_association": "NONE",
+                "labels": []
+            }, {
+                "number": 2,
+                "title": "typo in the docs",
+                "html_url": "https://github.com/pingcap/parser/issues/2",
+                "created_at": "2020-04-28T00:00:00Z",
+                "author_association": "NONE",
+                "labels": []
+            }]"#,
+        )
+        .unwrap();
+        let report = Report {
+            stale: vec![StaleIssues {
+                repo: "pingcap/parser".to_owned(),
+                days: 3,
+                issues,
+            }],
+            ..Report::new(snapshot)
+        };
+        let found = breaches(&conf, &report);
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].0, "pingcap/parser");
+        assert_eq!(found[0].1.number(), 1);
+
+        let conf: Config = toml::from_str("slack-channel = \"\"").unwrap();
+        assert!(breaches(&conf, &report).is_empty());
+    }
+}
@@ -0,0 +1,209 @@
+use chrono::{Duration, NaiveDate};
+
+use crate::config::Config;
+use crate::i18n::{fill, Messages};
+use crate::providers::github::Issue;
+use crate::report::Report;
+use crate::storage::{self, OpenedCounts};
+use crate::Result;
+
+/// Spike is a repo with much more issues opened today than usual, which often
+/// follows a bad release.
+#[derive(Debug)]
+pub struct Spike {
+    pub repo: String,
+    /// issues opened today
+    pub today: usize,
+    /// issues opened a day in the previous `days` days
+    pub average: f64,
+    pub days: i64,
+    /// issues opened since the last snapshot
+    pub issues: Vec<Issue>,
+}
+
+impl Spike {
+    pub fn render(&self, messages: &Messages) -> String {
+        let average = format!("{:.1}", self.average);
+        let mut text = fill(
+            messages.spike_alert,
+            &[&self.repo, &self.today, &average, &self.days],
+        );
+        text.push('\n');
+        for issue in &self.issues {
+            text.push_str(&format!("+ {} {}\n", issue.title(), issue));
+        }
+        text
+    }
+}
+
+/// detect adds the issues opened since the last snapshot to the counts of the
+/// day stored in `github-data`, and returns the repos opening `spike-factor`
+/// times their daily average today. A repo is alerted at most once a day,
+/// and nothing is stored in a dry run.
+pub fn detect(conf: &Config, report: &Report, dry_run: bool) -> Result<Vec<Spike>> {
+    if conf.spike_factor.is_none() {
+        return Ok(vec![]);
+    }
+    let today = report
+        .snapshot
+        .time()
+        .with_timezone(&conf.tz()?)
+        .date()
+        .naive_local();
+    let mut counts = storage::opened_counts(&conf.github_data)?;
+    let spikes = add_opened(conf, &mut counts, report, today);
+    if !dry_run {
+        storage::save_opened_counts(&conf.github_data, &counts)?;
+    }
+    Ok(spikes)
+}
+
+fn add_opened(
+    conf: &Config,
+    counts: &mut Vec<OpenedCounts>,
+    report: &Report,
+    today: NaiveDate,
+) -> Vec<Spike> {
+    let factor = conf.spike_factor.unwrap_or(f64::INFINITY);
+    let first_day = today - Duration::days(conf.spike_days);
+    let mut spikes = vec![];
+    for diff in &report.diffs {
+        let opened: Vec<Issue> = diff
+            .opened
+            .iter()
+            .filter(|i| !i.is_pull())
+            .cloned()
+            .collect();
+        let repo = match counts.iter().position(|c| c.repo == diff.repo) {
+            Some(i) => &mut counts[i],
+            None => {
+                counts.push(OpenedCounts {
+                    repo: diff.repo.clone(),
+                    ..OpenedCounts::default()
+                });
+                counts.last_mut().unwrap()
+            }
+        };
+        repo.days = repo.days.split_off(&first_day);
+        *repo.days.entry(today).or_insert(0) += opened.len();
+
+        // a baseline of a few days is too noisy
+        let previous: Vec<usize> = repo.days.range(..today).map(|(_, n)| *n).collect();
+        if (previous.len() as i64) * 2 < conf.spike_days || repo.alerted == Some(today) {
+            continue;
+        }
+        let average = previous.iter().sum::<usize>() as f64 / previous.len() as f64;
+        let count = repo.days[&today];
+        if count >= conf.spike_min && count as f64 >= factor * average {
+            repo.alerted = Some(today);
+            spikes.push(Spike {
+                repo: diff.repo.clone(),
+                today: count,
+                average,
+                days: conf.spike_days,
+                issues: opened,
+            });
+        }
+    }
+    spikes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::confidential;
+    use crate::i18n::Locale;
+    use crate::providers::github::{RepoDiff, Snapshot};
+
+    #[test]
+    fn detect_spikes() {
+        let conf: Config =
+            toml::from_str("slack-channel = \"\"\nspike-factor = 3.0\nspike-days = 4").unwrap();
+        let issues: Vec<Issue> = serde_json::from_str(
+            r#"[{
+                "number": 1,
+                "title": "crash",
+                "html_url": "https://github.com/pingcap/parser/issues/1",
+                "created_at": "2020-05-01T00:00:00Z",
+                "author_association": "NONE",
+                "labels": []
+            }]"#,
+        )
+        .unwrap();
+        let snapshot: Snapshot = serde_json::from_str(
+            r#"{"time": "2020-05-05T00:00:00Z", "repo_issues": [], "project_issues": []}"#,
+        )
+        .unwrap();
+        let report = Report {
+            diffs: vec![RepoDiff {
+                repo: "pingcap/parser".to_owned(),
+                opened: issues.repeat(5),
+                closed: vec![],
+            }],
+            ..Report::new(snapshot)
+        };
+        let mut counts = vec![OpenedCounts {
+            repo: "pingcap/parser".to_owned(),
+            ..OpenedCounts::default()
+        }];
+        let today = NaiveDate::from_ymd(2020, 5, 5);
+
+        // one day is not a baseline yet
+        counts[0].days.insert(NaiveDate::from_ymd(2020, 5, 4), 1);
+        assert!(add_opened(&conf, &mut counts, &report, today).is_empty());
+
+        counts[0].days.insert(NaiveDate::from_ymd(2020, 5, 3), 3);
+        // out of the window
+        counts[0].days.insert(NaiveDate::from_ymd(2020, 4, 1), 100);
+        let spikes = add_opened(&conf, &mut counts, &report, today);
+        assert_eq!(spikes.len(), 1);
+        assert_eq!(spikes[0].today, 10);
+        assert_eq!(
+            spikes[0].render(Locale::En.messages()).lines().next(),
+            Some("pingcap/parser: 10 issues opened today, 2.0 a day in the last 4 days")
+        );
+        assert!(!counts[0]
+            .days
+            .contains_key(&NaiveDate::from_ymd(2020, 4, 1)));
+
+        // alerted already today
+        assert!(add_opened(&conf, &mut counts, &report, today).is_empty());
+    }
+
+    #[test]
+    fn split_confidential_spike() {
+        let conf: Config =
+            toml::from_str("slack-channel = \"\"\nconfidential-labels = [\"security\"]").unwrap();
+        let issues: Vec<Issue> = serde_json::from_str(
+            r#"[{
+                "number": 1,
+                "title": "crash",
+                "html_url": "https://github.com/pingcap/parser/issues/1",
+                "created_at": "2020-05-01T00:00:00Z",
+                "author_association": "NONE",
+                "labels": []
+            }, {
+                "number": 2,
+                "title": "overflow in the lexer",
+                "html_url": "https://github.com/pingcap/parser/issues/2",
+                "created_at": "2020-05-01T00:00:00Z",
+                "author_association": "NONE",
+                "labels": [{"id": 1, "name": "security"}]
+            }]"#,
+        )
+        .unwrap();
+        let mut spike = Spike {
+            repo: "pingcap/parser".to_owned(),
+            today: 2,
+            average: 0.5,
+            days: 7,
+            issues,
+        };
+        let confidential = confidential::split(&conf, &mut spike.issues);
+        let text = spike.render(Locale::En.messages());
+        assert!(text.contains("crash"));
+        assert!(!text.contains("overflow in the lexer"));
+        assert_eq!(confidential.len(), 1);
+        assert_eq!(confidential[0].title(), "overflow in the lexer");
+    }
+}
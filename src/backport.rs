@@ -0,0 +1,169 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use regex::Regex;
+
+use crate::config::Config;
+use crate::i18n::{fill, Messages};
+use crate::providers::github::{BranchPull, GitHub, Issue};
+use crate::Result;
+
+/// MissingBackport is a merged pull request or a closed issue labeled to be
+/// cherry-picked to a branch, without a pull request against the branch
+/// referring to it yet.
+#[derive(Debug)]
+pub struct MissingBackport {
+    pub issue: Issue,
+    pub branch: String,
+}
+
+impl MissingBackport {
+    pub fn render(&self, messages: &Messages) -> String {
+        fill(
+            messages.backport_missing,
+            &[&self.issue, &self.branch, &self.issue.title()],
+        )
+    }
+}
+
+/// branches returns the target branches of the labels with the prefix, e.g.
+/// "release-4.0" of "needs-cherry-pick-release-4.0".
+pub fn branches<'a>(prefix: &'a str, issue: &'a Issue) -> impl Iterator<Item = &'a str> {
+    issue
+        .labels()
+        .filter_map(move |label| label.strip_prefix(prefix))
+        .filter(|branch| !branch.is_empty())
+}
+
+// refers_to checks whether the title or the body of the pull request mentions
+// the issue, by its url or like "#1234".
+fn refers_to(pull: &BranchPull, issue: &Issue) -> bool {
+    let url = issue.to_string();
+    let number = Regex::new(&format!(r"#{}\b", issue.number())).unwrap();
+    std::iter::once(&pull.title)
+        .chain(pull.body.as_ref())
+        .any(|text| text.contains(&url) || number.is_match(text))
+}
+
+/// missing returns the labeled issues of a repo which no pull request against
+/// their target branches refers to, `pulls` are keyed by the branch.
+pub fn missing(
+    prefix: &str,
+    labeled: &[Issue],
+    pulls: &BTreeMap<String, Vec<BranchPull>>,
+) -> Vec<MissingBackport> {
+    let mut missing = vec![];
+    for issue in labeled {
+        for branch in branches(prefix, issue) {
+            let found = pulls
+                .get(branch)
+                .map_or(false, |pulls| pulls.iter().any(|p| refers_to(p, issue)));
+            if !found {
+                missing.push(MissingBackport {
+                    issue: issue.clone(),
+                    branch: branch.to_owned(),
+                });
+            }
+        }
+    }
+    missing
+}
+
+/// check fetches the issues and pull requests of the watched GitHub repos
+/// labeled by `cherry-pick-prefix`, and returns the ones not cherry-picked
+/// to every target branch yet.
+pub async fn check(conf: &Config, client: &reqwest::Client) -> Result<Vec<MissingBackport>> {
+    let prefix = &conf.cherry_pick_prefix;
+    let github_client = GitHub::new(client.clone(), conf.github_token.clone(), vec![], vec![])?;
+    let mut all = vec![];
+    for rule in conf.repo_rules() {
+        let mut labeled: Vec<Issue> = vec![];
+        for label in github_client.list_labels(&rule.name).await? {
+            if !label.name.starts_with(&prefix[..]) {
+                continue;
+            }
+            for issue in github_client.list_labeled(&rule.name, &label.name).await? {
+                // labeled by several target branches
+                if !labeled.iter().any(|i| i.number() == issue.number()) {
+                    labeled.push(issue);
+                }
+            }
+        }
+        let targets: BTreeSet<&str> = labeled
+            .iter()
+            .flat_map(|issue| branches(prefix, issue))
+            .collect();
+        let mut pulls = BTreeMap::new();
+        for branch in targets {
+            let branch_pulls = github_client.list_pulls(&rule.name, branch, "all").await?;
+            pulls.insert(branch.to_owned(), branch_pulls);
+        }
+        all.extend(missing(prefix, &labeled, &pulls));
+    }
+    Ok(all)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::i18n::Locale;
+
+    #[test]
+    fn missing_backports() {
+        let labeled: Vec<Issue> = serde_json::from_str(
+            r#"[{
+                "number": 12,
+                "title": "fix a panic",
+                "html_url": "https://github.com/pingcap/parser/pull/12",
+                "created_at": "2020-05-01T00:00:00Z",
+                "author_association": "MEMBER",
+                "labels": [
+                    {"id": 1, "name": "needs-cherry-pick-release-3.0", "description": null},
+                    {"id": 2, "name": "needs-cherry-pick-release-4.0", "description": null},
+                    {"id": 3, "name": "type/bug", "description": null}
+                ]
+            }]"#,
+        )
+        .unwrap();
+        let pulls: Vec<BranchPull> = serde_json::from_str(
+            r#"[{
+                "number": 120,
+                "title": "fix a panic (#12) (#123)",
+                "body": null,
+                "html_url": "https://github.com/pingcap/parser/pull/120"
+            }, {
+                "number": 121,
+                "title": "fix a panic",
+                "body": "cherry-pick #12 to release-4.0",
+                "html_url": "https://github.com/pingcap/parser/pull/121"
+            }]"#,
+        )
+        .unwrap();
+        let mut branches = BTreeMap::new();
+        branches.insert("release-3.0".to_owned(), pulls[..1].to_vec());
+        branches.insert("release-4.0".to_owned(), pulls[1..].to_vec());
+        let prefix = "needs-cherry-pick-";
+        assert!(missing(prefix, &labeled, &branches).is_empty());
+
+        // #120 refers to #123 only
+        branches.insert(
+            "release-3.0".to_owned(),
+            serde_json::from_str(
+                r#"[{
+                    "number": 120,
+                    "title": "fix a panic (#123)",
+                    "body": "",
+                    "html_url": "https://github.com/pingcap/parser/pull/120"
+                }]"#,
+            )
+            .unwrap(),
+        );
+        let missing: Vec<String> = missing(prefix, &labeled, &branches)
+            .iter()
+            .map(|m| m.render(Locale::En.messages()))
+            .collect();
+        assert_eq!(
+            missing,
+            vec!["https://github.com/pingcap/parser/pull/12 is not cherry-picked to release-3.0 yet: fix a panic"]
+        );
+    }
+}
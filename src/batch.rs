@@ -0,0 +1,177 @@
+use std::sync::Mutex;
+
+use tracing::info;
+
+use crate::config::Config;
+use crate::notify;
+use crate::Result;
+
+// PENDING keeps the findings of a run, or of a `batch-window` of `serve`,
+// until they're flushed.
+static PENDING: Mutex<Vec<Finding>> = Mutex::new(Vec::new());
+
+/// Channel is where a finding is sent.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Channel {
+    /// every configured notifier
+    Notifiers,
+    /// a Slack channel other than `slack-channel`, e.g. the one of a team
+    Slack(String),
+}
+
+/// Finding is a message about some issues found during a run, like a spike or
+/// a review route.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Finding {
+    pub channel: Channel,
+    pub text: String,
+    /// the urls of the issues in the text
+    pub issues: Vec<String>,
+}
+
+/// send sends the finding to its channel at once, or keeps it to be coalesced
+/// with the others by `flush` if `batch-notifications` is set.
+pub async fn send(
+    conf: &Config,
+    client: &reqwest::Client,
+    channel: Channel,
+    text: String,
+    issues: &[String],
+    dry_run: bool,
+) -> Result<()> {
+    if conf.batch_notifications {
+        PENDING.lock().unwrap().push(Finding {
+            channel,
+            text,
+            issues: issues.to_vec(),
+        });
+        return Ok(());
+    }
+    deliver(conf, client, &channel, text, issues, dry_run).await
+}
+
+/// flush sends the findings kept since the last flush, one message per
+/// channel with at most `max-batch-size` findings each. Every message is sent
+/// even if some fail, the errors are returned together.
+pub async fn flush(conf: &Config, client: &reqwest::Client, dry_run: bool) -> Result<()> {
+    let findings = std::mem::take(&mut *PENDING.lock().unwrap());
+    if findings.is_empty() {
+        return Ok(());
+    }
+    let count = findings.len();
+    let messages = coalesce(findings, conf.max_batch_size);
+    info!(
+        "coalesced {} findings into {} messages",
+        count,
+        messages.len()
+    );
+    let total = messages.len();
+    let mut errors = vec![];
+    for message in messages {
+        let delivered = deliver(
+            conf,
+            client,
+            &message.channel,
+            message.text,
+            &message.issues,
+            dry_run,
+        )
+        .await;
+        if let Err(e) = delivered {
+            errors.push(format!("{:?}: {}", message.channel, e));
+        }
+    }
+    if errors.is_empty() {
+        return Ok(());
+    }
+    Err(format!(
+        "{} of {} messages failed: {}",
+        errors.len(),
+        total,
+        errors.join("; ")
+    )
+    .into())
+}
+
+/// coalesce joins the findings of each channel in the order they're found, a
+/// blank line between them, `max` findings in a message at most.
+pub fn coalesce(findings: Vec<Finding>, max: usize) -> Vec<Finding> {
+    let mut channels: Vec<(Channel, Vec<Finding>)> = vec![];
+    for finding in findings {
+        match channels.iter_mut().find(|(c, _)| *c == finding.channel) {
+            Some((_, found)) => found.push(finding),
+            None => channels.push((finding.channel.clone(), vec![finding])),
+        }
+    }
+    let mut messages = vec![];
+    for (channel, found) in channels {
+        for batch in found.chunks(max.max(1)) {
+            let text: Vec<&str> = batch.iter().map(|f| &f.text[..]).collect();
+            let mut issues: Vec<String> = vec![];
+            for url in batch.iter().flat_map(|f| &f.issues) {
+                if !issues.contains(url) {
+                    issues.push(url.clone());
+                }
+            }
+            messages.push(Finding {
+                channel: channel.clone(),
+                text: text.join("\n\n"),
+                issues,
+            });
+        }
+    }
+    messages
+}
+
+async fn deliver(
+    conf: &Config,
+    client: &reqwest::Client,
+    channel: &Channel,
+    text: String,
+    issues: &[String],
+    dry_run: bool,
+) -> Result<()> {
+    match channel {
+        Channel::Notifiers => notify::send(conf, client, text, issues, dry_run).await,
+        Channel::Slack(channel) => {
+            notify::send_to_slack_channel(conf, client, channel, &text, issues, dry_run).await
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn coalesce_findings() {
+        let finding = |channel: Channel, text: &str, issues: &[&str]| Finding {
+            channel,
+            text: text.to_owned(),
+            issues: issues.iter().map(|s| s.to_string()).collect(),
+        };
+        let team = Channel::Slack("sql-infra".to_owned());
+        let findings = vec![
+            finding(Channel::Notifiers, "spike in pingcap/tidb", &["tidb#1"]),
+            finding(
+                team.clone(),
+                "2 pull requests to review",
+                &["tidb#2", "tidb#3"],
+            ),
+            finding(Channel::Notifiers, "tidb#1 is a panic", &["tidb#1"]),
+            finding(Channel::Notifiers, "tidb#4 is a panic", &["tidb#4"]),
+        ];
+        assert_eq!(
+            coalesce(findings, 2),
+            vec![
+                finding(
+                    Channel::Notifiers,
+                    "spike in pingcap/tidb\n\ntidb#1 is a panic",
+                    &["tidb#1"]
+                ),
+                finding(Channel::Notifiers, "tidb#4 is a panic", &["tidb#4"]),
+                finding(team, "2 pull requests to review", &["tidb#2", "tidb#3"]),
+            ]
+        );
+    }
+}
@@ -0,0 +1,327 @@
+use std::{collections::HashMap, convert::TryFrom, fmt};
+
+use chrono::Duration;
+use tracing::info;
+
+use crate::config::{BoardRule, Config, WipLimit};
+use crate::providers::github::{Card, GitHub, Project, ProjectIssues, Snapshot};
+use crate::Result;
+
+/// CardMove is a card moved by a board rule.
+#[derive(Debug, PartialEq)]
+pub struct CardMove {
+    pub project: String,
+    pub card_id: i64,
+    pub title: String,
+    pub from: String,
+    pub to: String,
+    pub column_id: i64,
+}
+
+impl fmt::Display for CardMove {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}: {} {} -> {}",
+            self.project, self.title, self.from, self.to
+        )
+    }
+}
+
+/// CardTransition is a card in another column than in the previous snapshot.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CardTransition {
+    pub project: String,
+    /// the title of the card
+    pub card: String,
+    pub from: String,
+    pub to: String,
+}
+
+/// transitions returns the cards of the projects in both snapshots which are
+/// moved to another column, cards are matched by their issue or note.
+pub fn transitions(old: &Snapshot, new: &Snapshot) -> Vec<CardTransition> {
+    let mut transitions = vec![];
+    for project in new.project_issues() {
+        let previous = old
+            .project_issues()
+            .iter()
+            .find(|p| p.name() == project.name());
+        let previous = match previous {
+            Some(previous) => previous,
+            None => continue,
+        };
+        let columns: HashMap<String, &str> = previous
+            .columns()
+            .iter()
+            .flat_map(|c| c.cards().iter().map(move |card| (card_key(card), c.name())))
+            .collect();
+        for column in project.columns() {
+            for card in column.cards() {
+                match columns.get(&card_key(card)) {
+                    Some(from) if *from != column.name() => transitions.push(CardTransition {
+                        project: project.name(),
+                        card: card.title(),
+                        from: from.to_string(),
+                        to: column.name().to_owned(),
+                    }),
+                    _ => {}
+                }
+            }
+        }
+    }
+    transitions
+}
+
+// card_key identifies a card in any snapshot, the ids are not stored in old ones.
+fn card_key(card: &Card) -> String {
+    card.html_url().unwrap_or_else(|| card.title())
+}
+
+/// WipViolation is a column with more cards than its WIP limit.
+#[derive(Debug, Clone, PartialEq)]
+pub struct WipViolation {
+    pub project: String,
+    pub column: String,
+    pub cards: usize,
+    pub max: usize,
+}
+
+/// wip_violations checks the columns of the snapshot against the limits.
+pub fn wip_violations(limits: &[WipLimit], snapshot: &Snapshot) -> Vec<WipViolation> {
+    let mut violations = vec![];
+    for project in snapshot.project_issues() {
+        for limit in limits {
+            if !matches(&limit.project, project) {
+                continue;
+            }
+            let column = project.columns().iter().find(|c| c.name() == limit.column);
+            if let Some(column) = column {
+                if column.cards().len() > limit.max {
+                    violations.push(WipViolation {
+                        project: project.name(),
+                        column: limit.column.clone(),
+                        cards: column.cards().len(),
+                        max: limit.max,
+                    });
+                }
+            }
+        }
+    }
+    violations
+}
+
+// matches checks whether the project is the one of a rule, rules without
+// a project match all.
+fn matches(url: &Option<String>, project: &ProjectIssues) -> bool {
+    url.as_ref().map_or(true, |url| {
+        Project::try_from(url.clone()).map_or(false, |p| p.to_string() == project.name())
+    })
+}
+
+/// plan returns the cards of the snapshot to move by the rules, a card is moved
+/// by the first matching rule. Rules to a missing column are skipped.
+pub fn plan(rules: &[BoardRule], snapshot: &Snapshot) -> Vec<CardMove> {
+    let mut moves: Vec<CardMove> = vec![];
+    for project in snapshot.project_issues() {
+        for rule in rules {
+            if !matches(&rule.project, project) {
+                continue;
+            }
+            let to = match project.columns().iter().find(|c| c.name() == rule.to) {
+                Some(to) => to,
+                None => continue,
+            };
+            let columns = project.columns().iter().filter(|c| {
+                c.name() != rule.to
+                    && (rule.from.is_empty() || rule.from.iter().any(|f| f == c.name()))
+            });
+            for column in columns {
+                for card in column.cards() {
+                    // cards of stored snapshots or other providers can't be moved
+                    if card.id() == 0 || moves.iter().any(|m| m.card_id == card.id()) {
+                        continue;
+                    }
+                    let issue = snapshot.card_issue(card);
+                    if rule.assigned && issue.map_or(true, |i| i.assignees().next().is_none()) {
+                        continue;
+                    }
+                    if let Some(days) = rule.inactive_days {
+                        let updated_at = card
+                            .updated_at()
+                            .into_iter()
+                            .chain(issue.map(|i| i.updated_at()))
+                            .max();
+                        match updated_at {
+                            Some(time) if *time <= *snapshot.time() - Duration::days(days) => {}
+                            _ => continue,
+                        }
+                    }
+                    moves.push(CardMove {
+                        project: project.name(),
+                        card_id: card.id(),
+                        title: card.title(),
+                        from: column.name().to_owned(),
+                        to: to.name().to_owned(),
+                        column_id: to.id(),
+                    });
+                }
+            }
+        }
+    }
+    moves
+}
+
+/// apply moves the cards planned by `board-rule` for the snapshot, or prints
+/// them in a dry run.
+pub async fn apply(
+    conf: &Config,
+    client: &reqwest::Client,
+    snapshot: &Snapshot,
+    dry_run: bool,
+) -> Result<()> {
+    let moves = plan(&conf.board_rules, snapshot);
+    if moves.is_empty() {
+        return Ok(());
+    }
+    let github_client = GitHub::new(client.clone(), conf.github_token.clone(), vec![], vec![])?;
+    for card_move in moves {
+        if dry_run {
+            println!("[dry-run] move {}", card_move);
+            continue;
+        }
+        github_client
+            .move_card(card_move.card_id, card_move.column_id)
+            .await?;
+        info!("moved {}", card_move);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn plan_moves() {
+        let snapshot: Snapshot = serde_json::from_str(
+            r#"{
+                "time": "2020-05-01T00:00:00Z",
+                "repo_issues": [{
+                    "repo": {"owner": "pingcap", "repo": "parser"},
+                    "issues": [{
+                        "number": 1,
+                        "title": "assigned",
+                        "html_url": "https://github.com/pingcap/parser/issues/1",
+                        "assignee": {"id": 1, "login": "you06"},
+                        "created_at": "2020-04-30T00:00:00Z",
+                        "author_association": "NONE",
+                        "labels": []
+                    }]
+                }],
+                "project_issues": [{
+                    "project": {"owner": "pingcap", "repo": "parser", "number": 1, "id": 1},
+                    "columns": [{
+                        "id": 1,
+                        "name": "To Do",
+                        "cards": [{
+                            "id": 10,
+                            "content_url": "https://api.github.com/repos/pingcap/parser/issues/1",
+                            "updated_at": "2020-04-30T00:00:00Z"
+                        }, {
+                            "id": 11,
+                            "note": "old note",
+                            "updated_at": "2020-03-01T00:00:00Z"
+                        }]
+                    }, {
+                        "id": 2,
+                        "name": "In Progress",
+                        "cards": []
+                    }, {
+                        "id": 3,
+                        "name": "Stale",
+                        "cards": []
+                    }]
+                }]
+            }"#,
+        )
+        .unwrap();
+        let rules: Vec<BoardRule> = toml::from_str::<Config>(
+            r#"
+            slack-channel = ""
+            [[board-rule]]
+            project = "https://github.com/pingcap/parser/projects/1"
+            from = ["To Do"]
+            to = "In Progress"
+            assigned = true
+            [[board-rule]]
+            to = "Stale"
+            inactive-days = 14
+            [[board-rule]]
+            to = "Done"
+            assigned = true
+            "#,
+        )
+        .unwrap()
+        .board_rules;
+        let moves: Vec<String> = plan(&rules, &snapshot)
+            .iter()
+            .map(|m| m.to_string())
+            .collect();
+        assert_eq!(
+            moves,
+            vec![
+                "pingcap/parser project 1: pingcap/parser/issues/1 To Do -> In Progress",
+                "pingcap/parser project 1: old note To Do -> Stale",
+            ]
+        );
+
+        // the cards of "To Do" are in "Backlog" now
+        let moved: Snapshot = serde_json::from_str(
+            &serde_json::to_string(&snapshot)
+                .unwrap()
+                .replace(r#""name":"To Do""#, r#""name":"Backlog""#),
+        )
+        .unwrap();
+        assert_eq!(
+            transitions(&snapshot, &moved),
+            vec![
+                CardTransition {
+                    project: "pingcap/parser project 1".to_owned(),
+                    card: "pingcap/parser/issues/1".to_owned(),
+                    from: "To Do".to_owned(),
+                    to: "Backlog".to_owned(),
+                },
+                CardTransition {
+                    project: "pingcap/parser project 1".to_owned(),
+                    card: "old note".to_owned(),
+                    from: "To Do".to_owned(),
+                    to: "Backlog".to_owned(),
+                },
+            ]
+        );
+
+        let limits: Vec<WipLimit> = toml::from_str::<Config>(
+            r#"
+            slack-channel = ""
+            [[wip-limit]]
+            column = "To Do"
+            max = 1
+            [[wip-limit]]
+            column = "In Progress"
+            max = 1
+            "#,
+        )
+        .unwrap()
+        .wip_limits;
+        assert_eq!(
+            wip_violations(&limits, &snapshot),
+            vec![WipViolation {
+                project: "pingcap/parser project 1".to_owned(),
+                column: "To Do".to_owned(),
+                cards: 2,
+                max: 1,
+            }]
+        );
+    }
+}
@@ -0,0 +1,79 @@
+use std::{
+    collections::hash_map::DefaultHasher,
+    fs,
+    hash::{Hash, Hasher},
+    io::Result,
+    path::{Path, PathBuf},
+};
+
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use tracing::debug;
+
+const CACHE_DIR: &str = "cache";
+
+/// Cache keeps response bodies in `<github-data>/cache` for `ttl`,
+/// so repeated runs while trying filters don't hit the API again.
+pub struct Cache {
+    dir: PathBuf,
+    ttl: Duration,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Entry {
+    url: String,
+    time: DateTime<Utc>,
+    body: String,
+}
+
+impl Cache {
+    pub fn new(dir: &str, ttl_secs: u64) -> Self {
+        Cache {
+            dir: Path::new(dir).join(CACHE_DIR),
+            ttl: Duration::seconds(ttl_secs as i64),
+        }
+    }
+
+    fn path(&self, url: &str) -> PathBuf {
+        let mut hasher = DefaultHasher::new();
+        url.hash(&mut hasher);
+        self.dir.join(format!("{:016x}.json", hasher.finish()))
+    }
+
+    pub fn get(&self, url: &str) -> Option<String> {
+        let contents = fs::read_to_string(self.path(url)).ok()?;
+        let entry: Entry = serde_json::from_str(&contents).ok()?;
+        if entry.url != url || Utc::now() - entry.time >= self.ttl {
+            return None;
+        }
+        debug!("cache hit {}", url);
+        Some(entry.body)
+    }
+
+    pub fn put(&self, url: &str, body: &str) -> Result<()> {
+        fs::create_dir_all(&self.dir)?;
+        let entry = Entry {
+            url: url.to_owned(),
+            time: Utc::now(),
+            body: body.to_owned(),
+        };
+        fs::write(self.path(url), serde_json::to_string(&entry)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cache_responses() {
+        let dir = std::env::temp_dir().join("issues-watcher-cache-test");
+        let dir = dir.to_str().unwrap();
+        let url = "https://api.github.com/repos/pingcap/parser/issues?page=1";
+        let cache = Cache::new(dir, 60);
+        cache.put(url, "[]").unwrap();
+        assert_eq!(cache.get(url), Some("[]".to_owned()));
+        assert_eq!(cache.get("https://api.github.com/user"), None);
+        assert_eq!(Cache::new(dir, 0).get(url), None);
+    }
+}
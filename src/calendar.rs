@@ -0,0 +1,166 @@
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, Duration, Utc};
+
+use crate::i18n::{fill, Messages};
+use crate::report::Report;
+
+// lines longer than this are folded, RFC 5545 counts octets
+const LINE_LIMIT: usize = 75;
+
+/// render_ics builds an iCalendar of the milestone due dates in the snapshot,
+/// and the SLA deadlines of the stale issues if `sla_days` is set.
+pub fn render_ics(report: &Report, sla_days: Option<i64>, messages: &Messages) -> String {
+    let stamp = report.snapshot.time().format("%Y%m%dT%H%M%SZ").to_string();
+    let mut lines = vec![
+        "BEGIN:VCALENDAR".to_owned(),
+        "VERSION:2.0".to_owned(),
+        "PRODID:-//issues-watcher//EN".to_owned(),
+    ];
+
+    // the open issues of each milestone with a due date
+    let mut milestones: BTreeMap<(String, &str), (&DateTime<Utc>, usize)> = BTreeMap::new();
+    for repo in report.snapshot.repo_issues() {
+        for issue in repo.issues() {
+            if let (Some(title), Some(due_on)) = (issue.milestone(), issue.milestone_due_on()) {
+                milestones
+                    .entry((repo.name(), title))
+                    .or_insert((due_on, 0))
+                    .1 += 1;
+            }
+        }
+    }
+    for ((repo, title), (due_on, count)) in milestones {
+        lines.extend(vec![
+            "BEGIN:VEVENT".to_owned(),
+            format!("UID:milestone/{}/{}@issues-watcher", repo, title),
+            format!("DTSTAMP:{}", stamp),
+            format!("DTSTART;VALUE=DATE:{}", due_on.format("%Y%m%d")),
+            format!(
+                "SUMMARY:{}",
+                escape(&fill(messages.calendar_milestone, &[&repo, &title]))
+            ),
+            format!(
+                "DESCRIPTION:{}",
+                escape(&fill(messages.calendar_milestone_issues, &[&count]))
+            ),
+            "END:VEVENT".to_owned(),
+        ]);
+    }
+
+    if let Some(days) = sla_days {
+        for repo in &report.stale {
+            for issue in &repo.issues {
+                let deadline = *issue.created_at() + Duration::days(days);
+                lines.extend(vec![
+                    "BEGIN:VEVENT".to_owned(),
+                    format!("UID:sla/{}@issues-watcher", issue),
+                    format!("DTSTAMP:{}", stamp),
+                    format!("DTSTART:{}", deadline.format("%Y%m%dT%H%M%SZ")),
+                    format!(
+                        "SUMMARY:{}",
+                        escape(&fill(messages.calendar_sla, &[&issue.title()]))
+                    ),
+                    format!("URL:{}", issue),
+                    "END:VEVENT".to_owned(),
+                ]);
+            }
+        }
+    }
+
+    lines.push("END:VCALENDAR".to_owned());
+    lines
+        .iter()
+        .map(|line| fold(line))
+        .collect::<Vec<_>>()
+        .join("\r\n")
+        + "\r\n"
+}
+
+// escape escapes the special characters of text values.
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace('\n', "\\n")
+}
+
+// fold splits a long line, the following lines start with a space.
+fn fold(line: &str) -> String {
+    let mut folded = String::new();
+    let mut len = 0;
+    for c in line.chars() {
+        if len + c.len_utf8() > LINE_LIMIT {
+            folded.push_str("\r\n ");
+            len = 1;
+        }
+        folded.push(c);
+        len += c.len_utf8();
+    }
+    folded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::i18n::Locale;
+    use crate::providers::github::{Issue, Snapshot, StaleIssues};
+
+    #[test]
+    fn render_calendar() {
+        let snapshot: Snapshot = serde_json::from_str(
+            r#"{
+                "time": "2020-05-01T00:00:00Z",
+                "repo_issues": [{
+                    "repo": {"owner": "pingcap", "repo": "parser"},
+                    "issues": [{
+                        "number": 1,
+                        "title": "crash, again",
+                        "html_url": "https://github.com/pingcap/parser/issues/1",
+                        "milestone": {"number": 1, "title": "v4.0", "due_on": "2020-06-01T07:00:00Z"},
+                        "created_at": "2020-04-01T00:00:00Z",
+                        "author_association": "NONE",
+                        "labels": []
+                    }]
+                }],
+                "project_issues": []
+            }"#,
+        )
+        .unwrap();
+        let issues: Vec<Issue> = snapshot.repo_issues()[0].issues().to_vec();
+        let report = Report {
+            stale: vec![StaleIssues {
+                repo: "pingcap/parser".to_owned(),
+                days: 3,
+                issues,
+            }],
+            ..Report::new(snapshot)
+        };
+        let ics = render_ics(&report, Some(35), Locale::En.messages());
+        assert_eq!(
+            ics,
+            "BEGIN:VCALENDAR\r\n\
+             VERSION:2.0\r\n\
+             PRODID:-//issues-watcher//EN\r\n\
+             BEGIN:VEVENT\r\n\
+             UID:milestone/pingcap/parser/v4.0@issues-watcher\r\n\
+             DTSTAMP:20200501T000000Z\r\n\
+             DTSTART;VALUE=DATE:20200601\r\n\
+             SUMMARY:pingcap/parser v4.0 is due\r\n\
+             DESCRIPTION:1 open issues and pull requests\r\n\
+             END:VEVENT\r\n\
+             BEGIN:VEVENT\r\n\
+             UID:sla/https://github.com/pingcap/parser/issues/1@issues-watcher\r\n\
+             DTSTAMP:20200501T000000Z\r\n\
+             DTSTART:20200506T000000Z\r\n\
+             SUMMARY:SLA: crash\\, again\r\n\
+             URL:https://github.com/pingcap/parser/issues/1\r\n\
+             END:VEVENT\r\n\
+             END:VCALENDAR\r\n"
+        );
+        assert_eq!(
+            fold(&"a".repeat(80)),
+            format!("{}\r\n {}", "a".repeat(75), "a".repeat(5))
+        );
+    }
+}
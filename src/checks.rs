@@ -0,0 +1,156 @@
+use std::collections::HashMap;
+
+use tracing::warn;
+
+use crate::config::Config;
+use crate::providers::github::{CheckRun, GitHub, Issue, Review, Snapshot};
+use crate::Result;
+
+const FAILED: &[&str] = &["failure", "timed_out", "cancelled", "action_required"];
+
+/// FailingPull is an open pull request whose checks fail at its head commit.
+#[derive(Debug, Clone)]
+pub struct FailingPull {
+    pub issue: Issue,
+    /// names of the failed checks
+    pub checks: Vec<String>,
+    /// approved already, it waits for the CI instead of reviews
+    pub approved: bool,
+}
+
+/// FailingChecks are the pull requests of a repo with failed checks.
+#[derive(Debug, Clone)]
+pub struct FailingChecks {
+    pub repo: String,
+    pub pulls: Vec<FailingPull>,
+}
+
+/// failed returns the names of the failed check runs, only the `required`
+/// ones count if any is given.
+pub fn failed(runs: &[CheckRun], required: &[String]) -> Vec<String> {
+    let mut names: Vec<String> = runs
+        .iter()
+        .filter(|run| required.is_empty() || required.contains(&run.name))
+        .filter(|run| {
+            run.conclusion
+                .as_deref()
+                .map_or(false, |c| FAILED.contains(&c))
+        })
+        .map(|run| run.name.clone())
+        .collect();
+    // a check may be re-run
+    names.sort();
+    names.dedup();
+    names
+}
+
+/// approved checks whether someone approves the pull request in their latest
+/// review and no one requests changes, comments don't change a review.
+pub fn approved(reviews: &[Review]) -> bool {
+    let mut latest: HashMap<&str, &str> = HashMap::new();
+    for review in reviews {
+        if review.state == "COMMENTED" || review.state == "PENDING" {
+            continue;
+        }
+        let login = review.user.as_ref().map_or("", |u| u.login());
+        latest.insert(login, &review.state);
+    }
+    latest.values().any(|state| *state == "APPROVED")
+        && !latest.values().any(|state| *state == "CHANGES_REQUESTED")
+}
+
+/// detect fetches the check runs at the head of each open pull request of the
+/// watched GitHub repos and their reviews, if `check-runs` is set. Drafts
+/// and the pull requests failing to fetch are skipped.
+pub async fn detect(
+    conf: &Config,
+    client: &reqwest::Client,
+    snapshot: &Snapshot,
+) -> Result<Vec<FailingChecks>> {
+    if !conf.check_runs {
+        return Ok(vec![]);
+    }
+    let github_client = GitHub::new(client.clone(), conf.github_token.clone(), vec![], vec![])?;
+    let repos = conf.repo_names();
+    let mut all = vec![];
+    for repo in snapshot.repo_issues() {
+        if !repos.contains(&repo.name()) {
+            continue;
+        }
+        let ready = repo
+            .issues()
+            .iter()
+            .filter(|i| i.is_pull() && !i.is_draft());
+        let mut pulls = vec![];
+        for issue in ready {
+            match pull_checks(&github_client, issue, &conf.required_checks).await {
+                Ok(Some(pull)) => pulls.push(pull),
+                Ok(None) => {}
+                Err(e) => warn!("check the CI of {}: {}", issue, e),
+            }
+        }
+        if !pulls.is_empty() {
+            all.push(FailingChecks {
+                repo: repo.name(),
+                pulls,
+            });
+        }
+    }
+    Ok(all)
+}
+
+async fn pull_checks(
+    github_client: &GitHub,
+    issue: &Issue,
+    required: &[String],
+) -> Result<Option<FailingPull>> {
+    let head = github_client.get_pull(issue).await?.head;
+    let checks = failed(&github_client.check_runs(issue, &head.sha).await?, required);
+    if checks.is_empty() {
+        return Ok(None);
+    }
+    let reviews = github_client.list_reviews(issue).await?;
+    Ok(Some(FailingPull {
+        issue: issue.clone(),
+        checks,
+        approved: approved(&reviews),
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn failed_checks_and_approvals() {
+        let runs: Vec<CheckRun> = serde_json::from_str(
+            r#"[
+                {"name": "unit-test", "conclusion": "failure"},
+                {"name": "unit-test", "conclusion": "failure"},
+                {"name": "lint", "conclusion": "timed_out"},
+                {"name": "build", "conclusion": "success"},
+                {"name": "integration", "conclusion": null}
+            ]"#,
+        )
+        .unwrap();
+        assert_eq!(failed(&runs, &[]), vec!["lint", "unit-test"]);
+        assert_eq!(failed(&runs, &["unit-test".to_owned()]), vec!["unit-test"]);
+        assert!(failed(&runs, &["build".to_owned()]).is_empty());
+
+        let mut reviews: Vec<Review> = serde_json::from_str(
+            r#"[
+                {"user": {"login": "a"}, "state": "CHANGES_REQUESTED"},
+                {"user": {"login": "b"}, "state": "APPROVED"},
+                {"user": {"login": "a"}, "state": "COMMENTED"}
+            ]"#,
+        )
+        .unwrap();
+        assert!(!approved(&reviews));
+        assert!(approved(&reviews[1..]));
+        reviews.push(
+            serde_json::from_str(r#"{"user": {"login": "a"}, "state": "APPROVED"}"#).unwrap(),
+        );
+        assert!(approved(&reviews));
+        assert!(!approved(&[]));
+    }
+}
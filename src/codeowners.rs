@@ -0,0 +1,257 @@
+use regex::Regex;
+use tracing::warn;
+
+use crate::batch::{self, Channel};
+use crate::confidential;
+use crate::config::{Config, TeamRoute};
+use crate::i18n::{fill, Messages};
+use crate::notify;
+use crate::providers::github::{GitHub, Issue, Snapshot};
+use crate::Result;
+
+/// CodeOwners are the rules of a CODEOWNERS file, the last matching rule of a
+/// path wins like on GitHub.
+pub struct CodeOwners {
+    rules: Vec<(Regex, Vec<String>)>,
+}
+
+impl CodeOwners {
+    /// parse reads the rules, comments and invalid patterns are skipped.
+    pub fn parse(text: &str) -> CodeOwners {
+        let rules = text
+            .lines()
+            .map(|line| line.split('#').next().unwrap_or("").trim())
+            .filter_map(|line| {
+                let mut fields = line.split_whitespace();
+                let pattern = pattern_regex(fields.next()?)?;
+                Some((pattern, fields.map(str::to_owned).collect()))
+            })
+            .collect();
+        CodeOwners { rules }
+    }
+
+    /// owners returns the owners of the path, e.g. "src/main.rs".
+    pub fn owners(&self, path: &str) -> &[String] {
+        self.rules
+            .iter()
+            .rev()
+            .find(|(pattern, _)| pattern.is_match(path))
+            .map_or(&[], |(_, owners)| owners)
+    }
+}
+
+// pattern_regex translates a gitignore style pattern, which matches at any depth
+// unless it has a slash other than a trailing one.
+fn pattern_regex(pattern: &str) -> Option<Regex> {
+    let body = pattern.trim_start_matches('/').trim_end_matches('/');
+    let anchored = pattern.starts_with('/') || body.contains('/');
+    let mut re = String::new();
+    let mut chars = body.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '*' if chars.peek() == Some(&'*') => {
+                chars.next();
+                // "**/" matches no directory too
+                if chars.peek() == Some(&'/') {
+                    chars.next();
+                    re.push_str("(?:.*/)?");
+                } else {
+                    re.push_str(".*");
+                }
+            }
+            '*' => re.push_str("[^/]*"),
+            '?' => re.push_str("[^/]"),
+            c => re.push_str(&regex::escape(&c.to_string())),
+        }
+    }
+    let prefix = if anchored { "^" } else { "^(?:.*/)?" };
+    // a directory owns the files under it, "dir/*" only the ones right in it
+    let suffix = if pattern.ends_with('/') {
+        "/"
+    } else if pattern.ends_with("/*") {
+        "$"
+    } else {
+        "(?:$|/)"
+    };
+    Regex::new(&format!("{}{}{}", prefix, re, suffix)).ok()
+}
+
+// ReviewRoute is the unreviewed pull requests owned by a team.
+struct ReviewRoute<'a> {
+    route: &'a TeamRoute,
+    pulls: Vec<Issue>,
+}
+
+/// route sends the open pull requests of the watched GitHub repos without any
+/// review to the `[[team-route]]` of each team owning their files in
+/// CODEOWNERS. Drafts and the repos without CODEOWNERS are skipped.
+pub async fn route(
+    conf: &Config,
+    client: &reqwest::Client,
+    snapshot: &Snapshot,
+    dry_run: bool,
+) -> Result<()> {
+    if conf.team_routes.is_empty() {
+        return Ok(());
+    }
+    let github_client = GitHub::new(client.clone(), conf.github_token.clone(), vec![], vec![])?;
+    let mut routes: Vec<ReviewRoute> = conf
+        .team_routes
+        .iter()
+        .map(|route| ReviewRoute {
+            route,
+            pulls: vec![],
+        })
+        .collect();
+    let repos = conf.repo_names();
+    for repo in snapshot.repo_issues() {
+        if !repos.contains(&repo.name()) {
+            continue;
+        }
+        let codeowners = match github_client.codeowners(&repo.name()).await? {
+            Some(text) => CodeOwners::parse(&text),
+            None => continue,
+        };
+        let ready = repo
+            .issues()
+            .iter()
+            .filter(|i| i.is_pull() && !i.is_draft());
+        for pull in ready {
+            if !github_client.list_reviews(pull).await?.is_empty() {
+                continue;
+            }
+            let files = github_client.pull_files(pull).await?;
+            for route in routes.iter_mut() {
+                let owned = files
+                    .iter()
+                    .any(|f| codeowners.owners(f).contains(&route.route.team));
+                if owned {
+                    route.pulls.push(pull.clone());
+                }
+            }
+        }
+    }
+    let messages = conf.locale.messages();
+    let routes: Vec<ReviewRoute> = routes.into_iter().filter(|r| !r.pulls.is_empty()).collect();
+    if routes.is_empty() {
+        return Ok(());
+    }
+    let groups = notify::slack_group_mentions(conf, client).await?;
+    for ReviewRoute { route, mut pulls } in routes {
+        // the titles of the confidential pulls only go to `confidential-channel`
+        let confidential = confidential::split(conf, &mut pulls);
+        if !confidential.is_empty() {
+            let text = review_text(messages, &route.team, "", &confidential);
+            let issues: Vec<String> = confidential.iter().map(|p| p.to_string()).collect();
+            if let Err(e) = confidential::send(conf, client, &text, &issues, dry_run).await {
+                warn!("failed to route the reviews of {}: {}", route.team, e);
+            }
+        }
+        if pulls.is_empty() {
+            continue;
+        }
+        // the Slack user group of the team unless it's mentioned otherwise, only
+        // in the Slack channel of the team since other notifiers can't show it
+        let group = route.slack_channel.as_ref().and(groups.get(&route.team));
+        let mention = match group {
+            Some(group) if route.mention.is_empty() => group,
+            _ => &route.mention,
+        };
+        let text = review_text(messages, &route.team, mention, &pulls);
+        let issues: Vec<String> = pulls.iter().map(|p| p.to_string()).collect();
+        let channel = match &route.slack_channel {
+            Some(channel) => Channel::Slack(channel.clone()),
+            None => Channel::Notifiers,
+        };
+        let sent = batch::send(conf, client, channel, text, &issues, dry_run).await;
+        // one team failing shouldn't keep the others from their reviews
+        if let Err(e) = sent {
+            warn!("failed to route the reviews of {}: {}", route.team, e);
+        }
+    }
+    Ok(())
+}
+
+// review_text lists the pull requests for the team to review, after the
+// mention of the team if any.
+fn review_text(messages: &Messages, team: &str, mention: &str, pulls: &[Issue]) -> String {
+    let mut text = fill(messages.review_route, &[&pulls.len(), &team]);
+    if !mention.is_empty() {
+        text = format!("{} {}", mention, text);
+    }
+    text.push('\n');
+    for pull in pulls {
+        text.push_str(&format!("{} {}\n", pull.title(), pull));
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::i18n::Locale;
+
+    #[test]
+    fn match_codeowners() {
+        let codeowners = CodeOwners::parse(
+            r#"
+# the default owners
+*               @pingcap/maintainers
+*.md            @pingcap/docs # docs team
+/parser/        @pingcap/sql-infra
+executor/*      @pingcap/execution
+**/test/**      @you06
+"#,
+        );
+        assert_eq!(codeowners.owners("main.go"), ["@pingcap/maintainers"]);
+        assert_eq!(codeowners.owners("docs/design/a.md"), ["@pingcap/docs"]);
+        assert_eq!(
+            codeowners.owners("parser/yacc/parser.y"),
+            ["@pingcap/sql-infra"]
+        );
+        assert_eq!(
+            codeowners.owners("util/parser/a.go"),
+            ["@pingcap/maintainers"]
+        );
+        assert_eq!(
+            codeowners.owners("executor/join.go"),
+            ["@pingcap/execution"]
+        );
+        assert_eq!(
+            codeowners.owners("executor/aggfuncs/sum.go"),
+            ["@pingcap/maintainers"]
+        );
+        assert_eq!(codeowners.owners("parser/test/a_test.go"), ["@you06"]);
+        assert_eq!(codeowners.owners("test/a_test.go"), ["@you06"]);
+        assert!(CodeOwners::parse("").owners("main.go").is_empty());
+    }
+
+    #[test]
+    fn split_confidential_reviews() {
+        let conf: Config =
+            toml::from_str("slack-channel = \"\"\nconfidential-labels = [\"security\"]").unwrap();
+        let mut pulls: Vec<Issue> = serde_json::from_str(
+            r#"[{
+                "number": 1,
+                "title": "refine the planner",
+                "html_url": "https://github.com/pingcap/tidb/pull/1",
+                "created_at": "2020-05-01T00:00:00Z",
+                "author_association": "MEMBER",
+                "labels": []
+            }, {
+                "number": 2,
+                "title": "fix the privilege bypass",
+                "html_url": "https://github.com/pingcap/tidb/pull/2",
+                "created_at": "2020-05-01T00:00:00Z",
+                "author_association": "MEMBER",
+                "labels": [{"id": 1, "name": "security"}]
+            }]"#,
+        )
+        .unwrap();
+        let confidential = confidential::split(&conf, &mut pulls);
+        let text = review_text(Locale::En.messages(), "@pingcap/sql-infra", "", &pulls);
+        assert!(text.contains("refine the planner"));
+        assert!(!text.contains("fix the privilege bypass"));
+        assert_eq!(confidential.len(), 1);
+    }
+}
@@ -0,0 +1,188 @@
+use tracing::info;
+
+use crate::batch::{self, Channel};
+use crate::config::Config;
+use crate::i18n::fill;
+use crate::providers::github::{Issue, Snapshot};
+use crate::report::Report;
+use crate::Result;
+
+/// is_confidential checks whether the issue has any of the
+/// `confidential-labels`.
+pub fn is_confidential(conf: &Config, issue: &Issue) -> bool {
+    issue
+        .labels()
+        .any(|label| conf.confidential_labels.iter().any(|l| l == label))
+}
+
+/// split takes the confidential issues out of the issues, their titles are
+/// only sent to `confidential-channel` by `send`.
+pub fn split(conf: &Config, issues: &mut Vec<Issue>) -> Vec<Issue> {
+    let (confidential, public): (Vec<Issue>, Vec<Issue>) = std::mem::take(issues)
+        .into_iter()
+        .partition(|issue| is_confidential(conf, issue));
+    *issues = public;
+    confidential
+}
+
+/// redact hides the titles of the confidential issues in the report, before
+/// it's sent to the notifiers or to the digest.
+pub fn redact(conf: &Config, report: &mut Report) {
+    if conf.confidential_labels.is_empty() {
+        return;
+    }
+    redact_snapshot(conf, &mut report.snapshot);
+    let title = conf.locale.messages().confidential_title;
+    let redact = |issue: &mut Issue| {
+        if is_confidential(conf, issue) {
+            issue.redact(title);
+        }
+    };
+    report
+        .diffs
+        .iter_mut()
+        .flat_map(|d| d.opened.iter_mut().chain(d.closed.iter_mut()))
+        .chain(report.stale.iter_mut().flat_map(|s| s.issues.iter_mut()))
+        .chain(
+            report
+                .conflicts
+                .iter_mut()
+                .flat_map(|c| c.issues.iter_mut()),
+        )
+        .chain(
+            report
+                .checks
+                .iter_mut()
+                .flat_map(|c| c.pulls.iter_mut().map(|p| &mut p.issue)),
+        )
+        .chain(report.drafts.iter_mut().flat_map(|d| d.issues.iter_mut()))
+        .chain(
+            report
+                .needs_info
+                .iter_mut()
+                .flat_map(|n| n.issues.iter_mut().map(|i| &mut i.issue)),
+        )
+        .chain(report.queries.iter_mut().flat_map(|q| q.issues.iter_mut()))
+        .for_each(redact);
+}
+
+/// redact_snapshot hides the titles of the confidential issues in the
+/// snapshot, e.g. the one the API server serves.
+pub fn redact_snapshot(conf: &Config, snapshot: &mut Snapshot) {
+    let title = conf.locale.messages().confidential_title;
+    snapshot.update_issues(|issue| {
+        if is_confidential(conf, issue) {
+            issue.redact(title);
+        }
+    });
+}
+
+/// send sends the text about confidential issues to `confidential-channel`,
+/// nothing is sent if it's not set.
+pub async fn send(
+    conf: &Config,
+    client: &reqwest::Client,
+    text: &str,
+    issues: &[String],
+    dry_run: bool,
+) -> Result<()> {
+    match &conf.confidential_channel {
+        Some(channel) => {
+            let channel = Channel::Slack(channel.clone());
+            batch::send(conf, client, channel, text.to_owned(), issues, dry_run).await
+        }
+        None => {
+            info!("{} confidential issues are not sent", issues.len());
+            Ok(())
+        }
+    }
+}
+
+/// route sends the confidential issues opened since the previous snapshot or
+/// without reply to `confidential-channel`, with their titles.
+pub async fn route(
+    conf: &Config,
+    client: &reqwest::Client,
+    report: &Report,
+    dry_run: bool,
+) -> Result<()> {
+    let mut issues: Vec<&Issue> = vec![];
+    for issue in report
+        .diffs
+        .iter()
+        .flat_map(|d| &d.opened)
+        .chain(report.stale.iter().flat_map(|s| &s.issues))
+    {
+        if is_confidential(conf, issue)
+            && !issues
+                .iter()
+                .any(|i| i.number() == issue.number() && i.repo_name() == issue.repo_name())
+        {
+            issues.push(issue);
+        }
+    }
+    if issues.is_empty() {
+        return Ok(());
+    }
+    let mut text = fill(conf.locale.messages().confidential_issues, &[&issues.len()]);
+    for issue in &issues {
+        text.push_str(&format!("\n{} {}", issue.title(), issue));
+    }
+    let urls: Vec<String> = issues.iter().map(|i| i.to_string()).collect();
+    send(conf, client, &text, &urls, dry_run).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::providers::github::StaleIssues;
+
+    #[test]
+    fn redact_confidential_issues() {
+        let conf: Config =
+            toml::from_str("slack-channel = \"\"\nconfidential-labels = [\"security\"]").unwrap();
+        let snapshot: Snapshot = serde_json::from_str(
+            r#"{
+                "time": "2020-05-01T00:00:00Z",
+                "repo_issues": [{
+                    "repo": {"owner": "pingcap", "repo": "parser"},
+                    "issues": [{
+                        "number": 1,
+                        "title": "overflow in the lexer",
+                        "html_url": "https://github.com/pingcap/parser/issues/1",
+                        "created_at": "2020-04-01T00:00:00Z",
+                        "author_association": "NONE",
+                        "labels": [{"id": 1, "name": "security"}],
+                        "fields": {"bug-report": "a long string overflows"}
+                    }, {
+                        "number": 2,
+                        "title": "typo in the docs",
+                        "html_url": "https://github.com/pingcap/parser/issues/2",
+                        "created_at": "2020-04-01T00:00:00Z",
+                        "author_association": "NONE",
+                        "labels": []
+                    }]
+                }],
+                "project_issues": []
+            }"#,
+        )
+        .unwrap();
+        let issues = snapshot.repo_issues()[0].issues().to_vec();
+        let mut report = Report::new(snapshot);
+        report.stale = vec![StaleIssues {
+            repo: "pingcap/parser".to_owned(),
+            days: 7,
+            issues,
+        }];
+        assert!(is_confidential(&conf, &report.stale[0].issues[0]));
+        redact(&conf, &mut report);
+        for issues in vec![
+            report.snapshot.repo_issues()[0].issues(),
+            &report.stale[0].issues[..],
+        ] {
+            assert_eq!(issues[0].title(), "(confidential)");
+            assert!(issues[0].fields().is_empty());
+            assert_eq!(issues[1].title(), "typo in the docs");
+        }
+    }
+}
@@ -1,17 +1,51 @@
-use std::{fs::read_to_string, io::Error};
+use std::{
+    collections::BTreeMap,
+    convert::TryFrom,
+    env,
+    fs::read_to_string,
+    io::{Error, ErrorKind},
+    net::SocketAddr,
+    path::Path,
+};
 
-use serde::Deserialize;
+use chrono_tz::Tz;
+use glob::glob;
+use regex::Regex;
+use serde::{
+    de::{self, DeserializeOwned},
+    Deserialize, Deserializer,
+};
+use serde_json;
+use serde_yaml;
 use toml;
 
+use crate::expr::Expr;
+use crate::i18n::Locale;
+use crate::providers::github::Project;
+#[cfg(feature = "gitlab")]
+use crate::providers::gitlab;
+
 #[derive(Deserialize)]
 pub struct Config {
+    #[serde(default)]
     #[serde(rename = "slack-token")]
     pub slack_token: String,
+    #[serde(rename = "slack-token-file")]
+    pub slack_token_file: Option<String>,
     #[serde(rename = "slack-channel")]
     pub slack_channel: String,
 
+    #[serde(default)]
     #[serde(rename = "github-token")]
     pub github_token: String,
+    #[serde(rename = "github-token-file")]
+    pub github_token_file: Option<String>,
+    /// issue like "pingcap/tidb#123" the reports are posted to as comments
+    #[serde(rename = "github-report-issue")]
+    pub github_report_issue: Option<String>,
+    /// discussion like "pingcap/tidb#45" the reports are posted to as comments
+    #[serde(rename = "github-report-discussion")]
+    pub github_report_discussion: Option<String>,
     #[serde(default = "default_github_data")]
     #[serde(rename = "github-data")]
     pub github_data: String,
@@ -21,18 +55,1183 @@ pub struct Config {
     #[serde(default)]
     #[serde(rename = "projects")]
     pub projects: Vec<String>,
+    /// GitLab instance, e.g. a self-hosted "https://gitlab.example.com"
+    #[serde(default = "default_gitlab_url")]
+    #[serde(rename = "gitlab-url")]
+    pub gitlab_url: String,
+    #[serde(default)]
+    #[serde(rename = "gitlab-token")]
+    pub gitlab_token: String,
+    /// GitLab projects like group/project, subgroups are allowed
+    #[serde(default)]
+    #[serde(rename = "gitlab-projects")]
+    pub gitlab_projects: Vec<String>,
+    /// GitLab boards like https://gitlab.com/group/project/-/boards/1
+    #[serde(default)]
+    #[serde(rename = "gitlab-boards")]
+    pub gitlab_boards: Vec<String>,
+    /// Gitea or Forgejo instance, e.g. "https://gitea.com"
+    #[serde(rename = "gitea-url")]
+    pub gitea_url: Option<String>,
+    /// optional for public repos
+    #[serde(default)]
+    #[serde(rename = "gitea-token")]
+    pub gitea_token: String,
+    #[serde(default)]
+    #[serde(rename = "gitea-repos")]
+    pub gitea_repos: Vec<String>,
+    /// Bitbucket Cloud user and app password, optional for public repos
+    #[serde(default)]
+    #[serde(rename = "bitbucket-username")]
+    pub bitbucket_username: String,
+    #[serde(default)]
+    #[serde(rename = "bitbucket-app-password")]
+    pub bitbucket_app_password: String,
+    /// Bitbucket repos like workspace/repo
+    #[serde(default)]
+    #[serde(rename = "bitbucket-repos")]
+    pub bitbucket_repos: Vec<String>,
+    /// Jira instance, e.g. "https://example.atlassian.net"
+    #[serde(rename = "jira-url")]
+    pub jira_url: Option<String>,
+    /// the account of the API token on Jira Cloud, empty for a personal
+    /// access token of Jira Server
+    #[serde(default)]
+    #[serde(rename = "jira-email")]
+    pub jira_email: String,
+    #[serde(default)]
+    #[serde(rename = "jira-token")]
+    pub jira_token: String,
+    /// `[[jira-filter]]` blocks, JQL queries watched like repos
+    #[serde(default)]
+    #[serde(rename = "jira-filter")]
+    pub jira_filters: Vec<JiraFilter>,
+    /// `[[board-rule]]` blocks, move the cards of projects after each report
+    #[serde(default)]
+    #[serde(rename = "board-rule")]
+    pub board_rules: Vec<BoardRule>,
+    /// `[[wip-limit]]` blocks, the most cards allowed in project columns
+    #[serde(default)]
+    #[serde(rename = "wip-limit")]
+    pub wip_limits: Vec<WipLimit>,
+    /// `[[team-route]]` blocks, where the unreviewed pull requests of the
+    /// CODEOWNERS teams go
+    #[serde(default)]
+    #[serde(rename = "team-route")]
+    pub team_routes: Vec<TeamRoute>,
+    /// `[queries.<name>]` blocks, saved filters listed in reports and run by
+    /// `query <name>`
+    #[serde(default)]
+    #[serde(rename = "queries")]
+    pub queries: BTreeMap<String, Query>,
+    /// Rhai scripts evaluated on each report, whose actions are applied
+    #[serde(default)]
+    #[serde(rename = "rule-scripts")]
+    pub rule_scripts: Vec<String>,
+    /// the Slack channel `escalate` of rule scripts sends to, the configured
+    /// notifiers if it's not set
+    #[serde(rename = "escalate-channel")]
+    pub escalate_channel: Option<String>,
+    /// the issues with any of the labels are confidential, their titles are
+    /// redacted from reports and digests
+    #[serde(default)]
+    #[serde(rename = "confidential-labels")]
+    pub confidential_labels: Vec<String>,
+    /// the private Slack channel the confidential issues are sent to, they
+    /// aren't sent anywhere else
+    #[serde(rename = "confidential-channel")]
+    pub confidential_channel: Option<String>,
+    /// the directory of the `.wasm` plugins, notifiers and analyzers
+    #[serde(rename = "plugin-dir")]
+    pub plugin_dir: Option<String>,
+    /// `[[label]]` blocks, the canonical labels of `labels sync`
+    #[serde(default)]
+    #[serde(rename = "label")]
+    pub labels: Vec<LabelConfig>,
+    /// `[[repo]]` blocks, override the global rules for a single repo
+    #[serde(default)]
+    #[serde(rename = "repo")]
+    pub repo_configs: Vec<RepoConfig>,
+    /// other config files whose repos and projects are merged into this one,
+    /// relative paths are resolved against the directory of this file
+    #[serde(default)]
+    #[serde(rename = "include")]
+    pub include: Vec<String>,
+
+    /// max random delay in seconds before a run of report, digest or serve
+    #[serde(default)]
+    #[serde(rename = "jitter")]
+    pub jitter: u64,
+    /// delay in milliseconds between two GitHub requests
+    #[serde(default)]
+    #[serde(rename = "request-interval")]
+    pub request_interval: u64,
+    /// seconds to reuse cached GitHub responses, 0 disables the cache
+    #[serde(default)]
+    #[serde(rename = "cache-ttl")]
+    pub cache_ttl: u64,
+    /// the repos making the most API calls logged at the end of each run
+    #[serde(default = "default_api_usage_top")]
+    #[serde(rename = "api-usage-top")]
+    pub api_usage_top: usize,
+    /// fetch snapshots by a few batched GraphQL queries instead of REST pages
+    #[serde(default)]
+    #[serde(rename = "graphql")]
+    pub graphql: bool,
+    /// number of repos, or columns of a project, fetched at the same time
+    #[serde(default = "default_concurrency")]
+    #[serde(rename = "concurrency")]
+    pub concurrency: usize,
+    /// seconds between two reports in serve mode
+    #[serde(default = "default_serve_interval")]
+    #[serde(rename = "serve-interval")]
+    pub serve_interval: u64,
+    /// address of the JSON API in serve mode, e.g. "127.0.0.1:8080"
+    #[serde(rename = "listen")]
+    pub listen: Option<String>,
+    /// seconds between two digests in serve mode, 0 disables them
+    #[serde(default)]
+    #[serde(rename = "digest-interval")]
+    pub digest_interval: u64,
+    /// days of stored snapshots summarized by a digest
+    #[serde(default = "default_digest_days")]
+    #[serde(rename = "digest-days")]
+    pub digest_days: i64,
+    /// number of the oldest stale issues listed in a digest
+    #[serde(default = "default_digest_top")]
+    #[serde(rename = "digest-top")]
+    pub digest_top: usize,
+    /// issues without reply for more days than this are SLA breaches in digests
+    #[serde(rename = "sla-days")]
+    pub sla_days: Option<i64>,
+    /// draw a chart of the milestone burndowns in digests
+    #[serde(default)]
+    #[serde(rename = "burndown-chart")]
+    pub burndown_chart: bool,
+    /// store the comments of the watched GitHub repos to sum up the
+    /// contributors of each week in digests
+    #[serde(default)]
+    #[serde(rename = "contributor-stats")]
+    pub contributor_stats: bool,
+    /// alert when this many times the daily average of issues are opened in a
+    /// repo today, none disables the alerts
+    #[serde(rename = "spike-factor")]
+    pub spike_factor: Option<f64>,
+    /// days of the daily average compared by spike alerts
+    #[serde(default = "default_spike_days")]
+    #[serde(rename = "spike-days")]
+    pub spike_days: i64,
+    /// issues opened in a day below this are never a spike
+    #[serde(default = "default_spike_min")]
+    #[serde(rename = "spike-min")]
+    pub spike_min: usize,
+    /// pull requests open for more days than this are checked for merge
+    /// conflicts in reports, none disables the checks
+    #[serde(rename = "conflict-days")]
+    pub conflict_days: Option<i64>,
+    /// count the open issues of each repo by age in reports
+    #[serde(default)]
+    #[serde(rename = "age-histogram")]
+    pub age_histogram: bool,
+    /// prefixes of the label groups counted in reports, e.g. "type/"
+    #[serde(default)]
+    #[serde(rename = "label-groups")]
+    pub label_groups: Vec<String>,
+    /// draft pull requests open for more days than this are listed apart in
+    /// reports, none lists them with the others
+    #[serde(rename = "draft-days")]
+    pub draft_days: Option<i64>,
+    /// comment on the drafts listed by `draft-days` to ask their authors
+    /// whether they're still in progress
+    #[serde(default)]
+    #[serde(rename = "draft-ping")]
+    pub draft_ping: bool,
+    /// the issues opened in the last days missing the sections of the issue
+    /// templates are listed in reports, none disables the checks
+    #[serde(rename = "needs-info-days")]
+    pub needs_info_days: Option<i64>,
+    /// comment on the issues listed by `needs-info-days` to ask for the
+    /// missing sections
+    #[serde(default)]
+    #[serde(rename = "needs-info-ask")]
+    pub needs_info_ask: bool,
+    /// list the open pull requests failing their checks in reports
+    #[serde(default)]
+    #[serde(rename = "check-runs")]
+    pub check_runs: bool,
+    /// only these checks count if any is given, e.g. the required ones of
+    /// the protected branches
+    #[serde(default)]
+    #[serde(rename = "required-checks")]
+    pub required_checks: Vec<String>,
+    /// labels like "needs-cherry-pick-release-4.0" ask for a cherry-pick to the
+    /// branch after the prefix
+    #[serde(default = "default_cherry_pick_prefix")]
+    #[serde(rename = "cherry-pick-prefix")]
+    pub cherry_pick_prefix: String,
+    /// proxy for all requests, HTTP_PROXY and HTTPS_PROXY are used if it's not set
+    #[serde(rename = "proxy")]
+    pub proxy: Option<String>,
+    /// hosts which are connected directly, merged with NO_PROXY
+    #[serde(default)]
+    #[serde(rename = "no-proxy")]
+    pub no_proxy: Vec<String>,
+    /// PEM files of extra root certificates, e.g. for GitHub Enterprise with an internal CA
+    #[serde(default)]
+    #[serde(rename = "ca-certs")]
+    pub ca_certs: Vec<String>,
+    #[serde(default)]
+    #[serde(rename = "danger-accept-invalid-certs")]
+    pub danger_accept_invalid_certs: bool,
+    /// timeout of a whole request in seconds
+    #[serde(default = "default_timeout")]
+    #[serde(rename = "timeout")]
+    pub timeout: u64,
+    #[serde(default = "default_connect_timeout")]
+    #[serde(rename = "connect-timeout")]
+    pub connect_timeout: u64,
+    /// idle connections kept for each host, it doesn't cap the open ones
+    #[serde(rename = "max-idle-connections")]
+    pub max_idle_connections: Option<usize>,
+    /// requests sent at the same time by all providers
+    #[serde(rename = "max-connections")]
+    pub max_connections: Option<usize>,
+
+    /// timezone of the times in reports, e.g. "Asia/Shanghai"
+    #[serde(default = "default_timezone")]
+    #[serde(rename = "timezone")]
+    pub timezone: String,
+    /// language of reports, "en" or "zh"
+    #[serde(default)]
+    #[serde(rename = "locale")]
+    pub locale: Locale,
+    /// handlebars templates replacing the built-in text, Markdown and HTML reports
+    #[serde(rename = "text-template")]
+    pub text_template: Option<String>,
+    #[serde(rename = "markdown-template")]
+    pub markdown_template: Option<String>,
+    #[serde(rename = "html-template")]
+    pub html_template: Option<String>,
+    #[serde(rename = "digest-template")]
+    pub digest_template: Option<String>,
+    #[serde(rename = "atom-template")]
+    pub atom_template: Option<String>,
+    /// `[notifier-template.<notifier>]` blocks, the templates and subjects of
+    /// a notifier or a notifier plugin replacing the ones above
+    #[serde(default)]
+    #[serde(rename = "notifier-template")]
+    pub notifier_templates: BTreeMap<String, NotifierTemplate>,
+    /// `[slack-groups]`, the Slack user group of each GitHub team, e.g.
+    /// "@pingcap/sig-planner" = "@sig-planner", mentioned in routed notifications
+    #[serde(default)]
+    #[serde(rename = "slack-groups")]
+    pub slack_groups: BTreeMap<String, String>,
+    /// text reports longer than this are uploaded as secret gists and only
+    /// linked in chat, 0 disables it
+    #[serde(default)]
+    #[serde(rename = "gist-threshold")]
+    pub gist_threshold: usize,
+    /// format of the uploaded reports, "md" or "html"
+    #[serde(default = "default_gist_format")]
+    #[serde(rename = "gist-format")]
+    pub gist_format: String,
+    /// coalesce the findings of a run, like spikes, review routes and rule
+    /// notifications, into one message per channel
+    #[serde(default)]
+    #[serde(rename = "batch-notifications")]
+    pub batch_notifications: bool,
+    /// seconds `serve` coalesces the findings for, 0 for each round
+    #[serde(default)]
+    #[serde(rename = "batch-window")]
+    pub batch_window: u64,
+    /// the most findings in a coalesced message, the rest go in more messages
+    #[serde(default = "default_max_batch_size")]
+    #[serde(rename = "max-batch-size")]
+    pub max_batch_size: usize,
+    /// webhook of a WeCom group robot, like
+    /// "https://qyapi.weixin.qq.com/cgi-bin/webhook/send?key=..."
+    #[serde(default)]
+    #[serde(rename = "wecom-webhook")]
+    pub wecom_webhook: String,
+    /// API key of an Opsgenie integration, the SLA breaches are alerted to it
+    #[serde(default)]
+    #[serde(rename = "opsgenie-api-key")]
+    pub opsgenie_api_key: String,
+    /// Opsgenie API, "https://api.eu.opsgenie.com" for the EU instance
+    #[serde(default = "default_opsgenie_url")]
+    #[serde(rename = "opsgenie-url")]
+    pub opsgenie_url: String,
+    /// priority of the alerts, from "P1" to "P5"
+    #[serde(default = "default_opsgenie_priority")]
+    #[serde(rename = "opsgenie-priority")]
+    pub opsgenie_priority: String,
+
+    /// global rules, can be overridden in `[[repo]]` blocks
+    #[serde(default = "default_stale_days")]
+    #[serde(rename = "stale-days")]
+    pub stale_days: i64,
+    #[serde(default)]
+    #[serde(rename = "ignore-labels")]
+    pub ignore_labels: Vec<String>,
+    /// logins, or GitHub teams like "@pingcap/tidb-team"
+    #[serde(default)]
+    #[serde(rename = "members")]
+    pub members: Vec<String>,
+    /// seconds to reuse the stored members of the teams in `members`
+    #[serde(default = "default_member_cache_ttl")]
+    #[serde(rename = "member-cache-ttl")]
+    pub member_cache_ttl: u64,
+}
+
+#[derive(Deserialize, Clone)]
+pub struct RepoConfig {
+    #[serde(rename = "name")]
+    pub name: String,
+    #[serde(rename = "stale-days")]
+    pub stale_days: Option<i64>,
+    #[serde(rename = "ignore-labels")]
+    pub ignore_labels: Option<Vec<String>>,
+    #[serde(rename = "slack-channel")]
+    pub slack_channel: Option<String>,
+    #[serde(rename = "members")]
+    pub members: Option<Vec<String>>,
+    /// only watch the pull requests against the base branches, all if it's empty
+    #[serde(default)]
+    #[serde(rename = "branches")]
+    pub branches: Vec<String>,
+}
+
+/// JiraFilter is a JQL query watched like a repo named "jira/<name>".
+#[derive(Deserialize, Clone)]
+pub struct JiraFilter {
+    #[serde(rename = "name")]
+    pub name: String,
+    #[serde(rename = "jql")]
+    pub jql: String,
+}
+
+/// BoardRule moves the cards matching all of its conditions to a column.
+#[derive(Deserialize, Clone)]
+pub struct BoardRule {
+    /// url of the project, all the watched projects if it's not set
+    #[serde(rename = "project")]
+    pub project: Option<String>,
+    /// columns the cards are moved from, all the others if it's empty
+    #[serde(default)]
+    #[serde(rename = "from")]
+    pub from: Vec<String>,
+    #[serde(rename = "to")]
+    pub to: String,
+    /// cards whose issue or note is not updated in the days
+    #[serde(rename = "inactive-days")]
+    pub inactive_days: Option<i64>,
+    /// cards whose issue has an assignee
+    #[serde(default)]
+    #[serde(rename = "assigned")]
+    pub assigned: bool,
+}
+
+/// WipLimit is the most cards allowed in a column, more are reported.
+#[derive(Deserialize, Clone)]
+pub struct WipLimit {
+    /// url of the project, all the watched projects if it's not set
+    #[serde(rename = "project")]
+    pub project: Option<String>,
+    #[serde(rename = "column")]
+    pub column: String,
+    #[serde(rename = "max")]
+    pub max: usize,
+}
+
+/// NotifierTemplate words the reports and the digests sent to a notifier
+/// differently, e.g. shorter in chat.
+#[derive(Deserialize, Clone, Default)]
+pub struct NotifierTemplate {
+    #[serde(rename = "report-template")]
+    pub report_template: Option<String>,
+    #[serde(rename = "digest-template")]
+    pub digest_template: Option<String>,
+    /// the first line of the reports, plugins get it apart
+    #[serde(rename = "report-subject")]
+    pub report_subject: Option<String>,
+    #[serde(rename = "digest-subject")]
+    pub digest_subject: Option<String>,
+}
+
+/// TeamRoute sends the unreviewed pull requests owned by a team in CODEOWNERS
+/// to the team.
+#[derive(Deserialize, Clone)]
+pub struct TeamRoute {
+    /// the owner in CODEOWNERS, e.g. "@pingcap/sql-infra" or "@you06"
+    #[serde(rename = "team")]
+    pub team: String,
+    /// the Slack channel of the team, the configured notifiers if it's not set
+    #[serde(rename = "slack-channel")]
+    pub slack_channel: Option<String>,
+    /// prepended to the message, e.g. "<!subteam^S012345>" of a Slack group,
+    /// the `[slack-groups]` group of the team if it's empty
+    #[serde(default)]
+    #[serde(rename = "mention")]
+    pub mention: String,
+}
+
+/// Query is a saved filter of the issues and pull requests in the snapshots,
+/// all of the given conditions are required.
+#[derive(Deserialize, Clone, Default)]
+pub struct Query {
+    /// all of the labels are required
+    #[serde(default)]
+    #[serde(rename = "labels")]
+    pub labels: Vec<String>,
+    /// assigned to any of the users
+    #[serde(default)]
+    #[serde(rename = "assignees")]
+    pub assignees: Vec<String>,
+    /// assigned to someone or to no one
+    #[serde(rename = "assigned")]
+    pub assigned: Option<bool>,
+    /// opened at least this many days ago
+    #[serde(rename = "min-age")]
+    pub min_age: Option<i64>,
+    /// in the repos, owner/repo or just the repo name
+    #[serde(default)]
+    #[serde(rename = "repos")]
+    pub repos: Vec<String>,
+    /// the title contains the text, case-insensitively
+    #[serde(rename = "search")]
+    pub search: Option<String>,
+    /// an expression like `labels contains "type/bug" and age > 30d`
+    #[serde(default)]
+    #[serde(rename = "where")]
+    #[serde(deserialize_with = "parse_expr")]
+    pub expr: Option<Expr>,
 }
 
+fn parse_expr<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Expr>, D::Error> {
+    let expr = String::deserialize(deserializer)?;
+    expr.parse().map(Some).map_err(de::Error::custom)
+}
+
+/// LabelConfig is a label created or updated in every watched repo by `labels sync`.
+#[derive(Deserialize, Clone)]
+pub struct LabelConfig {
+    #[serde(rename = "name")]
+    pub name: String,
+    /// hex color, e.g. "d73a4a"
+    #[serde(rename = "color")]
+    pub color: String,
+    #[serde(default)]
+    #[serde(rename = "description")]
+    pub description: String,
+}
+
+/// GitHubThread is the issue or discussion the reports are posted to.
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub struct GitHubThread {
+    pub repo: String,
+    pub number: i32,
+    pub discussion: bool,
+}
+
+/// RepoRule is the rule of a repo after the global rules are applied.
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub struct RepoRule {
+    pub name: String,
+    pub stale_days: i64,
+    pub ignore_labels: Vec<String>,
+    pub slack_channel: String,
+    pub members: Vec<String>,
+    pub branches: Vec<String>,
+}
+
+// Include is the part of config that can be split into included files.
+#[derive(Deserialize)]
+struct Include {
+    #[serde(default)]
+    #[serde(rename = "repos")]
+    repos: Vec<String>,
+    #[serde(default)]
+    #[serde(rename = "projects")]
+    projects: Vec<String>,
+    #[serde(default)]
+    #[serde(rename = "repo")]
+    repo_configs: Vec<RepoConfig>,
+}
+
+const ENV_PREFIX: &str = "ISSUES_WATCHER_";
+
 fn default_github_data() -> String {
     "~/.issues-watcher".to_owned()
 }
 
+fn default_gitlab_url() -> String {
+    "https://gitlab.com".to_owned()
+}
+
+fn default_concurrency() -> usize {
+    4
+}
+
+fn default_gist_format() -> String {
+    "md".to_owned()
+}
+
+fn default_digest_days() -> i64 {
+    7
+}
+
+fn default_digest_top() -> usize {
+    10
+}
+
+fn default_spike_days() -> i64 {
+    14
+}
+
+fn default_spike_min() -> usize {
+    5
+}
+
+fn default_serve_interval() -> u64 {
+    24 * 60 * 60
+}
+
+fn default_timeout() -> u64 {
+    60
+}
+
+fn default_connect_timeout() -> u64 {
+    10
+}
+
+fn default_cherry_pick_prefix() -> String {
+    "needs-cherry-pick-".to_owned()
+}
+
+fn default_timezone() -> String {
+    "UTC".to_owned()
+}
+
+fn default_stale_days() -> i64 {
+    3
+}
+
+fn default_api_usage_top() -> usize {
+    5
+}
+
+fn default_member_cache_ttl() -> u64 {
+    86400
+}
+
+fn default_max_batch_size() -> usize {
+    20
+}
+
+fn default_opsgenie_url() -> String {
+    "https://api.opsgenie.com".to_owned()
+}
+
+fn default_opsgenie_priority() -> String {
+    "P3".to_owned()
+}
+
+fn read_file<T: DeserializeOwned>(filename: &Path) -> Result<T, Error> {
+    let contents = read_to_string(filename)?;
+    parse(filename, &contents[..]).map_err(|e| {
+        Error::new(
+            ErrorKind::InvalidData,
+            format!("{}: {}", filename.display(), e),
+        )
+    })
+}
+
+// parse decodes the config by file extension, TOML is used by default.
+fn parse<T: DeserializeOwned>(filename: &Path, contents: &str) -> Result<T, String> {
+    match filename.extension().and_then(|ext| ext.to_str()) {
+        Some("yaml") | Some("yml") => serde_yaml::from_str(contents).map_err(|e| e.to_string()),
+        Some("json") => serde_json::from_str(contents).map_err(|e| e.to_string()),
+        _ => toml::from_str(contents).map_err(|e| e.to_string()),
+    }
+}
+
+/// parse_issue parses "owner/repo#123" into the repo and the number.
+pub fn parse_issue(issue: &str) -> Option<(String, i32)> {
+    let re = Regex::new(r"^([\w.-]+/[\w.-]+)#(\d+)$").unwrap();
+    let m = re.captures(issue.trim())?;
+    Some((m[1].to_owned(), m[2].parse().ok()?))
+}
+
+// expand replaces `~` and `$VAR` in paths.
+fn expand(path: &str) -> Result<String, Error> {
+    shellexpand::full(path)
+        .map(|p| p.into_owned())
+        .map_err(|e| Error::new(ErrorKind::InvalidInput, format!("{}: {}", path, e)))
+}
+
+const KEYRING_PREFIX: &str = "keyring:";
+
+fn parse_keyring(value: &str) -> Option<(&str, &str)> {
+    if !value.starts_with(KEYRING_PREFIX) {
+        return None;
+    }
+    let mut parts = value[KEYRING_PREFIX.len()..].splitn(2, '/');
+    match (parts.next(), parts.next()) {
+        (Some(service), Some(username)) if !service.is_empty() && !username.is_empty() => {
+            Some((service, username))
+        }
+        _ => None,
+    }
+}
+
+#[cfg(feature = "keyring")]
+fn get_keyring_password(service: &str, username: &str) -> Result<String, Error> {
+    keyring::Keyring::new(service, username)
+        .get_password()
+        .map_err(|e| {
+            Error::new(
+                ErrorKind::NotFound,
+                format!("keyring {}/{}: {}", service, username, e),
+            )
+        })
+}
+
+#[cfg(not(feature = "keyring"))]
+fn get_keyring_password(service: &str, username: &str) -> Result<String, Error> {
+    Err(Error::new(
+        ErrorKind::Other,
+        format!(
+            "keyring {}/{}: keyring support is not enabled, build with `--features keyring`",
+            service, username
+        ),
+    ))
+}
+
 impl Config {
     pub fn new(filename: String) -> Result<Self, Error> {
-        let contents = read_to_string(filename)?;
-        let config: Config = toml::from_str(&contents[..]).unwrap();
+        let mut config: Config = read_file(Path::new(&filename))?;
+        config.read_includes(Path::new(&filename))?;
+        config.read_secret_files()?;
+        config.override_from_env(ENV_PREFIX);
+        config.read_keyring()?;
+        config.expand_paths()?;
         Ok(config)
     }
+
+    fn read_includes(&mut self, filename: &Path) -> Result<(), Error> {
+        let base = filename.parent().unwrap_or(Path::new(""));
+        for pattern in &self.include {
+            let pattern = base.join(expand(pattern)?);
+            let paths = glob(&pattern.to_string_lossy())
+                .map_err(|e| Error::new(ErrorKind::InvalidInput, format!("include: {}", e)))?;
+            for path in paths {
+                let path = path.map_err(|e| e.into_error())?;
+                let include: Include = read_file(&path)?;
+                self.repos.extend(include.repos);
+                self.projects.extend(include.projects);
+                self.repo_configs.extend(include.repo_configs);
+            }
+        }
+        Ok(())
+    }
+
+    /// validate checks the values which can be parsed but are not usable,
+    /// each error is prefixed by the field it belongs to.
+    pub fn validate(&self) -> Vec<String> {
+        let mut errors = vec![];
+        let gitlab = !self.gitlab_projects.is_empty() || !self.gitlab_boards.is_empty();
+        let others = gitlab
+            || !self.gitea_repos.is_empty()
+            || !self.bitbucket_repos.is_empty()
+            || !self.jira_filters.is_empty();
+        if self.github_token.is_empty()
+            && (!others || !self.repo_rules().is_empty() || !self.projects.is_empty())
+        {
+            errors.push("github-token: missing, set github-token or github-token-file".to_owned());
+        }
+        if !self.slack_token.is_empty() && self.slack_channel.is_empty() {
+            errors.push("slack-channel: required when slack-token is set".to_owned());
+        }
+        if let Err(e) = self.tz() {
+            errors.push(format!("timezone: {}", e));
+        }
+        if self.concurrency == 0 {
+            errors.push("concurrency: must be at least 1".to_owned());
+        }
+        if self.max_connections == Some(0) {
+            errors.push("max-connections: must be at least 1".to_owned());
+        }
+        if let Some(listen) = &self.listen {
+            if listen.parse::<SocketAddr>().is_err() {
+                errors.push(format!("listen: {:?} is not like 127.0.0.1:8080", listen));
+            }
+        }
+        if self.gist_format != "md" && self.gist_format != "html" {
+            errors.push(format!(
+                "gist-format: {:?} is not \"md\" or \"html\"",
+                self.gist_format
+            ));
+        }
+        if self.digest_days <= 0 {
+            errors.push("digest-days: must be positive".to_owned());
+        }
+        if let Some(sla_days) = self.sla_days {
+            if sla_days <= 0 {
+                errors.push("sla-days: must be positive".to_owned());
+            }
+        }
+        if let Some(spike_factor) = self.spike_factor {
+            if spike_factor <= 1.0 {
+                errors.push("spike-factor: must be greater than 1".to_owned());
+            }
+        }
+        if self.spike_days <= 0 {
+            errors.push("spike-days: must be positive".to_owned());
+        }
+        if self.conflict_days.map_or(false, |days| days < 0) {
+            errors.push("conflict-days: can't be negative".to_owned());
+        }
+        if self.draft_days.map_or(false, |days| days < 0) {
+            errors.push("draft-days: can't be negative".to_owned());
+        }
+        if self.draft_ping && self.draft_days.is_none() {
+            errors.push("draft-ping: requires draft-days".to_owned());
+        }
+        if self.needs_info_days.map_or(false, |days| days < 0) {
+            errors.push("needs-info-days: can't be negative".to_owned());
+        }
+        if self.needs_info_ask && self.needs_info_days.is_none() {
+            errors.push("needs-info-ask: requires needs-info-days".to_owned());
+        }
+        match (&self.github_report_issue, &self.github_report_discussion) {
+            (Some(_), Some(_)) => errors
+                .push("github-report-discussion: can't be set with github-report-issue".to_owned()),
+            (Some(thread), None) | (None, Some(thread)) if parse_issue(thread).is_none() => {
+                let key = match self.github_report_issue {
+                    Some(_) => "github-report-issue",
+                    None => "github-report-discussion",
+                };
+                errors.push(format!("{}: {:?} is not like owner/repo#123", key, thread));
+            }
+            _ => {}
+        }
+        let repo_re = Regex::new(r"^[\w.-]+/[\w.-]+$").unwrap();
+        for (i, repo) in self.repos.iter().enumerate() {
+            if !repo_re.is_match(repo) {
+                errors.push(format!(
+                    "repos[{}]: {:?} is not in owner/repo format",
+                    i, repo
+                ));
+            }
+        }
+        for (i, repo) in self.repo_configs.iter().enumerate() {
+            if !repo_re.is_match(&repo.name) {
+                errors.push(format!(
+                    "repo[{}].name: {:?} is not in owner/repo format",
+                    i, repo.name
+                ));
+            }
+            if let Some(stale_days) = repo.stale_days {
+                if stale_days <= 0 {
+                    errors.push(format!("repo[{}].stale-days: must be positive", i));
+                }
+            }
+        }
+        for (i, project) in self.projects.iter().enumerate() {
+            if let Err(e) = Project::try_from(project.clone()) {
+                errors.push(format!("projects[{}]: {}", i, e));
+            }
+        }
+        if gitlab && self.gitlab_token.is_empty() {
+            errors.push("gitlab-token: required when gitlab projects or boards are set".to_owned());
+        }
+        let gitlab_re = Regex::new(r"^[\w.-]+(/[\w.-]+)+$").unwrap();
+        for (i, project) in self.gitlab_projects.iter().enumerate() {
+            if !gitlab_re.is_match(project) {
+                errors.push(format!(
+                    "gitlab-projects[{}]: {:?} is not in group/project format",
+                    i, project
+                ));
+            }
+        }
+        #[cfg(feature = "gitlab")]
+        for (i, board) in self.gitlab_boards.iter().enumerate() {
+            if let Err(e) = gitlab::parse_board(board) {
+                errors.push(format!("gitlab-boards[{}]: {}", i, e));
+            }
+        }
+        if !self.gitea_repos.is_empty() && self.gitea_url.is_none() {
+            errors.push("gitea-url: required when gitea-repos is set".to_owned());
+        }
+        for (i, repo) in self.gitea_repos.iter().enumerate() {
+            if !repo_re.is_match(repo) {
+                errors.push(format!(
+                    "gitea-repos[{}]: {:?} is not in owner/repo format",
+                    i, repo
+                ));
+            }
+        }
+        for (i, repo) in self.bitbucket_repos.iter().enumerate() {
+            if !repo_re.is_match(repo) {
+                errors.push(format!(
+                    "bitbucket-repos[{}]: {:?} is not in workspace/repo format",
+                    i, repo
+                ));
+            }
+        }
+        if !self.jira_filters.is_empty() {
+            if self.jira_url.is_none() {
+                errors.push("jira-url: required when jira-filter is set".to_owned());
+            }
+            if self.jira_token.is_empty() {
+                errors.push("jira-token: required when jira-filter is set".to_owned());
+            }
+        }
+        let name_re = Regex::new(r"^[\w.-]+$").unwrap();
+        for (i, filter) in self.jira_filters.iter().enumerate() {
+            if !name_re.is_match(&filter.name) {
+                errors.push(format!(
+                    "jira-filter[{}].name: {:?} must be letters, digits, '.', '-' or '_'",
+                    i, filter.name
+                ));
+            }
+            if filter.jql.trim().is_empty() {
+                errors.push(format!("jira-filter[{}].jql: must not be empty", i));
+            }
+        }
+        for (i, rule) in self.board_rules.iter().enumerate() {
+            if let Some(Err(e)) = rule.project.clone().map(Project::try_from) {
+                errors.push(format!("board-rule[{}].project: {}", i, e));
+            }
+            if rule.to.trim().is_empty() {
+                errors.push(format!("board-rule[{}].to: must not be empty", i));
+            }
+            match rule.inactive_days {
+                Some(days) if days <= 0 => {
+                    errors.push(format!("board-rule[{}].inactive-days: must be positive", i))
+                }
+                None if !rule.assigned => errors.push(format!(
+                    "board-rule[{}]: inactive-days or assigned is required",
+                    i
+                )),
+                _ => {}
+            }
+        }
+        for (i, limit) in self.wip_limits.iter().enumerate() {
+            if let Some(Err(e)) = limit.project.clone().map(Project::try_from) {
+                errors.push(format!("wip-limit[{}].project: {}", i, e));
+            }
+            if limit.column.trim().is_empty() {
+                errors.push(format!("wip-limit[{}].column: must not be empty", i));
+            }
+        }
+        for (i, route) in self.team_routes.iter().enumerate() {
+            if !route.team.starts_with('@') {
+                errors.push(format!(
+                    "team-route[{}].team: {:?} is not like \"@org/team\" or \"@user\"",
+                    i, route.team
+                ));
+            }
+            if route.slack_channel.is_some() && self.slack_token.is_empty() {
+                errors.push(format!(
+                    "team-route[{}].slack-channel: requires slack-token",
+                    i
+                ));
+            }
+        }
+        for (name, query) in &self.queries {
+            if query.min_age.map_or(false, |days| days < 0) {
+                errors.push(format!("queries.{}.min-age: must not be negative", name));
+            }
+        }
+        if self.escalate_channel.is_some() && self.slack_token.is_empty() {
+            errors.push("escalate-channel: requires slack-token".to_owned());
+        }
+        if !self.slack_groups.is_empty() && self.slack_token.is_empty() {
+            errors.push("slack-groups: requires slack-token".to_owned());
+        }
+        if self.confidential_channel.is_some() && self.slack_token.is_empty() {
+            errors.push("confidential-channel: requires slack-token".to_owned());
+        }
+        if self.confidential_channel.is_some() && self.confidential_labels.is_empty() {
+            errors.push("confidential-channel: requires confidential-labels".to_owned());
+        }
+        if self.max_batch_size == 0 {
+            errors.push("max-batch-size: must be positive".to_owned());
+        }
+        if !self.wecom_webhook.is_empty() && !self.wecom_webhook.starts_with("https://") {
+            errors.push("wecom-webhook: must be an https url".to_owned());
+        }
+        if !self.opsgenie_api_key.is_empty() && self.sla_days.is_none() {
+            errors.push("opsgenie-api-key: requires sla-days".to_owned());
+        }
+        if !["P1", "P2", "P3", "P4", "P5"].contains(&&self.opsgenie_priority[..]) {
+            errors.push(format!(
+                "opsgenie-priority: {:?} must be one of P1 to P5",
+                self.opsgenie_priority
+            ));
+        }
+        let color_re = Regex::new(r"^#?[0-9a-fA-F]{6}$").unwrap();
+        for (i, label) in self.labels.iter().enumerate() {
+            if label.name.trim().is_empty() {
+                errors.push(format!("label[{}].name: must not be empty", i));
+            }
+            if !color_re.is_match(&label.color) {
+                errors.push(format!(
+                    "label[{}].color: {:?} is not a hex color like \"d73a4a\"",
+                    i, label.color
+                ));
+            }
+        }
+        errors.extend(self.disabled_providers());
+        errors
+    }
+
+    /// disabled_providers are the errors of the configured providers which
+    /// are not built in, they are optional features.
+    pub fn disabled_providers(&self) -> Vec<String> {
+        let providers = [
+            (
+                "gitlab",
+                cfg!(feature = "gitlab"),
+                self.gitlab_projects.len() + self.gitlab_boards.len(),
+            ),
+            ("gitea", cfg!(feature = "gitea"), self.gitea_repos.len()),
+            (
+                "bitbucket",
+                cfg!(feature = "bitbucket"),
+                self.bitbucket_repos.len(),
+            ),
+            ("jira", cfg!(feature = "jira"), self.jira_filters.len()),
+        ];
+        providers
+            .iter()
+            .filter(|(_, enabled, watched)| !enabled && *watched > 0)
+            .map(|(name, _, _)| format!("{}: not enabled, build with `--features {}`", name, name))
+            .collect()
+    }
+
+    /// repo_rules resolves the rules of all watched repos,
+    /// repos in the flat `repos` list use the global rules.
+    pub fn repo_rules(&self) -> Vec<RepoRule> {
+        let mut rules: Vec<RepoRule> = vec![];
+        for name in &self.repos {
+            if !self.repo_configs.iter().any(|r| &r.name == name) {
+                rules.push(RepoRule {
+                    name: name.to_owned(),
+                    stale_days: self.stale_days,
+                    ignore_labels: self.ignore_labels.clone(),
+                    slack_channel: self.slack_channel.clone(),
+                    members: self.members.clone(),
+                    branches: vec![],
+                });
+            }
+        }
+        for repo in &self.repo_configs {
+            rules.push(RepoRule {
+                name: repo.name.to_owned(),
+                stale_days: repo.stale_days.unwrap_or(self.stale_days),
+                ignore_labels: repo
+                    .ignore_labels
+                    .clone()
+                    .unwrap_or_else(|| self.ignore_labels.clone()),
+                slack_channel: repo
+                    .slack_channel
+                    .clone()
+                    .unwrap_or_else(|| self.slack_channel.clone()),
+                members: repo.members.clone().unwrap_or_else(|| self.members.clone()),
+                branches: repo.branches.clone(),
+            });
+        }
+        rules
+    }
+
+    /// repo_channels groups the repos with a `slack-channel` of their own by
+    /// the channel, in the order of the rules.
+    pub fn repo_channels(&self) -> Vec<(String, Vec<String>)> {
+        let mut channels: Vec<(String, Vec<String>)> = vec![];
+        for rule in self.repo_rules() {
+            if rule.slack_channel == self.slack_channel {
+                continue;
+            }
+            match channels.iter_mut().find(|(c, _)| *c == rule.slack_channel) {
+                Some((_, repos)) => repos.push(rule.name),
+                None => channels.push((rule.slack_channel, vec![rule.name])),
+            }
+        }
+        channels
+    }
+
+    /// extra_rules are the rules of the GitLab projects, Gitea and Bitbucket repos
+    /// and Jira filters, they use the global rules.
+    pub fn extra_rules(&self) -> Vec<RepoRule> {
+        self.gitlab_projects
+            .iter()
+            .chain(self.gitea_repos.iter())
+            .chain(self.bitbucket_repos.iter())
+            .cloned()
+            .chain(self.jira_filters.iter().map(|f| format!("jira/{}", f.name)))
+            .map(|name| RepoRule {
+                name,
+                stale_days: self.stale_days,
+                ignore_labels: self.ignore_labels.clone(),
+                slack_channel: self.slack_channel.clone(),
+                members: self.members.clone(),
+                branches: vec![],
+            })
+            .collect()
+    }
+
+    pub fn tz(&self) -> Result<Tz, String> {
+        self.timezone.parse::<Tz>()
+    }
+
+    /// add_repos adds repos and projects from command line,
+    /// with `only` the ones in config are not watched.
+    pub fn add_repos(&mut self, repos: Vec<String>, projects: Vec<String>, only: bool) {
+        if only {
+            self.repo_configs.retain(|r| repos.contains(&r.name));
+            self.repos.clear();
+            self.projects.clear();
+        }
+        for repo in repos {
+            if !self.repos.contains(&repo) {
+                self.repos.push(repo);
+            }
+        }
+        self.projects.extend(projects);
+    }
+
+    pub fn repo_names(&self) -> Vec<String> {
+        self.repo_rules().into_iter().map(|r| r.name).collect()
+    }
+
+    /// notifiers are the names of the configured notification sinks.
+    pub fn notifiers(&self) -> Vec<&'static str> {
+        let mut notifiers = vec![];
+        if !self.slack_token.is_empty() && !self.slack_channel.is_empty() {
+            notifiers.push("slack");
+        }
+        if self.github_thread().is_some() {
+            notifiers.push("github");
+        }
+        if !self.wecom_webhook.is_empty() {
+            notifiers.push("wecom");
+        }
+        notifiers
+    }
+
+    /// github_thread is the issue or discussion set by `github-report-issue` or
+    /// `github-report-discussion`, the issue wins if both are set.
+    pub fn github_thread(&self) -> Option<GitHubThread> {
+        let issue = self.github_report_issue.as_ref().map(|t| (t, false));
+        let discussion = self.github_report_discussion.as_ref().map(|t| (t, true));
+        let (thread, discussion) = issue.or(discussion)?;
+        let (repo, number) = parse_issue(thread)?;
+        Some(GitHubThread {
+            repo,
+            number,
+            discussion,
+        })
+    }
+
+    fn expand_paths(&mut self) -> Result<(), Error> {
+        self.github_data = expand(&self.github_data)?;
+        for path in self.ca_certs.iter_mut() {
+            *path = expand(path)?;
+        }
+        for path in self.rule_scripts.iter_mut() {
+            *path = expand(path)?;
+        }
+        for path in vec![
+            &mut self.text_template,
+            &mut self.markdown_template,
+            &mut self.html_template,
+            &mut self.digest_template,
+            &mut self.atom_template,
+            &mut self.plugin_dir,
+        ] {
+            if let Some(path) = path {
+                *path = expand(path)?;
+            }
+        }
+        for template in self.notifier_templates.values_mut() {
+            for path in vec![&mut template.report_template, &mut template.digest_template] {
+                if let Some(path) = path {
+                    *path = expand(path)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// read_secret_files loads tokens from `*-token-file` keys, which is how
+    /// Docker and Kubernetes secrets are usually mounted. `serve` reads them
+    /// again each round, the tokens set by the environment win.
+    pub fn read_secret_files(&mut self) -> Result<(), Error> {
+        let files = vec![
+            (&self.slack_token_file, &mut self.slack_token, "SLACK_TOKEN"),
+            (
+                &self.github_token_file,
+                &mut self.github_token,
+                "GITHUB_TOKEN",
+            ),
+        ];
+        for (file, field, key) in files {
+            if env::var_os(format!("{}{}", ENV_PREFIX, key)).is_some() {
+                continue;
+            }
+            if let Some(path) = file {
+                let path = expand(path)?;
+                let secret = read_to_string(&path)
+                    .map_err(|e| Error::new(e.kind(), format!("read {}: {}", path, e)))?;
+                *field = secret.trim().to_owned();
+            }
+        }
+        Ok(())
+    }
+
+    // read_keyring resolves tokens like "keyring:issues-watcher/github"
+    // from the system keyring, the format is `keyring:<service>/<username>`.
+    fn read_keyring(&mut self) -> Result<(), Error> {
+        for token in vec![
+            &mut self.slack_token,
+            &mut self.github_token,
+            &mut self.gitlab_token,
+            &mut self.gitea_token,
+            &mut self.bitbucket_app_password,
+            &mut self.jira_token,
+            &mut self.opsgenie_api_key,
+            &mut self.wecom_webhook,
+        ] {
+            if let Some((service, username)) = parse_keyring(token) {
+                *token = get_keyring_password(service, username)?;
+            }
+        }
+        Ok(())
+    }
+
+    // override_from_env replaces values with `<prefix><KEY>` environment variables,
+    // so secrets can be passed in without writing them into the config file.
+    fn override_from_env(&mut self, prefix: &str) {
+        let fields = vec![
+            ("SLACK_TOKEN", &mut self.slack_token),
+            ("SLACK_CHANNEL", &mut self.slack_channel),
+            ("GITHUB_TOKEN", &mut self.github_token),
+            ("GITHUB_DATA", &mut self.github_data),
+            ("GITLAB_TOKEN", &mut self.gitlab_token),
+            ("GITEA_TOKEN", &mut self.gitea_token),
+            ("BITBUCKET_APP_PASSWORD", &mut self.bitbucket_app_password),
+            ("JIRA_TOKEN", &mut self.jira_token),
+            ("OPSGENIE_API_KEY", &mut self.opsgenie_api_key),
+            ("WECOM_WEBHOOK", &mut self.wecom_webhook),
+        ];
+        for (key, field) in fields {
+            if let Ok(value) = env::var(format!("{}{}", prefix, key)) {
+                *field = value;
+            }
+        }
+    }
 }
 
 #[cfg(test)]
@@ -51,11 +1250,198 @@ mod tests {
         assert_eq!(config.slack_channel, "slack-channel");
         // github
         assert_eq!(config.github_token, "github-token");
-        assert_eq!(config.github_data, "~/.issues-watcher");
+        assert!(config.github_data.ends_with("/.issues-watcher"));
+        assert!(!config.github_data.starts_with('~'));
         assert_eq!(config.repos, vec!["pingcap/parser"]);
         assert_eq!(
             config.projects,
             vec!["https://github.com/pingcap/tidb/projects/40"]
         );
+        assert_eq!(config.jitter, 0);
+        assert_eq!(config.request_interval, 100);
+        assert_eq!(config.concurrency, 4);
+        assert_eq!(config.timeout, 60);
+        assert_eq!(config.tz().unwrap(), Tz::Asia__Shanghai);
+        assert_eq!(config.locale, Locale::En);
+        assert_eq!(config.connect_timeout, 10);
+        assert_eq!(config.notifiers(), vec!["slack"]);
+    }
+
+    #[test]
+    fn parse_yaml_and_json() {
+        let yaml = "slack-channel: yaml-channel\nrepos:\n  - pingcap/parser\n";
+        let config: Config = parse(Path::new("config.yaml"), yaml).unwrap();
+        assert_eq!(config.slack_channel, "yaml-channel");
+        assert_eq!(config.repos, vec!["pingcap/parser"]);
+
+        let json = r#"{"slack-channel": "json-channel", "repos": ["pingcap/tidb"]}"#;
+        let config: Config = parse(Path::new("config.json"), json).unwrap();
+        assert_eq!(config.slack_channel, "json-channel");
+        assert_eq!(config.repos, vec!["pingcap/tidb"]);
+    }
+
+    #[test]
+    fn expand_path() {
+        env::set_var("ISSUES_WATCHER_TEST_DIR", "/tmp/watcher");
+        assert_eq!(
+            expand("$ISSUES_WATCHER_TEST_DIR/data").unwrap(),
+            "/tmp/watcher/data"
+        );
+        assert!(!expand("~/.issues-watcher").unwrap().starts_with('~'));
+        assert!(expand("$ISSUES_WATCHER_TEST_NOT_EXIST").is_err());
+    }
+
+    #[test]
+    fn parse_keyring_token() {
+        assert_eq!(
+            parse_keyring("keyring:issues-watcher/github"),
+            Some(("issues-watcher", "github"))
+        );
+        assert_eq!(parse_keyring("keyring:issues-watcher"), None);
+        assert_eq!(parse_keyring("github-token"), None);
+    }
+
+    #[test]
+    fn resolve_repo_rules() {
+        let config: Config = parse(
+            Path::new("config.toml"),
+            r#"
+slack-channel = "global"
+repos = ["pingcap/parser", "pingcap/tidb"]
+stale-days = 7
+ignore-labels = ["question"]
+
+[[repo]]
+name = "pingcap/tidb"
+stale-days = 3
+slack-channel = "tidb"
+branches = ["release-4.0"]
+
+[[repo]]
+name = "tikv/tikv"
+members = ["you06"]
+"#,
+        )
+        .unwrap();
+        let rules = config.repo_rules();
+        assert_eq!(
+            config.repo_names(),
+            vec!["pingcap/parser", "pingcap/tidb", "tikv/tikv"]
+        );
+        assert_eq!(rules[0].stale_days, 7);
+        assert_eq!(rules[0].slack_channel, "global");
+        assert_eq!(rules[1].stale_days, 3);
+        assert_eq!(rules[1].slack_channel, "tidb");
+        assert_eq!(rules[1].ignore_labels, vec!["question"]);
+        assert_eq!(rules[1].branches, vec!["release-4.0"]);
+        assert!(rules[2].branches.is_empty());
+        assert_eq!(rules[2].members, vec!["you06"]);
+        assert_eq!(
+            config.repo_channels(),
+            vec![("tidb".to_owned(), vec!["pingcap/tidb".to_owned()])]
+        );
+    }
+
+    #[test]
+    fn add_repos_from_command_line() {
+        let mut config = new_config().unwrap();
+        config.add_repos(vec!["pingcap/tidb".to_owned()], vec![], false);
+        assert_eq!(config.repo_names(), vec!["pingcap/parser", "pingcap/tidb"]);
+        assert_eq!(config.projects.len(), 1);
+
+        config.add_repos(vec!["tikv/tikv".to_owned()], vec![], true);
+        assert_eq!(config.repo_names(), vec!["tikv/tikv"]);
+        assert!(config.projects.is_empty());
+    }
+
+    #[test]
+    fn read_included_files() {
+        let dir = std::env::temp_dir().join("issues-watcher-include-test");
+        std::fs::create_dir_all(dir.join("repos")).unwrap();
+        std::fs::write(
+            dir.join("config.toml"),
+            "slack-channel = \"\"\nrepos = [\"pingcap/tidb\"]\ninclude = [\"repos/*.toml\"]",
+        )
+        .unwrap();
+        std::fs::write(dir.join("repos/a.toml"), "repos = [\"pingcap/parser\"]").unwrap();
+        std::fs::write(dir.join("repos/b.toml"), "repos = [\"tikv/tikv\"]").unwrap();
+        let config = Config::new(dir.join("config.toml").to_str().unwrap().to_owned()).unwrap();
+        assert_eq!(
+            config.repos,
+            vec!["pingcap/tidb", "pingcap/parser", "tikv/tikv"]
+        );
+    }
+
+    #[test]
+    fn validate_config() {
+        let mut config = new_config().unwrap();
+        assert!(config.validate().is_empty());
+
+        config.timezone = "Mars/Olympus".to_owned();
+        config.repos = vec!["pingcap".to_owned(), "pingcap/tidb".to_owned()];
+        config.projects = vec!["https://github.com/pingcap/tidb/project/40".to_owned()];
+        let errors = config.validate();
+        assert_eq!(errors.len(), 3);
+        assert!(errors[0].starts_with("timezone"));
+        assert!(errors[1].starts_with("repos[0]"));
+        assert!(errors[2].starts_with("projects[0]"));
+
+        config.gitlab_projects = vec!["group".to_owned()];
+        let errors = config.validate();
+        assert_eq!(errors.len(), 5);
+        assert!(errors[3].starts_with("gitlab-token"));
+        assert!(errors[4].starts_with("gitlab-projects[0]"));
+
+        assert!(parse::<Config>(Path::new("config.toml"), "repos = [").is_err());
+    }
+
+    #[test]
+    fn github_report_thread() {
+        let mut config = new_config().unwrap();
+        assert_eq!(config.github_thread(), None);
+
+        config.github_report_discussion = Some("pingcap/tidb#45".to_owned());
+        assert_eq!(
+            config.github_thread(),
+            Some(GitHubThread {
+                repo: "pingcap/tidb".to_owned(),
+                number: 45,
+                discussion: true,
+            })
+        );
+        assert_eq!(config.notifiers(), vec!["slack", "github"]);
+
+        config.github_report_discussion = Some("pingcap/tidb/45".to_owned());
+        assert_eq!(config.github_thread(), None);
+        let errors = config.validate();
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].starts_with("github-report-discussion"));
+    }
+
+    #[test]
+    fn read_token_from_file() {
+        let path = std::env::temp_dir().join("issues-watcher-github-token");
+        std::fs::write(&path, "file-github-token\n").unwrap();
+        let mut config: Config = toml::from_str(&format!(
+            "slack-token = \"\"\nslack-channel = \"\"\ngithub-token-file = {:?}",
+            path.to_str().unwrap()
+        ))
+        .unwrap();
+        config.read_secret_files().unwrap();
+        assert_eq!(config.github_token, "file-github-token");
+
+        config.slack_token_file = Some("/not/exist".to_owned());
+        assert!(config.read_secret_files().is_err());
+    }
+
+    #[test]
+    fn override_config_from_env() {
+        let mut config = new_config().unwrap();
+        env::set_var("ISSUES_WATCHER_TEST_GITHUB_TOKEN", "env-github-token");
+        env::set_var("ISSUES_WATCHER_TEST_SLACK_TOKEN", "env-slack-token");
+        config.override_from_env("ISSUES_WATCHER_TEST_");
+        assert_eq!(config.github_token, "env-github-token");
+        assert_eq!(config.slack_token, "env-slack-token");
+        assert_eq!(config.slack_channel, "slack-channel");
     }
 }
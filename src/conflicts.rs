@@ -0,0 +1,124 @@
+use chrono::Duration;
+use tracing::warn;
+
+use crate::config::Config;
+use crate::providers::github::{GitHub, Issue, Snapshot};
+use crate::Result;
+
+/// ConflictedPulls are the pull requests of a repo open for more than `days`
+/// which have merge conflicts with their base branches.
+#[derive(Debug, Clone)]
+pub struct ConflictedPulls {
+    pub repo: String,
+    pub days: i64,
+    pub issues: Vec<Issue>,
+}
+
+/// candidates returns the pull requests of the repos in the snapshot open for
+/// more than the days, by repo. Drafts are left out.
+pub fn candidates<'a>(
+    snapshot: &'a Snapshot,
+    repos: &[String],
+    days: i64,
+) -> Vec<(String, Vec<&'a Issue>)> {
+    let before = *snapshot.time() - Duration::days(days);
+    snapshot
+        .repo_issues()
+        .iter()
+        .filter(|repo| repos.contains(&repo.name()))
+        .filter_map(|repo| {
+            let pulls: Vec<&Issue> = repo
+                .issues()
+                .iter()
+                .filter(|issue| {
+                    issue.is_pull() && !issue.is_draft() && *issue.created_at() < before
+                })
+                .collect();
+            if pulls.is_empty() {
+                None
+            } else {
+                Some((repo.name(), pulls))
+            }
+        })
+        .collect()
+}
+
+/// detect checks the mergeable state of the pull requests of the watched
+/// GitHub repos open for more than `conflict-days`, nothing is checked if
+/// it's not set. A pull request failing the check is skipped.
+pub async fn detect(
+    conf: &Config,
+    client: &reqwest::Client,
+    snapshot: &Snapshot,
+) -> Result<Vec<ConflictedPulls>> {
+    let days = match conf.conflict_days {
+        Some(days) => days,
+        None => return Ok(vec![]),
+    };
+    let github_client = GitHub::new(client.clone(), conf.github_token.clone(), vec![], vec![])?;
+    let mut conflicts = vec![];
+    for (repo, pulls) in candidates(snapshot, &conf.repo_names(), days) {
+        let mut issues = vec![];
+        for pull in pulls {
+            match github_client.is_conflicted(pull).await {
+                Ok(true) => issues.push(pull.clone()),
+                Ok(false) => {}
+                Err(e) => warn!("check the mergeable state of {}: {}", pull, e),
+            }
+        }
+        if !issues.is_empty() {
+            conflicts.push(ConflictedPulls { repo, days, issues });
+        }
+    }
+    Ok(conflicts)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn long_open_pulls() {
+        let snapshot: Snapshot = serde_json::from_str(
+            r#"{
+                "time": "2020-05-01T00:00:00Z",
+                "repo_issues": [{
+                    "repo": {"owner": "pingcap", "repo": "parser"},
+                    "issues": [{
+                        "number": 1,
+                        "title": "old issue",
+                        "created_at": "2020-03-01T00:00:00Z",
+                        "author_association": "NONE",
+                        "labels": []
+                    }, {
+                        "number": 2,
+                        "title": "old pull",
+                        "pull_request": {"html_url": "https://github.com/pingcap/parser/pull/2"},
+                        "created_at": "2020-03-01T00:00:00Z",
+                        "author_association": "MEMBER",
+                        "labels": []
+                    }, {
+                        "number": 3,
+                        "title": "new pull",
+                        "pull_request": {"html_url": "https://github.com/pingcap/parser/pull/3"},
+                        "created_at": "2020-04-30T00:00:00Z",
+                        "author_association": "MEMBER",
+                        "labels": []
+                    }]
+                }, {
+                    "repo": {"owner": "pingcap", "repo": "tidb"},
+                    "issues": []
+                }],
+                "project_issues": []
+            }"#,
+        )
+        .unwrap();
+        let repos = vec!["pingcap/parser".to_owned(), "pingcap/tidb".to_owned()];
+        let found: Vec<(String, Vec<i32>)> = candidates(&snapshot, &repos, 14)
+            .into_iter()
+            .map(|(repo, pulls)| (repo, pulls.iter().map(|p| p.number()).collect()))
+            .collect();
+        assert_eq!(found, vec![("pingcap/parser".to_owned(), vec![2])]);
+        assert!(candidates(&snapshot, &[], 14).is_empty());
+    }
+}
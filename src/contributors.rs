@@ -0,0 +1,179 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+
+use chrono::{DateTime, Datelike, Duration, NaiveDate, Utc};
+use chrono_tz::Tz;
+
+use crate::config::Config;
+use crate::providers::github::GitHub;
+use crate::storage::{self, StoredComment};
+use crate::Result;
+
+/// WeekContributors are the people commenting on the issues and pull requests
+/// of the watched repos in a week.
+#[derive(Debug, PartialEq)]
+pub struct WeekContributors {
+    /// the Monday of the week
+    pub week: NaiveDate,
+    pub contributors: usize,
+    /// the ones commenting for the first time, members are never new
+    pub new: Vec<String>,
+}
+
+#[derive(Debug, Default, PartialEq)]
+pub struct ContributorStats {
+    pub weeks: Vec<WeekContributors>,
+    /// members by their comments on issues, the most first
+    pub responders: Vec<(String, usize)>,
+}
+
+/// stats sums up the comments from `since` to `until` by week, at most `top`
+/// responders are kept. Bots are left out.
+pub fn stats(
+    comments: &[StoredComment],
+    since: &DateTime<Utc>,
+    until: &DateTime<Utc>,
+    tz: &Tz,
+    top: usize,
+) -> ContributorStats {
+    let week_of = |time: &DateTime<Utc>| {
+        let day = time.with_timezone(tz).date().naive_local();
+        day - Duration::days(day.weekday().num_days_from_monday() as i64)
+    };
+    let mut first: HashMap<&str, DateTime<Utc>> = HashMap::new();
+    for comment in comments {
+        let time = first.entry(&comment.login).or_insert(comment.created_at);
+        *time = (*time).min(comment.created_at);
+    }
+    let mut weeks: BTreeMap<NaiveDate, (HashSet<&str>, BTreeSet<&str>)> = BTreeMap::new();
+    let mut responders: HashMap<&str, usize> = HashMap::new();
+    let window = comments.iter().filter(|c| {
+        &c.created_at >= since
+            && &c.created_at <= until
+            && !c.login.is_empty()
+            && !c.login.ends_with("[bot]")
+    });
+    for comment in window {
+        let login = &comment.login[..];
+        let week = weeks.entry(week_of(&comment.created_at)).or_default();
+        week.0.insert(login);
+        if !comment.member && first[login] == comment.created_at {
+            week.1.insert(login);
+        }
+        if comment.member && !comment.pull {
+            *responders.entry(login).or_default() += 1;
+        }
+    }
+    let mut responders: Vec<(String, usize)> = responders
+        .into_iter()
+        .map(|(login, n)| (login.to_owned(), n))
+        .collect();
+    responders.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    responders.truncate(top);
+    ContributorStats {
+        weeks: weeks
+            .into_iter()
+            .map(|(week, (all, new))| WeekContributors {
+                week,
+                contributors: all.len(),
+                new: new.into_iter().map(str::to_owned).collect(),
+            })
+            .collect(),
+        responders,
+    }
+}
+
+/// collect fetches the comments of the watched GitHub repos after the latest
+/// stored one of each repo, or since the time for a repo without any, if
+/// `contributor-stats` is set. It returns all the stored comments, the new
+/// ones are stored in `github-data` unless it's a dry run.
+pub async fn collect(
+    conf: &Config,
+    client: &reqwest::Client,
+    since: &DateTime<Utc>,
+    dry_run: bool,
+) -> Result<Vec<StoredComment>> {
+    if !conf.contributor_stats {
+        return Ok(vec![]);
+    }
+    let mut stored = storage::comments(&conf.github_data)?;
+    let mut urls: HashSet<String> = stored.iter().map(|c| c.url.clone()).collect();
+    let github_client = GitHub::new(client.clone(), conf.github_token.clone(), vec![], vec![])?;
+    for rule in conf.repo_rules() {
+        let from = stored
+            .iter()
+            .filter(|c| c.repo == rule.name)
+            .map(|c| c.created_at)
+            .max()
+            .unwrap_or(*since);
+        // edited comments are listed again
+        for comment in github_client.list_comments(&rule.name, &from).await? {
+            if !urls.insert(comment.html_url.clone()) {
+                continue;
+            }
+            stored.push(StoredComment {
+                repo: rule.name.clone(),
+                url: comment.html_url.clone(),
+                login: comment.login().to_owned(),
+                member: comment.by_member(&rule.members),
+                pull: comment.is_pull(),
+                created_at: comment.created_at,
+            });
+        }
+    }
+    stored.sort_by_key(|c| c.created_at);
+    if !dry_run {
+        storage::save_comments(&conf.github_data, &stored)?;
+    }
+    Ok(stored)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn contributor_stats() {
+        let comment = |login: &str, member: bool, pull: bool, day: u32| StoredComment {
+            repo: "pingcap/parser".to_owned(),
+            url: format!(
+                "https://github.com/pingcap/parser/issues/1#{}{}",
+                login, day
+            ),
+            login: login.to_owned(),
+            member,
+            pull,
+            created_at: Utc.ymd(2020, 5, day).and_hms(0, 0, 0),
+        };
+        let comments = vec![
+            comment("old", false, false, 1),
+            comment("old", false, false, 5),
+            comment("new", false, true, 6),
+            comment("new", false, false, 12),
+            comment("member", true, false, 5),
+            comment("member", true, false, 12),
+            comment("member", true, true, 12),
+            comment("reviewer", true, false, 11),
+            comment("ci-bot[bot]", false, false, 12),
+        ];
+        let since = Utc.ymd(2020, 5, 4).and_hms(0, 0, 0);
+        let until = Utc.ymd(2020, 5, 12).and_hms(12, 0, 0);
+        let stats = stats(&comments, &since, &until, &Tz::UTC, 1);
+        assert_eq!(
+            stats.weeks,
+            vec![
+                WeekContributors {
+                    week: NaiveDate::from_ymd(2020, 5, 4),
+                    contributors: 3,
+                    new: vec!["new".to_owned()],
+                },
+                WeekContributors {
+                    week: NaiveDate::from_ymd(2020, 5, 11),
+                    contributors: 3,
+                    new: vec![],
+                },
+            ]
+        );
+        assert_eq!(stats.responders, vec![("member".to_owned(), 2)]);
+    }
+}
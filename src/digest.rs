@@ -0,0 +1,429 @@
+use std::collections::{BTreeMap, HashMap};
+
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+use chrono_tz::Tz;
+use handlebars::no_escape;
+use serde::Serialize;
+
+use crate::config::Config;
+use crate::contributors::{self, ContributorStats};
+use crate::i18n::{fill, Messages};
+use crate::providers::github::{Issue, Snapshot};
+use crate::report::{render_template, IssueContext, Report, Templates};
+use crate::storage::{self, StoredComment};
+use crate::Result;
+
+/// Digest sums up the stored snapshots of a few days, it's sent less often
+/// than the daily report of stale issues.
+pub struct Digest {
+    pub since: DateTime<Utc>,
+    pub until: DateTime<Utc>,
+    pub repos: Vec<RepoDigest>,
+    /// the oldest stale issues, at most `digest-top` of them
+    pub stale: Vec<Issue>,
+    /// stale issues older than `sla-days`
+    pub sla_breaches: Vec<Issue>,
+    pub sla_days: Option<i64>,
+    /// milestones with due dates which are open in the latest snapshot
+    pub burndowns: Vec<Burndown>,
+    /// draw the burndowns as charts too
+    pub chart: bool,
+    /// empty unless `contributor-stats` is set
+    pub contributors: ContributorStats,
+}
+
+/// Burndown is the open issues of a milestone at the end of each day.
+#[derive(Debug, PartialEq)]
+pub struct Burndown {
+    pub repo: String,
+    pub milestone: String,
+    pub due_on: NaiveDate,
+    /// the oldest day first
+    pub days: Vec<(NaiveDate, usize)>,
+}
+
+impl Burndown {
+    /// slipping projects the average burndown rate of the days, the milestone
+    /// is slipping if it's not done by the due date at that rate.
+    pub fn slipping(&self) -> bool {
+        let ((first_day, first), (last_day, last)) = match (self.days.first(), self.days.last()) {
+            (Some(first), Some(last)) => (first, last),
+            _ => return false,
+        };
+        let elapsed = (*last_day - *first_day).num_days();
+        if *last == 0 || elapsed == 0 {
+            return false;
+        }
+        if first <= last {
+            return true;
+        }
+        let rate = (first - last) as f64 / elapsed as f64;
+        let needed = (*last as f64 / rate).ceil() as i64;
+        *last_day + Duration::days(needed) > self.due_on
+    }
+
+    // chart draws the counts as a bar of block characters.
+    fn chart(&self) -> String {
+        const BARS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+        let max = self.days.iter().map(|(_, n)| *n).max().unwrap_or(0).max(1);
+        self.days.iter().map(|(_, n)| BARS[n * 7 / max]).collect()
+    }
+}
+
+#[derive(Debug, Default, Eq, PartialEq)]
+pub struct RepoDigest {
+    pub repo: String,
+    pub opened: usize,
+    pub closed: usize,
+    /// open issues and pull requests in the latest snapshot
+    pub open: usize,
+}
+
+#[derive(Serialize)]
+struct Context {
+    title: String,
+    repos: Vec<String>,
+    stale_title: String,
+    stale: Vec<IssueContext>,
+    sla_title: String,
+    sla: Vec<IssueContext>,
+    burndown_title: String,
+    burndown: Vec<String>,
+    contributors_title: String,
+    contributors: Vec<String>,
+}
+
+impl Digest {
+    /// build sums up the snapshots stored in the last `digest-days` days before
+    /// the report, the stale issues are taken from the report. The contributors
+    /// are summed up from the stored comments.
+    pub fn build(conf: &Config, report: &Report, comments: &[StoredComment]) -> Result<Digest> {
+        let until = *report.snapshot.time();
+        let since = until - Duration::days(conf.digest_days);
+        let tz = conf.tz()?;
+        let mut counts: HashMap<String, (usize, usize)> = HashMap::new();
+        let mut milestones = Milestones::new();
+        // only two snapshots are held at a time
+        let mut previous: Option<Snapshot> = None;
+        for path in storage::since(&conf.github_data, &since)? {
+            let snapshot = storage::load(&path)?;
+            // the report is stored already unless it's a dry run
+            if snapshot.time() >= &until {
+                break;
+            }
+            if let Some(previous) = &previous {
+                add_diffs(&mut counts, &snapshot, previous);
+            }
+            add_milestones(&mut milestones, &snapshot, &tz);
+            previous = Some(snapshot);
+        }
+        if let Some(previous) = &previous {
+            add_diffs(&mut counts, &report.snapshot, previous);
+        }
+        add_milestones(&mut milestones, &report.snapshot, &tz);
+        let today = until.with_timezone(&tz).date().naive_local();
+        let burndowns = milestones
+            .into_iter()
+            // the closed milestones are not in the latest snapshot
+            .filter(|(_, (_, days))| days.contains_key(&today))
+            .map(|((repo, milestone), (due_on, days))| Burndown {
+                repo,
+                milestone,
+                due_on,
+                days: days.into_iter().collect(),
+            })
+            .collect();
+
+        let repos = report
+            .snapshot
+            .repo_issues()
+            .iter()
+            .map(|repo| {
+                let (opened, closed) = counts.remove(&repo.name()).unwrap_or_default();
+                RepoDigest {
+                    repo: repo.name(),
+                    opened,
+                    closed,
+                    open: repo.issues().len(),
+                }
+            })
+            .collect();
+
+        let mut stale: Vec<Issue> = report
+            .stale
+            .iter()
+            .flat_map(|repo| repo.issues.iter().cloned())
+            .collect();
+        stale.sort_by_key(|issue| *issue.created_at());
+        let sla_breaches = match conf.sla_days {
+            Some(days) => stale
+                .iter()
+                .filter(|issue| until - *issue.created_at() > Duration::days(days))
+                .cloned()
+                .collect(),
+            None => vec![],
+        };
+        stale.truncate(conf.digest_top);
+
+        Ok(Digest {
+            since,
+            until,
+            repos,
+            stale,
+            sla_breaches,
+            sla_days: conf.sla_days,
+            burndowns,
+            chart: conf.burndown_chart,
+            contributors: contributors::stats(comments, &since, &until, &tz, conf.digest_top),
+        })
+    }
+
+    /// render builds the plain text digest by the digest template.
+    pub fn render(&self, templates: &Templates, messages: &Messages, tz: &Tz) -> Result<String> {
+        let issue = |issue: &Issue| IssueContext::new(issue, &self.until);
+        let context = Context {
+            title: fill(
+                messages.digest_title,
+                &[
+                    &self.since.with_timezone(tz).date().naive_local(),
+                    &self.until.with_timezone(tz).date().naive_local(),
+                ],
+            ),
+            repos: self
+                .repos
+                .iter()
+                .map(|r| {
+                    fill(
+                        messages.digest_repo,
+                        &[&r.repo, &r.opened, &r.closed, &r.open],
+                    )
+                })
+                .collect(),
+            stale_title: fill(messages.digest_stale, &[&self.stale.len()]),
+            stale: self.stale.iter().map(issue).collect(),
+            sla_title: fill(
+                messages.digest_sla,
+                &[&self.sla_breaches.len(), &self.sla_days.unwrap_or_default()],
+            ),
+            sla: self.sla_breaches.iter().map(issue).collect(),
+            burndown_title: messages.burndown_title.to_owned(),
+            burndown: self
+                .burndowns
+                .iter()
+                .map(|b| render_burndown(b, self.chart, messages))
+                .collect(),
+            contributors_title: messages.contributors_title.to_owned(),
+            contributors: render_contributors(&self.contributors, messages),
+        };
+        render_template(&templates.digest, no_escape, &context)
+    }
+}
+
+// render_burndown renders a line of the counts, and the chart if `chart`.
+fn render_burndown(burndown: &Burndown, chart: bool, messages: &Messages) -> String {
+    let counts: Vec<String> = burndown.days.iter().map(|(_, n)| n.to_string()).collect();
+    let mut text = fill(
+        messages.burndown_milestone,
+        &[
+            &burndown.repo,
+            &burndown.milestone,
+            &burndown.due_on,
+            &counts.join(" → "),
+        ],
+    );
+    if chart {
+        text.push(' ');
+        text.push_str(&burndown.chart());
+    }
+    if burndown.slipping() {
+        text.push(' ');
+        text.push_str(messages.burndown_slipping);
+    }
+    text
+}
+
+// render_contributors renders a line of each week with the new contributors,
+// and a line of the top responders.
+fn render_contributors(stats: &ContributorStats, messages: &Messages) -> Vec<String> {
+    let mut lines: Vec<String> = stats
+        .weeks
+        .iter()
+        .map(|w| {
+            let mut text = fill(
+                messages.contributors_week,
+                &[&w.week, &w.contributors, &w.new.len()],
+            );
+            if !w.new.is_empty() {
+                text.push_str(&format!(" ({})", w.new.join(", ")));
+            }
+            text
+        })
+        .collect();
+    if !stats.responders.is_empty() {
+        let responders: Vec<String> = stats
+            .responders
+            .iter()
+            .map(|(login, n)| format!("{} ({})", login, n))
+            .collect();
+        lines.push(fill(
+            messages.contributors_responders,
+            &[&responders.join(", ")],
+        ));
+    }
+    lines
+}
+
+// Milestones are the open issues of each day by repo and milestone, with the due date.
+type Milestones = BTreeMap<(String, String), (NaiveDate, BTreeMap<NaiveDate, usize>)>;
+
+// add_milestones counts the open issues of the milestones with due dates in a
+// snapshot, a later snapshot of the same day replaces the count.
+fn add_milestones(milestones: &mut Milestones, snapshot: &Snapshot, tz: &Tz) {
+    let day = snapshot.time().with_timezone(tz).date().naive_local();
+    let mut counts: HashMap<(String, String), (NaiveDate, usize)> = HashMap::new();
+    for repo in snapshot.repo_issues() {
+        for issue in repo.issues() {
+            if let (Some(title), Some(due_on)) = (issue.milestone(), issue.milestone_due_on()) {
+                let due_on = due_on.with_timezone(tz).date().naive_local();
+                counts
+                    .entry((repo.name(), title.to_owned()))
+                    .or_insert((due_on, 0))
+                    .1 += 1;
+            }
+        }
+    }
+    for (key, (due_on, count)) in counts {
+        let entry = milestones
+            .entry(key)
+            .or_insert_with(|| (due_on, BTreeMap::new()));
+        entry.0 = due_on;
+        entry.1.insert(day, count);
+    }
+}
+
+// add_diffs counts the opened and closed issues between two snapshots.
+fn add_diffs(counts: &mut HashMap<String, (usize, usize)>, new: &Snapshot, old: &Snapshot) {
+    for diff in new.diff(old) {
+        let count = counts.entry(diff.repo).or_default();
+        count.0 += diff.opened.len();
+        count.1 += diff.closed.len();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::i18n::Locale;
+    use crate::providers::github::StaleIssues;
+
+    fn snapshot(time: &str, numbers: &[i32]) -> Snapshot {
+        let issues: Vec<String> = numbers
+            .iter()
+            .map(|n| {
+                format!(
+                    r#"{{"number": {}, "title": "issue {}", "created_at": "2020-04-0{}T00:00:00Z",
+                        "html_url": "https://github.com/pingcap/parser/issues/{}",
+                        "milestone": {{"number": 1, "title": "v4.0", "due_on": "2020-05-10T00:00:00Z"}},
+                        "author_association": "NONE", "labels": []}}"#,
+                    n, n, n, n
+                )
+            })
+            .collect();
+        serde_json::from_str(&format!(
+            r#"{{"time": "{}", "repo_issues": [{{
+                "repo": {{"owner": "pingcap", "repo": "parser"}},
+                "issues": [{}]
+            }}], "project_issues": []}}"#,
+            time,
+            issues.join(",")
+        ))
+        .unwrap()
+    }
+
+    #[test]
+    fn build_digest() {
+        let dir = std::env::temp_dir().join("issues-watcher-digest-test");
+        let _ = std::fs::remove_dir_all(&dir);
+        let mut conf: Config = toml::from_str(&format!(
+            "slack-channel = \"\"\ngithub-data = {:?}\nsla-days = 33",
+            dir.to_str().unwrap()
+        ))
+        .unwrap();
+        conf.digest_top = 1;
+        conf.burndown_chart = true;
+        // the first one is out of the window
+        let stored: &[(&str, &[i32])] = &[
+            ("2020-04-20T00:00:00Z", &[]),
+            ("2020-05-01T00:00:00Z", &[1, 2]),
+            ("2020-05-04T00:00:00Z", &[1, 3]),
+        ];
+        for (time, numbers) in stored {
+            storage::save(&conf.github_data, &snapshot(time, numbers)).unwrap();
+        }
+
+        let latest = snapshot("2020-05-07T00:00:00Z", &[1, 3, 4]);
+        let stale = latest.repo_issues()[0].issues().to_vec();
+        let report = Report {
+            stale: vec![StaleIssues {
+                repo: "pingcap/parser".to_owned(),
+                days: 3,
+                issues: stale,
+            }],
+            ..Report::new(latest)
+        };
+        let comments: Vec<StoredComment> = serde_json::from_str(
+            r#"[{
+                "repo": "pingcap/parser",
+                "url": "https://github.com/pingcap/parser/issues/3#issuecomment-1",
+                "login": "you06",
+                "member": true,
+                "pull": false,
+                "created_at": "2020-05-05T00:00:00Z"
+            }, {
+                "repo": "pingcap/parser",
+                "url": "https://github.com/pingcap/parser/issues/3#issuecomment-2",
+                "login": "newbie",
+                "member": false,
+                "pull": false,
+                "created_at": "2020-05-06T00:00:00Z"
+            }]"#,
+        )
+        .unwrap();
+        let digest = Digest::build(&conf, &report, &comments).unwrap();
+        assert_eq!(
+            digest.repos,
+            vec![RepoDigest {
+                repo: "pingcap/parser".to_owned(),
+                opened: 2,
+                closed: 1,
+                open: 3,
+            }]
+        );
+        assert_eq!(digest.stale.len(), 1);
+        assert_eq!(digest.stale[0].number(), 1);
+        assert_eq!(digest.sla_breaches.len(), 2);
+
+        let text = digest
+            .render(&Templates::default(), Locale::En.messages(), &Tz::UTC)
+            .unwrap();
+        assert_eq!(
+            text,
+            "Digest from 2020-04-30 to 2020-05-07\n\
+             pingcap/parser: 2 opened, 1 closed, 3 open now\n\
+             \n\
+             Milestone burndown\n\
+             pingcap/parser v4.0 due 2020-05-10: 2 → 2 → 3 open ▅▅█ (slipping)\n\
+             \n\
+             Contributors\n\
+             Week of 2020-05-04: 2 contributors, 1 new (newbie)\n\
+             Top responders on issues: you06 (1)\n\
+             \n\
+             Top 1 issues without reply\n\
+             36d issue 1 https://github.com/pingcap/parser/issues/1\n\
+             \n\
+             2 issues without reply for more than 33 days\n\
+             36d issue 1 https://github.com/pingcap/parser/issues/1\n\
+             34d issue 3 https://github.com/pingcap/parser/issues/3\n"
+        );
+    }
+}
@@ -0,0 +1,257 @@
+use crate::config::Config;
+use crate::providers::github::GitHub;
+use crate::providers::opsgenie::Opsgenie;
+use crate::providers::slack::Slack;
+use crate::providers::wecom::WeCom;
+use crate::snapshot;
+
+/// Check is the result of checking one target of an integration, like a repo
+/// of GitHub or a channel of Slack, with what's found or why it failed.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Check {
+    pub integration: String,
+    pub target: String,
+    pub result: Result<String, String>,
+}
+
+impl Check {
+    fn new<E: ToString>(integration: &str, target: &str, result: Result<String, E>) -> Self {
+        Check {
+            integration: integration.to_owned(),
+            target: target.to_owned(),
+            result: result.map_err(|e| e.to_string()),
+        }
+    }
+}
+
+/// run checks the config and every configured integration: the GitHub token,
+/// its scopes and the access to each watched repo, project and the thread the
+/// reports are posted to, the Slack token and the channels it posts to, the
+/// keys of WeCom and Opsgenie, and the other providers listing their issues.
+pub async fn run(conf: &Config, client: &reqwest::Client) -> Vec<Check> {
+    let mut checks: Vec<Check> = conf
+        .validate()
+        .into_iter()
+        .map(|e| Check::new("config", "", Err(e)))
+        .collect();
+    checks.extend(check_github(conf, client).await);
+    if !conf.slack_token.is_empty() {
+        checks.extend(check_slack(conf, client).await);
+    }
+    if !conf.wecom_webhook.is_empty() {
+        let checked = match WeCom::new(client.clone(), &conf.wecom_webhook) {
+            Ok(wecom_client) => wecom_client
+                .check_key()
+                .await
+                .map(|_| "valid key".to_owned()),
+            Err(e) => Err(e),
+        };
+        checks.push(Check::new("wecom", "webhook", checked));
+    }
+    if !conf.opsgenie_api_key.is_empty() {
+        let checked =
+            match Opsgenie::new(client.clone(), &conf.opsgenie_url, &conf.opsgenie_api_key) {
+                Ok(opsgenie) => opsgenie
+                    .check_key()
+                    .await
+                    .map(|_| conf.opsgenie_url.clone()),
+                Err(e) => Err(e),
+            };
+        checks.push(Check::new("opsgenie", "api key", checked));
+    }
+    match snapshot::extra_providers(conf, client) {
+        Ok(providers) => {
+            for provider in providers {
+                let listed = provider.list_issues().await.map(|repos| {
+                    let names: Vec<String> = repos.iter().map(|r| r.name()).collect();
+                    names.join(", ")
+                });
+                checks.push(Check::new(provider.name(), "issues", listed));
+            }
+        }
+        Err(e) => checks.push(Check::new("providers", "", Err(e))),
+    }
+    checks
+}
+
+async fn check_github(conf: &Config, client: &reqwest::Client) -> Vec<Check> {
+    let github_client = match GitHub::new(client.clone(), conf.github_token.clone(), vec![], vec![])
+    {
+        Ok(github_client) => github_client,
+        Err(e) => return vec![Check::new("github", "token", Err(e))],
+    };
+    let user = match github_client.get_user_result().await {
+        Ok(user) => user,
+        Err(e) => return vec![Check::new("github", "token", Err(e))],
+    };
+    let mut checks = vec![Check::new(
+        "github",
+        "token",
+        match missing_scopes(conf, user.scopes()) {
+            missing if missing.is_empty() => Ok(format!(
+                "{}, scopes: {}",
+                user.login(),
+                user.scopes().join(", ")
+            )),
+            missing => Err(format!("missing scopes: {}", missing.join(", "))),
+        },
+    )];
+    for repo in conf.repo_names() {
+        let access = github_client.repo_access(&repo).await.map(|access| {
+            let visibility = if access.private { "private" } else { "public" };
+            let permission = if access.push { "read, write" } else { "read" };
+            format!("{}, {}", visibility, permission)
+        });
+        checks.push(Check::new("github", &repo, access));
+    }
+    for project in &conf.projects {
+        let resolved = match GitHub::new(
+            client.clone(),
+            conf.github_token.clone(),
+            vec![],
+            vec![project.clone()],
+        ) {
+            Ok(mut project_client) => project_client
+                .get_projects_id()
+                .await
+                .map(|_| "found".to_owned()),
+            Err(e) => Err(e),
+        };
+        checks.push(Check::new("github", project, resolved));
+    }
+    if let Some(thread) = conf.github_thread() {
+        let found = github_client
+            .thread_url(&thread.repo, thread.number, thread.discussion)
+            .await;
+        let target = format!("{}#{}", thread.repo, thread.number);
+        checks.push(Check::new("github", &target, found));
+    }
+    checks
+}
+
+// missing_scopes returns the scopes of a classic token the config needs which
+// it doesn't have, fine-grained tokens have no scopes to check.
+fn missing_scopes(conf: &Config, scopes: &[String]) -> Vec<&'static str> {
+    if scopes.is_empty() {
+        return vec![];
+    }
+    let mut needed = vec![];
+    let mut teams = conf.members.iter().chain(
+        conf.repo_configs
+            .iter()
+            .flat_map(|r| r.members.iter().flatten()),
+    );
+    if teams.any(|m| m.starts_with('@')) {
+        needed.push("read:org");
+    }
+    if conf.gist_threshold > 0 {
+        needed.push("gist");
+    }
+    needed
+        .into_iter()
+        .filter(|scope| !scopes.iter().any(|s| s == scope))
+        .collect()
+}
+
+async fn check_slack(conf: &Config, client: &reqwest::Client) -> Vec<Check> {
+    let slack_client = match Slack::new(client.clone(), conf.slack_token.clone()) {
+        Ok(slack_client) => slack_client,
+        Err(e) => return vec![Check::new("slack", "token", Err(e))],
+    };
+    let mut checks = vec![Check::new("slack", "token", slack_client.auth_test().await)];
+    if checks[0].result.is_err() {
+        return checks;
+    }
+    let mut names: Vec<&str> = vec![];
+    for name in std::iter::once(&conf.slack_channel)
+        .chain(&conf.escalate_channel)
+        .chain(&conf.confidential_channel)
+        .chain(conf.team_routes.iter().flat_map(|r| &r.slack_channel))
+    {
+        let name = name.trim_start_matches('#');
+        if !name.is_empty() && !names.contains(&name) {
+            names.push(name);
+        }
+    }
+    if names.is_empty() {
+        return checks;
+    }
+    let channels = match slack_client.channels().await {
+        Ok(channels) => channels,
+        Err(e) => {
+            checks.push(Check::new("slack", "channels", Err(e)));
+            return checks;
+        }
+    };
+    for name in names {
+        let joined = match channels.iter().find(|c| c.name == name) {
+            Some(channel) if channel.is_member => Ok(format!("joined {}", channel.id)),
+            Some(_) => Err("not a member, invite the app to the channel".to_owned()),
+            None => Err("not found, or private without the app in it".to_owned()),
+        };
+        checks.push(Check::new("slack", &format!("#{}", name), joined));
+    }
+    checks
+}
+
+/// render prints the checks as a table, the failed ones with why.
+pub fn render(checks: &[Check]) -> String {
+    let width = |f: fn(&Check) -> usize| checks.iter().map(f).max().unwrap_or(0);
+    let integration_width = width(|c| c.integration.len());
+    let target_width = width(|c| c.target.len());
+    let mut table = String::new();
+    for check in checks {
+        let (status, detail) = match &check.result {
+            Ok(detail) => ("ok", detail),
+            Err(reason) => ("FAIL", reason),
+        };
+        let line = format!(
+            "{:iw$}  {:tw$}  {:4}  {}",
+            check.integration,
+            check.target,
+            status,
+            detail,
+            iw = integration_width,
+            tw = target_width
+        );
+        table.push_str(line.trim_end());
+        table.push('\n');
+    }
+    table
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn render_checks() {
+        let checks = vec![
+            Check::new::<String>("github", "token", Ok("you06, scopes: repo".to_owned())),
+            Check::new(
+                "github",
+                "pingcap/tidb",
+                Ok::<_, String>("public, read".to_owned()),
+            ),
+            Check::new(
+                "slack",
+                "#tidb-dev",
+                Err("not a member, invite the app to the channel"),
+            ),
+        ];
+        assert_eq!(
+            render(&checks),
+            "github  token         ok    you06, scopes: repo\n\
+             github  pingcap/tidb  ok    public, read\n\
+             slack   #tidb-dev     FAIL  not a member, invite the app to the channel\n"
+        );
+
+        let conf: Config =
+            toml::from_str("slack-channel = \"\"\nmembers = [\"@pingcap/tidb-team\"]").unwrap();
+        assert_eq!(
+            missing_scopes(&conf, &["repo".to_owned()]),
+            vec!["read:org"]
+        );
+        assert!(missing_scopes(&conf, &[]).is_empty());
+    }
+}
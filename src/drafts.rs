@@ -0,0 +1,147 @@
+use chrono::Duration;
+use tracing::{info, warn};
+
+use crate::config::Config;
+use crate::i18n::fill;
+use crate::providers::github::{GitHub, Issue, Snapshot};
+use crate::storage;
+use crate::Result;
+
+/// AgingDrafts are the draft pull requests of a repo open for more than `days`,
+/// listed apart from the ones ready for review.
+#[derive(Debug, Clone)]
+pub struct AgingDrafts {
+    pub repo: String,
+    pub days: i64,
+    pub issues: Vec<Issue>,
+}
+
+/// aging returns the draft pull requests in the snapshot open for more than
+/// the days, by repo.
+pub fn aging(snapshot: &Snapshot, days: i64) -> Vec<AgingDrafts> {
+    let before = *snapshot.time() - Duration::days(days);
+    snapshot
+        .repo_issues()
+        .iter()
+        .filter_map(|repo| {
+            let issues: Vec<Issue> = repo
+                .issues()
+                .iter()
+                .filter(|issue| issue.is_draft() && *issue.created_at() < before)
+                .cloned()
+                .collect();
+            if issues.is_empty() {
+                None
+            } else {
+                Some(AgingDrafts {
+                    repo: repo.name(),
+                    days,
+                    issues,
+                })
+            }
+        })
+        .collect()
+}
+
+/// ping comments on each aging draft of the watched GitHub repos to ask its
+/// author whether it's still in progress, once a draft. The pinged drafts are
+/// stored in `github-data`, and nothing is commented or stored in a dry run.
+pub async fn ping(
+    conf: &Config,
+    client: &reqwest::Client,
+    drafts: &[AgingDrafts],
+    dry_run: bool,
+) -> Result<()> {
+    if !conf.draft_ping {
+        return Ok(());
+    }
+    let mut pinged = storage::pinged_drafts(&conf.github_data)?;
+    let repos = conf.repo_names();
+    // the drafts which are closed or ready for review can't be pinged again
+    let urls: Vec<String> = drafts
+        .iter()
+        .flat_map(|d| d.issues.iter().map(|i| i.to_string()))
+        .collect();
+    pinged.retain(|url| urls.contains(url));
+    let github_client = GitHub::new(client.clone(), conf.github_token.clone(), vec![], vec![])?;
+    for repo in drafts.iter().filter(|d| repos.contains(&d.repo)) {
+        for issue in &repo.issues {
+            let url = issue.to_string();
+            if pinged.contains(&url) {
+                continue;
+            }
+            let text = fill(
+                conf.locale.messages().draft_ping,
+                &[&issue.author().unwrap_or("author"), &repo.days],
+            );
+            if dry_run {
+                println!("[dry-run] comment on {}: {}", url, text);
+                continue;
+            }
+            let commented = github_client
+                .comment_issue(&repo.repo, issue.number(), &text)
+                .await;
+            if let Err(e) = commented {
+                warn!("failed to ping the author of {}: {}", url, e);
+                continue;
+            }
+            info!("pinged the author of {}", url);
+            pinged.push(url);
+            // stored at once, a draft is never pinged twice even if the run fails
+            storage::save_pinged_drafts(&conf.github_data, &pinged)?;
+        }
+    }
+    if !dry_run {
+        storage::save_pinged_drafts(&conf.github_data, &pinged)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn aging_drafts() {
+        let snapshot: Snapshot = serde_json::from_str(
+            r#"{
+                "time": "2020-05-01T00:00:00Z",
+                "repo_issues": [{
+                    "repo": {"owner": "pingcap", "repo": "parser"},
+                    "issues": [{
+                        "number": 1,
+                        "title": "old pull",
+                        "pull_request": {"html_url": "https://github.com/pingcap/parser/pull/1"},
+                        "created_at": "2020-03-01T00:00:00Z",
+                        "author_association": "MEMBER",
+                        "labels": []
+                    }, {
+                        "number": 2,
+                        "title": "old draft",
+                        "pull_request": {"html_url": "https://github.com/pingcap/parser/pull/2"},
+                        "draft": true,
+                        "created_at": "2020-03-01T00:00:00Z",
+                        "author_association": "MEMBER",
+                        "labels": []
+                    }, {
+                        "number": 3,
+                        "title": "new draft",
+                        "pull_request": {"html_url": "https://github.com/pingcap/parser/pull/3"},
+                        "draft": true,
+                        "created_at": "2020-04-30T00:00:00Z",
+                        "author_association": "MEMBER",
+                        "labels": []
+                    }]
+                }],
+                "project_issues": []
+            }"#,
+        )
+        .unwrap();
+        let drafts = aging(&snapshot, 14);
+        assert_eq!(drafts.len(), 1);
+        assert_eq!(drafts[0].repo, "pingcap/parser");
+        let numbers: Vec<i32> = drafts[0].issues.iter().map(|i| i.number()).collect();
+        assert_eq!(numbers, vec![2]);
+        assert!(aging(&snapshot, 100).is_empty());
+    }
+}
@@ -0,0 +1,428 @@
+use std::str::FromStr;
+
+use chrono::{DateTime, Utc};
+
+use crate::providers::github::Issue;
+
+/// Expr is a filter expression of issues, like
+/// `labels contains "type/bug" and age > 30d and assignee == none`.
+///
+/// Fields are `labels`, `assignee`, `author`, `title`, `repo`, `milestone`,
+/// `age` and `updated` in days, `comments`, `pull`, `draft` and the fields of
+/// issue forms like `field.affected-version`. Comparisons
+/// are `==`, `!=`, `>`, `>=`, `<`, `<=` and `contains`, combined by `and`,
+/// `or`, `not` and parentheses. Texts are compared case-insensitively.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Expr {
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Not(Box<Expr>),
+    Compare(Field, Op, Value),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Field {
+    Labels,
+    Assignee,
+    Author,
+    Title,
+    Repo,
+    Milestone,
+    Age,
+    Updated,
+    Comments,
+    Pull,
+    Draft,
+    /// a field of the issue form, by its field name
+    Form(String),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Op {
+    Eq,
+    Ne,
+    Gt,
+    Ge,
+    Lt,
+    Le,
+    Contains,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    Text(String),
+    /// days like `30d`, or a plain number
+    Number(i64),
+    Bool(bool),
+    None,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Word(String),
+    Text(String),
+    Number(i64),
+    Op(&'static str),
+    Open,
+    Close,
+}
+
+impl Expr {
+    /// matches evaluates the expression on the issue, the ages are from `now`.
+    pub fn matches(&self, issue: &Issue, now: &DateTime<Utc>) -> bool {
+        match self {
+            Expr::And(a, b) => a.matches(issue, now) && b.matches(issue, now),
+            Expr::Or(a, b) => a.matches(issue, now) || b.matches(issue, now),
+            Expr::Not(a) => !a.matches(issue, now),
+            Expr::Compare(field, op, value) => compare(issue, now, field, *op, value),
+        }
+    }
+}
+
+fn compare(issue: &Issue, now: &DateTime<Utc>, field: &Field, op: Op, value: &Value) -> bool {
+    let days = |time: &DateTime<Utc>| (*now - *time).num_days();
+    match field {
+        Field::Labels => compare_all(issue.labels(), op, value),
+        Field::Assignee => compare_all(issue.assignees(), op, value),
+        Field::Author => compare_text(issue.author(), op, value),
+        Field::Title => compare_text(Some(issue.title()), op, value),
+        Field::Milestone => compare_text(issue.milestone(), op, value),
+        Field::Repo => {
+            let repo = issue.repo_name();
+            // just the repo name matches too
+            let name = repo.rsplit('/').next();
+            match (op, value) {
+                (Op::Eq, Value::Text(_)) => {
+                    compare_text(Some(&repo), op, value) || compare_text(name, op, value)
+                }
+                (Op::Ne, Value::Text(_)) => {
+                    compare_text(Some(&repo), op, value) && compare_text(name, op, value)
+                }
+                _ => compare_text(Some(&repo), op, value),
+            }
+        }
+        Field::Age => compare_number(days(issue.created_at()), op, value),
+        Field::Updated => compare_number(days(issue.updated_at()), op, value),
+        Field::Comments => compare_number(issue.comments() as i64, op, value),
+        Field::Pull => compare_bool(issue.is_pull(), op, value),
+        Field::Draft => compare_bool(issue.is_draft(), op, value),
+        Field::Form(name) => compare_text(issue.field(name), op, value),
+    }
+}
+
+// compare_all compares a field of many values, `== none` checks it's empty,
+// `==` and `contains` check any of the values is equal, `!=` checks none is.
+fn compare_all<'a>(mut values: impl Iterator<Item = &'a str>, op: Op, value: &Value) -> bool {
+    match (op, value) {
+        (Op::Eq, Value::None) => values.next().is_none(),
+        (Op::Ne, Value::None) => values.next().is_some(),
+        (Op::Eq, Value::Text(text)) | (Op::Contains, Value::Text(text)) => {
+            values.any(|v| v.eq_ignore_ascii_case(text))
+        }
+        (Op::Ne, Value::Text(text)) => !values.any(|v| v.eq_ignore_ascii_case(text)),
+        _ => false,
+    }
+}
+
+fn compare_text(field: Option<&str>, op: Op, value: &Value) -> bool {
+    match (op, value, field) {
+        (Op::Eq, Value::None, field) => field.is_none(),
+        (Op::Ne, Value::None, field) => field.is_some(),
+        (Op::Eq, Value::Text(text), Some(field)) => field.eq_ignore_ascii_case(text),
+        (Op::Ne, Value::Text(text), Some(field)) => !field.eq_ignore_ascii_case(text),
+        (Op::Ne, Value::Text(_), None) => true,
+        (Op::Contains, Value::Text(text), Some(field)) => {
+            field.to_lowercase().contains(&text.to_lowercase())
+        }
+        _ => false,
+    }
+}
+
+fn compare_number(field: i64, op: Op, value: &Value) -> bool {
+    let value = match value {
+        Value::Number(n) => *n,
+        _ => return false,
+    };
+    match op {
+        Op::Eq => field == value,
+        Op::Ne => field != value,
+        Op::Gt => field > value,
+        Op::Ge => field >= value,
+        Op::Lt => field < value,
+        Op::Le => field <= value,
+        Op::Contains => false,
+    }
+}
+
+fn compare_bool(field: bool, op: Op, value: &Value) -> bool {
+    match (op, value) {
+        (Op::Eq, Value::Bool(b)) => field == *b,
+        (Op::Ne, Value::Bool(b)) => field != *b,
+        _ => false,
+    }
+}
+
+impl FromStr for Expr {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let tokens = tokenize(s)?;
+        let mut parser = Parser { tokens, pos: 0 };
+        let expr = parser.or()?;
+        match parser.tokens.get(parser.pos) {
+            None => Ok(expr),
+            Some(token) => Err(format!("unexpected {:?} in {:?}", token, s)),
+        }
+    }
+}
+
+fn tokenize(s: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = vec![];
+    let mut chars = s.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+        } else if c == '(' || c == ')' {
+            chars.next();
+            tokens.push(if c == '(' { Token::Open } else { Token::Close });
+        } else if c == '"' {
+            chars.next();
+            let mut text = String::new();
+            loop {
+                match chars.next() {
+                    Some('"') => break,
+                    Some('\\') => text.extend(chars.next()),
+                    Some(c) => text.push(c),
+                    None => return Err(format!("unterminated string in {:?}", s)),
+                }
+            }
+            tokens.push(Token::Text(text));
+        } else if c.is_ascii_digit() {
+            let mut digits = String::new();
+            while let Some(&c) = chars.peek().filter(|c| c.is_ascii_digit()) {
+                digits.push(c);
+                chars.next();
+            }
+            // 30d is 30 days
+            if chars.peek() == Some(&'d') {
+                chars.next();
+            }
+            let n = digits
+                .parse()
+                .map_err(|_| format!("invalid number {} in {:?}", digits, s))?;
+            tokens.push(Token::Number(n));
+        } else if c.is_alphabetic() {
+            let mut word = String::new();
+            // field names of issue forms are like field.affected-version
+            let in_word = |c: &&char| c.is_alphanumeric() || ['_', '.', '-'].contains(*c);
+            while let Some(&c) = chars.peek().filter(in_word) {
+                word.push(c);
+                chars.next();
+            }
+            tokens.push(Token::Word(word));
+        } else {
+            chars.next();
+            let next = chars.peek().copied();
+            let op = match (c, next) {
+                ('=', Some('=')) => "==",
+                ('!', Some('=')) => "!=",
+                ('>', Some('=')) => ">=",
+                ('<', Some('=')) => "<=",
+                ('>', _) => ">",
+                ('<', _) => "<",
+                _ => return Err(format!("unexpected {:?} in {:?}", c, s)),
+            };
+            if op.len() == 2 {
+                chars.next();
+            }
+            tokens.push(Token::Op(op));
+        }
+    }
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn keyword(&mut self, word: &str) -> bool {
+        match self.tokens.get(self.pos) {
+            Some(Token::Word(w)) if w == word => {
+                self.pos += 1;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn or(&mut self) -> Result<Expr, String> {
+        let mut expr = self.and()?;
+        while self.keyword("or") {
+            expr = Expr::Or(Box::new(expr), Box::new(self.and()?));
+        }
+        Ok(expr)
+    }
+
+    fn and(&mut self) -> Result<Expr, String> {
+        let mut expr = self.not()?;
+        while self.keyword("and") {
+            expr = Expr::And(Box::new(expr), Box::new(self.not()?));
+        }
+        Ok(expr)
+    }
+
+    fn not(&mut self) -> Result<Expr, String> {
+        if self.keyword("not") {
+            return Ok(Expr::Not(Box::new(self.not()?)));
+        }
+        match self.next() {
+            Some(Token::Open) => {
+                let expr = self.or()?;
+                match self.next() {
+                    Some(Token::Close) => Ok(expr),
+                    _ => Err("missing )".to_owned()),
+                }
+            }
+            Some(Token::Word(word)) => self.compare(&word),
+            Some(token) => Err(format!("expect a field, got {:?}", token)),
+            None => Err("expect a field, got the end".to_owned()),
+        }
+    }
+
+    fn compare(&mut self, name: &str) -> Result<Expr, String> {
+        let field = match name {
+            "labels" | "label" => Field::Labels,
+            "assignee" | "assignees" => Field::Assignee,
+            "author" => Field::Author,
+            "title" => Field::Title,
+            "repo" => Field::Repo,
+            "milestone" => Field::Milestone,
+            "age" => Field::Age,
+            "updated" => Field::Updated,
+            "comments" => Field::Comments,
+            "pull" => Field::Pull,
+            "draft" => Field::Draft,
+            _ if name.starts_with("field.") => Field::Form(name["field.".len()..].to_owned()),
+            _ => return Err(format!("unknown field {:?}", name)),
+        };
+        let op = match self.next() {
+            Some(Token::Op("==")) => Op::Eq,
+            Some(Token::Op("!=")) => Op::Ne,
+            Some(Token::Op(">")) => Op::Gt,
+            Some(Token::Op(">=")) => Op::Ge,
+            Some(Token::Op("<")) => Op::Lt,
+            Some(Token::Op("<=")) => Op::Le,
+            Some(Token::Word(w)) if w == "contains" => Op::Contains,
+            token => {
+                return Err(format!(
+                    "expect an operator after {}, got {:?}",
+                    name, token
+                ))
+            }
+        };
+        let value = match self.next() {
+            Some(Token::Text(text)) => Value::Text(text),
+            Some(Token::Number(n)) => Value::Number(n),
+            Some(Token::Word(w)) if w == "none" => Value::None,
+            Some(Token::Word(w)) if w == "true" => Value::Bool(true),
+            Some(Token::Word(w)) if w == "false" => Value::Bool(false),
+            token => {
+                return Err(format!(
+                    "expect a value to compare {} with, got {:?}",
+                    name, token
+                ))
+            }
+        };
+        let valid = match field {
+            Field::Labels
+            | Field::Assignee
+            | Field::Author
+            | Field::Title
+            | Field::Repo
+            | Field::Milestone
+            | Field::Form(_) => match value {
+                Value::None => op == Op::Eq || op == Op::Ne,
+                Value::Text(_) => op == Op::Eq || op == Op::Ne || op == Op::Contains,
+                _ => false,
+            },
+            Field::Age | Field::Updated | Field::Comments => {
+                matches!(value, Value::Number(_)) && op != Op::Contains
+            }
+            Field::Pull | Field::Draft => {
+                matches!(value, Value::Bool(_)) && (op == Op::Eq || op == Op::Ne)
+            }
+        };
+        if !valid {
+            return Err(format!(
+                "{} can't be compared by {:?} with {:?}",
+                name, op, value
+            ));
+        }
+        Ok(Expr::Compare(field, op, value))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn parse_and_match() {
+        let issues: Vec<Issue> = serde_json::from_str(
+            r#"[{
+                "number": 1,
+                "title": "Panic in the parser",
+                "created_at": "2020-03-01T00:00:00Z",
+                "author_association": "NONE",
+                "user": {"login": "someone"},
+                "labels": [{"id": 1, "name": "type/bug", "description": null}]
+            }, {
+                "number": 2,
+                "title": "support window functions",
+                "assignee": {"id": 1, "login": "you06"},
+                "created_at": "2020-04-30T00:00:00Z",
+                "author_association": "MEMBER",
+                "labels": [{"id": 2, "name": "type/feature", "description": null}],
+                "fields": {"affected-version": "v4.0.0"}
+            }]"#,
+        )
+        .unwrap();
+        let now = Utc.ymd(2020, 5, 1).and_hms(0, 0, 0);
+        let matched = |s: &str| -> Vec<i32> {
+            let expr: Expr = s.parse().unwrap();
+            issues
+                .iter()
+                .filter(|i| expr.matches(i, &now))
+                .map(|i| i.number())
+                .collect()
+        };
+        assert_eq!(
+            matched(r#"labels contains "type/bug" and age > 30d and assignee == none"#),
+            vec![1]
+        );
+        assert_eq!(
+            matched(r#"assignee == "You06" or title contains "PANIC""#),
+            vec![1, 2]
+        );
+        assert_eq!(matched(r#"not (labels == none or age >= 60)"#), vec![2]);
+        assert_eq!(matched(r#"author != "someone" and pull == false"#), vec![2]);
+        assert_eq!(matched("milestone == none and comments < 1"), vec![1, 2]);
+        assert_eq!(matched("field.affected-version == none"), vec![1]);
+        assert_eq!(matched(r#"field.affected-version contains "V4""#), vec![2]);
+
+        assert!("labels contains".parse::<Expr>().is_err());
+        assert!("age > \"old\"".parse::<Expr>().is_err());
+        assert!("size > 3".parse::<Expr>().is_err());
+        assert!("(age > 3".parse::<Expr>().is_err());
+        assert!("age > 3 age < 5".parse::<Expr>().is_err());
+    }
+}
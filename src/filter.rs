@@ -0,0 +1,320 @@
+use std::{collections::BTreeMap, str::FromStr};
+
+use chrono::{DateTime, Duration, NaiveDate, TimeZone, Utc};
+use clap::Clap;
+
+use crate::ages;
+use crate::config::Query;
+use crate::expr::Expr;
+use crate::providers::github::{Issue, Snapshot};
+use crate::report::Report;
+
+/// Filter narrows the issues of a report, the stored snapshots always have
+/// all the issues so the diffs are not affected.
+#[derive(Clap, Clone, Default)]
+pub struct Filter {
+    /// Only issues with the label, all of the repeated labels are required
+    #[clap(long = "label")]
+    pub labels: Vec<String>,
+    /// Only issues assigned to the user, can be repeated
+    #[clap(long = "assignee")]
+    pub assignees: Vec<String>,
+    /// Only issues opened at least this many days ago
+    #[clap(long = "min-age")]
+    pub min_age: Option<i64>,
+    /// Only issues in the repo, owner/repo or just the repo name, can be repeated
+    #[clap(long = "repo")]
+    pub repos: Vec<String>,
+    /// Only issues created or updated since the date or in the days, e.g. 2020-05-01 or 7d
+    #[clap(long = "since")]
+    pub since: Option<Since>,
+    /// Only issues assigned to someone if true, or to no one if false
+    #[clap(long = "assigned")]
+    pub assigned: Option<bool>,
+    /// Only issues whose title contains the text, case-insensitively
+    #[clap(long = "search")]
+    pub search: Option<String>,
+    /// Only issues matching the expression, e.g. 'labels contains "type/bug" and age > 30d'
+    #[clap(long = "where")]
+    pub expr: Option<Expr>,
+}
+
+impl From<&Query> for Filter {
+    fn from(query: &Query) -> Self {
+        Filter {
+            labels: query.labels.clone(),
+            assignees: query.assignees.clone(),
+            min_age: query.min_age,
+            repos: query.repos.clone(),
+            since: None,
+            assigned: query.assigned,
+            search: query.search.clone(),
+            expr: query.expr.clone(),
+        }
+    }
+}
+
+/// QueryIssues are the issues and pull requests matching a saved query.
+#[derive(Debug, Clone)]
+pub struct QueryIssues {
+    pub name: String,
+    pub issues: Vec<Issue>,
+}
+
+/// queries runs the saved queries on the snapshot, in the order of their names.
+pub fn queries(queries: &BTreeMap<String, Query>, snapshot: &Snapshot) -> Vec<QueryIssues> {
+    queries
+        .iter()
+        .map(|(name, query)| QueryIssues {
+            name: name.clone(),
+            issues: Filter::from(query).select(snapshot),
+        })
+        .collect()
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Since {
+    Time(DateTime<Utc>),
+    Days(i64),
+}
+
+impl Since {
+    pub fn resolve(self, now: &DateTime<Utc>) -> DateTime<Utc> {
+        match self {
+            Since::Time(time) => time,
+            Since::Days(days) => *now - Duration::days(days),
+        }
+    }
+}
+
+impl FromStr for Since {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Some(days) = s.strip_suffix('d') {
+            if let Ok(days) = days.parse() {
+                return Ok(Since::Days(days));
+            }
+        }
+        if let Ok(date) = NaiveDate::parse_from_str(s, "%Y-%m-%d") {
+            return Ok(Since::Time(Utc.from_utc_datetime(&date.and_hms(0, 0, 0))));
+        }
+        match DateTime::parse_from_rfc3339(s) {
+            Ok(time) => Ok(Since::Time(time.with_timezone(&Utc))),
+            Err(_) => Err(format!(
+                "invalid since {:?}, expect a date like 2020-05-01 or days like 7d",
+                s
+            )),
+        }
+    }
+}
+
+impl Filter {
+    pub fn apply(&self, report: &mut Report) {
+        let now = *report.snapshot.time();
+        report.retain_repos(|repo| self.match_repo(repo));
+        report
+            .snapshot
+            .retain_issues(|issue| self.match_issue(issue, &now));
+        // counted again from the issues left
+        if !report.ages.is_empty() {
+            report.ages = ages::histogram(&report.snapshot);
+        }
+        for query in report.queries.iter_mut() {
+            query.issues.retain(|issue| self.match_issue(issue, &now));
+        }
+        for diff in report.diffs.iter_mut() {
+            diff.opened.retain(|issue| self.match_issue(issue, &now));
+            diff.closed.retain(|issue| self.match_issue(issue, &now));
+        }
+        for stale in report.stale.iter_mut() {
+            stale.issues.retain(|issue| self.match_issue(issue, &now));
+        }
+        for conflicts in report.conflicts.iter_mut() {
+            conflicts
+                .issues
+                .retain(|issue| self.match_issue(issue, &now));
+        }
+        for drafts in report.drafts.iter_mut() {
+            drafts.issues.retain(|issue| self.match_issue(issue, &now));
+        }
+        for checks in report.checks.iter_mut() {
+            checks
+                .pulls
+                .retain(|pull| self.match_issue(&pull.issue, &now));
+        }
+        for needs_info in report.needs_info.iter_mut() {
+            needs_info
+                .issues
+                .retain(|incomplete| self.match_issue(&incomplete.issue, &now));
+        }
+    }
+
+    /// narrows_issues is true if the filter may drop issues of a repo.
+    pub fn narrows_issues(&self) -> bool {
+        !self.labels.is_empty()
+            || !self.assignees.is_empty()
+            || self.min_age.is_some()
+            || self.since.is_some()
+            || self.assigned.is_some()
+            || self.search.is_some()
+            || self.expr.is_some()
+    }
+
+    /// select returns the matching issues of the snapshot, the ages are from
+    /// the snapshot time.
+    pub fn select(&self, snapshot: &Snapshot) -> Vec<Issue> {
+        snapshot
+            .repo_issues()
+            .iter()
+            .filter(|repo| self.match_repo(&repo.name()))
+            .flat_map(|repo| repo.issues())
+            .filter(|issue| self.match_issue(issue, snapshot.time()))
+            .cloned()
+            .collect()
+    }
+
+    /// matcher checks issues while they are fetched, the ages are from now.
+    pub fn matcher(&self) -> impl Fn(&Issue) -> bool + Send + Sync + 'static {
+        let filter = self.clone();
+        let now = Utc::now();
+        move |issue| filter.match_issue(issue, &now)
+    }
+
+    pub fn match_repo(&self, repo: &str) -> bool {
+        self.repos.is_empty()
+            || self
+                .repos
+                .iter()
+                .any(|r| r == repo || repo.rsplit('/').next() == Some(&r[..]))
+    }
+
+    fn match_issue(&self, issue: &Issue, now: &DateTime<Utc>) -> bool {
+        self.labels
+            .iter()
+            .all(|label| issue.labels().any(|l| l.eq_ignore_ascii_case(label)))
+            && (self.assignees.is_empty()
+                || issue
+                    .assignees()
+                    .any(|login| self.assignees.iter().any(|a| a == login)))
+            && self.min_age.map_or(true, |days| {
+                *now - *issue.created_at() >= Duration::days(days)
+            })
+            && self
+                .since
+                .map_or(true, |since| issue.updated_at() >= &since.resolve(now))
+            && self.assigned.map_or(true, |assigned| {
+                issue.assignees().next().is_some() == assigned
+            })
+            && self.search.as_ref().map_or(true, |text| {
+                issue.title().to_lowercase().contains(&text.to_lowercase())
+            })
+            && self
+                .expr
+                .as_ref()
+                .map_or(true, |expr| expr.matches(issue, now))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn filter_report() {
+        let snapshot: Snapshot = serde_json::from_str(
+            r#"{
+                "time": "2020-05-01T00:00:00Z",
+                "repo_issues": [{
+                    "repo": {"owner": "pingcap", "repo": "tidb"},
+                    "issues": [{
+                        "number": 1,
+                        "title": "old bug",
+                        "assignee": {"id": 1, "login": "you06"},
+                        "created_at": "2020-03-01T00:00:00Z",
+                        "author_association": "NONE",
+                        "labels": [{"id": 1, "name": "type/bug", "description": null}]
+                    }, {
+                        "number": 2,
+                        "title": "new bug",
+                        "created_at": "2020-04-30T00:00:00Z",
+                        "author_association": "NONE",
+                        "labels": [{"id": 1, "name": "type/bug", "description": null}]
+                    }, {
+                        "number": 3,
+                        "title": "old question",
+                        "created_at": "2020-03-01T00:00:00Z",
+                        "author_association": "NONE",
+                        "labels": []
+                    }]
+                }, {
+                    "repo": {"owner": "pingcap", "repo": "parser"},
+                    "issues": []
+                }],
+                "project_issues": []
+            }"#,
+        )
+        .unwrap();
+        let mut report = Report::new(snapshot);
+        let mut saved = BTreeMap::new();
+        saved.insert(
+            "unassigned-bugs".to_owned(),
+            Query {
+                labels: vec!["type/bug".to_owned()],
+                assigned: Some(false),
+                ..Default::default()
+            },
+        );
+        saved.insert(
+            "questions".to_owned(),
+            Query {
+                search: Some("QUESTION".to_owned()),
+                ..Default::default()
+            },
+        );
+        let found: Vec<(String, Vec<i32>)> = queries(&saved, &report.snapshot)
+            .into_iter()
+            .map(|q| (q.name, q.issues.iter().map(|i| i.number()).collect()))
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                ("questions".to_owned(), vec![3]),
+                ("unassigned-bugs".to_owned(), vec![2]),
+            ]
+        );
+
+        let filter = Filter {
+            labels: vec!["Type/Bug".to_owned()],
+            min_age: Some(30),
+            repos: vec!["tidb".to_owned()],
+            ..Default::default()
+        };
+        filter.apply(&mut report);
+        let repos = report.snapshot.repo_issues();
+        assert_eq!(repos.len(), 1);
+        assert_eq!(repos[0].name(), "pingcap/tidb");
+        let numbers: Vec<_> = repos[0].issues().iter().map(|i| i.number()).collect();
+        assert_eq!(numbers, vec![1]);
+
+        let filter = Filter {
+            assignees: vec!["someone".to_owned()],
+            ..Default::default()
+        };
+        assert!(filter.narrows_issues());
+        filter.apply(&mut report);
+        assert!(report.snapshot.repo_issues()[0].issues().is_empty());
+    }
+
+    #[test]
+    fn parse_since() {
+        let now = Utc.ymd(2020, 5, 8).and_hms(0, 0, 0);
+        let since: Since = "7d".parse().unwrap();
+        assert_eq!(since.resolve(&now), Utc.ymd(2020, 5, 1).and_hms(0, 0, 0));
+        let since: Since = "2020-05-01".parse().unwrap();
+        assert_eq!(since.resolve(&now), Utc.ymd(2020, 5, 1).and_hms(0, 0, 0));
+        let since: Since = "2020-05-01T08:00:00+08:00".parse().unwrap();
+        assert_eq!(since.resolve(&now), Utc.ymd(2020, 5, 1).and_hms(0, 0, 0));
+        assert!("7w".parse::<Since>().is_err());
+    }
+}
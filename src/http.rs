@@ -0,0 +1,293 @@
+use std::{
+    env, fs,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue, USER_AGENT};
+use reqwest::{Certificate, Client, Method, Proxy, RequestBuilder, Response, StatusCode, Url};
+use tokio::sync::Semaphore;
+use tokio::time::delay_for;
+use tracing::{debug, warn};
+
+use crate::cache::Cache;
+use crate::config::Config;
+
+// RETRIES is how many times a GET request failing by the network or an
+// unavailable server is sent again, after 1s, 2s...
+const RETRIES: u32 = 2;
+
+// CONNECTIONS caps the requests in flight to `max-connections`, it's shared
+// by the providers like the client, which can't cap them itself.
+static CONNECTIONS: Mutex<Option<Arc<Semaphore>>> = Mutex::new(None);
+
+/// new_client builds the HTTP client shared by providers,
+/// so the network options in config apply to all of them.
+pub fn new_client(conf: &Config) -> Result<Client, String> {
+    *CONNECTIONS.lock().unwrap() = conf
+        .max_connections
+        .map(|max| Arc::new(Semaphore::new(max)));
+    let mut builder = Client::builder()
+        .no_proxy()
+        // sends `Accept-Encoding: gzip` and decompresses the large issue pages
+        .gzip(true)
+        .timeout(Duration::from_secs(conf.timeout))
+        .connect_timeout(Duration::from_secs(conf.connect_timeout));
+    if let Some(max) = conf.max_idle_connections {
+        builder = builder.pool_max_idle_per_host(max);
+    }
+
+    let https_proxy = proxy_url(&conf.proxy, &["HTTPS_PROXY", "https_proxy"])?;
+    let http_proxy = proxy_url(&conf.proxy, &["HTTP_PROXY", "http_proxy"])?;
+    if https_proxy.is_some() || http_proxy.is_some() {
+        let mut no_proxy = conf.no_proxy.clone();
+        for key in &["NO_PROXY", "no_proxy"] {
+            if let Ok(value) = env::var(key) {
+                no_proxy.extend(value.split(',').map(|h| h.trim().to_owned()));
+            }
+        }
+        builder = builder.proxy(Proxy::custom(move |url| {
+            if matches_no_proxy(url.host_str().unwrap_or(""), &no_proxy) {
+                return None;
+            }
+            match url.scheme() {
+                "https" => https_proxy.clone(),
+                _ => http_proxy.clone(),
+            }
+        }));
+    }
+
+    for path in &conf.ca_certs {
+        let pem = fs::read_to_string(path).map_err(|e| format!("read {}: {}", path, e))?;
+        for cert in split_pem(&pem) {
+            let cert = Certificate::from_pem(cert.as_bytes())
+                .map_err(|e| format!("parse certificate in {}: {}", path, e))?;
+            builder = builder.add_root_certificate(cert);
+        }
+    }
+    if conf.danger_accept_invalid_certs {
+        builder = builder.danger_accept_invalid_certs(true);
+    }
+
+    builder.build().map_err(|e| e.to_string())
+}
+
+/// Http is the middleware the providers send their requests through: it adds
+/// the credentials and the user agent of the provider to each request, sends
+/// the GET requests failing by the network or an unavailable server again, and
+/// keeps the bodies in the disk cache if it's set.
+#[derive(Clone)]
+pub struct Http {
+    client: Client,
+    headers: HeaderMap,
+    timeout: Option<Duration>,
+    cache: Option<Arc<Cache>>,
+}
+
+impl Http {
+    pub fn new(client: Client, user_agent: &str) -> Result<Self, String> {
+        Http {
+            client,
+            headers: HeaderMap::new(),
+            timeout: None,
+            cache: None,
+        }
+        .header(USER_AGENT, user_agent)
+    }
+
+    /// header adds a header to each request, like the credentials.
+    pub fn header(mut self, key: HeaderName, value: &str) -> Result<Self, String> {
+        let value =
+            HeaderValue::from_str(value).map_err(|e| format!("invalid {} header: {}", key, e))?;
+        self.headers.insert(key, value);
+        Ok(self)
+    }
+
+    /// set_timeout applies to each request, on top of the timeout of the client.
+    pub fn set_timeout(&mut self, timeout: Option<Duration>) {
+        self.timeout = timeout;
+    }
+
+    pub fn set_cache(&mut self, cache: Option<Cache>) {
+        self.cache = cache.map(Arc::new);
+    }
+
+    /// request starts a request with the headers and the timeout.
+    pub fn request(&self, method: Method, url: &str) -> RequestBuilder {
+        let req = self
+            .client
+            .request(method, url)
+            .headers(self.headers.clone());
+        match self.timeout {
+            Some(timeout) => req.timeout(timeout),
+            None => req,
+        }
+    }
+
+    /// send sends the request `build` makes of `request`, a GET request failing
+    /// by the network or a 502, 503 or 504 is built and sent again. It waits
+    /// for a connection while `max-connections` requests are in flight.
+    pub async fn send(
+        &self,
+        method: Method,
+        url: &str,
+        build: impl Fn(RequestBuilder) -> RequestBuilder,
+    ) -> reqwest::Result<Response> {
+        // the others may have been done by the server before failing
+        let retries = if method == Method::GET { RETRIES } else { 0 };
+        let limit = CONNECTIONS.lock().unwrap().clone();
+        let mut attempt = 0;
+        loop {
+            let permit = match &limit {
+                Some(limit) => Some(limit.acquire().await),
+                None => None,
+            };
+            debug!("{} {}", method, redact(url));
+            let sent = build(self.request(method.clone(), url)).send().await;
+            drop(permit);
+            let failed = match &sent {
+                Ok(res) => matches!(
+                    res.status(),
+                    StatusCode::BAD_GATEWAY
+                        | StatusCode::SERVICE_UNAVAILABLE
+                        | StatusCode::GATEWAY_TIMEOUT
+                ),
+                Err(e) => e.is_timeout() || e.is_connect(),
+            };
+            if !failed || attempt == retries {
+                return sent;
+            }
+            attempt += 1;
+            warn!(
+                "{} {} failed, sending it again in {}s",
+                method,
+                redact(url),
+                attempt
+            );
+            delay_for(Duration::from_secs(attempt as u64)).await;
+        }
+    }
+
+    /// cached returns the cached body of the resource, the url requested with
+    /// the extra headers, if the cache is set and it's not expired.
+    pub fn cached(&self, url: &str, headers: &[(&str, &str)]) -> Option<String> {
+        self.cache.as_ref()?.get(&resource_key(url, headers))
+    }
+
+    /// store caches the body of the resource if the cache is set, only the
+    /// bodies of successful responses should be stored.
+    pub fn store(&self, url: &str, headers: &[(&str, &str)], body: &str) {
+        if let Some(cache) = &self.cache {
+            if let Err(e) = cache.put(&resource_key(url, headers), body) {
+                debug!("failed to cache {}: {}", redact(url), e);
+            }
+        }
+    }
+
+    pub fn headers(&self) -> &HeaderMap {
+        &self.headers
+    }
+
+    pub fn has_cache(&self) -> bool {
+        self.cache.is_some()
+    }
+}
+
+// resource_key tells the responses of a url apart by the extra headers, e.g.
+// the raw contents of a file from its metadata.
+fn resource_key(url: &str, headers: &[(&str, &str)]) -> String {
+    let mut key = url.to_owned();
+    for (name, value) in headers {
+        key.push_str(&format!("\n{}: {}", name.to_lowercase(), value));
+    }
+    key
+}
+
+/// redact drops the query of the url, which may have a secret like the key of
+/// a WeCom webhook.
+pub fn redact(url: &str) -> &str {
+    url.split('?').next().unwrap_or(url)
+}
+
+const PEM_END: &str = "-----END CERTIFICATE-----";
+
+// split_pem splits a bundle into single certificates,
+// Certificate::from_pem only reads the first one.
+fn split_pem(pem: &str) -> Vec<String> {
+    pem.split(PEM_END)
+        .filter(|cert| cert.contains("-----BEGIN CERTIFICATE-----"))
+        .map(|cert| format!("{}\n{}\n", cert.trim(), PEM_END))
+        .collect()
+}
+
+// proxy_url prefers the proxy in config to the environment variables.
+fn proxy_url(proxy: &Option<String>, keys: &[&str]) -> Result<Option<Url>, String> {
+    let proxy = match proxy {
+        Some(proxy) => Some(proxy.to_owned()),
+        None => keys.iter().filter_map(|key| env::var(key).ok()).next(),
+    };
+    match proxy {
+        Some(proxy) if !proxy.is_empty() => Url::parse(&proxy[..])
+            .map(Some)
+            .map_err(|e| format!("invalid proxy {:?}: {}", proxy, e)),
+        _ => Ok(None),
+    }
+}
+
+fn matches_no_proxy(host: &str, no_proxy: &[String]) -> bool {
+    no_proxy.iter().any(|entry| {
+        let entry = entry.trim_start_matches('.');
+        entry == "*" || host == entry || host.ends_with(&format!(".{}", entry))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn split_pem_bundle() {
+        let bundle = "-----BEGIN CERTIFICATE-----\nA\n-----END CERTIFICATE-----\n\
+                      -----BEGIN CERTIFICATE-----\nB\n-----END CERTIFICATE-----\n";
+        assert_eq!(
+            split_pem(bundle),
+            vec![
+                "-----BEGIN CERTIFICATE-----\nA\n-----END CERTIFICATE-----\n",
+                "-----BEGIN CERTIFICATE-----\nB\n-----END CERTIFICATE-----\n",
+            ]
+        );
+    }
+
+    #[test]
+    fn key_resources() {
+        let url = "https://api.github.com/repos/pingcap/parser/contents/README.md";
+        assert_eq!(resource_key(url, &[]), url);
+        assert_eq!(
+            resource_key(url, &[("Accept", "application/vnd.github.v3.raw")]),
+            format!("{}\naccept: application/vnd.github.v3.raw", url)
+        );
+    }
+
+    #[test]
+    fn redact_query() {
+        assert_eq!(
+            redact("https://qyapi.weixin.qq.com/cgi-bin/webhook/send?key=secret"),
+            "https://qyapi.weixin.qq.com/cgi-bin/webhook/send"
+        );
+        assert_eq!(
+            redact("https://api.github.com/user"),
+            "https://api.github.com/user"
+        );
+    }
+
+    #[test]
+    fn match_no_proxy() {
+        let no_proxy = vec![".internal.com".to_owned(), "localhost".to_owned()];
+        assert!(matches_no_proxy("localhost", &no_proxy));
+        assert!(matches_no_proxy("github.internal.com", &no_proxy));
+        assert!(matches_no_proxy("internal.com", &no_proxy));
+        assert!(!matches_no_proxy("api.github.com", &no_proxy));
+        assert!(!matches_no_proxy("notinternal.com", &no_proxy));
+        assert!(matches_no_proxy("api.github.com", &["*".to_owned()]));
+    }
+}
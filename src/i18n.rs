@@ -0,0 +1,214 @@
+use std::fmt::Display;
+
+use serde::Deserialize;
+
+#[derive(Deserialize, Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Locale {
+    #[serde(rename = "en")]
+    En,
+    #[serde(rename = "zh")]
+    Zh,
+}
+
+impl Default for Locale {
+    fn default() -> Self {
+        Locale::En
+    }
+}
+
+/// Messages are the strings used in reports, `{}` is replaced by `fill`.
+pub struct Messages {
+    pub current_user: &'static str,
+    pub snapshot_time: &'static str,
+    pub repo_summary: &'static str,
+    pub repo_changes: &'static str,
+    pub stale_issues: &'static str,
+    pub column_title: &'static str,
+    pub column_age: &'static str,
+    pub column_labels: &'static str,
+    pub age_histogram: &'static str,
+    pub age_buckets: &'static str,
+    pub label_stats: &'static str,
+    pub query_issues: &'static str,
+    pub digest_title: &'static str,
+    pub digest_repo: &'static str,
+    pub digest_stale: &'static str,
+    pub digest_sla: &'static str,
+    pub contributors_title: &'static str,
+    pub contributors_week: &'static str,
+    pub contributors_responders: &'static str,
+    pub report_totals: &'static str,
+    pub full_report: &'static str,
+    pub feed_title: &'static str,
+    pub feed_entry: &'static str,
+    pub calendar_milestone: &'static str,
+    pub calendar_milestone_issues: &'static str,
+    pub calendar_sla: &'static str,
+    pub spike_alert: &'static str,
+    pub wip_violation: &'static str,
+    pub board_moves: &'static str,
+    pub card_moved: &'static str,
+    pub burndown_title: &'static str,
+    pub burndown_milestone: &'static str,
+    pub burndown_slipping: &'static str,
+    pub release_notes_title: &'static str,
+    pub release_notes_other: &'static str,
+    pub backport_missing: &'static str,
+    pub merge_conflicts: &'static str,
+    pub failing_checks: &'static str,
+    pub checks_failed: &'static str,
+    pub checks_approved: &'static str,
+    pub aging_drafts: &'static str,
+    pub draft_ping: &'static str,
+    pub review_route: &'static str,
+    pub needs_info: &'static str,
+    pub needs_info_missing: &'static str,
+    pub needs_info_ask: &'static str,
+    pub confidential_title: &'static str,
+    pub confidential_issues: &'static str,
+}
+
+const EN: Messages = Messages {
+    current_user: "Current user: {}",
+    snapshot_time: "Snapshot at {}",
+    repo_summary: "{}: {} open issues, {} open pull requests",
+    repo_changes: "{}: {} opened, {} closed",
+    stale_issues: "{}: {} issues without reply in {} days",
+    column_title: "Title",
+    column_age: "Age (days)",
+    column_labels: "Labels",
+    age_histogram: "Issue ages",
+    age_buckets: "{}: {} under 7 days, {} in 7-30 days, {} in 30-90 days, {} over 90 days",
+    label_stats: "Labels, changes in a week",
+    query_issues: "{}: {} issues and pull requests",
+    digest_title: "Digest from {} to {}",
+    digest_repo: "{}: {} opened, {} closed, {} open now",
+    digest_stale: "Top {} issues without reply",
+    digest_sla: "{} issues without reply for more than {} days",
+    contributors_title: "Contributors",
+    contributors_week: "Week of {}: {} contributors, {} new",
+    contributors_responders: "Top responders on issues: {}",
+    report_totals: "{} repos: {} opened, {} closed, {} issues without reply",
+    full_report: "Full report: {}",
+    feed_title: "Issues without reply",
+    feed_entry: "{}: no reply from members in {} days",
+    calendar_milestone: "{} {} is due",
+    calendar_milestone_issues: "{} open issues and pull requests",
+    calendar_sla: "SLA: {}",
+    spike_alert: "{}: {} issues opened today, {} a day in the last {} days",
+    wip_violation: "{} {}: {} cards, over the WIP limit of {}",
+    board_moves: "{}: {} cards moved",
+    card_moved: "{} moved {} → {}",
+    burndown_title: "Milestone burndown",
+    burndown_milestone: "{} {} due {}: {} open",
+    burndown_slipping: "(slipping)",
+    release_notes_title: "Release notes since {}",
+    release_notes_other: "Other",
+    backport_missing: "{} is not cherry-picked to {} yet: {}",
+    merge_conflicts: "{}: {} pull requests open for more than {} days have merge conflicts",
+    failing_checks: "{}: {} pull requests with failing checks",
+    checks_failed: "failing {}",
+    checks_approved: "approved, failing {}",
+    aging_drafts: "{}: {} draft pull requests open for more than {} days",
+    draft_ping: "@{} this draft has been open for more than {} days, is it still in progress?",
+    review_route: "{} pull requests wait for a review of {}",
+    needs_info: "{}: {} issues opened in the last {} days miss sections of the issue templates",
+    needs_info_missing: "missing {}",
+    needs_info_ask: "@{} thanks for the report! Could you add {} as asked by the issue template?",
+    confidential_title: "(confidential)",
+    confidential_issues: "{} confidential issues opened or without reply",
+};
+
+const ZH: Messages = Messages {
+    current_user: "当前用户：{}",
+    snapshot_time: "快照时间：{}",
+    repo_summary: "{}：{} 个未关闭的 issue，{} 个未关闭的 pull request",
+    repo_changes: "{}：新增 {} 个，关闭 {} 个",
+    stale_issues: "{}：{} 个 issue 超过 {} 天没有回复",
+    column_title: "标题",
+    column_age: "天数",
+    column_labels: "标签",
+    age_histogram: "issue 存在时间",
+    age_buckets: "{}：7 天内 {} 个，7-30 天 {} 个，30-90 天 {} 个，超过 90 天 {} 个",
+    label_stats: "标签统计（与一周前相比）",
+    query_issues: "{}：{} 个 issue 和 pull request",
+    digest_title: "{} 至 {} 的摘要",
+    digest_repo: "{}：新增 {} 个，关闭 {} 个，当前 {} 个未关闭",
+    digest_stale: "最久没有回复的 {} 个 issue",
+    digest_sla: "{} 个 issue 超过 {} 天没有回复",
+    contributors_title: "贡献者",
+    contributors_week: "{} 当周：{} 位贡献者，{} 位新贡献者",
+    contributors_responders: "回复 issue 最多的成员：{}",
+    report_totals: "{} 个仓库：新增 {} 个，关闭 {} 个，{} 个 issue 没有回复",
+    full_report: "完整报告：{}",
+    feed_title: "没有回复的 issue",
+    feed_entry: "{}：超过 {} 天没有成员回复",
+    calendar_milestone: "{} {} 到期",
+    calendar_milestone_issues: "{} 个未关闭的 issue 和 pull request",
+    calendar_sla: "SLA：{}",
+    spike_alert: "{}：今天新增 {} 个 issue，平均每天 {} 个（过去 {} 天）",
+    wip_violation: "{} {}：{} 张卡片，超过 WIP 上限 {}",
+    board_moves: "{}：移动了 {} 张卡片",
+    card_moved: "{} 从 {} 移到 {}",
+    burndown_title: "里程碑燃尽",
+    burndown_milestone: "{} {} 截止于 {}：{} 个未关闭",
+    burndown_slipping: "（可能延期）",
+    release_notes_title: "自 {} 以来的发布说明",
+    release_notes_other: "其他",
+    backport_missing: "{} 还没有 cherry-pick 到 {}：{}",
+    merge_conflicts: "{}：{} 个超过 {} 天未合并的 pull request 有冲突",
+    failing_checks: "{}：{} 个 pull request 的检查失败",
+    checks_failed: "失败：{}",
+    checks_approved: "已批准，失败：{}",
+    aging_drafts: "{}：{} 个草稿 pull request 超过 {} 天",
+    draft_ping: "@{} 这个草稿已经超过 {} 天了，还在进行中吗？",
+    review_route: "{} 个 pull request 等待 {} 评审",
+    needs_info: "{}：{} 个最近 {} 天内的 issue 缺少模板要求的内容",
+    needs_info_missing: "缺少 {}",
+    needs_info_ask: "@{} 感谢反馈！能否按照 issue 模板补充 {}？",
+    confidential_title: "（保密）",
+    confidential_issues: "{} 个新开或未回复的保密 issue",
+};
+
+impl Locale {
+    pub fn messages(self) -> &'static Messages {
+        match self {
+            Locale::En => &EN,
+            Locale::Zh => &ZH,
+        }
+    }
+}
+
+/// fill replaces the `{}` placeholders in order.
+pub fn fill(template: &str, args: &[&dyn Display]) -> String {
+    let mut parts = template.split("{}");
+    let mut result = parts.next().unwrap_or("").to_owned();
+    for (i, part) in parts.enumerate() {
+        if let Some(arg) = args.get(i) {
+            result.push_str(&arg.to_string());
+        }
+        result.push_str(part);
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fill_messages() {
+        assert_eq!(
+            fill("{} issues in {} days", &[&2, &3]),
+            "2 issues in 3 days"
+        );
+        assert_eq!(
+            fill(Locale::En.messages().current_user, &[&"you06"]),
+            "Current user: you06"
+        );
+        assert_eq!(
+            fill(Locale::Zh.messages().current_user, &[&"you06"]),
+            "当前用户：you06"
+        );
+    }
+}
@@ -0,0 +1,93 @@
+use std::{
+    fs,
+    io::{self, BufRead, Error, ErrorKind, Write},
+    path::Path,
+};
+
+pub struct Answers {
+    pub github_token: String,
+    pub repos: Vec<String>,
+    pub slack_token: String,
+    pub slack_channel: String,
+}
+
+// run asks for the required values and writes a commented config file.
+pub fn run(filename: &str) -> io::Result<()> {
+    if Path::new(filename).exists() {
+        return Err(Error::new(
+            ErrorKind::AlreadyExists,
+            format!("{} already exists", filename),
+        ));
+    }
+    let stdin = io::stdin();
+    let mut input = stdin.lock();
+    let github_token = ask(&mut input, "GitHub token")?;
+    let repos = ask(&mut input, "Watched repos, separated by comma")?;
+    let answers = Answers {
+        github_token,
+        repos: repos
+            .split(',')
+            .map(|r| r.trim().to_owned())
+            .filter(|r| !r.is_empty())
+            .collect(),
+        slack_token: ask(&mut input, "Slack token (leave empty to print reports)")?,
+        slack_channel: ask(&mut input, "Slack channel")?,
+    };
+    fs::write(filename, render(&answers))?;
+    println!("config is written to {}", filename);
+    Ok(())
+}
+
+fn ask<R: BufRead>(input: &mut R, question: &str) -> io::Result<String> {
+    print!("{}: ", question);
+    io::stdout().flush()?;
+    let mut line = String::new();
+    input.read_line(&mut line)?;
+    Ok(line.trim().to_owned())
+}
+
+pub fn render(answers: &Answers) -> String {
+    let repos = answers
+        .repos
+        .iter()
+        .map(|r| format!("{:?}", r))
+        .collect::<Vec<String>>()
+        .join(", ");
+    format!(
+        r#"# slack
+# OAuth access token, user token scopes are required.
+slack-token = {:?}
+slack-channel = {:?}
+
+# github
+# generate one from https://github.com/settings/tokens/new
+github-token = {:?}
+# github-data = "~/.issues-watcher"
+repos = [{}]
+# projects = ["https://github.com/owner/repo/projects/1"]
+
+# issues without reply from members in `stale-days` are reported
+# stale-days = 3
+"#,
+        answers.slack_token, answers.slack_channel, answers.github_token, repos
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn render_config() {
+        let answers = Answers {
+            github_token: "github-token".to_owned(),
+            repos: vec!["pingcap/parser".to_owned(), "pingcap/tidb".to_owned()],
+            slack_token: "".to_owned(),
+            slack_channel: "".to_owned(),
+        };
+        let config: issues_watcher::config::Config = toml::from_str(&render(&answers)).unwrap();
+        assert_eq!(config.github_token, "github-token");
+        assert_eq!(config.repos, vec!["pingcap/parser", "pingcap/tidb"]);
+        assert!(config.validate().is_empty());
+    }
+}
@@ -0,0 +1,96 @@
+use std::collections::BTreeMap;
+
+use regex::Regex;
+
+/// parse extracts the fields of an issue body written with an issue form or an
+/// issue template: the text under each heading like "### Affected version",
+/// or a line in bold like "**Affected version:**", by the field name of the
+/// heading. Comments, empty fields and the "_No response_" of issue forms are
+/// left out.
+pub fn parse(body: &str) -> BTreeMap<String, String> {
+    sections(body)
+        .into_iter()
+        .map(|(title, value)| (field_name(&title), value))
+        .filter(|(name, value)| !name.is_empty() && !value.is_empty() && value != "_No response_")
+        .collect()
+}
+
+/// headings returns the headings in a body, e.g. the sections of a Markdown
+/// issue template.
+pub fn headings(body: &str) -> Vec<String> {
+    sections(body).into_iter().map(|(title, _)| title).collect()
+}
+
+// sections splits the body without comments by the headings, the text before
+// the first heading is left out.
+fn sections(body: &str) -> Vec<(String, String)> {
+    let comment = Regex::new(r"(?s)<!--.*?-->").unwrap();
+    let heading = Regex::new(r"^(?:#{1,6}\s+(.+?)\s*#*|\*\*([^*]+?):?\*\*:?)$").unwrap();
+    let mut sections: Vec<(String, Vec<&str>)> = vec![];
+    let body = comment.replace_all(body, "");
+    for line in body.lines().map(str::trim_end) {
+        match heading.captures(line.trim_start()) {
+            Some(m) => {
+                let title = m.get(1).or_else(|| m.get(2)).unwrap().as_str();
+                sections.push((title.to_owned(), vec![]));
+            }
+            None => {
+                if let Some((_, lines)) = sections.last_mut() {
+                    lines.push(line);
+                }
+            }
+        }
+    }
+    sections
+        .into_iter()
+        .map(|(title, lines)| (title, lines.join("\n").trim().to_owned()))
+        .collect()
+}
+
+/// field_name is the lowercase words of a heading joined by "-", e.g.
+/// "affected-version" of "Affected Version".
+pub fn field_name(heading: &str) -> String {
+    heading
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+        .collect::<Vec<_>>()
+        .join("-")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_issue_form() {
+        let body = "### Bug Report\n\n\
+                    <!-- describe the bug -->\n\
+                    The parser panics on `SELECT`.\n\
+                    It's a regression.\n\n\
+                    ### Affected version\n\n\
+                    v4.0.0\n\n\
+                    ### Component\n\n\
+                    _No response_\n\n\
+                    **Operating system:**\n\
+                    Linux\n";
+        let fields = parse(body);
+        assert_eq!(fields.len(), 3);
+        assert_eq!(
+            fields["bug-report"],
+            "The parser panics on `SELECT`.\nIt's a regression."
+        );
+        assert_eq!(fields["affected-version"], "v4.0.0");
+        assert_eq!(fields["operating-system"], "Linux");
+        assert!(parse("no headings at all").is_empty());
+        assert_eq!(
+            headings(body),
+            vec![
+                "Bug Report",
+                "Affected version",
+                "Component",
+                "Operating system"
+            ]
+        );
+    }
+}
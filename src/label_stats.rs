@@ -0,0 +1,191 @@
+use std::collections::BTreeMap;
+
+use chrono::Duration;
+
+use crate::config::Config;
+use crate::providers::github::Snapshot;
+use crate::storage;
+use crate::Result;
+
+/// LabelCount is the open issues and pull requests with a label, and the
+/// change since a week ago.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LabelCount {
+    /// without the group prefix, e.g. "bug" of "type/bug"
+    pub label: String,
+    pub count: usize,
+    /// none if no snapshot a week ago is stored
+    pub change: Option<i64>,
+}
+
+/// LabelGroupStats are the counts of the labels of a group in a repo, like
+/// the labels starting with "type/".
+#[derive(Debug, Clone, PartialEq)]
+pub struct LabelGroupStats {
+    pub repo: String,
+    pub group: String,
+    pub labels: Vec<LabelCount>,
+}
+
+impl LabelGroupStats {
+    /// render renders the counts in a line like "bug 5 (+2), feature 3".
+    pub fn render(&self) -> String {
+        let counts: Vec<String> = self
+            .labels
+            .iter()
+            .map(|l| match l.change {
+                Some(change) if change != 0 => format!("{} {} ({:+})", l.label, l.count, change),
+                _ => format!("{} {}", l.label, l.count),
+            })
+            .collect();
+        format!("{} {}: {}", self.repo, self.group, counts.join(", "))
+    }
+}
+
+// LabelCounts are the open issues and pull requests by repo, group and label.
+type LabelCounts = BTreeMap<(String, String), BTreeMap<String, usize>>;
+
+fn count(snapshot: &Snapshot, groups: &[String]) -> LabelCounts {
+    let mut counts = LabelCounts::new();
+    for repo in snapshot.repo_issues() {
+        for group in groups {
+            let labels = counts.entry((repo.name(), group.clone())).or_default();
+            for issue in repo.issues() {
+                for label in issue.labels().filter_map(|l| l.strip_prefix(&group[..])) {
+                    *labels.entry(label.to_owned()).or_default() += 1;
+                }
+            }
+        }
+    }
+    counts
+}
+
+/// stats counts the labels of the groups in each repo of the snapshot, the
+/// labels only in the previous snapshot are counted as 0. Groups without any
+/// label are left out.
+pub fn stats(
+    snapshot: &Snapshot,
+    previous: Option<&Snapshot>,
+    groups: &[String],
+) -> Vec<LabelGroupStats> {
+    let previous = previous.map(|previous| count(previous, groups));
+    count(snapshot, groups)
+        .into_iter()
+        .filter_map(|((repo, group), mut labels)| {
+            let before = previous.as_ref().map(|p| {
+                p.get(&(repo.clone(), group.clone()))
+                    .cloned()
+                    .unwrap_or_default()
+            });
+            if let Some(before) = &before {
+                for label in before.keys() {
+                    labels.entry(label.clone()).or_default();
+                }
+            }
+            if labels.is_empty() {
+                return None;
+            }
+            let labels = labels
+                .into_iter()
+                .map(|(label, count)| {
+                    let change = before.as_ref().map(|before| {
+                        count as i64 - before.get(&label).copied().unwrap_or_default() as i64
+                    });
+                    LabelCount {
+                        label,
+                        count,
+                        change,
+                    }
+                })
+                .collect();
+            Some(LabelGroupStats {
+                repo,
+                group,
+                labels,
+            })
+        })
+        .collect()
+}
+
+/// build counts the `label-groups` of the snapshot, compared with the first
+/// snapshot stored in the day a week before it. It must be called before the
+/// snapshot is stored.
+pub fn build(conf: &Config, snapshot: &Snapshot) -> Result<Vec<LabelGroupStats>> {
+    if conf.label_groups.is_empty() {
+        return Ok(vec![]);
+    }
+    let week_ago = *snapshot.time() - Duration::weeks(1);
+    let previous = match storage::since(&conf.github_data, &week_ago)?.first() {
+        Some(path) => Some(storage::load(path)?),
+        None => None,
+    };
+    let previous = previous.filter(|p| *p.time() < week_ago + Duration::days(1));
+    Ok(stats(snapshot, previous.as_ref(), &conf.label_groups))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn snapshot(labels: &[&[&str]]) -> Snapshot {
+        let issues: Vec<String> = labels
+            .iter()
+            .enumerate()
+            .map(|(i, labels)| {
+                let labels: Vec<String> = labels
+                    .iter()
+                    .map(|l| format!(r#"{{"id": 1, "name": "{}", "description": null}}"#, l))
+                    .collect();
+                format!(
+                    r#"{{"number": {}, "title": "issue", "created_at": "2020-04-01T00:00:00Z",
+                        "author_association": "NONE", "labels": [{}]}}"#,
+                    i,
+                    labels.join(",")
+                )
+            })
+            .collect();
+        serde_json::from_str(&format!(
+            r#"{{"time": "2020-05-01T00:00:00Z", "repo_issues": [{{
+                "repo": {{"owner": "pingcap", "repo": "parser"}},
+                "issues": [{}]
+            }}], "project_issues": []}}"#,
+            issues.join(",")
+        ))
+        .unwrap()
+    }
+
+    #[test]
+    fn count_label_groups() {
+        let groups = vec!["type/".to_owned(), "severity/".to_owned()];
+        let now = snapshot(&[
+            &["type/bug", "severity/major"],
+            &["type/bug"],
+            &["type/feature"],
+            &["component/executor"],
+        ]);
+        let lines: Vec<String> = stats(&now, None, &groups)
+            .iter()
+            .map(LabelGroupStats::render)
+            .collect();
+        assert_eq!(
+            lines,
+            vec![
+                "pingcap/parser severity/: major 1",
+                "pingcap/parser type/: bug 2, feature 1",
+            ]
+        );
+
+        let week_ago = snapshot(&[&["type/bug"], &["type/question"], &["type/feature"]]);
+        let lines: Vec<String> = stats(&now, Some(&week_ago), &groups)
+            .iter()
+            .map(LabelGroupStats::render)
+            .collect();
+        assert_eq!(
+            lines,
+            vec![
+                "pingcap/parser severity/: major 1 (+1)",
+                "pingcap/parser type/: bug 2 (+1), feature 1, question 0 (-1)",
+            ]
+        );
+    }
+}
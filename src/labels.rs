@@ -0,0 +1,141 @@
+use std::fmt;
+
+use crate::config::{Config, LabelConfig};
+use crate::providers::github::{GitHub, RepoLabel};
+use crate::Result;
+
+/// LabelChange is a label to create or to update in a repo.
+#[derive(Debug, PartialEq)]
+pub enum LabelChange {
+    Create(RepoLabel),
+    /// the label in the repo and the canonical one
+    Update(RepoLabel, RepoLabel),
+}
+
+impl fmt::Display for LabelChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let description = |label: &RepoLabel| label.description.clone().unwrap_or_default();
+        match self {
+            LabelChange::Create(label) => write!(
+                f,
+                "+ {} #{} {:?}",
+                label.name,
+                label.color,
+                description(label)
+            ),
+            LabelChange::Update(old, new) => write!(
+                f,
+                "~ {} #{} {:?} -> {} #{} {:?}",
+                old.name,
+                old.color,
+                description(old),
+                new.name,
+                new.color,
+                description(new)
+            ),
+        }
+    }
+}
+
+/// diff compares the canonical labels with the labels of a repo by name,
+/// case-insensitively like GitHub does. Labels only in the repo are kept.
+pub fn diff(canonical: &[LabelConfig], current: &[RepoLabel]) -> Vec<LabelChange> {
+    canonical
+        .iter()
+        .filter_map(|label| {
+            let label = RepoLabel {
+                name: label.name.clone(),
+                color: label.color.trim_start_matches('#').to_lowercase(),
+                description: Some(label.description.clone()),
+            };
+            let old = current
+                .iter()
+                .find(|c| c.name.to_lowercase() == label.name.to_lowercase());
+            match old {
+                None => Some(LabelChange::Create(label)),
+                Some(old)
+                    if old.name != label.name
+                        || old.color.to_lowercase() != label.color
+                        || old.description.as_deref().unwrap_or("")
+                            != label.description.as_deref().unwrap_or("") =>
+                {
+                    Some(LabelChange::Update(old.clone(), label))
+                }
+                Some(_) => None,
+            }
+        })
+        .collect()
+}
+
+/// sync prints the label changes of each watched GitHub repo, and applies
+/// them unless `dry_run`.
+pub async fn sync(conf: &Config, client: &reqwest::Client, dry_run: bool) -> Result<()> {
+    if conf.labels.is_empty() {
+        return Err("no label is configured, add them by [[label]] blocks".into());
+    }
+    let github_client = GitHub::new(client.clone(), conf.github_token.clone(), vec![], vec![])?;
+    for rule in conf.repo_rules() {
+        let changes = diff(&conf.labels, &github_client.list_labels(&rule.name).await?);
+        if changes.is_empty() {
+            println!("{}: up to date", rule.name);
+            continue;
+        }
+        println!("{}:", rule.name);
+        for change in changes {
+            println!("  {}", change);
+            if dry_run {
+                continue;
+            }
+            match &change {
+                LabelChange::Create(label) => github_client.create_label(&rule.name, label).await?,
+                LabelChange::Update(old, label) => {
+                    github_client
+                        .update_label(&rule.name, &old.name, label)
+                        .await?
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn diff_labels() {
+        let canonical = vec![
+            LabelConfig {
+                name: "type/bug".to_owned(),
+                color: "#D73A4A".to_owned(),
+                description: "Something isn't working".to_owned(),
+            },
+            LabelConfig {
+                name: "type/question".to_owned(),
+                color: "d876e3".to_owned(),
+                description: String::new(),
+            },
+            LabelConfig {
+                name: "good first issue".to_owned(),
+                color: "7057ff".to_owned(),
+                description: String::new(),
+            },
+        ];
+        let current: Vec<RepoLabel> = serde_json::from_str(
+            r#"[
+                {"name": "type/bug", "color": "d73a4a", "description": "Something isn't working"},
+                {"name": "Type/Question", "color": "d876e3", "description": null},
+                {"name": "wontfix", "color": "ffffff", "description": null}
+            ]"#,
+        )
+        .unwrap();
+        let changes = diff(&canonical, &current);
+        assert_eq!(changes.len(), 2);
+        assert_eq!(
+            changes[0].to_string(),
+            "~ Type/Question #d876e3 \"\" -> type/question #d876e3 \"\""
+        );
+        assert_eq!(changes[1].to_string(), "+ good first issue #7057ff \"\"");
+    }
+}
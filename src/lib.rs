@@ -0,0 +1,58 @@
+//! Watch GitHub issues and projects and report the changes.
+//!
+//! The `issues-watcher` binary is a thin command line over this library,
+//! other programs can embed the same steps:
+//!
+//! ```no_run
+//! use issues_watcher::{config::Config, http, notify, report::Templates, snapshot};
+//!
+//! # async fn run() -> issues_watcher::Result<()> {
+//! let conf = Config::new("config.toml".to_owned())?;
+//! let client = http::new_client(&conf)?;
+//! let report = snapshot::build_report(&conf, &client, false).await?;
+//! let templates = Templates::from_config(&conf)?;
+//! let text = report.render(&templates, conf.locale.messages(), &conf.tz()?)?;
+//! notify::send(&conf, &client, text, &report.issue_urls(), false).await?;
+//! # Ok(())
+//! # }
+//! ```
+
+pub mod ages;
+pub mod alerts;
+pub mod anomaly;
+pub mod backport;
+pub mod batch;
+pub mod board;
+pub mod cache;
+pub mod calendar;
+pub mod checks;
+pub mod codeowners;
+pub mod confidential;
+pub mod config;
+pub mod conflicts;
+pub mod contributors;
+pub mod digest;
+pub mod doctor;
+pub mod drafts;
+pub mod expr;
+pub mod filter;
+pub mod http;
+pub mod i18n;
+pub mod issue_form;
+pub mod label_stats;
+pub mod labels;
+pub mod lock;
+pub mod members;
+pub mod needs_info;
+pub mod notify;
+pub mod plugins;
+pub mod policy;
+pub mod providers;
+pub mod release_notes;
+pub mod report;
+pub mod server;
+pub mod snapshot;
+pub mod storage;
+pub mod usage;
+
+pub type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
@@ -0,0 +1,61 @@
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{Error, ErrorKind, Result, Write},
+    path::Path,
+};
+
+use fs2::FileExt;
+use tracing::debug;
+
+const LOCK_FILE: &str = "issues-watcher.lock";
+
+/// Lock holds an advisory lock on a file inside the data directory,
+/// the lock is released when it's dropped or the process exits.
+pub struct Lock {
+    file: File,
+}
+
+impl Lock {
+    pub fn acquire(dir: &str) -> Result<Self> {
+        fs::create_dir_all(dir)?;
+        let path = Path::new(dir).join(LOCK_FILE);
+        let mut file = OpenOptions::new().write(true).create(true).open(&path)?;
+        if let Err(e) = file.try_lock_exclusive() {
+            if e.kind() == fs2::lock_contended_error().kind() {
+                return Err(Error::new(
+                    ErrorKind::Other,
+                    format!(
+                        "another issues-watcher is running, lock file {} is held",
+                        path.display()
+                    ),
+                ));
+            }
+            return Err(e);
+        }
+        file.set_len(0)?;
+        write!(file, "{}", std::process::id())?;
+        debug!("acquired lock {}", path.display());
+        Ok(Lock { file })
+    }
+}
+
+impl Drop for Lock {
+    fn drop(&mut self) {
+        let _ = self.file.unlock();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn acquire_lock() {
+        let dir = std::env::temp_dir().join("issues-watcher-lock-test");
+        let dir = dir.to_str().unwrap();
+        let lock = Lock::acquire(dir).unwrap();
+        assert!(Lock::acquire(dir).is_err());
+        drop(lock);
+        assert!(Lock::acquire(dir).is_ok());
+    }
+}
@@ -1,65 +1,1045 @@
-mod config;
-mod providers;
+mod init;
+mod man;
+#[cfg(feature = "tui")]
+mod tui;
 
-use clap::Clap;
-use config::Config;
-use providers::github::GitHub;
-use providers::slack::Slack;
+use std::{
+    fs::{self, OpenOptions},
+    io::Write,
+    net::SocketAddr,
+    path::Path,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use chrono::{DateTime, Utc};
+use clap::{Clap, IntoApp};
+use clap_generate::{
+    generate,
+    generators::{Bash, Elvish, Fish, PowerShell, Zsh},
+};
+use issues_watcher::{
+    alerts, anomaly, backport,
+    batch::{self, Channel},
+    board, calendar, codeowners, confidential,
+    config::{self, Config},
+    contributors,
+    digest::Digest,
+    doctor, drafts,
+    filter::{Filter, Since},
+    http, labels,
+    lock::Lock,
+    members, needs_info, notify, policy,
+    providers::{github::GitHub, IssueProvider},
+    release_notes,
+    report::{Report, Templates},
+    server::{self, SharedState},
+    snapshot::{self, build_report, new_github},
+    storage::{self, Notification, Snooze},
+    usage, Result,
+};
+use rand::Rng;
+use tokio::time::delay_for;
+use tracing::{error, info};
+use tracing_subscriber::EnvFilter;
+
+const NAME: &str = "issues-watcher";
 
 #[derive(Clap)]
-#[clap(version = "1.0", author = "you06")]
+#[clap(
+    version = "1.0",
+    author = "you06",
+    about = "Watch GitHub issues and projects and report the changes to Slack"
+)]
 struct Opts {
     #[clap(short = "c", long = "config", default_value = "config.toml")]
     config: String,
-    #[clap(short = "p", long = "ping")]
-    ping: Option<String>,
+    /// Watch the repo in addition to the config, e.g. pingcap/parser
+    #[clap(short = "r", long = "repo")]
+    repos: Vec<String>,
+    /// Watch the project in addition to the config
+    #[clap(long = "project")]
+    projects: Vec<String>,
+    /// Only watch the repos and projects given by command line
+    #[clap(long = "only")]
+    only: bool,
+    /// Log more, -v for info, -vv for debug, -vvv for trace, overridden by RUST_LOG
+    #[clap(short = "v", long = "verbose", parse(from_occurrences))]
+    verbose: i32,
+    /// Write logs in JSON
+    #[clap(long = "log-json")]
+    log_json: bool,
+    /// Print the notifications instead of sending them, and don't store snapshots
+    #[clap(long = "dry-run")]
+    dry_run: bool,
+    /// Don't color the output, also disabled by the NO_COLOR environment variable
+    #[clap(long = "no-color")]
+    no_color: bool,
+    #[clap(subcommand)]
+    subcmd: Option<SubCommand>,
+}
+
+#[derive(Clap)]
+enum SubCommand {
+    /// Fetch a snapshot and store it in github-data, this is the default command
+    #[clap(name = "snapshot")]
+    Snapshot(SnapshotCmd),
+    /// Fetch a snapshot and send the changes since the last one to Slack
+    #[clap(name = "report")]
+    Report(ReportCmd),
+    /// Print the changes between two snapshots, the latest two stored ones by default
+    #[clap(name = "diff")]
+    Diff(Diff),
+    /// Fetch a snapshot and send a digest of the stored snapshots in digest-days
+    #[clap(name = "digest")]
+    Digest(DigestCmd),
+    /// Run report periodically, and digest if digest-interval is set
+    #[clap(name = "serve")]
+    Serve(Serve),
+    /// Send a message to the notifiers to check their credentials
+    #[clap(name = "ping")]
+    Ping(Ping),
+    /// Keep an issue out of the stale issues of reports for a while
+    #[clap(name = "snooze")]
+    Snooze(SnoozeCmd),
+    /// Print the sent and failed notifications recorded in github-data
+    #[clap(name = "history")]
+    History(History),
+    /// Validate the config file and print the invalid fields
+    #[clap(name = "check-config")]
+    CheckConfig(CheckConfig),
+    /// Fetch a snapshot and write it as JSON
+    #[clap(name = "export")]
+    Export(Export),
+    /// Create a config file by answering questions
+    #[clap(name = "init")]
+    Init(Init),
+    /// Fetch the issues and pull requests matching a saved query and print them
+    #[clap(name = "query")]
+    Query(QueryCmd),
+    /// Print the issues and pull requests labeled to be cherry-picked without a backport yet
+    #[clap(name = "backports")]
+    Backports(Backports),
+    /// Manage the labels of the watched repos
+    #[clap(name = "labels")]
+    Labels(Labels),
+    /// Draft Markdown release notes from the issues and pull requests closed since a tag or date
+    #[clap(name = "release-notes")]
+    ReleaseNotes(ReleaseNotes),
+    /// List the project boards of a repo or an org
+    #[clap(name = "list-projects")]
+    ListProjects(ListProjects),
+    /// Print the GitHub user, token scopes, rate limit and orgs
+    #[clap(name = "whoami")]
+    Whoami(Whoami),
+    /// Check the token, repos, projects and channels of every configured integration
+    #[clap(name = "doctor")]
+    Doctor(Doctor),
+    /// Print the completion script of the shell
+    #[clap(name = "completions")]
+    Completions(Completions),
+    /// Print the man page
+    #[clap(name = "man")]
+    Man(Man),
+    /// Browse the latest stored snapshot as a board, a new one is fetched if there is none
+    #[clap(name = "tui")]
+    Tui(Tui),
+}
+
+#[derive(Clap)]
+struct SnapshotCmd {
+    #[clap(flatten)]
+    filter: Filter,
+}
+
+#[derive(Clap)]
+struct ReportCmd {
+    #[clap(flatten)]
+    filter: Filter,
+    /// Exit with code 2 if there are more stale issues than this, for CI jobs
+    #[clap(long = "max-stale")]
+    max_stale: Option<usize>,
+    /// Where the report goes, "slack", "github-step-summary", "md", "html", "atom" or "ics"
+    #[clap(long = "output", default_value = "slack")]
+    output: String,
+    /// Write the "md", "html", "atom" or "ics" report to the file instead of stdout
+    #[clap(short = "o", long = "output-file")]
+    output_file: Option<String>,
+}
+
+#[derive(Clap)]
+struct QueryCmd {
+    /// The name of a query in the queries of the config
+    name: String,
+}
+
+#[derive(Clap)]
+struct Diff {
+    /// The older snapshot file
+    old: Option<String>,
+    /// The newer snapshot file
+    new: Option<String>,
+}
+
+#[derive(Clap)]
+struct DigestCmd {
+    #[clap(flatten)]
+    filter: Filter,
+}
+
+#[derive(Clap)]
+struct Serve {}
+
+#[derive(Clap)]
+struct Ping {
+    /// Only ping the notifier, e.g. slack, all the configured ones by default
+    #[clap(long = "provider")]
+    provider: Option<String>,
+    message: String,
+}
+
+#[derive(Clap)]
+struct SnoozeCmd {
+    /// The issue like pingcap/tidb#1234, the active snoozes are listed if it's not given
+    issue: Option<String>,
+    /// For the days or until the date, e.g. 7d or 2020-06-01
+    #[clap(default_value = "7d")]
+    until: Since,
+    /// Remove the snooze of the issue
+    #[clap(long = "cancel")]
+    cancel: bool,
+}
+
+#[derive(Clap)]
+struct History {
+    /// Only notifications of the notifier, e.g. slack
+    #[clap(long = "notifier")]
+    notifier: Option<String>,
+    /// Only notifications since the date or in the days, e.g. 2020-05-01 or 7d
+    #[clap(long = "since")]
+    since: Option<Since>,
+    /// Only failed notifications
+    #[clap(long = "failed")]
+    failed: bool,
+    /// Only notifications including the issue url
+    #[clap(long = "issue")]
+    issue: Option<String>,
+}
+
+impl History {
+    fn matches(&self, notification: &Notification, since: Option<DateTime<Utc>>) -> bool {
+        self.notifier
+            .as_ref()
+            .map_or(true, |n| n == &notification.notifier)
+            && since.map_or(true, |since| notification.time >= since)
+            && (!self.failed || notification.error.is_some())
+            && self
+                .issue
+                .as_ref()
+                .map_or(true, |issue| notification.issues.contains(issue))
+    }
+}
+
+#[derive(Clap)]
+struct CheckConfig {}
+
+#[derive(Clap)]
+struct Export {
+    /// Write to the file instead of stdout
+    #[clap(short = "o", long = "output")]
+    output: Option<String>,
+}
+
+#[derive(Clap)]
+struct Init {}
+
+#[derive(Clap)]
+struct Whoami {}
+
+#[derive(Clap)]
+struct Doctor {}
+
+#[derive(Clap)]
+struct Tui {}
+
+#[derive(Clap)]
+struct Completions {
+    /// bash, zsh, fish, powershell or elvish
+    shell: String,
+}
+
+#[derive(Clap)]
+struct Man {}
+
+#[derive(Clap)]
+struct Backports {
+    /// Send them to the notifiers instead of printing
+    #[clap(long = "notify")]
+    notify: bool,
+}
+
+#[derive(Clap)]
+struct Labels {
+    #[clap(subcommand)]
+    subcmd: LabelsCommand,
+}
+
+#[derive(Clap)]
+enum LabelsCommand {
+    /// Create or update the labels configured by [[label]] in every watched repo,
+    /// --dry-run only prints the changes
+    #[clap(name = "sync")]
+    Sync(LabelsSync),
+}
+
+#[derive(Clap)]
+struct LabelsSync {}
+
+#[derive(Clap)]
+struct ReleaseNotes {
+    /// A tag of the watched repos like v4.0.0, a date like 2020-05-01 or days like 7d
+    #[clap(long = "since")]
+    since: String,
+    /// Group by the labels in order, the unlabeled ones go to "Other"
+    #[clap(
+        long = "group",
+        default_value = "type/feature,type/bug",
+        use_delimiter = true
+    )]
+    groups: Vec<String>,
+    /// Write to the file instead of stdout
+    #[clap(short = "o", long = "output")]
+    output: Option<String>,
+}
+
+#[derive(Clap)]
+struct ListProjects {
+    /// owner/repo or org
+    target: String,
+}
+
+fn check_config(filename: String) -> ! {
+    let errors = match Config::new(filename) {
+        Ok(conf) => conf.validate(),
+        Err(e) => vec![e.to_string()],
+    };
+    if errors.is_empty() {
+        println!("config is valid");
+        std::process::exit(0);
+    }
+    for error in errors {
+        eprintln!("{}", error);
+    }
+    std::process::exit(1);
+}
+
+// init_logger writes logs to stderr, RUST_LOG takes precedence over the verbose flags.
+fn init_logger(verbose: i32, json: bool) {
+    let level = match verbose {
+        0 => "warn",
+        1 => "info",
+        2 => "debug",
+        _ => "trace",
+    };
+    let filter = EnvFilter::try_from_default_env()
+        .unwrap_or_else(|_| EnvFilter::new(format!("issues_watcher={}", level)));
+    let builder = tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_writer(std::io::stderr);
+    if json {
+        builder.json().init();
+    } else {
+        builder.init();
+    }
+}
+
+async fn jitter(conf: &Config) {
+    if conf.jitter > 0 {
+        let jitter = rand::thread_rng().gen_range(0, conf.jitter * 1000);
+        info!("wait {}ms before start", jitter);
+        delay_for(Duration::from_millis(jitter)).await;
+    }
+}
+
+async fn output_report(
+    conf: &Config,
+    client: &reqwest::Client,
+    report: &Report,
+    output: &str,
+    output_file: Option<&str>,
+    dry_run: bool,
+) -> Result<()> {
+    let messages = conf.locale.messages();
+    let tz = conf.tz()?;
+    let templates = Templates::from_config(conf)?;
+    match output {
+        "slack" => {
+            let text = report.render(&templates, messages, &tz)?;
+            if conf.gist_threshold > 0 && text.chars().count() > conf.gist_threshold {
+                let text = upload_report(conf, client, report, &templates, dry_run).await?;
+                notify::send(conf, client, text, &report.issue_urls(), dry_run).await?
+            } else {
+                // the repos with a slack-channel of their own are posted there
+                let channels = conf.repo_channels();
+                let mut rest = report.clone();
+                rest.retain_repos(|repo| {
+                    !channels
+                        .iter()
+                        .any(|(_, repos)| repos.iter().any(|r| r == repo))
+                });
+                let render = |notifier: &str| -> Result<(Option<String>, String)> {
+                    let subject = conf
+                        .notifier_templates
+                        .get(notifier)
+                        .and_then(|t| t.report_subject.clone());
+                    let templates = templates.for_notifier(conf, notifier)?;
+                    let report = if notifier == "slack" { &rest } else { report };
+                    Ok((subject, report.render(&templates, messages, &tz)?))
+                };
+                notify::send_each(conf, client, render, &report.issue_urls(), dry_run).await?;
+                if conf.notifiers().contains(&"slack") {
+                    let templates = templates.for_notifier(conf, "slack")?;
+                    send_repo_channels(conf, client, report, &templates, &channels, dry_run).await?
+                }
+            }
+        }
+        "github-step-summary" => {
+            let markdown = report.render_markdown(&templates, messages, &tz)?;
+            if dry_run {
+                println!("[dry-run] write to GITHUB_STEP_SUMMARY:\n{}", markdown);
+            } else {
+                let path = std::env::var("GITHUB_STEP_SUMMARY")
+                    .map_err(|_| "GITHUB_STEP_SUMMARY is not set")?;
+                let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+                file.write_all(markdown.as_bytes())?;
+            }
+            for annotation in report.github_annotations(messages) {
+                println!("{}", annotation);
+            }
+        }
+        "md" => {
+            let markdown = report.render_markdown(&templates, messages, &tz)?;
+            match output_file {
+                Some(path) => fs::write(path, markdown)?,
+                None => print!("{}", markdown),
+            }
+        }
+        "html" => {
+            let html = report.render_html(&templates, messages, &tz)?;
+            match output_file {
+                Some(path) => fs::write(path, html)?,
+                None => print!("{}", html),
+            }
+        }
+        "atom" => {
+            let feed = report.render_atom(&templates, messages)?;
+            match output_file {
+                Some(path) => fs::write(path, feed)?,
+                None => print!("{}", feed),
+            }
+        }
+        "ics" => {
+            let ics = calendar::render_ics(report, conf.sla_days, messages);
+            match output_file {
+                Some(path) => fs::write(path, ics)?,
+                None => print!("{}", ics),
+            }
+        }
+        _ => return Err(format!("unknown output {:?}", output).into()),
+    }
+    Ok(())
+}
+
+// upload_report uploads the full report as a secret gist, and returns the
+// short report linking to it.
+async fn upload_report(
+    conf: &Config,
+    client: &reqwest::Client,
+    report: &Report,
+    templates: &Templates,
+    dry_run: bool,
+) -> Result<String> {
+    let messages = conf.locale.messages();
+    let tz = conf.tz()?;
+    let (filename, content) = match &conf.gist_format[..] {
+        "html" => ("report.html", report.render_html(templates, messages, &tz)?),
+        _ => (
+            "report.md",
+            report.render_markdown(templates, messages, &tz)?,
+        ),
+    };
+    let url = if dry_run {
+        println!("[dry-run] upload {} as a gist:\n{}", filename, content);
+        "https://gist.github.com/dry-run".to_owned()
+    } else {
+        let github_client =
+            GitHub::new(client.clone(), conf.github_token.to_owned(), vec![], vec![])?;
+        let description = format!(
+            "{} report at {}",
+            NAME,
+            report.snapshot.time().with_timezone(&tz)
+        );
+        github_client
+            .create_gist(&description, filename, &content)
+            .await?
+    };
+    Ok(report.render_link(messages, &tz, &url))
+}
+
+// send_repo_channels posts the sections of the repos with a slack-channel of
+// their own to the channel, the board is only posted to `slack-channel`.
+async fn send_repo_channels(
+    conf: &Config,
+    client: &reqwest::Client,
+    report: &Report,
+    templates: &Templates,
+    channels: &[(String, Vec<String>)],
+    dry_run: bool,
+) -> Result<()> {
+    let messages = conf.locale.messages();
+    let tz = conf.tz()?;
+    for (channel, repos) in channels {
+        let mut routed = report.clone();
+        routed.retain_repos(|repo| repos.iter().any(|r| r == repo));
+        routed.wip.clear();
+        routed.transitions.clear();
+        let text = routed.render(templates, messages, &tz)?;
+        let issues = routed.issue_urls();
+        notify::send_to_slack_channel(conf, client, channel, &text, &issues, dry_run).await?;
+    }
+    Ok(())
+}
+
+// act_on_report alerts, comments, moves cards and routes what the report
+// finds, stopping at the first step failing.
+async fn act_on_report(
+    conf: &Config,
+    client: &reqwest::Client,
+    report: &Report,
+    dry_run: bool,
+) -> Result<()> {
+    alert_spikes(conf, client, report, dry_run).await?;
+    board::apply(conf, client, &report.snapshot, dry_run).await?;
+    drafts::ping(conf, client, &report.drafts, dry_run).await?;
+    needs_info::ask(conf, client, &report.needs_info, dry_run).await?;
+    codeowners::route(conf, client, &report.snapshot, dry_run).await?;
+    policy::run(conf, client, report, dry_run).await?;
+    confidential::route(conf, client, report, dry_run).await
+}
+
+// alert_spikes sends an alert of each repo opening much more issues than usual.
+async fn alert_spikes(
+    conf: &Config,
+    client: &reqwest::Client,
+    report: &Report,
+    dry_run: bool,
+) -> Result<()> {
+    for mut spike in anomaly::detect(conf, report, dry_run)? {
+        // the report isn't redacted yet, the confidential issues go on their own
+        let confidential = confidential::split(conf, &mut spike.issues);
+        let text = spike.render(conf.locale.messages());
+        let issues: Vec<String> = spike.issues.iter().map(|i| i.to_string()).collect();
+        batch::send(conf, client, Channel::Notifiers, text, &issues, dry_run).await?;
+        if !confidential.is_empty() {
+            let spike = anomaly::Spike {
+                issues: confidential,
+                ..spike
+            };
+            let issues: Vec<String> = spike.issues.iter().map(|i| i.to_string()).collect();
+            let text = spike.render(conf.locale.messages());
+            confidential::send(conf, client, &text, &issues, dry_run).await?;
+        }
+    }
+    Ok(())
+}
+
+async fn send_digest(
+    conf: &Config,
+    client: &reqwest::Client,
+    report: &Report,
+    dry_run: bool,
+) -> Result<()> {
+    let since = *report.snapshot.time() - chrono::Duration::days(conf.digest_days);
+    let comments = contributors::collect(conf, client, &since, dry_run).await?;
+    let digest = Digest::build(conf, report, &comments)?;
+    let templates = Templates::from_config(conf)?;
+    let (messages, tz) = (conf.locale.messages(), conf.tz()?);
+    let render = |notifier: &str| -> Result<(Option<String>, String)> {
+        let subject = conf
+            .notifier_templates
+            .get(notifier)
+            .and_then(|t| t.digest_subject.clone());
+        let templates = templates.for_notifier(conf, notifier)?;
+        Ok((subject, digest.render(&templates, messages, &tz)?))
+    };
+    let issues: Vec<String> = digest
+        .stale
+        .iter()
+        .chain(&digest.sla_breaches)
+        .map(|issue| issue.to_string())
+        .collect();
+    notify::send_each(conf, client, render, &issues, dry_run).await
 }
 
 #[tokio::main]
-async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let opts: Opts = Opts::parse();
-    let conf = Config::new(opts.config).unwrap();
-
-    if let Some(ping) = opts.ping {
-        let slack_client = Slack::new(conf.slack_token.clone());
-        let _ = slack_client
-            .send_message(conf.slack_channel.clone(), ping)
-            .await?;
-        return Ok(());
+async fn main() -> Result<()> {
+    let mut opts: Opts = Opts::parse();
+    init_logger(opts.verbose, opts.log_json);
+    let subcmd = opts
+        .subcmd
+        .take()
+        .unwrap_or(SubCommand::Snapshot(SnapshotCmd {
+            filter: Filter::default(),
+        }));
+
+    match subcmd {
+        SubCommand::CheckConfig(_) => check_config(opts.config),
+        SubCommand::Init(_) => return Ok(init::run(&opts.config)?),
+        SubCommand::Completions(completions) => {
+            let mut app = Opts::into_app();
+            let mut stdout = std::io::stdout();
+            match &completions.shell[..] {
+                "bash" => generate::<Bash, _>(&mut app, NAME, &mut stdout),
+                "zsh" => generate::<Zsh, _>(&mut app, NAME, &mut stdout),
+                "fish" => generate::<Fish, _>(&mut app, NAME, &mut stdout),
+                "powershell" => generate::<PowerShell, _>(&mut app, NAME, &mut stdout),
+                "elvish" => generate::<Elvish, _>(&mut app, NAME, &mut stdout),
+                shell => return Err(format!("unsupported shell {:?}", shell).into()),
+            }
+            return Ok(());
+        }
+        SubCommand::Man(_) => {
+            print!("{}", man::render(&Opts::into_app()));
+            return Ok(());
+        }
+        _ => {}
     }
 
-    // let mut report = "".to_owned();
-    // let mut has_issue = false;
-
-    let mut github_client = GitHub::new(
-        conf.github_token.to_owned(),
-        conf.repos.clone(),
-        conf.projects.clone(),
-    );
-    github_client.get_projects_id().await?;
-    let user = github_client.get_user_result().await?;
-    println!("Current user: {}", user);
-
-    let snapshot = github_client.get_snapshot().await?;
-    println!("{:?}", snapshot);
-
-    // if issues.len() != 0 {
-    //     has_issue = true;
-    //     report.push_str(&format!("{} no-reply issues in 3 days\n", issues.len())[..]);
-    //     for issue in issues {
-    //         report.push_str(&format!("{}\n", issue)[..]);
-    //     }
-    // }
-
-    // if conf.slack_token != "" && conf.slack_channel != "" {
-    //     if has_issue {
-    //         let slack_client = Slack::new(conf.slack_token.clone());
-    //         let _ = slack_client
-    //             .send_message(conf.slack_channel.clone(), report)
-    //             .await?;
-    //     }
-    // } else {
-    //     println!("{}", report);
-    // }
+    let mut conf = Config::new(opts.config.clone())?;
+    conf.add_repos(opts.repos.clone(), opts.projects.clone(), opts.only);
+    let client = http::new_client(&conf)?;
+    let usage_top = conf.api_usage_top;
+    let ran = run(&opts, subcmd, conf, client).await;
+    // failed runs are logged too, the budget they used matters most then
+    usage::log(usage_top);
+    ran
+}
+
+/// run runs the commands fetching from the providers.
+async fn run(
+    opts: &Opts,
+    subcmd: SubCommand,
+    mut conf: Config,
+    client: reqwest::Client,
+) -> Result<()> {
+    // the snapshots of the repos given by command line are partial, they're not
+    // stored so the next full run still compares every repo
+    let partial = opts.only || !opts.repos.is_empty() || !opts.projects.is_empty();
+    let tz = conf.tz()?;
+    let messages = conf.locale.messages();
+    // serve resolves the teams each round, the other commands don't read members
+    if matches!(
+        subcmd,
+        SubCommand::Snapshot(_) | SubCommand::Report(_) | SubCommand::Digest(_)
+    ) {
+        members::resolve(&mut conf, &client).await?;
+    }
+    let usage_top = conf.api_usage_top;
+
+    match subcmd {
+        SubCommand::Snapshot(cmd) => {
+            let _lock = Lock::acquire(&conf.github_data)?;
+            let mut github_client = new_github(&conf, &client, !opts.log_json).await?;
+            // filter while fetching, the snapshot is partial then and not stored
+            let since = cmd.filter.since.map(|since| since.resolve(&Utc::now()));
+            github_client.set_since(since);
+            github_client.retain_repos(|repo| cmd.filter.match_repo(repo));
+            if cmd.filter.narrows_issues() {
+                github_client.set_issue_filter(cmd.filter.matcher());
+            }
+            let mut snapshot = github_client.get_snapshot().await?;
+            // other providers can't filter while fetching, the filter applies below
+            for provider in snapshot::extra_providers(&conf, &client)? {
+                snapshot = snapshot.merge(provider.get_snapshot().await?);
+            }
+            if partial || cmd.filter.narrows_issues() || !cmd.filter.repos.is_empty() {
+                info!("the snapshot is filtered, it's not stored");
+            } else if !opts.dry_run {
+                let path = storage::save(&conf.github_data, &snapshot)?;
+                info!("snapshot is stored at {}", path.display());
+            }
+            let color = !opts.no_color && std::env::var_os("NO_COLOR").is_none();
+            let mut report = Report::new(snapshot);
+            cmd.filter.apply(&mut report);
+            print!("{}", report.render_terminal(messages, &tz, color));
+        }
+        SubCommand::Report(cmd) => {
+            let _lock = Lock::acquire(&conf.github_data)?;
+            jitter(&conf).await;
+            let mut report = build_report(&conf, &client).await?;
+            // stored once delivered, unlike the report it's not redacted or filtered
+            let snapshot = report.snapshot.clone();
+            let acted = act_on_report(&conf, &client, &report, opts.dry_run).await;
+            // the findings kept before a failed step are sent anyway
+            batch::flush(&conf, &client, opts.dry_run).await?;
+            acted?;
+            confidential::redact(&conf, &mut report);
+            alerts::sync(&conf, &client, &report, opts.dry_run).await?;
+            cmd.filter.apply(&mut report);
+            output_report(
+                &conf,
+                &client,
+                &report,
+                &cmd.output,
+                cmd.output_file.as_deref(),
+                opts.dry_run,
+            )
+            .await?;
+            snapshot::store(&conf, &snapshot, opts.dry_run || partial)?;
+            let stale = report.stale_count();
+            if let Some(max_stale) = cmd.max_stale {
+                if stale > max_stale {
+                    eprintln!("{} stale issues, more than {}", stale, max_stale);
+                    usage::log(usage_top);
+                    std::process::exit(2);
+                }
+            }
+        }
+        SubCommand::Diff(diff) => {
+            let (old, new) = match (diff.old, diff.new) {
+                (Some(old), Some(new)) => (
+                    storage::load(Path::new(&old))?,
+                    storage::load(Path::new(&new))?,
+                ),
+                (None, None) => {
+                    let paths = storage::latest(&conf.github_data, 2)?;
+                    if paths.len() < 2 {
+                        return Err("at least two stored snapshots are required".into());
+                    }
+                    (storage::load(&paths[1])?, storage::load(&paths[0])?)
+                }
+                _ => return Err("both old and new snapshot files are required".into()),
+            };
+            let diffs = new.diff(&old);
+            let transitions = board::transitions(&old, &new);
+            let report = Report {
+                diffs,
+                transitions,
+                ..Report::new(new)
+            };
+            let templates = Templates::from_config(&conf)?;
+            print!("{}", report.render(&templates, messages, &tz)?);
+        }
+        SubCommand::Digest(cmd) => {
+            let _lock = Lock::acquire(&conf.github_data)?;
+            jitter(&conf).await;
+            let mut report = build_report(&conf, &client).await?;
+            let snapshot = report.snapshot.clone();
+            let alerted = alert_spikes(&conf, &client, &report, opts.dry_run).await;
+            batch::flush(&conf, &client, opts.dry_run).await?;
+            alerted?;
+            confidential::redact(&conf, &mut report);
+            cmd.filter.apply(&mut report);
+            send_digest(&conf, &client, &report, opts.dry_run).await?;
+            snapshot::store(&conf, &snapshot, opts.dry_run || partial)?;
+        }
+        SubCommand::Serve(_) => {
+            let _lock = Lock::acquire(&conf.github_data)?;
+            let state = SharedState::default();
+            if let Some(listen) = &conf.listen {
+                let addr: SocketAddr = listen.parse()?;
+                let mut current = state.write().map_err(|e| e.to_string())?;
+                current.data_dir = conf.github_data.clone();
+                if let Some(path) = storage::latest(&conf.github_data, 1)?.first() {
+                    let mut snapshot = storage::load(path)?;
+                    confidential::redact_snapshot(&conf, &mut snapshot);
+                    current.snapshot = Some(snapshot);
+                }
+                drop(current);
+                let state = Arc::clone(&state);
+                tokio::spawn(async move {
+                    if let Err(e) = server::run(addr, state).await {
+                        error!("API server failed: {}", e);
+                    }
+                });
+            }
+            let digest_interval = Duration::from_secs(conf.digest_interval);
+            let mut last_digest = Instant::now();
+            let batch_window = Duration::from_secs(conf.batch_window);
+            let mut last_batch = Instant::now();
+            let configured = members::Configured::of(&conf);
+            loop {
+                jitter(&conf).await;
+                // the mounted secrets may be rotated
+                if let Err(e) = conf.read_secret_files() {
+                    error!("reading the secret files failed: {}", e);
+                }
+                // the teams are fetched again after member-cache-ttl
+                configured.restore(&mut conf);
+                if let Err(e) = members::resolve(&mut conf, &client).await {
+                    error!("resolving the teams of members failed: {}", e);
+                }
+                let mut report = match build_report(&conf, &client).await {
+                    Ok(report) => report,
+                    Err(e) => {
+                        error!("report failed: {}", e);
+                        delay_for(Duration::from_secs(conf.serve_interval)).await;
+                        continue;
+                    }
+                };
+                if let Err(e) = alert_spikes(&conf, &client, &report, opts.dry_run).await {
+                    error!("spike alert failed: {}", e);
+                }
+                if let Err(e) = board::apply(&conf, &client, &report.snapshot, opts.dry_run).await {
+                    error!("moving cards failed: {}", e);
+                }
+                if let Err(e) = drafts::ping(&conf, &client, &report.drafts, opts.dry_run).await {
+                    error!("pinging drafts failed: {}", e);
+                }
+                if let Err(e) =
+                    needs_info::ask(&conf, &client, &report.needs_info, opts.dry_run).await
+                {
+                    error!("asking for more info failed: {}", e);
+                }
+                if let Err(e) =
+                    codeowners::route(&conf, &client, &report.snapshot, opts.dry_run).await
+                {
+                    error!("routing reviews failed: {}", e);
+                }
+                if let Err(e) = policy::run(&conf, &client, &report, opts.dry_run).await {
+                    error!("rule scripts failed: {}", e);
+                }
+                if let Err(e) = confidential::route(&conf, &client, &report, opts.dry_run).await {
+                    error!("routing confidential issues failed: {}", e);
+                }
+                if last_batch.elapsed() >= batch_window {
+                    last_batch = Instant::now();
+                    if let Err(e) = batch::flush(&conf, &client, opts.dry_run).await {
+                        error!("sending the coalesced findings failed: {}", e);
+                    }
+                }
+                let snapshot = report.snapshot.clone();
+                confidential::redact(&conf, &mut report);
+                if let Err(e) = alerts::sync(&conf, &client, &report, opts.dry_run).await {
+                    error!("syncing Opsgenie alerts failed: {}", e);
+                }
+                // the changes of a report failing to be sent are sent next round
+                let sent = output_report(&conf, &client, &report, "slack", None, opts.dry_run)
+                    .await
+                    .and_then(|_| snapshot::store(&conf, &snapshot, opts.dry_run || partial));
+                if let Err(e) = sent {
+                    error!("report failed: {}", e);
+                }
+                // the digest reuses the snapshot of the report
+                if conf.digest_interval > 0 && last_digest.elapsed() >= digest_interval {
+                    last_digest = Instant::now();
+                    if let Err(e) = send_digest(&conf, &client, &report, opts.dry_run).await {
+                        error!("digest failed: {}", e);
+                    }
+                }
+                let feed = Templates::from_config(&conf)
+                    .and_then(|templates| report.render_atom(&templates, messages));
+                if let Err(e) = &feed {
+                    error!("feed failed: {}", e);
+                }
+                {
+                    let mut state = match state.write() {
+                        Ok(state) => state,
+                        // a panic while updating it before, what's updated here is replaced
+                        Err(poisoned) => poisoned.into_inner(),
+                    };
+                    state.feed = feed.ok();
+                    state.calendar = Some(calendar::render_ics(&report, conf.sla_days, messages));
+                    let time = *report.snapshot.time();
+                    state.push_stale(time, report.stale_count());
+                    state.snapshot = Some(report.snapshot);
+                    state.stale = report.stale;
+                }
+                usage::log(usage_top);
+                delay_for(Duration::from_secs(conf.serve_interval)).await;
+            }
+        }
+        SubCommand::Ping(ping) => {
+            let providers = match ping.provider {
+                Some(provider) => vec![provider],
+                None => conf.notifiers().into_iter().map(String::from).collect(),
+            };
+            if providers.is_empty() {
+                return Err("no notifier is configured".into());
+            }
+            let mut failed = 0;
+            for provider in providers {
+                let sent =
+                    notify::send_to(&conf, &client, &provider, &ping.message, &[], opts.dry_run);
+                match sent.await {
+                    Ok(_) => println!("{}: ok", provider),
+                    Err(e) => {
+                        println!("{}: {}", provider, e);
+                        failed += 1;
+                    }
+                }
+            }
+            if failed > 0 {
+                return Err(format!("{} notifiers failed", failed).into());
+            }
+        }
+        SubCommand::Snooze(cmd) => {
+            let now = Utc::now();
+            let mut snoozes = storage::snoozes(&conf.github_data)?;
+            snoozes.retain(|s| s.until > now);
+            let issue = match cmd.issue {
+                Some(issue) => issue,
+                None => {
+                    println!("issue\tuntil");
+                    for s in &snoozes {
+                        println!("{}#{}\t{}", s.repo, s.number, s.until.with_timezone(&tz));
+                    }
+                    return Ok(());
+                }
+            };
+            let (repo, number) = config::parse_issue(&issue)
+                .ok_or_else(|| format!("{:?} is not like owner/repo#123", issue))?;
+            snoozes.retain(|s| s.repo != repo || s.number != number);
+            if cmd.cancel {
+                println!("{} is not snoozed", issue);
+            } else {
+                let until = match cmd.until {
+                    Since::Days(days) => now + chrono::Duration::days(days),
+                    Since::Time(time) => time,
+                };
+                if until <= now {
+                    return Err(format!("{} is in the past", until.with_timezone(&tz)).into());
+                }
+                println!("{} is snoozed until {}", issue, until.with_timezone(&tz));
+                snoozes.push(Snooze {
+                    repo,
+                    number,
+                    until,
+                });
+            }
+            storage::save_snoozes(&conf.github_data, &snoozes)?;
+        }
+        SubCommand::History(history) => {
+            let since = history.since.map(|since| since.resolve(&Utc::now()));
+            println!("time\tnotifier\tchannel\tresult\tissues");
+            for notification in storage::notifications(&conf.github_data)?
+                .iter()
+                .filter(|n| history.matches(n, since))
+            {
+                println!(
+                    "{}\t{}\t{}\t{}\t{}",
+                    notification.time.with_timezone(&tz),
+                    notification.notifier,
+                    notification.channel,
+                    notification.error.as_deref().unwrap_or("ok"),
+                    notification.issues.len()
+                );
+            }
+        }
+        SubCommand::Export(export) => {
+            let snapshot = snapshot::fetch(&conf, &client, !opts.log_json).await?;
+            let json = serde_json::to_string_pretty(&snapshot)?;
+            match export.output {
+                Some(output) => fs::write(output, json)?,
+                None => println!("{}", json),
+            }
+        }
+        SubCommand::Query(cmd) => {
+            let filter = match conf.queries.get(&cmd.name) {
+                Some(query) => Filter::from(query),
+                None => {
+                    let names: Vec<&str> = conf.queries.keys().map(|n| &n[..]).collect();
+                    return Err(format!(
+                        "no query named {:?}, the queries are [{}]",
+                        cmd.name,
+                        names.join(", ")
+                    )
+                    .into());
+                }
+            };
+            let mut github_client = new_github(&conf, &client, !opts.log_json).await?;
+            github_client.retain_repos(|repo| filter.match_repo(repo));
+            github_client.set_issue_filter(filter.matcher());
+            let mut snapshot = github_client.get_snapshot().await?;
+            for provider in snapshot::extra_providers(&conf, &client)? {
+                snapshot = snapshot.merge(provider.get_snapshot().await?);
+            }
+            let color = !opts.no_color && std::env::var_os("NO_COLOR").is_none();
+            let mut report = Report::new(snapshot);
+            filter.apply(&mut report);
+            print!("{}", report.render_terminal(messages, &tz, color));
+        }
+        SubCommand::Backports(cmd) => {
+            let missing = backport::check(&conf, &client).await?;
+            let lines: Vec<String> = missing.iter().map(|m| m.render(messages)).collect();
+            if !cmd.notify {
+                for line in lines {
+                    println!("{}", line);
+                }
+            } else if !lines.is_empty() {
+                let issues: Vec<String> = missing.iter().map(|m| m.issue.to_string()).collect();
+                notify::send(&conf, &client, lines.join("\n"), &issues, opts.dry_run).await?;
+            }
+        }
+        SubCommand::Labels(cmd) => match cmd.subcmd {
+            LabelsCommand::Sync(_) => labels::sync(&conf, &client, opts.dry_run).await?,
+        },
+        SubCommand::ReleaseNotes(cmd) => {
+            let github_client =
+                GitHub::new(client.clone(), conf.github_token.to_owned(), vec![], vec![])?;
+            let since = cmd.since.parse::<Since>().ok();
+            let mut issues = vec![];
+            for rule in conf.repo_rules() {
+                // not a date, it's a tag of every repo
+                let time = match since {
+                    Some(since) => since.resolve(&Utc::now()),
+                    None => github_client.tag_time(&rule.name, &cmd.since).await?,
+                };
+                issues.extend(github_client.list_closed(&rule.name, &time).await?);
+            }
+            let notes = release_notes::render(messages, &cmd.since, &cmd.groups, &issues);
+            match cmd.output {
+                Some(output) => fs::write(output, notes)?,
+                None => print!("{}", notes),
+            }
+        }
+        SubCommand::ListProjects(list) => {
+            let github_client =
+                GitHub::new(client.clone(), conf.github_token.to_owned(), vec![], vec![])?;
+            println!("number\tname\tid\turl");
+            for project in github_client.list_project_boards(&list.target).await? {
+                println!("{}", project);
+            }
+        }
+        SubCommand::Whoami(_) => {
+            let github_client =
+                GitHub::new(client.clone(), conf.github_token.to_owned(), vec![], vec![])?;
+            println!("{}", github_client.get_user_result().await?);
+        }
+        SubCommand::Doctor(_) => {
+            let checks = doctor::run(&conf, &client).await;
+            print!("{}", doctor::render(&checks));
+            let failed = checks.iter().filter(|c| c.result.is_err()).count();
+            if failed > 0 {
+                return Err(format!("{} checks failed", failed).into());
+            }
+        }
+        #[cfg(feature = "tui")]
+        SubCommand::Tui(_) => {
+            let snapshot = match storage::latest(&conf.github_data, 1)?.first() {
+                Some(path) => storage::load(path)?,
+                None => snapshot::fetch(&conf, &client, true).await?,
+            };
+            tui::run(&snapshot)?;
+        }
+        #[cfg(not(feature = "tui"))]
+        SubCommand::Tui(_) => {
+            return Err("tui is not enabled, build with `--features tui`".into());
+        }
+        SubCommand::CheckConfig(_)
+        | SubCommand::Init(_)
+        | SubCommand::Completions(_)
+        | SubCommand::Man(_) => unreachable!(),
+    }
     Ok(())
 }
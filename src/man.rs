@@ -0,0 +1,60 @@
+use clap::App;
+
+// render builds a roff man page from the help of the app and its subcommands.
+pub fn render(app: &App) -> String {
+    let name = app.get_name().to_owned();
+    let mut page = format!(
+        ".TH {} 1\n.SH NAME\n{} \\- {}\n.SH SYNOPSIS\n\\fB{}\\fR [OPTIONS] [SUBCOMMAND]\n",
+        name.to_uppercase(),
+        name,
+        escape(app.get_about().unwrap_or("")),
+        name
+    );
+    page.push_str(".SH OPTIONS\n");
+    page.push_str(&help(app));
+    page.push_str(".SH SUBCOMMANDS\n");
+    for subcommand in app.get_subcommands() {
+        page.push_str(&format!(".SS {}\n", subcommand.get_name()));
+        page.push_str(&help(subcommand));
+    }
+    page
+}
+
+fn help(app: &App) -> String {
+    let mut buf = vec![];
+    let _ = app.clone().write_long_help(&mut buf);
+    format!(".nf\n{}\n.fi\n", escape(&String::from_utf8_lossy(&buf)))
+}
+
+// escape keeps the help text from being read as roff requests.
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .lines()
+        .map(|line| {
+            if line.starts_with('.') || line.starts_with('\'') {
+                format!("\\&{}", line)
+            } else {
+                line.to_owned()
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn render_man_page() {
+        let app = App::new("issues-watcher")
+            .about("watch issues")
+            .subcommand(App::new("ping").about("send a message"));
+        let page = render(&app);
+        assert!(
+            page.starts_with(".TH ISSUES-WATCHER 1\n.SH NAME\nissues-watcher \\- watch issues\n")
+        );
+        assert!(page.contains(".SS ping\n.nf\n"));
+        assert_eq!(escape(".x\n'y\na\\b"), "\\&.x\n\\&'y\na\\\\b");
+    }
+}
@@ -0,0 +1,150 @@
+use chrono::{Duration, Utc};
+use tracing::info;
+
+use crate::config::Config;
+use crate::providers::github::GitHub;
+use crate::storage::{self, TeamMembers};
+use crate::Result;
+
+/// resolve replaces the GitHub teams like "@pingcap/tidb-team" in the global
+/// and the repo `members` by the logins of their members. The members of the
+/// teams are stored in `github-data` and fetched again after
+/// `member-cache-ttl`, so repeated runs don't look them up again.
+pub async fn resolve(conf: &mut Config, client: &reqwest::Client) -> Result<()> {
+    let mut teams: Vec<String> = vec![];
+    for member in conf.members.iter().chain(
+        conf.repo_configs
+            .iter()
+            .flat_map(|r| r.members.iter().flatten()),
+    ) {
+        if let Some(team) = team(member) {
+            if !teams.iter().any(|t| t == team) {
+                teams.push(team.to_owned());
+            }
+        }
+    }
+    if teams.is_empty() {
+        return Ok(());
+    }
+    let mut stored = storage::team_members(&conf.github_data)?;
+    let now = Utc::now();
+    let ttl = Duration::seconds(conf.member_cache_ttl as i64);
+    let github_client = GitHub::new(client.clone(), conf.github_token.clone(), vec![], vec![])?;
+    let mut fetched = false;
+    for team in teams {
+        if stored.iter().any(|t| t.team == team && now - t.time < ttl) {
+            continue;
+        }
+        let logins = github_client.team_members(&team).await?;
+        info!("fetched {} members of {}", logins.len(), team);
+        stored.retain(|t| t.team != team);
+        stored.push(TeamMembers {
+            team,
+            time: now,
+            logins,
+        });
+        fetched = true;
+    }
+    if fetched {
+        storage::save_team_members(&conf.github_data, &stored)?;
+    }
+    expand(&mut conf.members, &stored);
+    for repo in conf.repo_configs.iter_mut() {
+        if let Some(members) = &mut repo.members {
+            expand(members, &stored);
+        }
+    }
+    Ok(())
+}
+
+/// Configured are the members as configured, with the teams in them, so `serve`
+/// resolves them again each round.
+pub struct Configured {
+    members: Vec<String>,
+    repo_members: Vec<Option<Vec<String>>>,
+}
+
+impl Configured {
+    pub fn of(conf: &Config) -> Self {
+        Configured {
+            members: conf.members.clone(),
+            repo_members: conf
+                .repo_configs
+                .iter()
+                .map(|r| r.members.clone())
+                .collect(),
+        }
+    }
+
+    /// restore puts the members with the teams back into the config.
+    pub fn restore(&self, conf: &mut Config) {
+        conf.members = self.members.clone();
+        for (repo, members) in conf.repo_configs.iter_mut().zip(&self.repo_members) {
+            repo.members = members.clone();
+        }
+    }
+}
+
+// team returns "org/team" of a member like "@org/team".
+fn team(member: &str) -> Option<&str> {
+    member.strip_prefix('@').filter(|team| team.contains('/'))
+}
+
+// expand replaces the teams in the members by their logins.
+fn expand(members: &mut Vec<String>, teams: &[TeamMembers]) {
+    let mut logins: Vec<String> = vec![];
+    for member in members.drain(..) {
+        let found = team(&member).and_then(|team| teams.iter().find(|t| t.team == team));
+        let expanded = match found {
+            Some(team) => team.logins.clone(),
+            None => vec![member],
+        };
+        for login in expanded {
+            if !logins.contains(&login) {
+                logins.push(login);
+            }
+        }
+    }
+    *members = logins;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn expand_teams() {
+        let teams = vec![TeamMembers {
+            team: "pingcap/tidb-team".to_owned(),
+            time: Utc::now(),
+            logins: vec!["you06".to_owned(), "tiancaiamao".to_owned()],
+        }];
+        let mut members = vec![
+            "you06".to_owned(),
+            "@pingcap/tidb-team".to_owned(),
+            "@pingcap/unknown".to_owned(),
+        ];
+        expand(&mut members, &teams);
+        assert_eq!(members, vec!["you06", "tiancaiamao", "@pingcap/unknown"]);
+        assert_eq!(team("@pingcap/tidb-team"), Some("pingcap/tidb-team"));
+        assert_eq!(team("you06"), None);
+    }
+
+    #[test]
+    fn restore_configured_teams() {
+        let mut conf: Config = toml::from_str(
+            "slack-channel = \"\"\nmembers = [\"@pingcap/tidb-team\"]\n\
+             [[repo]]\nname = \"pingcap/tidb\"\nmembers = [\"@pingcap/sql-infra\"]",
+        )
+        .unwrap();
+        let configured = Configured::of(&conf);
+        conf.members = vec!["you06".to_owned()];
+        conf.repo_configs[0].members = Some(vec!["tiancaiamao".to_owned()]);
+        configured.restore(&mut conf);
+        assert_eq!(conf.members, vec!["@pingcap/tidb-team"]);
+        assert_eq!(
+            conf.repo_configs[0].members,
+            Some(vec!["@pingcap/sql-infra".to_owned()])
+        );
+    }
+}
@@ -0,0 +1,341 @@
+use chrono::Duration;
+use serde_yaml::Value;
+use tracing::{info, warn};
+
+use crate::config::Config;
+use crate::i18n::fill;
+use crate::issue_form::{self, field_name};
+use crate::providers::github::{GitHub, Issue, Snapshot};
+use crate::storage;
+use crate::Result;
+
+/// IssueTemplate is an issue template of a repo, the issues opened with it are
+/// expected to fill its sections.
+#[derive(Debug, PartialEq)]
+pub struct IssueTemplate {
+    pub name: String,
+    /// the labels added to the issues opened with the template
+    pub labels: Vec<String>,
+    /// the headings of the required sections
+    pub sections: Vec<String>,
+}
+
+impl IssueTemplate {
+    /// parse reads an issue form in YAML, whose required inputs are the
+    /// sections, or a Markdown template, whose headings are. Other files like
+    /// `config.yml` are left out.
+    pub fn parse(filename: &str, contents: &str) -> Option<IssueTemplate> {
+        if filename.ends_with(".yml") || filename.ends_with(".yaml") {
+            let form: Value = serde_yaml::from_str(contents).ok()?;
+            let sections = form
+                .get("body")?
+                .as_sequence()?
+                .iter()
+                .filter(|item| item["type"].as_str() != Some("markdown"))
+                .filter(|item| item["validations"]["required"].as_bool() == Some(true))
+                .filter_map(|item| item["attributes"]["label"].as_str())
+                .map(str::to_owned)
+                .collect();
+            Some(IssueTemplate {
+                name: name(&form, filename),
+                labels: labels(&form["labels"]),
+                sections,
+            })
+        } else if filename.ends_with(".md") {
+            let (front, body) = front_matter(contents);
+            let front: Value = front
+                .and_then(|front| serde_yaml::from_str(front).ok())
+                .unwrap_or(Value::Null);
+            Some(IssueTemplate {
+                name: name(&front, filename),
+                labels: labels(&front["labels"]),
+                sections: issue_form::headings(body),
+            })
+        } else {
+            None
+        }
+    }
+}
+
+fn name(template: &Value, filename: &str) -> String {
+    template["name"].as_str().unwrap_or(filename).to_owned()
+}
+
+// labels are a list or a string separated by commas.
+fn labels(labels: &Value) -> Vec<String> {
+    match labels {
+        Value::String(labels) => labels
+            .split(',')
+            .map(str::trim)
+            .filter(|l| !l.is_empty())
+            .map(str::to_owned)
+            .collect(),
+        Value::Sequence(labels) => labels
+            .iter()
+            .filter_map(Value::as_str)
+            .map(str::to_owned)
+            .collect(),
+        _ => vec![],
+    }
+}
+
+// front_matter splits the YAML between the leading "---" lines from the
+// template.
+fn front_matter(contents: &str) -> (Option<&str>, &str) {
+    let rest = match contents.strip_prefix("---") {
+        Some(rest) => rest,
+        None => return (None, contents),
+    };
+    match rest.find("\n---") {
+        Some(end) => {
+            let body = &rest[end + 4..];
+            (
+                Some(&rest[..end]),
+                body.splitn(2, '\n').nth(1).unwrap_or(""),
+            )
+        }
+        None => (None, contents),
+    }
+}
+
+/// missing returns the sections of the template the issue is opened with that
+/// it doesn't fill. The template is the one whose labels are all on the issue,
+/// or else the one with the most sections filled by the issue, or else the only
+/// one; none if no template is found.
+pub fn missing(issue: &Issue, templates: &[IssueTemplate]) -> Option<Vec<String>> {
+    let filled = |t: &IssueTemplate| {
+        t.sections
+            .iter()
+            .filter(|s| issue.field(&field_name(s)).is_some())
+            .count()
+    };
+    let template = templates
+        .iter()
+        .find(|t| !t.labels.is_empty() && t.labels.iter().all(|l| issue.labels().any(|i| i == l)))
+        .or_else(|| {
+            templates
+                .iter()
+                .filter(|t| filled(t) > 0)
+                .max_by_key(|t| filled(t))
+        })
+        .or_else(|| match templates {
+            [template] => Some(template),
+            _ => None,
+        })?;
+    Some(
+        template
+            .sections
+            .iter()
+            .filter(|s| issue.field(&field_name(s)).is_none())
+            .cloned()
+            .collect(),
+    )
+}
+
+/// IncompleteIssue is an issue with the sections of its template it misses.
+#[derive(Debug, Clone)]
+pub struct IncompleteIssue {
+    pub issue: Issue,
+    pub missing: Vec<String>,
+}
+
+/// NeedsInfo are the issues of a repo opened in the last `days` which don't
+/// fill the required sections of the repo's issue templates.
+#[derive(Debug, Clone)]
+pub struct NeedsInfo {
+    pub repo: String,
+    pub days: i64,
+    pub issues: Vec<IncompleteIssue>,
+}
+
+/// detect checks the issues of the watched GitHub repos opened in the last
+/// `needs-info-days` against the issue templates of the repos, nothing is
+/// checked if it's not set.
+pub async fn detect(
+    conf: &Config,
+    client: &reqwest::Client,
+    snapshot: &Snapshot,
+) -> Result<Vec<NeedsInfo>> {
+    let days = match conf.needs_info_days {
+        Some(days) => days,
+        None => return Ok(vec![]),
+    };
+    let since = *snapshot.time() - Duration::days(days);
+    let repos = conf.repo_names();
+    let github_client = GitHub::new(client.clone(), conf.github_token.clone(), vec![], vec![])?;
+    let mut needs_info = vec![];
+    for repo in snapshot
+        .repo_issues()
+        .iter()
+        .filter(|repo| repos.contains(&repo.name()))
+    {
+        let recent: Vec<&Issue> = repo
+            .issues()
+            .iter()
+            .filter(|issue| !issue.is_pull() && *issue.created_at() >= since)
+            .collect();
+        if recent.is_empty() {
+            continue;
+        }
+        let templates: Vec<IssueTemplate> = github_client
+            .issue_templates(&repo.name())
+            .await?
+            .iter()
+            .filter_map(|(filename, contents)| IssueTemplate::parse(filename, contents))
+            .collect();
+        let issues: Vec<IncompleteIssue> = recent
+            .into_iter()
+            .filter_map(|issue| {
+                let missing = missing(issue, &templates)?;
+                if missing.is_empty() {
+                    None
+                } else {
+                    Some(IncompleteIssue {
+                        issue: issue.clone(),
+                        missing,
+                    })
+                }
+            })
+            .collect();
+        if !issues.is_empty() {
+            needs_info.push(NeedsInfo {
+                repo: repo.name(),
+                days,
+                issues,
+            });
+        }
+    }
+    Ok(needs_info)
+}
+
+/// ask comments on each issue needing more info to ask its author for the
+/// missing sections, once an issue. The asked issues are stored in
+/// `github-data`, and nothing is commented or stored in a dry run.
+pub async fn ask(
+    conf: &Config,
+    client: &reqwest::Client,
+    needs_info: &[NeedsInfo],
+    dry_run: bool,
+) -> Result<()> {
+    if !conf.needs_info_ask {
+        return Ok(());
+    }
+    let mut asked = storage::asked_info(&conf.github_data)?;
+    // the issues out of the days are never asked again
+    let urls: Vec<String> = needs_info
+        .iter()
+        .flat_map(|n| n.issues.iter().map(|i| i.issue.to_string()))
+        .collect();
+    asked.retain(|url| urls.contains(url));
+    let github_client = GitHub::new(client.clone(), conf.github_token.clone(), vec![], vec![])?;
+    for repo in needs_info {
+        for incomplete in &repo.issues {
+            let url = incomplete.issue.to_string();
+            if asked.contains(&url) {
+                continue;
+            }
+            let text = fill(
+                conf.locale.messages().needs_info_ask,
+                &[
+                    &incomplete.issue.author().unwrap_or("author"),
+                    &incomplete.missing.join(", "),
+                ],
+            );
+            if dry_run {
+                println!("[dry-run] comment on {}: {}", url, text);
+                continue;
+            }
+            let commented = github_client
+                .comment_issue(&repo.repo, incomplete.issue.number(), &text)
+                .await;
+            if let Err(e) = commented {
+                warn!("failed to ask the author of {} for more info: {}", url, e);
+                continue;
+            }
+            info!("asked the author of {} for more info", url);
+            asked.push(url);
+            // stored at once, an issue is never asked twice even if the run fails
+            storage::save_asked_info(&conf.github_data, &asked)?;
+        }
+    }
+    if !dry_run {
+        storage::save_asked_info(&conf.github_data, &asked)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn missing_sections() {
+        let form = "name: Bug Report\n\
+                    labels: [type/bug]\n\
+                    body:\n\
+                    \x20 - type: markdown\n\
+                    \x20   attributes:\n\
+                    \x20     value: Thanks for the report!\n\
+                    \x20 - type: textarea\n\
+                    \x20   attributes:\n\
+                    \x20     label: Bug Report\n\
+                    \x20   validations:\n\
+                    \x20     required: true\n\
+                    \x20 - type: input\n\
+                    \x20   attributes:\n\
+                    \x20     label: Affected version\n\
+                    \x20   validations:\n\
+                    \x20     required: true\n\
+                    \x20 - type: input\n\
+                    \x20   attributes:\n\
+                    \x20     label: Operating system\n";
+        let bug = IssueTemplate::parse("bug-report.yml", form).unwrap();
+        assert_eq!(
+            bug,
+            IssueTemplate {
+                name: "Bug Report".to_owned(),
+                labels: vec!["type/bug".to_owned()],
+                sections: vec!["Bug Report".to_owned(), "Affected version".to_owned()],
+            }
+        );
+        let markdown = "---\n\
+                        name: Feature Request\n\
+                        labels: type/feature-request\n\
+                        ---\n\n\
+                        ## Feature Request\n\n\
+                        ### Describe the solution you'd like\n";
+        let feature = IssueTemplate::parse("feature-request.md", markdown).unwrap();
+        assert_eq!(feature.labels, vec!["type/feature-request"]);
+        assert_eq!(
+            feature.sections,
+            vec!["Feature Request", "Describe the solution you'd like"]
+        );
+        assert!(IssueTemplate::parse("config.yml", "blank_issues_enabled: false").is_none());
+
+        let issue: Issue = serde_json::from_str(
+            r#"{
+                "number": 1,
+                "title": "panic in the parser",
+                "html_url": "https://github.com/pingcap/parser/issues/1",
+                "created_at": "2020-04-30T00:00:00Z",
+                "author_association": "NONE",
+                "labels": [],
+                "fields": {"bug-report": "The parser panics."}
+            }"#,
+        )
+        .unwrap();
+        let templates = vec![bug, feature];
+        assert_eq!(
+            missing(&issue, &templates),
+            Some(vec!["Affected version".to_owned()])
+        );
+        assert_eq!(
+            missing(&issue, &templates[1..]),
+            Some(vec![
+                "Feature Request".to_owned(),
+                "Describe the solution you'd like".to_owned()
+            ])
+        );
+        assert_eq!(missing(&issue, &[]), None);
+    }
+}
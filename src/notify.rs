@@ -0,0 +1,260 @@
+use std::collections::HashMap;
+
+use chrono::Utc;
+use tracing::{info, warn};
+
+use crate::config::Config;
+use crate::plugins::{self, Plugin, Request};
+use crate::providers::{github::GitHub, slack::Slack, wecom::WeCom};
+use crate::storage::{self, Notification};
+use crate::Result;
+
+/// send posts the text to each configured notifier and notifier plugin, or prints
+/// it if there is none. `issues` are the urls of the issues in the text, which are
+/// recorded with the notifications.
+pub async fn send(
+    conf: &Config,
+    client: &reqwest::Client,
+    text: String,
+    issues: &[String],
+    dry_run: bool,
+) -> Result<()> {
+    send_each(conf, client, |_| Ok((None, text.clone())), issues, dry_run).await
+}
+
+/// send_each is `send` with a subject and a text rendered for each notifier,
+/// by its name, e.g. with the templates of its `[notifier-template]`. The
+/// subject is the first line of the text but for plugins, which get it apart.
+pub async fn send_each<F>(
+    conf: &Config,
+    client: &reqwest::Client,
+    render: F,
+    issues: &[String],
+    dry_run: bool,
+) -> Result<()>
+where
+    F: Fn(&str) -> Result<(Option<String>, String)>,
+{
+    let notifiers = conf.notifiers();
+    let plugins: Vec<Plugin> = plugins::load(conf)?
+        .into_iter()
+        .filter(|p| p.exports("notify"))
+        .collect();
+    let with_subject = |(subject, text): (Option<String>, String)| match subject {
+        Some(subject) => format!("{}\n{}", subject, text),
+        None => text,
+    };
+    if notifiers.is_empty() && plugins.is_empty() {
+        println!("{}", with_subject(render("")?));
+    }
+    for notifier in notifiers {
+        let text = with_subject(render(notifier)?);
+        send_to(conf, client, notifier, &text, issues, dry_run).await?;
+    }
+    for plugin in &plugins {
+        let (subject, text) = render(&plugin.name)?;
+        send_to_plugin(
+            conf,
+            client,
+            plugin,
+            subject.as_deref(),
+            &text,
+            issues,
+            dry_run,
+        )
+        .await?;
+    }
+    Ok(())
+}
+
+/// send_to_plugin sends the request a notifier plugin makes of the subject and
+/// the text, and records it like `send_to`. Nothing is sent if the plugin makes
+/// no request.
+pub async fn send_to_plugin(
+    conf: &Config,
+    client: &reqwest::Client,
+    plugin: &Plugin,
+    subject: Option<&str>,
+    text: &str,
+    issues: &[String],
+    dry_run: bool,
+) -> Result<()> {
+    let request = match plugin.request(subject, text, issues)? {
+        Some(request) => request,
+        None => return Ok(()),
+    };
+    if dry_run {
+        println!(
+            "[dry-run] send to plugin {} by {}:\n{}",
+            plugin.name, request.url, request.body
+        );
+        return Ok(());
+    }
+    let result = post(client, &request).await;
+    record(conf, &plugin.name, request.url.clone(), issues, &result);
+    result
+}
+
+async fn post(client: &reqwest::Client, request: &Request) -> Result<()> {
+    let mut req = client.post(&request.url).body(request.body.clone());
+    for (name, value) in &request.headers {
+        req = req.header(&name[..], &value[..]);
+    }
+    req.send().await?.error_for_status()?;
+    Ok(())
+}
+
+/// send_to sends the text to one notifier, e.g. `"slack"`, it fails if the notifier
+/// is unknown or not configured. The notification is recorded in `github-data`
+/// unless it's a dry run, whether it's sent or not.
+pub async fn send_to(
+    conf: &Config,
+    client: &reqwest::Client,
+    provider: &str,
+    text: &str,
+    issues: &[String],
+    dry_run: bool,
+) -> Result<()> {
+    if !conf.notifiers().iter().any(|n| *n == provider) {
+        return Err(format!(
+            "{} is not configured, the configured notifiers are [{}]",
+            provider,
+            conf.notifiers().join(", ")
+        )
+        .into());
+    }
+    if dry_run {
+        println!("[dry-run] send to {}:\n{}", provider, text);
+        return Ok(());
+    }
+    let result = deliver(conf, client, provider, text).await;
+    record(conf, provider, channel(conf, provider), issues, &result);
+    result
+}
+
+/// send_to_slack_channel sends the text to a Slack channel other than
+/// `slack-channel`, e.g. the one of a team, and records it like `send_to`.
+pub async fn send_to_slack_channel(
+    conf: &Config,
+    client: &reqwest::Client,
+    slack_channel: &str,
+    text: &str,
+    issues: &[String],
+    dry_run: bool,
+) -> Result<()> {
+    if conf.slack_token.is_empty() {
+        return Err("slack is not configured, slack-token is required".into());
+    }
+    if dry_run {
+        println!("[dry-run] send to slack #{}:\n{}", slack_channel, text);
+        return Ok(());
+    }
+    let slack_client = Slack::new(client.clone(), conf.slack_token.clone())?;
+    let result: Result<()> = slack_client
+        .send_message(slack_channel.to_owned(), text.to_owned())
+        .await
+        .map_err(Into::into);
+    let channel = format!("#{}", slack_channel);
+    record(conf, "slack", channel, issues, &result);
+    result
+}
+
+/// slack_group_mentions resolves the `[slack-groups]` to the mentions of the
+/// Slack user groups by GitHub team. A group which isn't found, or every group
+/// if they can't be listed, is mentioned by its handle, which doesn't notify
+/// its members.
+pub async fn slack_group_mentions(
+    conf: &Config,
+    client: &reqwest::Client,
+) -> Result<HashMap<String, String>> {
+    if conf.slack_groups.is_empty() {
+        return Ok(HashMap::new());
+    }
+    let slack_client = Slack::new(client.clone(), conf.slack_token.clone())?;
+    let groups = match slack_client.user_groups().await {
+        Ok(groups) => groups,
+        // e.g. without the `usergroups:read` scope, the handles are mentioned
+        Err(e) => {
+            warn!("failed to list the Slack user groups: {}", e);
+            vec![]
+        }
+    };
+    let mentions = conf
+        .slack_groups
+        .iter()
+        .map(|(team, handle)| {
+            let handle = handle.trim_start_matches('@');
+            let mention = match groups.iter().find(|g| g.handle == handle) {
+                Some(group) => group.mention(),
+                None => {
+                    warn!("Slack user group @{} of {} is not found", handle, team);
+                    format!("@{}", handle)
+                }
+            };
+            (team.clone(), mention)
+        })
+        .collect();
+    Ok(mentions)
+}
+
+fn record(conf: &Config, provider: &str, channel: String, issues: &[String], result: &Result<()>) {
+    let notification = Notification {
+        time: Utc::now(),
+        notifier: provider.to_owned(),
+        channel,
+        issues: issues.to_vec(),
+        error: result.as_ref().err().map(|e| e.to_string()),
+    };
+    if let Err(e) = storage::record(&conf.github_data, &notification) {
+        warn!("failed to record the notification: {}", e);
+    }
+}
+
+async fn deliver(
+    conf: &Config,
+    client: &reqwest::Client,
+    provider: &str,
+    text: &str,
+) -> Result<()> {
+    match provider {
+        "slack" => {
+            let slack_client = Slack::new(client.clone(), conf.slack_token.clone())?;
+            slack_client
+                .send_message(conf.slack_channel.clone(), text.to_owned())
+                .await?;
+        }
+        "github" => {
+            // configured, so it's parsed
+            let thread = conf.github_thread().unwrap();
+            let github_client =
+                GitHub::new(client.clone(), conf.github_token.clone(), vec![], vec![])?;
+            let url = if thread.discussion {
+                github_client
+                    .comment_discussion(&thread.repo, thread.number, text)
+                    .await?
+            } else {
+                github_client
+                    .comment_issue(&thread.repo, thread.number, text)
+                    .await?
+            };
+            info!("posted the report to {}", url);
+        }
+        "wecom" => {
+            let wecom_client = WeCom::new(client.clone(), &conf.wecom_webhook)?;
+            wecom_client.send_markdown(text).await?;
+        }
+        _ => unreachable!(),
+    }
+    Ok(())
+}
+
+// channel is where a notifier sends to, e.g. "#channel" of Slack.
+fn channel(conf: &Config, provider: &str) -> String {
+    match provider {
+        "slack" => format!("#{}", conf.slack_channel),
+        "github" => conf
+            .github_thread()
+            .map_or_else(String::new, |t| format!("{}#{}", t.repo, t.number)),
+        _ => String::new(),
+    }
+}
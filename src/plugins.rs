@@ -0,0 +1,275 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::config::Config;
+use crate::policy::{Action, IssueAction};
+use crate::providers::github::{Issue, RepoDiff, Snapshot};
+use crate::Result;
+
+/// Plugin is a WebAssembly module in `plugin-dir`, named by its file name
+/// without `.wasm`. It's a notifier if it exports `notify`, and an analyzer
+/// if it exports `analyze`, the ABI is described in the README.
+pub struct Plugin {
+    pub name: String,
+    #[cfg(feature = "plugins")]
+    module: wasmtime::Module,
+}
+
+/// Request is the HTTP request a notifier plugin asks to send, its body is
+/// posted to the url.
+#[derive(Deserialize, Debug, PartialEq)]
+pub struct Request {
+    pub url: String,
+    #[serde(default)]
+    pub headers: BTreeMap<String, String>,
+    pub body: String,
+}
+
+#[derive(Serialize)]
+struct NotifyInput<'a> {
+    subject: Option<&'a str>,
+    text: &'a str,
+    issues: &'a [String],
+}
+
+#[derive(Serialize)]
+struct AnalyzeInput<'a> {
+    snapshot: &'a Snapshot,
+    diffs: &'a [RepoDiff],
+}
+
+#[derive(Deserialize)]
+struct PluginAction {
+    /// the url of the issue
+    issue: String,
+    /// "notify", "label" or "escalate"
+    action: String,
+    value: String,
+}
+
+/// load loads the `.wasm` plugins in `plugin-dir` by name, none if it's not
+/// set.
+pub fn load(conf: &Config) -> Result<Vec<Plugin>> {
+    let dir = match &conf.plugin_dir {
+        Some(dir) => dir,
+        None => return Ok(vec![]),
+    };
+    let mut paths: Vec<_> = fs::read_dir(dir)
+        .map_err(|e| format!("{}: {}", dir, e))?
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| path.extension().map_or(false, |ext| ext == "wasm"))
+        .collect();
+    paths.sort();
+    paths.iter().map(|path| Plugin::open(path)).collect()
+}
+
+// FUEL bounds the instructions of a call, so a plugin looping forever fails
+// instead of blocking the reports.
+#[cfg(feature = "plugins")]
+const FUEL: u64 = 100_000_000;
+
+impl Plugin {
+    #[cfg(feature = "plugins")]
+    fn open(path: &Path) -> Result<Plugin> {
+        let name = path
+            .file_stem()
+            .map_or_else(String::new, |s| s.to_string_lossy().into_owned());
+        let module = fs::read(path).map_err(|e| format!("{:?}: {}", path, e))?;
+        Plugin::new(name, &module)
+    }
+
+    #[cfg(not(feature = "plugins"))]
+    fn open(path: &Path) -> Result<Plugin> {
+        Err(format!(
+            "{:?}: plugins are not enabled, build with `--features plugins`",
+            path
+        )
+        .into())
+    }
+
+    // new compiles the module, which is the binary or the text format.
+    #[cfg(feature = "plugins")]
+    fn new(name: String, module: &[u8]) -> Result<Plugin> {
+        let mut config = wasmtime::Config::new();
+        config.consume_fuel(true);
+        let engine = wasmtime::Engine::new(&config).map_err(|e| format!("{}: {}", name, e))?;
+        let module =
+            wasmtime::Module::new(&engine, module).map_err(|e| format!("{}: {}", name, e))?;
+        Ok(Plugin { name, module })
+    }
+
+    /// exports checks whether the plugin exports the function, e.g. "notify".
+    #[cfg(feature = "plugins")]
+    pub fn exports(&self, function: &str) -> bool {
+        self.module.get_export(function).is_some()
+    }
+
+    #[cfg(not(feature = "plugins"))]
+    pub fn exports(&self, _: &str) -> bool {
+        false
+    }
+
+    /// request asks a notifier plugin for the request sending the subject and
+    /// the text, none if it sends nothing.
+    pub fn request(
+        &self,
+        subject: Option<&str>,
+        text: &str,
+        issues: &[String],
+    ) -> Result<Option<Request>> {
+        let input = serde_json::to_vec(&NotifyInput {
+            subject,
+            text,
+            issues,
+        })?;
+        let output = self.call("notify", &input)?;
+        if output.is_empty() {
+            return Ok(None);
+        }
+        let request = serde_json::from_slice(&output).map_err(|e| self.error(e))?;
+        Ok(Some(request))
+    }
+
+    /// analyze asks an analyzer plugin for the actions on the issues of the
+    /// snapshot and the diffs.
+    pub fn analyze(&self, snapshot: &Snapshot, diffs: &[RepoDiff]) -> Result<Vec<IssueAction>> {
+        let input = serde_json::to_vec(&AnalyzeInput { snapshot, diffs })?;
+        let output = self.call("analyze", &input)?;
+        if output.is_empty() {
+            return Ok(vec![]);
+        }
+        let asked: Vec<PluginAction> =
+            serde_json::from_slice(&output).map_err(|e| self.error(e))?;
+        let issues: HashMap<String, &Issue> = snapshot
+            .repo_issues()
+            .iter()
+            .flat_map(|r| r.issues())
+            .chain(diffs.iter().flat_map(|d| d.opened.iter().chain(&d.closed)))
+            .map(|issue| (issue.to_string(), issue))
+            .collect();
+        asked
+            .into_iter()
+            .map(|asked| -> Result<IssueAction> {
+                let issue = issues
+                    .get(&asked.issue)
+                    .ok_or_else(|| self.error(format!("unknown issue {}", asked.issue)))?;
+                let action = match &asked.action[..] {
+                    "notify" => Action::Notify(asked.value),
+                    "label" => Action::Label(asked.value),
+                    "escalate" => Action::Escalate(asked.value),
+                    action => {
+                        return Err(self.error(format!("unknown action {:?}", action)).into())
+                    }
+                };
+                Ok(IssueAction {
+                    issue: (*issue).clone(),
+                    action,
+                })
+            })
+            .collect()
+    }
+
+    // call passes the input to the function in the memory allocated by the
+    // exported `alloc`, the function returns where its output is, the pointer
+    // in the high 32 bits and the length in the low ones.
+    #[cfg(feature = "plugins")]
+    fn call(&self, function: &str, input: &[u8]) -> Result<Vec<u8>> {
+        use wasmtime::{Instance, Store};
+
+        let mut store = Store::new(self.module.engine(), ());
+        store.add_fuel(FUEL).map_err(|e| self.error(e))?;
+        let instance = Instance::new(&mut store, &self.module, &[]).map_err(|e| self.error(e))?;
+        let memory = instance
+            .get_memory(&mut store, "memory")
+            .ok_or_else(|| self.error("no exported memory"))?;
+        let alloc = instance
+            .get_typed_func::<i32, i32, _>(&mut store, "alloc")
+            .map_err(|e| self.error(e))?;
+        let func = instance
+            .get_typed_func::<(i32, i32), i64, _>(&mut store, function)
+            .map_err(|e| self.error(e))?;
+        let ptr = alloc
+            .call(&mut store, input.len() as i32)
+            .map_err(|e| self.error(e))?;
+        memory
+            .write(&mut store, ptr as usize, input)
+            .map_err(|e| self.error(e))?;
+        let packed = func
+            .call(&mut store, (ptr, input.len() as i32))
+            .map_err(|e| self.error(e))?;
+        let mut output = vec![0; packed as u32 as usize];
+        memory
+            .read(&store, (packed >> 32) as u32 as usize, &mut output)
+            .map_err(|e| self.error(e))?;
+        Ok(output)
+    }
+
+    #[cfg(not(feature = "plugins"))]
+    fn call(&self, _: &str, _: &[u8]) -> Result<Vec<u8>> {
+        Err(self
+            .error("plugins are not enabled, build with `--features plugins`")
+            .into())
+    }
+
+    fn error(&self, e: impl fmt::Display) -> String {
+        format!("plugin {}: {}", self.name, e)
+    }
+}
+
+#[cfg(all(test, feature = "plugins"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn call_plugin() {
+        let request = r#"{"url": "https://example.com/hook", "body": "stale issues"}"#;
+        let actions = r#"[{"issue": "https://github.com/pingcap/parser/issues/1", "action": "label", "value": "triage"}]"#;
+        // the outputs are constants at 0 and 1024
+        let wat = format!(
+            r#"(module
+                (memory (export "memory") 1)
+                (data (i32.const 0) "{}")
+                (data (i32.const 1024) "{}")
+                (func (export "alloc") (param i32) (result i32) (i32.const 2048))
+                (func (export "notify") (param i32 i32) (result i64) (i64.const {}))
+                (func (export "analyze") (param i32 i32) (result i64) (i64.const {})))"#,
+            request.replace('"', "\\\""),
+            actions.replace('"', "\\\""),
+            request.len(),
+            (1024 << 32) | actions.len() as i64,
+        );
+        let plugin = Plugin::new("hook".to_owned(), wat.as_bytes()).unwrap();
+        assert!(plugin.exports("notify") && plugin.exports("analyze"));
+        assert_eq!(
+            plugin.request(None, "stale issues", &[]).unwrap(),
+            Some(Request {
+                url: "https://example.com/hook".to_owned(),
+                headers: BTreeMap::new(),
+                body: "stale issues".to_owned(),
+            })
+        );
+
+        let snapshot: Snapshot = serde_json::from_str(
+            r#"{"time": "2020-05-01T00:00:00Z", "repo_issues": [{
+                "repo": {"owner": "pingcap", "repo": "parser"},
+                "issues": [{
+                    "number": 1,
+                    "title": "panic in the parser",
+                    "html_url": "https://github.com/pingcap/parser/issues/1",
+                    "created_at": "2020-03-01T00:00:00Z",
+                    "author_association": "NONE",
+                    "labels": []
+                }]
+            }], "project_issues": []}"#,
+        )
+        .unwrap();
+        let actions = plugin.analyze(&snapshot, &[]).unwrap();
+        assert_eq!(actions.len(), 1);
+        assert_eq!(actions[0].issue.number(), 1);
+        assert_eq!(actions[0].action, Action::Label("triage".to_owned()));
+    }
+}
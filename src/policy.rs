@@ -0,0 +1,308 @@
+use std::fs;
+
+use tracing::warn;
+
+use crate::batch::{self, Channel};
+use crate::confidential;
+use crate::config::Config;
+use crate::plugins::{self, Plugin};
+use crate::providers::github::{GitHub, Issue, RepoDiff, Snapshot};
+use crate::report::Report;
+use crate::Result;
+
+/// Action is what a rule script asks to do with an issue.
+#[derive(Debug, PartialEq)]
+pub enum Action {
+    /// send the text with the issue to the notifiers
+    Notify(String),
+    /// add the label to the issue if it doesn't have it
+    Label(String),
+    /// send the text with the issue to the `escalate-channel`
+    Escalate(String),
+}
+
+/// IssueAction is an action a rule script asks for an issue.
+#[derive(Debug)]
+pub struct IssueAction {
+    pub issue: Issue,
+    pub action: Action,
+}
+
+/// evaluate runs a Rhai script on the snapshot and the diffs, it calls
+/// `on_issue(issue)` for each open issue and `on_change(issue, change)` for
+/// each issue opened or closed since the previous snapshot if the script
+/// defines them. The script asks for actions by `notify(text)`,
+/// `label(name)` and `escalate(text)`.
+#[cfg(feature = "scripting")]
+pub fn evaluate(script: &str, snapshot: &Snapshot, diffs: &[RepoDiff]) -> Result<Vec<IssueAction>> {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    use rhai::{Dynamic, Engine, Scope};
+
+    let asked: Rc<RefCell<Vec<Action>>> = Rc::default();
+    let mut engine = Engine::new();
+    // a script looping forever shouldn't block the reports
+    engine.set_max_operations(MAX_OPERATIONS);
+    let actions = asked.clone();
+    engine.register_fn("notify", move |text: &str| {
+        actions.borrow_mut().push(Action::Notify(text.to_owned()))
+    });
+    let actions = asked.clone();
+    engine.register_fn("label", move |name: &str| {
+        actions.borrow_mut().push(Action::Label(name.to_owned()))
+    });
+    let actions = asked.clone();
+    engine.register_fn("escalate", move |text: &str| {
+        actions.borrow_mut().push(Action::Escalate(text.to_owned()))
+    });
+    let ast = engine.compile(script)?;
+    let defines = |name: &str, params: usize| {
+        ast.iter_functions()
+            .any(|f| f.name == name && f.params.len() == params)
+    };
+    let (on_issue, on_change) = (defines("on_issue", 1), defines("on_change", 2));
+
+    let mut issue_actions = vec![];
+    let mut call = |issue: &Issue, change: Option<&str>| -> Result<()> {
+        let map = issue_map(issue, snapshot);
+        let result = match change {
+            Some(change) => engine.call_fn::<Dynamic>(
+                &mut Scope::new(),
+                &ast,
+                "on_change",
+                (map, Dynamic::from(change.to_owned())),
+            ),
+            None => engine.call_fn::<Dynamic>(&mut Scope::new(), &ast, "on_issue", (map,)),
+        };
+        result.map_err(|e| format!("{}: {}", issue, e))?;
+        let actions = asked.borrow_mut().drain(..).collect::<Vec<_>>();
+        issue_actions.extend(actions.into_iter().map(|action| IssueAction {
+            issue: issue.clone(),
+            action,
+        }));
+        Ok(())
+    };
+    if on_issue {
+        for issue in snapshot.repo_issues().iter().flat_map(|r| r.issues()) {
+            call(issue, None)?;
+        }
+    }
+    if on_change {
+        for diff in diffs {
+            let opened = diff.opened.iter().map(|i| (i, "opened"));
+            for (issue, change) in opened.chain(diff.closed.iter().map(|i| (i, "closed"))) {
+                call(issue, Some(change))?;
+            }
+        }
+    }
+    Ok(issue_actions)
+}
+
+#[cfg(not(feature = "scripting"))]
+pub fn evaluate(_: &str, _: &Snapshot, _: &[RepoDiff]) -> Result<Vec<IssueAction>> {
+    Err("rule scripts are not enabled, build with `--features scripting`".into())
+}
+
+#[cfg(feature = "scripting")]
+const MAX_OPERATIONS: u64 = 1_000_000;
+
+// issue_map is the issue seen by the scripts, with the fields of `--where`,
+// the ages are in days at the snapshot time.
+#[cfg(feature = "scripting")]
+fn issue_map(issue: &Issue, snapshot: &Snapshot) -> rhai::Map {
+    use chrono::{DateTime, Utc};
+    use rhai::{Array, Dynamic};
+
+    let text = |s: &str| -> Dynamic { s.into() };
+    let days = |time: &DateTime<Utc>| (*snapshot.time() - *time).num_days();
+    let labels: Array = issue.labels().map(text).collect();
+    let assignees: Array = issue.assignees().map(text).collect();
+    let mut map = rhai::Map::new();
+    map.insert("repo".into(), text(&issue.repo_name()));
+    map.insert("number".into(), Dynamic::from(issue.number() as i64));
+    map.insert("title".into(), text(issue.title()));
+    map.insert("url".into(), text(&issue.to_string()));
+    map.insert("author".into(), issue.author().map_or(Dynamic::UNIT, text));
+    map.insert("labels".into(), labels.into());
+    map.insert("assignees".into(), assignees.into());
+    map.insert(
+        "milestone".into(),
+        issue.milestone().map_or(Dynamic::UNIT, text),
+    );
+    map.insert("age".into(), Dynamic::from(days(issue.created_at())));
+    map.insert("updated".into(), Dynamic::from(days(issue.updated_at())));
+    map.insert("comments".into(), Dynamic::from(issue.comments() as i64));
+    map.insert("pull".into(), Dynamic::from(issue.is_pull()));
+    map.insert("draft".into(), Dynamic::from(issue.is_draft()));
+    let fields: rhai::Map = issue
+        .fields()
+        .iter()
+        .map(|(name, value)| (name.as_str().into(), text(value)))
+        .collect();
+    map.insert("fields".into(), fields.into());
+    map
+}
+
+/// run evaluates the `rule-scripts` and the analyzer plugins on the report,
+/// and applies the actions they ask for. The notifications and escalations of all the issues are sent
+/// at once, and only the issues of the watched GitHub repos can be labeled.
+pub async fn run(
+    conf: &Config,
+    client: &reqwest::Client,
+    report: &Report,
+    dry_run: bool,
+) -> Result<()> {
+    let analyzers: Vec<Plugin> = plugins::load(conf)?
+        .into_iter()
+        .filter(|p| p.exports("analyze"))
+        .collect();
+    if conf.rule_scripts.is_empty() && analyzers.is_empty() {
+        return Ok(());
+    }
+    let mut actions = vec![];
+    for path in &conf.rule_scripts {
+        let script = fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+        let asked = evaluate(&script, &report.snapshot, &report.diffs)
+            .map_err(|e| format!("{}: {}", path, e))?;
+        actions.extend(asked);
+    }
+    for plugin in &analyzers {
+        actions.extend(plugin.analyze(&report.snapshot, &report.diffs)?);
+    }
+
+    let github_client = GitHub::new(client.clone(), conf.github_token.clone(), vec![], vec![])?;
+    let repos = conf.repo_names();
+    let (mut notified, mut escalated) = (vec![], vec![]);
+    for IssueAction { issue, action } in actions {
+        match action {
+            Action::Notify(text) => notified.push((text, issue)),
+            Action::Escalate(text) => escalated.push((text, issue)),
+            Action::Label(label) => {
+                if issue.labels().any(|l| l == label) {
+                    continue;
+                }
+                if !repos.contains(&issue.repo_name()) {
+                    warn!("can't label {}, it's not in a watched GitHub repo", issue);
+                    continue;
+                }
+                if dry_run {
+                    println!("[dry-run] label {} with {}", issue, label);
+                    continue;
+                }
+                let labels = [label];
+                let added = github_client
+                    .add_labels(&issue.repo_name(), issue.number(), &labels)
+                    .await;
+                if let Err(e) = added {
+                    warn!("failed to label {} with {}: {}", issue, labels[0], e);
+                }
+            }
+        }
+    }
+    // the confidential issues are only sent to `confidential-channel`
+    let is_confidential = |(_, issue): &(String, Issue)| confidential::is_confidential(conf, issue);
+    let confidential: Vec<(String, Issue)> = notified
+        .iter()
+        .chain(&escalated)
+        .filter(|text| is_confidential(text))
+        .cloned()
+        .collect();
+    notified.retain(|text| !is_confidential(text));
+    escalated.retain(|text| !is_confidential(text));
+    if !confidential.is_empty() {
+        let (text, issues) = message(&confidential);
+        confidential::send(conf, client, &text, &issues, dry_run).await?;
+    }
+    if !notified.is_empty() {
+        let (text, issues) = message(&notified);
+        batch::send(conf, client, Channel::Notifiers, text, &issues, dry_run).await?;
+    }
+    if !escalated.is_empty() {
+        let (text, issues) = message(&escalated);
+        let channel = match &conf.escalate_channel {
+            Some(channel) => Channel::Slack(channel.clone()),
+            None => Channel::Notifiers,
+        };
+        batch::send(conf, client, channel, text, &issues, dry_run).await?;
+    }
+    Ok(())
+}
+
+// message puts the texts of the issues in lines, and returns the urls too.
+fn message(texts: &[(String, Issue)]) -> (String, Vec<String>) {
+    let mut text = String::new();
+    let mut issues: Vec<String> = vec![];
+    for (line, issue) in texts {
+        text.push_str(&format!("{} {}\n", line, issue));
+        if !issues.contains(&issue.to_string()) {
+            issues.push(issue.to_string());
+        }
+    }
+    (text, issues)
+}
+
+#[cfg(all(test, feature = "scripting"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn evaluate_rules() {
+        let snapshot: Snapshot = serde_json::from_str(
+            r#"{"time": "2020-05-01T00:00:00Z", "repo_issues": [{
+                "repo": {"owner": "pingcap", "repo": "parser"},
+                "issues": [{
+                    "number": 1,
+                    "title": "panic in the parser",
+                    "html_url": "https://github.com/pingcap/parser/issues/1",
+                    "created_at": "2020-03-01T00:00:00Z",
+                    "author_association": "NONE",
+                    "labels": [{"id": 1, "name": "type/bug", "description": null}]
+                }, {
+                    "number": 2,
+                    "title": "support window functions",
+                    "html_url": "https://github.com/pingcap/parser/issues/2",
+                    "created_at": "2020-04-30T00:00:00Z",
+                    "author_association": "NONE",
+                    "labels": []
+                }]
+            }], "project_issues": []}"#,
+        )
+        .unwrap();
+        let diffs = vec![RepoDiff {
+            repo: "pingcap/parser".to_owned(),
+            opened: vec![snapshot.repo_issues()[0].issues()[1].clone()],
+            closed: vec![],
+        }];
+        let script = r#"
+            fn on_issue(issue) {
+                if issue.title.contains("panic") && !issue.labels.contains("severity/critical") {
+                    label("severity/critical");
+                    if issue.age > 30 {
+                        escalate("a panic open for " + issue.age + " days");
+                    }
+                }
+            }
+
+            fn on_change(issue, change) {
+                if change == "opened" && issue.assignees.is_empty() {
+                    notify("new issue without assignee");
+                }
+            }
+        "#;
+        let actions: Vec<(i32, Action)> = evaluate(script, &snapshot, &diffs)
+            .unwrap()
+            .into_iter()
+            .map(|a| (a.issue.number(), a.action))
+            .collect();
+        assert_eq!(
+            actions,
+            vec![
+                (1, Action::Label("severity/critical".to_owned())),
+                (1, Action::Escalate("a panic open for 61 days".to_owned())),
+                (2, Action::Notify("new issue without assignee".to_owned())),
+            ]
+        );
+        assert!(evaluate("fn on_issue(issue) { loop {} }", &snapshot, &diffs).is_err());
+    }
+}
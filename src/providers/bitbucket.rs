@@ -0,0 +1,307 @@
+use std::{convert::TryFrom, sync::Arc};
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::{de::DeserializeOwned, Deserialize};
+use tracing::{debug, info, warn};
+
+use super::github::{
+    Assignee, Comment, Issue, Milestone, ProjectIssues, Pull, Repo, RepoIssues, User,
+};
+use super::{check_status, Error, IssueProvider, Result};
+
+const API_BASE_URL: &str = "https://api.bitbucket.org/2.0";
+const PER_PAGE: usize = 50;
+
+// Bitbucket has no author association like GitHub, the replies are only
+// counted by the `members` in config.
+pub struct Bitbucket {
+    username: String,
+    app_password: String,
+    client: reqwest::Client,
+    repos: Vec<Arc<Repo>>,
+}
+
+#[derive(Deserialize)]
+struct Page<T> {
+    values: Vec<T>,
+    next: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct BitbucketIssue {
+    id: i32,
+    title: String,
+    created_on: DateTime<Utc>,
+    updated_on: DateTime<Utc>,
+    // the author of issues
+    reporter: Option<Account>,
+    // the author of pull requests
+    author: Option<Account>,
+    assignee: Option<Account>,
+    milestone: Option<BitbucketMilestone>,
+    links: Links,
+}
+
+#[derive(Deserialize)]
+struct Account {
+    #[serde(default)]
+    nickname: Option<String>,
+    display_name: String,
+}
+
+impl Account {
+    fn login(self) -> String {
+        self.nickname.unwrap_or(self.display_name)
+    }
+}
+
+#[derive(Deserialize)]
+struct BitbucketMilestone {
+    name: String,
+}
+
+#[derive(Deserialize)]
+struct Links {
+    html: Link,
+}
+
+#[derive(Deserialize)]
+struct Link {
+    href: String,
+}
+
+#[derive(Deserialize)]
+struct BitbucketComment {
+    user: Option<Account>,
+    links: Links,
+}
+
+impl Bitbucket {
+    pub fn new(
+        client: reqwest::Client,
+        username: String,
+        app_password: String,
+        repos: Vec<String>,
+    ) -> Result<Self> {
+        let repos = repos
+            .into_iter()
+            .map(|repo| Repo::try_from(repo).map(Arc::new))
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Bitbucket {
+            username,
+            app_password,
+            client,
+            repos,
+        })
+    }
+
+    fn repo_url(&self, owner: &str, repo: &str) -> String {
+        format!("{}/repositories/{}/{}", API_BASE_URL, owner, repo)
+    }
+
+    // get_all follows the `next` links until the last page.
+    async fn get_all<T: DeserializeOwned>(
+        &self,
+        url: &str,
+        query: &[(&str, &str)],
+    ) -> Result<Vec<T>> {
+        let mut all = vec![];
+        let mut next = Some(url.to_owned());
+        let mut first = true;
+        while let Some(url) = next {
+            debug!("GET {}", url);
+            let mut req = self.client.get(&url[..]);
+            // the next links have the query already
+            if first {
+                req = req.query(query).query(&[("pagelen", PER_PAGE)]);
+                first = false;
+            }
+            if !self.username.is_empty() {
+                req = req.basic_auth(&self.username, Some(&self.app_password));
+            }
+            let res = req.send().await?;
+            let body = check_status(&url, res)?.text().await?;
+            let page: Page<T> =
+                serde_json::from_str(&body).map_err(|source| Error::Deserialize {
+                    url: url.clone(),
+                    source,
+                })?;
+            all.extend(page.values);
+            next = page.next;
+        }
+        Ok(all)
+    }
+
+    async fn get_issues(&self, repo: &Repo) -> Result<Vec<Issue>> {
+        let base = self.repo_url(&repo.owner, &repo.repo);
+        let owner: Arc<str> = Arc::from(&repo.owner[..]);
+        let name: Arc<str> = Arc::from(&repo.repo[..]);
+        let query = [("q", r#"state="new" OR state="open""#)];
+        // repos without the issue tracker still have pull requests
+        let issues: Vec<BitbucketIssue> =
+            match self.get_all(&format!("{}/issues", base), &query).await {
+                Ok(issues) => issues,
+                Err(Error::NotFound { .. }) => {
+                    debug!("{} has no issue tracker", repo);
+                    vec![]
+                }
+                Err(e) => return Err(e),
+            };
+        let pulls: Vec<BitbucketIssue> = self
+            .get_all(&format!("{}/pullrequests", base), &[("state", "OPEN")])
+            .await?;
+        Ok(issues
+            .into_iter()
+            .map(|issue| issue.into_issue(&owner, &name, false))
+            .chain(
+                pulls
+                    .into_iter()
+                    .map(|pull| pull.into_issue(&owner, &name, true)),
+            )
+            .collect())
+    }
+}
+
+impl BitbucketIssue {
+    fn into_issue(self, owner: &Arc<str>, repo: &Arc<str>, pull: bool) -> Issue {
+        let assignees: Vec<Assignee> = self
+            .assignee
+            .map(|a| Assignee {
+                id: 0,
+                login: a.login(),
+            })
+            .into_iter()
+            .collect();
+        let author = self.reporter.or(self.author).map(Account::login);
+        Issue {
+            number: self.id,
+            title: self.title,
+            assignee: assignees.first().cloned(),
+            assignees,
+            milestone: self.milestone.map(|m| Milestone {
+                number: 0,
+                title: m.name,
+                due_on: None,
+            }),
+            comments: 0,
+            // issues are "new" or "open", pull requests are "OPEN"
+            state: "open".to_owned(),
+            html_url: self.links.html.href.clone(),
+            owner: Arc::clone(owner),
+            repo: Arc::clone(repo),
+            pull_request: if pull {
+                Some(Pull {
+                    html_url: self.links.html.href,
+                })
+            } else {
+                None
+            },
+            draft: false,
+            created_at: self.created_on,
+            updated_at: Some(self.updated_on),
+            author_association: "NONE".to_owned(),
+            labels: vec![],
+            user: author.map(|login| User { login }),
+            body: None,
+            fields: Default::default(),
+        }
+    }
+}
+
+#[async_trait]
+impl IssueProvider for Bitbucket {
+    fn name(&self) -> &'static str {
+        "bitbucket"
+    }
+
+    async fn list_issues(&self) -> Result<Vec<RepoIssues>> {
+        let mut all = vec![];
+        for (i, repo) in self.repos.iter().enumerate() {
+            // one missing repo shouldn't fail the whole snapshot
+            let issues = match self.get_issues(repo).await {
+                Ok(issues) => issues,
+                Err(e @ Error::NotFound { .. }) => {
+                    warn!("skipped bitbucket repo {}: {}", repo, e);
+                    continue;
+                }
+                Err(e) => return Err(e),
+            };
+            info!(
+                "[{}/{}] fetched bitbucket repo {}",
+                i + 1,
+                self.repos.len(),
+                repo
+            );
+            all.push(RepoIssues {
+                repo: Arc::clone(repo),
+                issues,
+            });
+        }
+        Ok(all)
+    }
+
+    // Bitbucket has no project boards
+    async fn list_projects(&self) -> Result<Vec<ProjectIssues>> {
+        Ok(vec![])
+    }
+
+    async fn get_comments(&self, issue: &Issue) -> Result<Vec<Comment>> {
+        let kind = if issue.is_pull() {
+            "pullrequests"
+        } else {
+            "issues"
+        };
+        let url = format!(
+            "{}/{}/{}/comments",
+            self.repo_url(&issue.owner, &issue.repo),
+            kind,
+            issue.number
+        );
+        let comments: Vec<BitbucketComment> = self.get_all(&url, &[]).await?;
+        Ok(comments
+            .into_iter()
+            .map(|c| Comment {
+                html_url: c.links.html.href,
+                author_association: "NONE".to_owned(),
+                user: User {
+                    login: c.user.map_or_else(String::new, Account::login),
+                },
+            })
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn convert_bitbucket_pages() {
+        let page: Page<BitbucketIssue> = serde_json::from_str(
+            r#"{
+                "values": [{
+                    "id": 5,
+                    "title": "fix crash",
+                    "state": "OPEN",
+                    "created_on": "2020-04-01T00:00:00.000000+00:00",
+                    "updated_on": "2020-04-02T00:00:00.000000+00:00",
+                    "author": {"display_name": "You06", "nickname": "you06"},
+                    "links": {"html": {"href": "https://bitbucket.org/ws/repo/pull-requests/5"}}
+                }],
+                "next": "https://api.bitbucket.org/2.0/repositories/ws/repo/pullrequests?page=2"
+            }"#,
+        )
+        .unwrap();
+        assert!(page.next.is_some());
+        let pull = page.values.into_iter().next().unwrap();
+        let pull = pull.into_issue(&Arc::from("ws"), &Arc::from("repo"), true);
+        assert!(pull.is_pull());
+        assert_eq!(
+            pull.to_string(),
+            "https://bitbucket.org/ws/repo/pull-requests/5"
+        );
+        assert_eq!(pull.state(), "open");
+        assert_eq!(pull.user.unwrap().login, "you06");
+    }
+}
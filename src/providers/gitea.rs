@@ -0,0 +1,328 @@
+use std::{
+    collections::{HashMap, HashSet},
+    convert::TryFrom,
+    sync::{Arc, Mutex},
+};
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use reqwest::{header::AUTHORIZATION, Method, Url};
+use serde::{de::DeserializeOwned, Deserialize};
+use tracing::{info, warn};
+
+use super::github::{
+    Assignee, Comment, Issue, Label, Milestone, ProjectIssues, Repo, RepoIssues, User, USER_AGENT,
+};
+use super::{check_status, Error, IssueProvider, Result};
+use crate::cache::Cache;
+use crate::http::Http;
+
+// the default max page size of Gitea
+const PER_PAGE: usize = 50;
+
+// Gitea works with Forgejo too, they share the API.
+pub struct Gitea {
+    // adds the token and the user agent, and keeps the cache
+    http: Http,
+    base_url: String,
+    repos: Vec<Arc<Repo>>,
+    // collaborators of each repo, fetched once
+    members: Mutex<HashMap<String, Arc<HashSet<String>>>>,
+}
+
+#[derive(Deserialize)]
+struct GiteaIssue {
+    number: i32,
+    title: String,
+    state: String,
+    html_url: String,
+    created_at: DateTime<Utc>,
+    updated_at: DateTime<Utc>,
+    #[serde(default)]
+    comments: u32,
+    user: Option<GiteaUser>,
+    // null if there is no assignee
+    #[serde(default)]
+    assignees: Option<Vec<GiteaUser>>,
+    milestone: Option<GiteaMilestone>,
+    #[serde(default)]
+    labels: Vec<GiteaLabel>,
+}
+
+#[derive(Deserialize)]
+struct GiteaUser {
+    id: i64,
+    login: String,
+}
+
+#[derive(Deserialize)]
+struct GiteaMilestone {
+    id: i32,
+    title: String,
+    due_on: Option<DateTime<Utc>>,
+}
+
+#[derive(Deserialize)]
+struct GiteaLabel {
+    id: i64,
+    name: String,
+    #[serde(default)]
+    description: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct GiteaComment {
+    html_url: String,
+    user: GiteaUser,
+}
+
+impl Gitea {
+    pub fn new(
+        client: reqwest::Client,
+        base_url: &str,
+        token: String,
+        repos: Vec<String>,
+    ) -> Result<Self> {
+        let repos = repos
+            .into_iter()
+            .map(|repo| Repo::try_from(repo).map(Arc::new))
+            .collect::<Result<Vec<_>>>()?;
+        let mut http = Http::new(client, USER_AGENT)?;
+        // public repos can be read without a token
+        if !token.is_empty() {
+            http = http.header(AUTHORIZATION, &format!("token {}", token))?;
+        }
+        Ok(Gitea {
+            http,
+            base_url: base_url.trim_end_matches('/').to_owned(),
+            repos,
+            members: Mutex::new(HashMap::new()),
+        })
+    }
+
+    pub fn set_cache(&mut self, cache: Option<Cache>) {
+        self.http.set_cache(cache);
+    }
+
+    fn repo_url(&self, owner: &str, repo: &str) -> String {
+        format!("{}/api/v1/repos/{}/{}", self.base_url, owner, repo)
+    }
+
+    // get_all fetches all the pages of a list.
+    async fn get_all<T: DeserializeOwned>(
+        &self,
+        url: &str,
+        query: &[(&str, &str)],
+    ) -> Result<Vec<T>> {
+        let mut all = vec![];
+        let mut page = 0;
+        while all.len() == page * PER_PAGE {
+            page += 1;
+            let (page_param, limit) = (page.to_string(), PER_PAGE.to_string());
+            let paging = [("page", &page_param[..]), ("limit", &limit[..])];
+            let page_url = Url::parse_with_params(url, query.iter().chain(&paging))
+                .map_err(|e| format!("invalid url {}: {}", url, e))?;
+            let body = match self.http.cached(page_url.as_str(), &[]) {
+                Some(body) => body,
+                None => {
+                    let res = self
+                        .http
+                        .send(Method::GET, page_url.as_str(), |req| req)
+                        .await?;
+                    let body = check_status(url, res)?.text().await?;
+                    self.http.store(page_url.as_str(), &[], &body);
+                    body
+                }
+            };
+            let batch: Vec<T> =
+                serde_json::from_str(&body).map_err(|source| Error::Deserialize {
+                    url: url.to_owned(),
+                    source,
+                })?;
+            all.extend(batch);
+        }
+        Ok(all)
+    }
+
+    // get_members returns the collaborators of the repo, their comments are
+    // replies to the issues.
+    async fn get_members(&self, owner: &str, repo: &str) -> Result<Arc<HashSet<String>>> {
+        let url = format!("{}/collaborators", self.repo_url(owner, repo));
+        let cached = self.members.lock().unwrap().get(&url).cloned();
+        if let Some(members) = cached {
+            return Ok(members);
+        }
+        let members: Vec<GiteaUser> = self.get_all(&url, &[]).await?;
+        let mut members: HashSet<String> = members.into_iter().map(|m| m.login).collect();
+        // the owner of a user repo is not one of its collaborators
+        members.insert(owner.to_owned());
+        let members = Arc::new(members);
+        self.members
+            .lock()
+            .unwrap()
+            .insert(url, Arc::clone(&members));
+        Ok(members)
+    }
+
+    async fn get_issues(&self, repo: &Repo) -> Result<Vec<Issue>> {
+        let url = format!("{}/issues", self.repo_url(&repo.owner, &repo.repo));
+        let query = [("state", "open"), ("type", "issues")];
+        let issues: Vec<GiteaIssue> = self.get_all(&url, &query).await?;
+        let members = self.get_members(&repo.owner, &repo.repo).await?;
+        let owner: Arc<str> = Arc::from(&repo.owner[..]);
+        let name: Arc<str> = Arc::from(&repo.repo[..]);
+        Ok(issues
+            .into_iter()
+            .map(|issue| issue.into_issue(&owner, &name, &members))
+            .collect())
+    }
+}
+
+fn association(login: &str, members: &HashSet<String>) -> String {
+    if members.contains(login) {
+        "MEMBER".to_owned()
+    } else {
+        "NONE".to_owned()
+    }
+}
+
+impl GiteaIssue {
+    fn into_issue(self, owner: &Arc<str>, repo: &Arc<str>, members: &HashSet<String>) -> Issue {
+        let assignees: Vec<Assignee> = self
+            .assignees
+            .unwrap_or_default()
+            .into_iter()
+            .map(|a| Assignee {
+                id: a.id,
+                login: a.login,
+            })
+            .collect();
+        let author = self.user.map(|u| u.login);
+        Issue {
+            number: self.number,
+            title: self.title,
+            assignee: assignees.first().cloned(),
+            assignees,
+            milestone: self.milestone.map(|m| Milestone {
+                number: m.id,
+                title: m.title,
+                due_on: m.due_on,
+            }),
+            comments: self.comments,
+            state: self.state,
+            html_url: self.html_url,
+            owner: Arc::clone(owner),
+            repo: Arc::clone(repo),
+            pull_request: None,
+            draft: false,
+            created_at: self.created_at,
+            updated_at: Some(self.updated_at),
+            author_association: author
+                .as_ref()
+                .map_or_else(|| "NONE".to_owned(), |login| association(login, members)),
+            labels: self
+                .labels
+                .into_iter()
+                .map(|l| Label {
+                    id: l.id,
+                    name: l.name,
+                    description: l.description,
+                })
+                .collect(),
+            user: author.map(|login| User { login }),
+            body: None,
+            fields: Default::default(),
+        }
+    }
+}
+
+#[async_trait]
+impl IssueProvider for Gitea {
+    fn name(&self) -> &'static str {
+        "gitea"
+    }
+
+    async fn list_issues(&self) -> Result<Vec<RepoIssues>> {
+        let mut all = vec![];
+        for (i, repo) in self.repos.iter().enumerate() {
+            // one missing repo shouldn't fail the whole snapshot
+            let issues = match self.get_issues(repo).await {
+                Ok(issues) => issues,
+                Err(e @ Error::NotFound { .. }) => {
+                    warn!("skipped gitea repo {}: {}", repo, e);
+                    continue;
+                }
+                Err(e) => return Err(e),
+            };
+            info!(
+                "[{}/{}] fetched gitea repo {}",
+                i + 1,
+                self.repos.len(),
+                repo
+            );
+            all.push(RepoIssues {
+                repo: Arc::clone(repo),
+                issues,
+            });
+        }
+        Ok(all)
+    }
+
+    // Gitea has project boards but no API for them yet
+    async fn list_projects(&self) -> Result<Vec<ProjectIssues>> {
+        Ok(vec![])
+    }
+
+    async fn get_comments(&self, issue: &Issue) -> Result<Vec<Comment>> {
+        let url = format!(
+            "{}/issues/{}/comments",
+            self.repo_url(&issue.owner, &issue.repo),
+            issue.number
+        );
+        let comments: Vec<GiteaComment> = self.get_all(&url, &[]).await?;
+        let members = self.get_members(&issue.owner, &issue.repo).await?;
+        Ok(comments
+            .into_iter()
+            .map(|c| Comment {
+                html_url: c.html_url,
+                author_association: association(&c.user.login, &members),
+                user: User {
+                    login: c.user.login,
+                },
+            })
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn convert_gitea_issues() {
+        let issue: GiteaIssue = serde_json::from_str(
+            r#"{
+                "number": 3,
+                "title": "crash",
+                "state": "open",
+                "html_url": "https://gitea.com/org/repo/issues/3",
+                "created_at": "2020-04-01T00:00:00Z",
+                "updated_at": "2020-04-02T00:00:00Z",
+                "comments": 1,
+                "user": {"id": 1, "login": "you06"},
+                "assignees": null,
+                "milestone": {"id": 2, "title": "v1.0"},
+                "labels": [{"id": 1, "name": "bug", "description": ""}]
+            }"#,
+        )
+        .unwrap();
+        let members = HashSet::new();
+        let issue = issue.into_issue(&Arc::from("org"), &Arc::from("repo"), &members);
+        assert_eq!(issue.to_string(), "https://gitea.com/org/repo/issues/3");
+        assert_eq!(issue.assignee(), None);
+        assert_eq!(issue.milestone(), Some("v1.0"));
+        assert_eq!(issue.comments(), 1);
+        assert_eq!(issue.labels().collect::<Vec<_>>(), vec!["bug"]);
+        assert_eq!(issue.author_association, "NONE");
+    }
+}
@@ -1,251 +1,1442 @@
 use regex::Regex;
-use std::{convert::From, fmt, collections::HashMap};
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    convert::{From, TryFrom},
+    fmt,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
 
-use chrono::{DateTime, Utc};
-use reqwest;
-use serde::{Deserialize, Serialize};
-use serde_json::error::Error as JsonError;
+use async_trait::async_trait;
+use chrono::{DateTime, SecondsFormat, TimeZone, Utc};
+use futures::stream::{self, Stream, StreamExt, TryStreamExt};
+use reqwest::{self, header::AUTHORIZATION, StatusCode};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use indicatif::{ProgressBar, ProgressStyle};
+use tokio::time::delay_for;
+use tracing::{info, warn};
+
+use super::{json, IssueProvider};
+pub use super::{Error, Result};
+use crate::cache::Cache;
+use crate::http::Http;
+use crate::usage;
+
+mod graphql;
 
 const API_BASE_URL: &str = "https://api.github.com";
+pub(super) const USER_AGENT: &str = "pingbot";
 const PER_PAGE: usize = 100;
 
-pub type Result<T> = std::result::Result<T, Error>;
+pub struct GitHub {
+    // adds the token and the user agent, and keeps the cache
+    http: Http,
+    // e.g. "https://github.example.com/api/v3" for GitHub Enterprise
+    base_url: String,
+    // shared with the fetched RepoIssues instead of cloned
+    repos: Vec<Arc<Repo>>,
+    projects: Vec<Project>,
+    time: DateTime<Utc>,
+    interval: Duration,
+    progress: ProgressBar,
+    // only fetch the issues updated since then
+    since: Option<DateTime<Utc>>,
+    concurrency: usize,
+    // fetch the snapshot by GraphQL instead of REST
+    graphql: bool,
+    // only the matched issues are kept, applied to each page
+    issue_filter: Option<Box<dyn Fn(&Issue) -> bool + Send + Sync>>,
+    // only the pull requests against the branches are kept, keyed by the repo
+    pull_branches: HashMap<String, Vec<String>>,
+}
+
+struct Header {
+    key: &'static str,
+    value: &'static str,
+}
+
+#[derive(Serialize, Deserialize, Debug, Eq, PartialEq, Clone)]
+pub(super) struct Repo {
+    pub(super) owner: String,
+    pub(super) repo: String,
+}
+
+impl fmt::Display for Repo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.owner, self.repo)
+    }
+}
+
+impl TryFrom<String> for Repo {
+    type Error = Error;
+
+    fn try_from(r: String) -> Result<Self> {
+        let parsed = r.split("/").collect::<Vec<&str>>();
+        if parsed.len() != 2 || parsed[0].is_empty() || parsed[1].is_empty() {
+            return Err(format!("repo {:?} is not in owner/repo format", r).into());
+        }
+        Ok(Repo {
+            owner: parsed[0].to_owned(),
+            repo: parsed[1].to_owned(),
+        })
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Eq, PartialEq, Clone)]
+pub struct Project {
+    pub(super) owner: String,
+    pub(super) repo: String,
+    pub(super) number: i32,
+    pub(super) id: Option<i64>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct GitHubProject {
+    id: i64,
+    number: i32,
+    #[serde(default)]
+    name: String,
+    #[serde(default)]
+    html_url: String,
+}
+
+impl fmt::Display for GitHubProject {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}\t{}\t{}\t{}",
+            self.number, self.name, self.id, self.html_url
+        )
+    }
+}
+
+impl fmt::Display for Project {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{} project {}", self.owner, self.repo, self.number)
+    }
+}
+
+impl TryFrom<String> for Project {
+    type Error = Error;
+
+    fn try_from(r: String) -> Result<Self> {
+        let re = Regex::new(r"^https://github.com/([\w.-]+)/([\w.-]+)/projects/(\d+)/?$").unwrap();
+        let m = re.captures(r.trim()).ok_or_else(|| {
+            format!(
+                "project {:?} is not like https://github.com/owner/repo/projects/1",
+                r
+            )
+        })?;
+        let number = m[3]
+            .parse::<i32>()
+            .map_err(|e| format!("project {:?} has an invalid number: {}", r, e))?;
+        Ok(Project {
+            owner: m[1].to_owned(),
+            repo: m[2].to_owned(),
+            number,
+            id: None,
+        })
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct User {
+    pub(super) login: String,
+}
+
+impl User {
+    pub fn login(&self) -> &str {
+        &self.login
+    }
+}
+
+/// PullDetail is what the pulls API tells about a pull request besides the
+/// issues API.
+#[derive(Deserialize, Debug, Clone)]
+pub struct PullDetail {
+    /// none while GitHub is computing it
+    pub mergeable: Option<bool>,
+    /// "dirty" if there are merge conflicts
+    #[serde(default)]
+    pub mergeable_state: String,
+    pub head: PullHead,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct PullHead {
+    pub sha: String,
+}
+
+/// CheckRun is a CI check of a commit.
+#[derive(Deserialize, Debug, Clone)]
+pub struct CheckRun {
+    pub name: String,
+    /// e.g. "success" or "failure", none until it's completed
+    pub conclusion: Option<String>,
+}
+
+/// Review is a review of a pull request.
+#[derive(Deserialize, Debug, Clone)]
+pub struct Review {
+    pub user: Option<User>,
+    /// "APPROVED", "CHANGES_REQUESTED", "COMMENTED" or "DISMISSED"
+    pub state: String,
+}
+
+/// UserResult is the authenticated user with the token's permissions.
+pub struct UserResult {
+    login: String,
+    scopes: Vec<String>,
+    rate_limit: String,
+    rate_remaining: String,
+    rate_reset: Option<DateTime<Utc>>,
+    orgs: Vec<String>,
+}
+
+impl fmt::Display for UserResult {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "user: {}", self.login)?;
+        writeln!(f, "scopes: {}", self.scopes.join(", "))?;
+        write!(
+            f,
+            "rate limit: {}/{} remaining",
+            self.rate_remaining, self.rate_limit
+        )?;
+        if let Some(reset) = self.rate_reset {
+            write!(f, ", resets at {}", reset)?;
+        }
+        writeln!(f)?;
+        write!(f, "orgs: {}", self.orgs.join(", "))
+    }
+}
+
+impl UserResult {
+    pub fn login(&self) -> &str {
+        &self.login
+    }
+
+    /// scopes are empty for fine-grained tokens and GitHub App tokens.
+    pub fn scopes(&self) -> &[String] {
+        &self.scopes
+    }
+}
+
+/// RepoAccess is what the token can do to a repo it can read.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RepoAccess {
+    pub private: bool,
+    pub push: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Pull {
+    pub(super) html_url: String,
+}
+
+/// BranchPull is a pull request against a branch, like a cherry-pick of
+/// another pull request.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct BranchPull {
+    pub number: i32,
+    pub title: String,
+    pub body: Option<String>,
+    pub html_url: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Assignee {
+    pub(super) id: i64,
+    pub(super) login: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Milestone {
+    pub(super) number: i32,
+    pub(super) title: String,
+    #[serde(default)]
+    #[serde(alias = "dueOn")]
+    pub(super) due_on: Option<DateTime<Utc>>,
+}
+
+/// RepoLabel is a label of a repo, which may not be used by any issue.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct RepoLabel {
+    pub name: String,
+    /// hex color without `#`, e.g. "d73a4a"
+    pub color: String,
+    pub description: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Label {
+    pub(super) id: i64,
+    pub(super) name: String,
+    pub(super) description: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Issue {
+    pub(super) number: i32,
+    pub(super) title: String,
+    pub(super) assignee: Option<Assignee>,
+    #[serde(default)]
+    pub(super) assignees: Vec<Assignee>,
+    #[serde(default)]
+    pub(super) milestone: Option<Milestone>,
+    #[serde(default)]
+    pub(super) comments: u32,
+    #[serde(default)]
+    pub(super) state: String,
+    #[serde(default)]
+    pub(super) html_url: String,
+    // not in the GitHub API, filled after fetched, shared by the issues of a repo
+    #[serde(default = "empty_str")]
+    pub(super) owner: Arc<str>,
+    #[serde(default = "empty_str")]
+    pub(super) repo: Arc<str>,
+    pub(super) pull_request: Option<Pull>,
+    // only pull requests can be drafts
+    #[serde(default)]
+    pub(super) draft: bool,
+    pub(super) created_at: DateTime<Utc>,
+    #[serde(default)]
+    pub(super) updated_at: Option<DateTime<Utc>>,
+    pub(super) author_association: String,
+    pub(super) labels: Vec<Label>,
+    pub(super) user: Option<User>,
+    // not kept in snapshots, only the fields extracted from it are
+    #[serde(default)]
+    #[serde(skip_serializing)]
+    pub(super) body: Option<String>,
+    #[serde(default)]
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub(super) fields: BTreeMap<String, String>,
+}
+
+impl Issue {
+    pub fn number(&self) -> i32 {
+        self.number
+    }
+
+    pub fn title(&self) -> &str {
+        &self.title
+    }
 
-#[derive(Debug)]
-pub struct Error {
-    reason: String,
+    pub fn is_pull(&self) -> bool {
+        self.pull_request.is_some()
+    }
+
+    pub fn is_draft(&self) -> bool {
+        self.draft
+    }
+
+    pub fn author(&self) -> Option<&str> {
+        self.user.as_ref().map(|u| &u.login[..])
+    }
+
+    /// repo_name is "owner/repo" of the repo the issue belongs to.
+    pub fn repo_name(&self) -> String {
+        format!("{}/{}", self.owner, self.repo)
+    }
+
+    pub fn assignee(&self) -> Option<&str> {
+        self.assignee.as_ref().map(|a| &a.login[..])
+    }
+
+    /// assignees falls back to the single assignee of the snapshots stored
+    /// before the assignees were kept.
+    pub fn assignees(&self) -> impl Iterator<Item = &str> {
+        let fallback = if self.assignees.is_empty() {
+            self.assignee.as_ref()
+        } else {
+            None
+        };
+        self.assignees
+            .iter()
+            .chain(fallback)
+            .map(|a| &a.login[..])
+    }
+
+    pub fn milestone(&self) -> Option<&str> {
+        self.milestone.as_ref().map(|m| &m.title[..])
+    }
+
+    pub fn milestone_due_on(&self) -> Option<&DateTime<Utc>> {
+        self.milestone.as_ref().and_then(|m| m.due_on.as_ref())
+    }
+
+    pub fn comments(&self) -> u32 {
+        self.comments
+    }
+
+    pub fn state(&self) -> &str {
+        if self.state.is_empty() {
+            "open"
+        } else {
+            &self.state
+        }
+    }
+
+    pub fn html_url(&self) -> String {
+        self.to_string()
+    }
+
+    pub fn created_at(&self) -> &DateTime<Utc> {
+        &self.created_at
+    }
+
+    pub fn updated_at(&self) -> &DateTime<Utc> {
+        self.updated_at.as_ref().unwrap_or(&self.created_at)
+    }
+
+    pub fn labels(&self) -> impl Iterator<Item = &str> {
+        self.labels.iter().map(|label| &label.name[..])
+    }
+
+    /// fields are extracted from the body written with an issue form, by
+    /// their field names like "affected-version".
+    pub fn fields(&self) -> &BTreeMap<String, String> {
+        &self.fields
+    }
+
+    pub fn field(&self, name: &str) -> Option<&str> {
+        self.fields.get(name).map(|f| &f[..])
+    }
+
+    /// redact replaces the title, and drops the fields which may tell what
+    /// the issue is about.
+    pub fn redact(&mut self, title: &str) {
+        self.title = title.to_owned();
+        self.body = None;
+        self.fields.clear();
+    }
+
+    // extract_fields parses the fetched body into the fields, and drops it.
+    pub(super) fn extract_fields(&mut self) {
+        if let Some(body) = self.body.take() {
+            self.fields = crate::issue_form::parse(&body);
+        }
+    }
+
+    // may_be_stale checks everything except comments,
+    // issues created by members or with ignored labels are never stale.
+    pub(super) fn may_be_stale(&self, before: &DateTime<Utc>, rule: &StaleRule) -> bool {
+        let login = self.user.as_ref().map_or("", |u| &u.login[..]);
+        !self.is_pull()
+            && &self.created_at < before
+            && !is_member(&self.author_association, login, &rule.members)
+            && !self
+                .labels
+                .iter()
+                .any(|label| rule.ignore_labels.contains(&label.name))
+    }
 }
 
-impl fmt::Display for Error {
+impl fmt::Display for Issue {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{}", self.reason)
+        // issues of other providers and the recent snapshots have their own url
+        if !self.html_url.is_empty() {
+            return write!(f, "{}", self.html_url);
+        }
+        write!(
+            f,
+            "https://github.com/{}/{}/issues/{}",
+            self.owner, self.repo, self.number
+        )
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct Comment {
+    pub(super) html_url: String,
+    pub(super) author_association: String,
+    pub(super) user: User,
+}
+
+impl Comment {
+    pub(super) fn by_member(&self, members: &[String]) -> bool {
+        is_member(&self.author_association, &self.user.login, members)
+    }
+}
+
+/// RepoComment is a comment of an issue or a pull request listed by repo.
+#[derive(Deserialize, Debug, Clone)]
+pub struct RepoComment {
+    pub html_url: String,
+    pub user: Option<User>,
+    pub author_association: String,
+    pub created_at: DateTime<Utc>,
+}
+
+impl RepoComment {
+    pub fn login(&self) -> &str {
+        self.user.as_ref().map_or("", |u| &u.login[..])
+    }
+
+    pub fn by_member(&self, members: &[String]) -> bool {
+        is_member(&self.author_association, self.login(), members)
+    }
+
+    /// is_pull checks whether it's a comment of a pull request by its url.
+    pub fn is_pull(&self) -> bool {
+        self.html_url.contains("/pull/")
+    }
+}
+
+/// StaleRule decides which issues are stale, issues without reply from members
+/// in `days` are stale.
+pub struct StaleRule {
+    pub days: i64,
+    pub ignore_labels: Vec<String>,
+    pub members: Vec<String>,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct StaleIssues {
+    pub repo: String,
+    pub days: i64,
+    pub issues: Vec<Issue>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RepoIssues {
+    pub(super) repo: Arc<Repo>,
+    pub(super) issues: Vec<Issue>,
+}
+
+impl RepoIssues {
+    pub fn name(&self) -> String {
+        self.repo.to_string()
+    }
+
+    pub fn issues(&self) -> &[Issue] {
+        &self.issues
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ProjectIssues {
+    pub(super) project: Project,
+    pub(super) columns: Vec<Column>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Column {
+    pub(super) id: i64,
+    pub(super) name: String,
+    // not in the GitHub API, filled after fetched
+    #[serde(default)]
+    pub(super) cards: Vec<Card>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Card {
+    // the stored snapshots before cards were moved have no ids
+    #[serde(default)]
+    pub(super) id: i64,
+    #[serde(default)]
+    pub(super) note: Option<String>,
+    // the API url of the issue or pull request, none for notes
+    #[serde(default)]
+    pub(super) content_url: Option<String>,
+    #[serde(default)]
+    pub(super) updated_at: Option<DateTime<Utc>>,
+}
+
+impl ProjectIssues {
+    pub fn name(&self) -> String {
+        self.project.to_string()
+    }
+
+    pub fn columns(&self) -> &[Column] {
+        &self.columns
+    }
+}
+
+impl Column {
+    pub fn id(&self) -> i64 {
+        self.id
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn cards(&self) -> &[Card] {
+        &self.cards
     }
 }
 
-impl std::error::Error for Error {
-    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
-        None
+impl Card {
+    pub fn id(&self) -> i64 {
+        self.id
+    }
+
+    pub fn updated_at(&self) -> Option<&DateTime<Utc>> {
+        self.updated_at.as_ref()
+    }
+
+    /// title is the note, or the issue path for issue cards.
+    pub fn title(&self) -> String {
+        match (&self.note, self.html_url()) {
+            (Some(note), _) => note.lines().next().unwrap_or("").to_owned(),
+            (None, Some(url)) => url.trim_start_matches("https://github.com/").to_owned(),
+            (None, None) => "".to_owned(),
+        }
+    }
+
+    pub fn html_url(&self) -> Option<String> {
+        self.content_url
+            .as_ref()
+            .map(|url| url.replace("https://api.github.com/repos/", "https://github.com/"))
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Snapshot {
+    time: DateTime<Utc>,
+    repo_issues: Vec<RepoIssues>,
+    project_issues: Vec<ProjectIssues>,
+}
+
+/// RepoDiff is the changes of a repo between two snapshots.
+#[derive(Serialize, Debug, Clone)]
+pub struct RepoDiff {
+    pub repo: String,
+    pub opened: Vec<Issue>,
+    pub closed: Vec<Issue>,
+}
+
+impl Snapshot {
+    pub(super) fn new(
+        time: DateTime<Utc>,
+        repo_issues: Vec<RepoIssues>,
+        project_issues: Vec<ProjectIssues>,
+    ) -> Self {
+        Snapshot {
+            time,
+            repo_issues,
+            project_issues,
+        }
+    }
+
+    /// merge appends the repos and projects of a snapshot from another provider.
+    pub fn merge(mut self, other: Snapshot) -> Snapshot {
+        self.repo_issues.extend(other.repo_issues);
+        self.project_issues.extend(other.project_issues);
+        self
+    }
+
+    pub fn time(&self) -> &DateTime<Utc> {
+        &self.time
+    }
+
+    pub fn repo_issues(&self) -> &[RepoIssues] {
+        &self.repo_issues
+    }
+
+    pub fn project_issues(&self) -> &[ProjectIssues] {
+        &self.project_issues
+    }
+
+    /// card_issue finds the issue or pull request of a card in the watched repos.
+    pub fn card_issue(&self, card: &Card) -> Option<&Issue> {
+        let url = card.html_url()?;
+        // pull request cards link to the issue of the pull request
+        let pull_url = url.replace("/issues/", "/pull/");
+        self.repo_issues
+            .iter()
+            .flat_map(|r| r.issues.iter())
+            .find(|i| {
+                let html_url = i.to_string();
+                html_url == url || html_url == pull_url
+            })
+    }
+
+    pub fn retain_repos(&mut self, f: impl Fn(&str) -> bool) {
+        self.repo_issues.retain(|repo| f(&repo.name()));
+    }
+
+    pub fn retain_issues(&mut self, f: impl Fn(&Issue) -> bool) {
+        for repo in self.repo_issues.iter_mut() {
+            repo.issues.retain(|issue| f(issue));
+        }
+    }
+
+    pub fn update_issues(&mut self, f: impl Fn(&mut Issue)) {
+        for repo in self.repo_issues.iter_mut() {
+            repo.issues.iter_mut().for_each(|issue| f(issue));
+        }
+    }
+
+    /// diff returns the issues opened and closed since `old`,
+    /// repos which are not in `old` are skipped.
+    pub fn diff(&self, old: &Snapshot) -> Vec<RepoDiff> {
+        self.repo_issues
+            .iter()
+            .filter_map(|current| {
+                let previous = old.repo_issues.iter().find(|r| r.repo == current.repo)?;
+                // issues and pull requests are numbered separately on some providers
+                let same =
+                    |a: &Issue, b: &Issue| a.number == b.number && a.is_pull() == b.is_pull();
+                let opened = current
+                    .issues
+                    .iter()
+                    .filter(|i| !previous.issues.iter().any(|p| same(p, i)))
+                    .cloned()
+                    .collect();
+                let closed = previous
+                    .issues
+                    .iter()
+                    .filter(|p| !current.issues.iter().any(|i| same(i, p)))
+                    .cloned()
+                    .collect();
+                Some(RepoDiff {
+                    repo: current.repo.to_string(),
+                    opened,
+                    closed,
+                })
+            })
+            .collect()
+    }
+}
+
+/// GitHubBuilder configures a GitHub client, created by `GitHub::builder`.
+///
+/// The base URL points the client to GitHub Enterprise, or to a mock server
+/// in tests.
+pub struct GitHubBuilder {
+    token: String,
+    repos: Vec<String>,
+    projects: Vec<String>,
+    base_url: String,
+    user_agent: String,
+    timeout: Option<Duration>,
+    client: Option<reqwest::Client>,
+    client_builder: Option<reqwest::ClientBuilder>,
+}
+
+impl GitHubBuilder {
+    pub fn repos(mut self, repos: Vec<String>) -> Self {
+        self.repos = repos;
+        self
+    }
+
+    pub fn projects(mut self, projects: Vec<String>) -> Self {
+        self.projects = projects;
+        self
+    }
+
+    /// base_url is "https://api.github.com" by default, the GraphQL API is
+    /// derived from it.
+    pub fn base_url(mut self, base_url: &str) -> Self {
+        self.base_url = base_url.trim_end_matches('/').to_owned();
+        self
+    }
+
+    pub fn user_agent(mut self, user_agent: &str) -> Self {
+        self.user_agent = user_agent.to_owned();
+        self
+    }
+
+    /// timeout applies to each request, on top of the timeout of the client.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// client shares an existing client, it wins over `client_builder`.
+    pub fn client(mut self, client: reqwest::Client) -> Self {
+        self.client = Some(client);
+        self
+    }
+
+    /// client_builder builds the client of GitHub only, e.g. with a proxy or
+    /// extra root certificates.
+    pub fn client_builder(mut self, builder: reqwest::ClientBuilder) -> Self {
+        self.client_builder = Some(builder);
+        self
+    }
+
+    pub fn build(self) -> Result<GitHub> {
+        let repos = self
+            .repos
+            .into_iter()
+            .map(|repo| Repo::try_from(repo).map(Arc::new))
+            .collect::<Result<Vec<Arc<Repo>>>>()?;
+        let projects = self
+            .projects
+            .into_iter()
+            .map(Project::try_from)
+            .collect::<Result<Vec<Project>>>()?
+            .into_iter()
+            .filter(|p| {
+                !repos
+                    .iter()
+                    .any(|r| r.owner == p.owner && r.repo == p.repo)
+            })
+            .collect();
+        let client = match (self.client, self.client_builder) {
+            (Some(client), _) => client,
+            (None, Some(builder)) => builder.build()?,
+            (None, None) => reqwest::Client::builder().gzip(true).build()?,
+        };
+        let mut http = Http::new(client, &self.user_agent)?
+            .header(AUTHORIZATION, &format!("token {}", self.token))?;
+        http.set_timeout(self.timeout);
+        Ok(GitHub {
+            http,
+            base_url: self.base_url,
+            repos,
+            projects,
+            time: Utc::now(),
+            interval: Duration::from_millis(0),
+            progress: ProgressBar::hidden(),
+            since: None,
+            concurrency: 1,
+            graphql: false,
+            issue_filter: None,
+            pull_branches: HashMap::new(),
+        })
     }
 }
 
-impl From<&str> for Error {
-    fn from(err: &str) -> Self {
-        Error {
-            reason: err.to_string(),
+impl GitHub {
+    pub fn new(
+        client: reqwest::Client,
+        token: String,
+        repos: Vec<String>,
+        projects: Vec<String>,
+    ) -> Result<Self> {
+        GitHub::builder(token)
+            .client(client)
+            .repos(repos)
+            .projects(projects)
+            .build()
+    }
+
+    /// builder creates a client with more options than `new`, like the base URL
+    /// of GitHub Enterprise.
+    pub fn builder(token: String) -> GitHubBuilder {
+        GitHubBuilder {
+            token,
+            repos: vec![],
+            projects: vec![],
+            base_url: API_BASE_URL.to_owned(),
+            user_agent: USER_AGENT.to_owned(),
+            timeout: None,
+            client: None,
+            client_builder: None,
+        }
+    }
+
+    // graphql_url is "/api/graphql" on GitHub Enterprise, whose REST API is "/api/v3".
+    fn graphql_url(&self) -> String {
+        match self.base_url.strip_suffix("/api/v3") {
+            Some(host) => format!("{}/api/graphql", host),
+            None => format!("{}/graphql", self.base_url),
+        }
+    }
+
+    /// show_progress draws a progress bar of fetched repos and projects on stderr,
+    /// it's hidden if stderr is not a terminal.
+    pub fn show_progress(&mut self) {
+        self.progress = ProgressBar::new(0);
+        self.progress
+            .set_style(ProgressStyle::default_bar().template("{bar:40} {pos}/{len} {msg}"));
+    }
+
+    pub fn set_request_interval(&mut self, interval: Duration) {
+        self.interval = interval;
+    }
+
+    pub fn set_since(&mut self, since: Option<DateTime<Utc>>) {
+        self.since = since;
+    }
+
+    pub fn set_concurrency(&mut self, concurrency: usize) {
+        self.concurrency = concurrency.max(1);
+    }
+
+    pub fn set_graphql(&mut self, graphql: bool) {
+        self.graphql = graphql;
+    }
+
+    pub fn set_cache(&mut self, cache: Option<Cache>) {
+        self.http.set_cache(cache);
+    }
+
+    pub fn set_issue_filter(&mut self, filter: impl Fn(&Issue) -> bool + Send + Sync + 'static) {
+        self.issue_filter = Some(Box::new(filter));
+    }
+
+    /// set_pull_branches keeps only the pull requests against the branches in
+    /// snapshots, the repos without branches keep all.
+    pub fn set_pull_branches(&mut self, branches: HashMap<String, Vec<String>>) {
+        self.pull_branches = branches;
+    }
+
+    pub fn retain_repos(&mut self, f: impl Fn(&str) -> bool) {
+        self.repos.retain(|repo| f(&repo.to_string()));
+    }
+
+    fn keep(&self, issue: &Issue) -> bool {
+        self.issue_filter.as_ref().map_or(true, |f| f(issue))
+    }
+
+    // the GraphQL API can't filter pull requests by `since`
+    fn use_graphql(&self) -> bool {
+        self.graphql && self.since.is_none()
+    }
+
+    async fn request(&self, url: &str, headers: Vec<Header>) -> Result<String> {
+        let headers: Vec<(&str, &str)> = headers.iter().map(|h| (h.key, h.value)).collect();
+        if let Some(body) = self.http.cached(url, &headers) {
+            return Ok(body);
+        }
+        // failed responses are errors
+        let body = self.send(url, &headers).await?.text().await?;
+        self.http.store(url, &headers, &body);
+        Ok(body)
+    }
+
+    // request_json parses the response while it's received, for the large pages.
+    async fn request_json<T>(&self, url: &str, headers: Vec<Header>) -> Result<T>
+    where
+        T: DeserializeOwned + Send + 'static,
+    {
+        // cached bodies are whole strings anyway
+        if self.http.has_cache() {
+            let res = self.request(url, headers).await?;
+            return parse(url, &res);
+        }
+        let headers: Vec<(&str, &str)> = headers.iter().map(|h| (h.key, h.value)).collect();
+        let res = self.send(url, &headers).await?;
+        json::from_response(res)
+            .await
+            .map_err(|source| Error::Deserialize {
+                url: url.to_owned(),
+                source,
+            })
+    }
+
+    async fn send(&self, url: &str, headers: &[(&str, &str)]) -> Result<reqwest::Response> {
+        if self.interval > Duration::from_millis(0) {
+            delay_for(self.interval).await;
+        }
+        let res = self
+            .http
+            .send(reqwest::Method::GET, url, |req| {
+                headers
+                    .iter()
+                    .fold(req, |req, (key, value)| req.header(*key, *value))
+            })
+            .await?;
+        // renamed or transferred repos are redirected to `/repositories/<id>`
+        if res.url().as_str() != url {
+            warn!("{} is moved to {}, update the config", url, res.url());
+        }
+        check_status(url, res)
+    }
+
+    // post sends a JSON body to the REST API, like `send` does for GET.
+    async fn post<T: Serialize + ?Sized>(&self, url: &str, body: &T) -> Result<reqwest::Response> {
+        self.send_json(reqwest::Method::POST, url, body).await
+    }
+
+    async fn send_json<T: Serialize + ?Sized>(
+        &self,
+        method: reqwest::Method,
+        url: &str,
+        body: &T,
+    ) -> Result<reqwest::Response> {
+        let res = self.http.send(method, url, |req| req.json(body)).await?;
+        check_status(url, res)
+    }
+
+    /// comment_issue posts a comment to an issue or a pull request of `repo`,
+    /// and returns the url of the comment.
+    pub async fn comment_issue(&self, repo: &str, number: i32, body: &str) -> Result<String> {
+        let repo = Repo::try_from(repo.to_owned())?;
+        let url = format!(
+            "{}/repos/{}/issues/{}/comments",
+            self.base_url, repo, number
+        );
+        let body = serde_json::json!({ "body": body });
+        let res = self.post(&url, &body).await?;
+        let comment: Comment = parse(&url, &res.text().await?)?;
+        Ok(comment.html_url)
+    }
+
+    /// add_labels adds the labels to an issue or a pull request of `repo`.
+    pub async fn add_labels(&self, repo: &str, number: i32, labels: &[String]) -> Result<()> {
+        let repo = Repo::try_from(repo.to_owned())?;
+        let url = format!("{}/repos/{}/issues/{}/labels", self.base_url, repo, number);
+        let body = serde_json::json!({ "labels": labels });
+        self.post(&url, &body).await?;
+        Ok(())
+    }
+
+    /// create_gist uploads a file as a secret gist, and returns the url of the gist.
+    /// The token needs the `gist` scope.
+    pub async fn create_gist(
+        &self,
+        description: &str,
+        filename: &str,
+        content: &str,
+    ) -> Result<String> {
+        #[derive(Deserialize)]
+        struct Gist {
+            html_url: String,
+        }
+        let url = format!("{}/gists", self.base_url);
+        let body = serde_json::json!({
+            "description": description,
+            "public": false,
+            "files": { filename: { "content": content } },
+        });
+        let res = self.post(&url, &body).await?;
+        let gist: Gist = parse(&url, &res.text().await?)?;
+        Ok(gist.html_url)
+    }
+
+    /// list_labels returns the labels of `repo`.
+    pub async fn list_labels(&self, repo: &str) -> Result<Vec<RepoLabel>> {
+        let repo = Repo::try_from(repo.to_owned())?;
+        let mut all = vec![];
+        let mut page = 0;
+        while all.len() == page * PER_PAGE {
+            page += 1;
+            let url = format!(
+                "{}/repos/{}/labels?page={}&per_page={}",
+                self.base_url, repo, page, PER_PAGE
+            );
+            let res = self.request(&url[..], vec![]).await?;
+            let batch: Vec<RepoLabel> = parse(&url, &res)?;
+            all.extend(batch);
+        }
+        Ok(all)
+    }
+
+    /// create_label creates a label in `repo`.
+    pub async fn create_label(&self, repo: &str, label: &RepoLabel) -> Result<()> {
+        let repo = Repo::try_from(repo.to_owned())?;
+        let url = format!("{}/repos/{}/labels", self.base_url, repo);
+        self.post(&url, label).await?;
+        Ok(())
+    }
+
+    /// update_label updates the label named `name` in `repo` to `label`, the
+    /// name can be changed too, e.g. its case.
+    pub async fn update_label(&self, repo: &str, name: &str, label: &RepoLabel) -> Result<()> {
+        let repo = Repo::try_from(repo.to_owned())?;
+        let mut url = reqwest::Url::parse(&format!("{}/repos/{}/labels", self.base_url, repo))
+            .map_err(|e| e.to_string())?;
+        url.path_segments_mut()
+            .map_err(|_| "the base url can't have paths")?
+            .push(name);
+        let body = serde_json::json!({
+            "new_name": label.name,
+            "color": label.color,
+            "description": label.description,
+        });
+        self.send_json(reqwest::Method::PATCH, url.as_str(), &body)
+            .await?;
+        Ok(())
+    }
+
+    /// move_card moves a project card to the top of the column.
+    pub async fn move_card(&self, card_id: i64, column_id: i64) -> Result<()> {
+        let url = format!("{}/projects/columns/cards/{}/moves", self.base_url, card_id);
+        let body = serde_json::json!({ "position": "top", "column_id": column_id });
+        self.post(&url, &body).await?;
+        Ok(())
+    }
+
+    /// list_closed returns the issues and the merged pull requests of `repo`
+    /// closed since the time.
+    pub async fn list_closed(&self, repo: &str, since: &DateTime<Utc>) -> Result<Vec<Issue>> {
+        let param = since.to_rfc3339_opts(SecondsFormat::Secs, true);
+        self.closed_issues(repo, ("since", &param), Some(since))
+            .await
+    }
+
+    /// list_comments returns the comments of the issues and pull requests of
+    /// `repo` updated since the time, the oldest first.
+    pub async fn list_comments(
+        &self,
+        repo: &str,
+        since: &DateTime<Utc>,
+    ) -> Result<Vec<RepoComment>> {
+        let repo = Repo::try_from(repo.to_owned())?;
+        let base = format!("{}/repos/{}/issues/comments", self.base_url, repo);
+        let since = since.to_rfc3339_opts(SecondsFormat::Secs, true);
+        let mut all = vec![];
+        let mut page = 0;
+        while all.len() == page * PER_PAGE {
+            page += 1;
+            let url = reqwest::Url::parse_with_params(
+                &base,
+                &[
+                    ("since", &since[..]),
+                    ("page", &page.to_string()),
+                    ("per_page", &PER_PAGE.to_string()),
+                ],
+            )
+            .map_err(|e| e.to_string())?
+            .to_string();
+            let batch: Vec<RepoComment> = self.request_json(&url[..], vec![]).await?;
+            all.extend(batch);
+        }
+        Ok(all)
+    }
+
+    /// team_members returns the logins of the members of `team` like
+    /// "pingcap/tidb-team", the child teams included.
+    pub async fn team_members(&self, team: &str) -> Result<Vec<String>> {
+        let (org, slug) = match team.find('/') {
+            Some(i) => (&team[..i], &team[i + 1..]),
+            None => return Err(format!("{}: not like org/team", team).into()),
+        };
+        let base = format!("{}/orgs/{}/teams/{}/members", self.base_url, org, slug);
+        let mut all: Vec<User> = vec![];
+        let mut page = 0;
+        while all.len() == page * PER_PAGE {
+            page += 1;
+            let url = format!("{}?page={}&per_page={}", base, page, PER_PAGE);
+            let batch: Vec<User> = self.request_json(&url[..], vec![]).await?;
+            all.extend(batch);
+        }
+        Ok(all.into_iter().map(|user| user.login).collect())
+    }
+
+    /// list_labeled returns the closed issues and the merged pull requests of
+    /// `repo` with the label.
+    pub async fn list_labeled(&self, repo: &str, label: &str) -> Result<Vec<Issue>> {
+        self.closed_issues(repo, ("labels", label), None).await
+    }
+
+    // closed_issues pages the closed issues of `repo` filtered by the query
+    // parameter, the pull requests not merged or closed before `since` are left out.
+    async fn closed_issues(
+        &self,
+        repo: &str,
+        param: (&str, &str),
+        since: Option<&DateTime<Utc>>,
+    ) -> Result<Vec<Issue>> {
+        let repo = Repo::try_from(repo.to_owned())?;
+        let owner: Arc<str> = Arc::from(&repo.owner[..]);
+        let name: Arc<str> = Arc::from(&repo.repo[..]);
+        let base = format!("{}/repos/{}/issues", self.base_url, repo);
+        let mut all = vec![];
+        let mut fetched = 0;
+        let mut page = 0;
+        while fetched == page * PER_PAGE {
+            page += 1;
+            let url = reqwest::Url::parse_with_params(
+                &base,
+                &[
+                    ("state", "closed"),
+                    param,
+                    ("page", &page.to_string()),
+                    ("per_page", &PER_PAGE.to_string()),
+                ],
+            )
+            .map_err(|e| e.to_string())?
+            .to_string();
+            let res = self.request(&url[..], vec![]).await?;
+            // closed_at and merged_at are only needed here, they're not kept in issues
+            let batch: Vec<serde_json::Value> = parse(&url, &res)?;
+            fetched += batch.len();
+            for value in batch {
+                let closed_at: Option<DateTime<Utc>> =
+                    serde_json::from_value(value["closed_at"].clone()).unwrap_or(None);
+                let unmerged = value["pull_request"].is_object()
+                    && value["pull_request"]["merged_at"].is_null();
+                let before =
+                    since.map_or(false, |since| closed_at.map_or(true, |time| &time < since));
+                if unmerged || before {
+                    continue;
+                }
+                let mut issue: Issue =
+                    serde_json::from_value(value).map_err(|source| Error::Deserialize {
+                        url: url.clone(),
+                        source,
+                    })?;
+                issue.owner = owner.clone();
+                issue.repo = name.clone();
+                all.push(issue);
+            }
+        }
+        Ok(all)
+    }
+
+    /// list_pulls returns the pull requests of `repo` against the branch in the
+    /// state, "open", "closed" or "all".
+    pub async fn list_pulls(
+        &self,
+        repo: &str,
+        branch: &str,
+        state: &str,
+    ) -> Result<Vec<BranchPull>> {
+        let repo = Repo::try_from(repo.to_owned())?;
+        let base = format!("{}/repos/{}/pulls", self.base_url, repo);
+        let mut all = vec![];
+        let mut page = 0;
+        while all.len() == page * PER_PAGE {
+            page += 1;
+            let url = reqwest::Url::parse_with_params(
+                &base,
+                &[
+                    ("state", state),
+                    ("base", branch),
+                    ("page", &page.to_string()),
+                    ("per_page", &PER_PAGE.to_string()),
+                ],
+            )
+            .map_err(|e| e.to_string())?
+            .to_string();
+            let res = self.request(&url[..], vec![]).await?;
+            let batch: Vec<BranchPull> = parse(&url, &res)?;
+            all.extend(batch);
+        }
+        Ok(all)
+    }
+
+    /// is_conflicted checks whether the pull request has merge conflicts with its
+    /// base branch, it's false while GitHub is still computing the mergeable state.
+    pub async fn is_conflicted(&self, pull: &Issue) -> Result<bool> {
+        let detail = self.get_pull(pull).await?;
+        Ok(detail.mergeable == Some(false) && detail.mergeable_state == "dirty")
+    }
+
+    /// get_pull returns the details of a pull request which the issues API
+    /// doesn't tell.
+    pub async fn get_pull(&self, pull: &Issue) -> Result<PullDetail> {
+        let url = format!(
+            "{}/repos/{}/{}/pulls/{}",
+            self.base_url, pull.owner, pull.repo, pull.number
+        );
+        let res = self.request(&url[..], vec![]).await?;
+        parse(&url, &res)
+    }
+
+    /// check_runs returns the check runs of a commit in the repo of the pull
+    /// request, e.g. its head.
+    pub async fn check_runs(&self, pull: &Issue, sha: &str) -> Result<Vec<CheckRun>> {
+        #[derive(Deserialize)]
+        struct CheckRuns {
+            check_runs: Vec<CheckRun>,
+        }
+        let mut all = vec![];
+        let mut page = 0;
+        while all.len() == page * PER_PAGE {
+            page += 1;
+            let url = format!(
+                "{}/repos/{}/{}/commits/{}/check-runs?page={}&per_page={}",
+                self.base_url, pull.owner, pull.repo, sha, page, PER_PAGE
+            );
+            let res = self.request(&url[..], vec![]).await?;
+            let batch: CheckRuns = parse(&url, &res)?;
+            all.extend(batch.check_runs);
         }
+        Ok(all)
     }
-}
 
-impl From<JsonError> for Error {
-    fn from(err: JsonError) -> Self {
-        Error {
-            reason: err.to_string(),
+    /// pull_files returns the paths of the files changed by the pull request.
+    pub async fn pull_files(&self, pull: &Issue) -> Result<Vec<String>> {
+        #[derive(Deserialize)]
+        struct File {
+            filename: String,
+        }
+        let mut all = vec![];
+        let mut page = 0;
+        while all.len() == page * PER_PAGE {
+            page += 1;
+            let url = format!(
+                "{}/repos/{}/{}/pulls/{}/files?page={}&per_page={}",
+                self.base_url, pull.owner, pull.repo, pull.number, page, PER_PAGE
+            );
+            let batch: Vec<File> = self.request_json(&url[..], vec![]).await?;
+            all.extend(batch.into_iter().map(|f| f.filename));
         }
+        Ok(all)
     }
-}
 
-impl From<reqwest::Error> for Error {
-    fn from(err: reqwest::Error) -> Self {
-        Error {
-            reason: err.to_string(),
+    /// repo_access checks whether the token can read `repo`, and push to it.
+    pub async fn repo_access(&self, repo: &str) -> Result<RepoAccess> {
+        #[derive(Deserialize)]
+        struct Permissions {
+            push: bool,
         }
+        #[derive(Deserialize)]
+        struct Detail {
+            private: bool,
+            permissions: Option<Permissions>,
+        }
+        let repo = Repo::try_from(repo.to_owned())?;
+        let url = format!("{}/repos/{}", self.base_url, repo);
+        let res = self.request(&url[..], vec![]).await?;
+        let detail: Detail = parse(&url, &res)?;
+        Ok(RepoAccess {
+            private: detail.private,
+            push: detail.permissions.map_or(false, |p| p.push),
+        })
     }
-}
 
-#[derive(Debug)]
-pub struct GitHub {
-    token: String,
-    client: reqwest::Client,
-    repos: Vec<Repo>,
-    projects: Vec<Project>,
-    time: DateTime<Utc>,
-}
-
-struct Header {
-    key: String,
-    value: String,
-}
-
-#[derive(Debug, Eq, PartialEq)]
-struct Repo {
-    owner: String,
-    repo: String,
-}
-
-impl From<String> for Repo {
-    fn from(r: String) -> Self {
-        let parsed = r.split("/").map(Into::into).collect::<Vec<String>>();
-        Repo {
-            owner: parsed[0].to_owned(),
-            repo: parsed[1].to_owned(),
+    /// thread_url returns the url of the issue or the discussion of `repo` the
+    /// reports are posted to, if the token can read it.
+    pub async fn thread_url(&self, repo: &str, number: i32, discussion: bool) -> Result<String> {
+        #[derive(Deserialize)]
+        struct Thread {
+            html_url: String,
         }
+        let repo = Repo::try_from(repo.to_owned())?;
+        let kind = if discussion { "discussions" } else { "issues" };
+        let url = format!("{}/repos/{}/{}/{}", self.base_url, repo, kind, number);
+        let res = self.request(&url[..], vec![]).await?;
+        let thread: Thread = parse(&url, &res)?;
+        Ok(thread.html_url)
     }
-}
-
-#[derive(Debug, Eq, PartialEq, Clone)]
-pub struct Project {
-    owner: String,
-    repo: String,
-    number: i32,
-    id: Option<i64>,
-}
 
-#[derive(Serialize, Deserialize)]
-struct GitHubProject {
-    id: i64,
-    number: i32
-}
-
-impl From<String> for Project {
-    fn from(r: String) -> Self {
-        let re = Regex::new(r"https://github.com/([\w-]+)/([\w-]+)/projects/(\d+)").unwrap();
-        let mat = re.captures(&r[..]);
-        if let Some(m) = mat {
-            Project {
-                owner: m.get(1).unwrap().as_str().to_owned(),
-                repo: m.get(2).unwrap().as_str().to_owned(),
-                number: m.get(3).unwrap().as_str().parse::<i32>().unwrap(),
-                id: None,
-            }
-        } else {
-            Project {
-                owner: "".to_owned(),
-                repo: "".to_owned(),
-                number: 0,
-                id: None,
+    /// codeowners returns the CODEOWNERS file of `repo` in the places GitHub
+    /// looks for it, none if there is no such file.
+    pub async fn codeowners(&self, repo: &str) -> Result<Option<String>> {
+        let repo = Repo::try_from(repo.to_owned())?;
+        for path in &[".github/CODEOWNERS", "CODEOWNERS", "docs/CODEOWNERS"] {
+            let url = format!("{}/repos/{}/contents/{}", self.base_url, repo, path);
+            let headers = vec![Header {
+                key: "Accept",
+                value: "application/vnd.github.v3.raw",
+            }];
+            match self.request(&url[..], headers).await {
+                Ok(contents) => return Ok(Some(contents)),
+                Err(Error::NotFound { .. }) => continue,
+                Err(e) => return Err(e),
             }
         }
+        Ok(None)
     }
-}
-
-#[derive(Serialize, Deserialize)]
-pub struct User {
-    login: String,
-}
-
-#[derive(Serialize, Deserialize, Debug)]
-pub struct Pull {
-    html_url: String,
-}
-
-#[derive(Serialize, Deserialize, Debug)]
-pub struct Assignee {
-    id: i64,
-    login: String,
-}
-
-#[derive(Serialize, Deserialize, Debug)]
-pub struct Label {
-    id: i64,
-    name: String,
-    description: Option<String>,
-}
-
-#[derive(Serialize, Deserialize, Debug)]
-pub struct Issue {
-    number: i32,
-    title: String,
-    assignee: Option<Assignee>,
-    #[serde(skip_deserializing)]
-    owner: String,
-    #[serde(skip_deserializing)]
-    repo: String,
-    pull_request: Option<Pull>,
-    created_at: DateTime<Utc>,
-    author_association: String,
-    labels: Vec<Label>,
-}
 
-impl fmt::Display for Issue {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(
-            f,
-            "https://github.com/{}/{}/issues/{}",
-            self.owner, self.repo, self.number
-        )
+    /// issue_templates returns the issue templates of `repo` by file name, the
+    /// files in `.github/ISSUE_TEMPLATE/` or `.github/ISSUE_TEMPLATE.md`.
+    pub async fn issue_templates(&self, repo: &str) -> Result<Vec<(String, String)>> {
+        #[derive(Deserialize)]
+        struct Entry {
+            name: String,
+            path: String,
+            #[serde(rename = "type")]
+            kind: String,
+        }
+        let repo = Repo::try_from(repo.to_owned())?;
+        let raw = || {
+            vec![Header {
+                key: "Accept",
+                value: "application/vnd.github.v3.raw",
+            }]
+        };
+        let url = format!(
+            "{}/repos/{}/contents/.github/ISSUE_TEMPLATE",
+            self.base_url, repo
+        );
+        let entries: Vec<Entry> = match self.request(&url[..], vec![]).await {
+            Ok(res) => parse(&url, &res)?,
+            Err(Error::NotFound { .. }) => {
+                let url = format!(
+                    "{}/repos/{}/contents/.github/ISSUE_TEMPLATE.md",
+                    self.base_url, repo
+                );
+                return match self.request(&url[..], raw()).await {
+                    Ok(contents) => Ok(vec![("ISSUE_TEMPLATE.md".to_owned(), contents)]),
+                    Err(Error::NotFound { .. }) => Ok(vec![]),
+                    Err(e) => Err(e),
+                };
+            }
+            Err(e) => return Err(e),
+        };
+        let mut templates = vec![];
+        for entry in entries.into_iter().filter(|e| e.kind == "file") {
+            let url = format!("{}/repos/{}/contents/{}", self.base_url, repo, entry.path);
+            templates.push((entry.name, self.request(&url[..], raw()).await?));
+        }
+        Ok(templates)
     }
-}
-
-#[derive(Serialize, Deserialize)]
-pub struct Comment {
-    html_url: String,
-    author_association: String,
-}
-
-#[derive(Debug)]
-pub struct RepoIssues<'a> {
-    repo: &'a Repo,
-    issues: Vec<Issue>,
-}
-
-#[derive(Debug)]
-pub struct ProjectIssues<'a> {
-    project: &'a Project,
-    columns: Vec<Column>,
-}
-
-#[derive(Serialize, Deserialize, Debug)]
-pub struct Column {
-    id: i64,
-    name: String,
-    #[serde(skip_deserializing)]
-    cards: Vec<Card>,
-}
-
-#[derive(Serialize, Deserialize, Debug)]
-pub struct Card {
-
-}
-
-#[derive(Debug)]
-pub struct Snapshot<'a> {
-    time: &'a DateTime<Utc>,
-    repo_issues: Vec<RepoIssues<'a>>,
-    project_issues: Vec<ProjectIssues<'a>>,
-}
 
-impl GitHub {
-    pub fn new(token: String, repos: Vec<String>, projects: Vec<String>) -> Self {
-        let mut auth_header = "token ".to_owned();
-        auth_header.push_str(&token);
-        let repos: Vec<Repo> = repos.into_iter().map(Into::into).collect();
-        let projects = projects
-            .into_iter()
-            .map(Into::into)
-            .filter(|p: &Project| {
-                !&repos.contains(&Repo {
-                    owner: p.owner.to_owned(),
-                    repo: p.repo.to_owned(),
-                })
-            })
-            .collect();
-        GitHub {
-            token: auth_header,
-            client: reqwest::Client::new(),
-            repos,
-            projects,
-            time: Utc::now(),
+    /// list_reviews returns the reviews of the pull request, the oldest first.
+    pub async fn list_reviews(&self, pull: &Issue) -> Result<Vec<Review>> {
+        let mut all = vec![];
+        let mut page = 0;
+        while all.len() == page * PER_PAGE {
+            page += 1;
+            let url = format!(
+                "{}/repos/{}/{}/pulls/{}/reviews?page={}&per_page={}",
+                self.base_url, pull.owner, pull.repo, pull.number, page, PER_PAGE
+            );
+            let batch: Vec<Review> = self.request_json(&url[..], vec![]).await?;
+            all.extend(batch);
         }
+        Ok(all)
     }
 
-    async fn request(&self, url: &str, headers: Vec<Header>) -> Result<String> {
-        let mut req = self
-            .client
-            .get(url)
-            .header(reqwest::header::USER_AGENT, "pingbot")
-            .header(reqwest::header::AUTHORIZATION, &self.token[..]);
-        for header in headers {
-            req = req.header(&header.key[..], &header.value[..]);
+    /// tag_time returns the commit time of a tag of `repo`.
+    pub async fn tag_time(&self, repo: &str, tag: &str) -> Result<DateTime<Utc>> {
+        #[derive(Deserialize)]
+        struct Commit {
+            commit: CommitDetail,
         }
-        let res = req.send().await?.text().await?;
-        Ok(res)
+        #[derive(Deserialize)]
+        struct CommitDetail {
+            committer: Signature,
+        }
+        #[derive(Deserialize)]
+        struct Signature {
+            date: DateTime<Utc>,
+        }
+        let repo = Repo::try_from(repo.to_owned())?;
+        let url = format!("{}/repos/{}/commits/{}", self.base_url, repo, tag);
+        let res = self.request(&url[..], vec![]).await?;
+        let commit: Commit = parse(&url, &res)?;
+        Ok(commit.commit.committer.date)
     }
 
-    pub async fn get_user_result(&self) -> Result<String> {
-        let url = format!("{}/user", API_BASE_URL);
+    pub async fn get_user_result(&self) -> Result<UserResult> {
+        let url = format!("{}/user", self.base_url);
+        let res = self.send(&url[..], &[]).await?;
+        let header = |key: &str| {
+            res.headers()
+                .get(key)
+                .and_then(|v| v.to_str().ok())
+                .unwrap_or("")
+                .to_owned()
+        };
+        let scopes = header("X-OAuth-Scopes")
+            .split(',')
+            .map(|s| s.trim().to_owned())
+            .filter(|s| !s.is_empty())
+            .collect();
+        let rate_limit = header("X-RateLimit-Limit");
+        let rate_remaining = header("X-RateLimit-Remaining");
+        let rate_reset = header("X-RateLimit-Reset")
+            .parse::<i64>()
+            .ok()
+            .map(|reset| Utc.timestamp(reset, 0));
+        let u: User = parse(&url, &res.text().await?)?;
+
+        let url = format!("{}/user/orgs?per_page={}", self.base_url, PER_PAGE);
         let res = self.request(&url[..], vec![]).await?;
-        let u: User = serde_json::from_str(&res[..])?;
-        Ok(u.login.to_owned())
+        let orgs: Vec<User> = parse(&url, &res)?;
+
+        Ok(UserResult {
+            login: u.login.to_owned(),
+            scopes,
+            rate_limit,
+            rate_remaining,
+            rate_reset,
+            orgs: orgs.into_iter().map(|org| org.login).collect(),
+        })
     }
 
     // pub async fn get_issues(&self) -> Result<Vec<Issue>> {
@@ -264,61 +1455,169 @@ impl GitHub {
     //     Ok(opened_issues)
     // }
 
-    async fn get_opened_issues_by_repo<'a> (&self, repo: &'a Repo) -> Result<RepoIssues<'a>> {
-        let mut all = Vec::<Issue>::new();
-        let mut page = 0;
+    async fn get_issues_page(&self, repo: &Repo, page: usize) -> Result<Vec<Issue>> {
+        let mut url = format!(
+            "{}/repos/{}/{}/issues?page={}&per_page={}",
+            self.base_url, repo.owner, repo.repo, page, PER_PAGE
+        );
+        if let Some(since) = self.since {
+            url.push_str(&format!(
+                "&since={}",
+                since.to_rfc3339_opts(SecondsFormat::Secs, true)
+            ));
+        }
+        let headers = vec![Header {
+            key: "Accept",
+            value: "application/vnd.github.machine-man-preview",
+        }];
+        let batch: Vec<Issue> = self.request_json(&url[..], headers).await?;
+        let owner: Arc<str> = Arc::from(&repo.owner[..]);
+        let name: Arc<str> = Arc::from(&repo.repo[..]);
+        Ok(batch
+            .into_iter()
+            .map(|mut issue| {
+                issue.owner = owner.clone();
+                issue.repo = name.clone();
+                issue
+            })
+            .collect())
+    }
+
+    /// issues_stream yields the open issues of a repo (`owner/repo`) page by page,
+    /// so the issues of a big repo are never held together. The issue filter
+    /// and `since` apply as in snapshots.
+    pub fn issues_stream<'a>(&'a self, repo: &str) -> impl Stream<Item = Result<Issue>> + 'a {
+        self.issue_pages(Repo::try_from(repo.to_owned()).map(Arc::new))
+    }
+
+    fn issue_pages<'a>(
+        &'a self,
+        repo: Result<Arc<Repo>>,
+    ) -> impl Stream<Item = Result<Issue>> + 'a {
+        // the state is the repo and the next page, None after the last page
+        stream::unfold(Some(repo.map(|repo| (repo, 1))), move |state| async move {
+            let (repo, page) = match state? {
+                Ok(next) => next,
+                Err(e) => return Some((vec![Err(e)], None)),
+            };
+            match self.get_issues_page(&repo, page).await {
+                Ok(batch) => {
+                    let next = match batch.len() {
+                        PER_PAGE => Some(Ok((repo, page + 1))),
+                        _ => None,
+                    };
+                    // filter each page so the dropped issues are never held together
+                    let issues = batch
+                        .into_iter()
+                        .filter(|issue| self.keep(issue))
+                        .map(Ok)
+                        .collect::<Vec<_>>();
+                    Some((issues, next))
+                }
+                Err(e) => Some((vec![Err(e)], None)),
+            }
+        })
+        .flat_map(stream::iter)
+    }
+
+    async fn get_opened_issues_by_repo(&self, repo: &Arc<Repo>) -> Result<RepoIssues> {
+        let issues = self.issue_pages(Ok(Arc::clone(repo))).try_collect().await?;
+        Ok(RepoIssues {
+            repo: Arc::clone(repo),
+            issues,
+        })
+    }
+
+    // retain_branches drops the pull requests against other branches than the
+    // ones set for their repo, the issues API doesn't tell the base branch.
+    async fn retain_branches(&self, repo_issues: &mut [RepoIssues]) -> Result<()> {
+        for repo_issues in repo_issues.iter_mut() {
+            let name = repo_issues.name();
+            let branches = match self.pull_branches.get(&name) {
+                Some(branches) if !branches.is_empty() => branches,
+                _ => continue,
+            };
+            let mut numbers = HashSet::new();
+            for branch in branches {
+                for pull in self.list_pulls(&name, branch, "open").await? {
+                    numbers.insert(pull.number);
+                }
+            }
+            repo_issues
+                .issues
+                .retain(|issue| !issue.is_pull() || numbers.contains(&issue.number));
+        }
+        Ok(())
+    }
 
+    async fn get_comments_by_issue(&self, issue: &Issue) -> Result<Vec<Comment>> {
+        let mut all = vec![];
+        let mut page = 0;
         while all.len() == page * PER_PAGE {
             page += 1;
             let url = format!(
-                "{}/repos/{}/{}/issues?page={}&per_page={}",
-                API_BASE_URL, repo.owner, repo.repo, page, PER_PAGE
+                "{}/repos/{}/{}/issues/{}/comments?page={}&per_page={}",
+                self.base_url, issue.owner, issue.repo, issue.number, page, PER_PAGE
             );
-            let headers = vec![Header {
-                key: "Accept".to_owned(),
-                value: "application/vnd.github.machine-man-preview".to_owned(),
-            }];
-            let res = self.request(&url[..], headers).await?;
-            let batch: Vec<Issue> = serde_json::from_str(&res[..])?;
+            let batch: Vec<Comment> = self.request_json(&url[..], vec![]).await?;
             all.extend(batch);
         }
+        Ok(all)
+    }
 
-        let opened_all = all
-            .into_iter()
-            .map(|mut issue| {
-                issue.owner = repo.owner.to_owned();
-                issue.repo = repo.repo.to_owned();
-                issue
+    // get_opened_issues fetches `concurrency` repos at the same time,
+    // the result is in the order of the config.
+    async fn get_opened_issues(&self) -> Result<Vec<RepoIssues>> {
+        let fetched = AtomicUsize::new(0);
+        let fetched = &fetched;
+        let mut repos: Vec<(usize, Option<RepoIssues>)> = stream::iter(self.repos.iter().enumerate())
+            .map(|(i, repo)| async move {
+                self.progress.set_message(repo.to_string());
+                self.progress.inc(1);
+                let n = fetched.fetch_add(1, Ordering::SeqCst) + 1;
+                // one missing repo shouldn't fail the whole snapshot
+                match self.get_opened_issues_by_repo(repo).await {
+                    Ok(repo_issues) => {
+                        info!("[{}/{}] fetched repo {}", n, self.repos.len(), repo);
+                        Ok((i, Some(repo_issues)))
+                    }
+                    Err(e @ Error::NotFound { .. }) => {
+                        warn!("[{}/{}] skipped repo {}: {}", n, self.repos.len(), repo, e);
+                        Ok((i, None))
+                    }
+                    Err(e) => Err(e),
+                }
             })
-            .collect();
-
-        Ok(RepoIssues{
-            repo: repo,
-            issues: opened_all,
-        })
+            .buffer_unordered(self.concurrency)
+            .try_collect()
+            .await?;
+        repos.sort_by_key(|(i, _)| *i);
+        Ok(repos.into_iter().filter_map(|(_, repo)| repo).collect())
     }
 
-    // async fn get_comments_by_issue(&self, issue: &Issue) -> Result<usize> {
-    //     let url = format!(
-    //         "{}/repos/{}/{}/issues/{}/comments?per_page={}",
-    //         API_BASE_URL, issue.owner, issue.repo, issue.number, PER_PAGE
-    //     );
-    //     let res = self.request(&url[..], vec![]).await?;
-    //     let comments: Vec<Comment> = serde_json::from_str(&res[..])?;
-    //     let member_comments: Vec<Comment> = comments
-    //         .into_iter()
-    //         .filter(|comment| if_member(&comment.author_association))
-    //         .collect();
-    //     Ok(member_comments.len())
-    // }
-
-    async fn get_opened_issues<'a> (&'a self) -> Result<Vec<RepoIssues<'a>>> {
-        let mut repos: Vec<RepoIssues> = Vec::new();
-        for repo in &self.repos {
-            let repo_issues = self.get_opened_issues_by_repo(repo).await?;
-            repos.push(repo_issues);
+    /// list_project_boards returns the project boards of a repo (`owner/repo`) or an org.
+    pub async fn list_project_boards(&self, target: &str) -> Result<Vec<GitHubProject>> {
+        let path = match target.contains('/') {
+            true => format!("repos/{}", target),
+            false => format!("orgs/{}", target),
+        };
+        let mut all = vec![];
+        let mut page = 0;
+        while all.len() == page * PER_PAGE {
+            page += 1;
+            let url = format!(
+                "{}/{}/projects?page={}&per_page={}",
+                self.base_url, path, page, PER_PAGE
+            );
+            let headers = vec![Header {
+                key: "Accept",
+                value: "application/vnd.github.inertia-preview+json",
+            }];
+            let res = self.request(&url[..], headers).await?;
+            let batch: Vec<GitHubProject> = parse(&url, &res)?;
+            all.extend(batch);
         }
-        Ok(repos)
+        Ok(all)
     }
 
     pub async fn get_projects_id(&mut self) -> Result<()> {
@@ -329,14 +1628,14 @@ impl GitHub {
 
                 'outer: loop {
                     page += 1;
-                    let url = format!("{}/repos/{}/{}/projects?page={}&per_page={}", API_BASE_URL, project.owner, project.repo, page, PER_PAGE);
+                    let url = format!("{}/repos/{}/{}/projects?page={}&per_page={}", self.base_url, project.owner, project.repo, page, PER_PAGE);
                     let res = self.request(&url[..], vec![
                         Header{
-                            key: "Accept".to_owned(),
-                            value: "application/vnd.github.inertia-preview+json".to_owned(),
+                            key: "Accept",
+                            value: "application/vnd.github.inertia-preview+json",
                         }
                     ]).await?;
-                    let ps: Vec<GitHubProject> = serde_json::from_str(&res[..])?;
+                    let ps: Vec<GitHubProject> = parse(&url, &res)?;
                     for p in &ps {
                         if p.number == project.number {
                             number2id.insert(p.number, p.id);
@@ -360,8 +1659,8 @@ impl GitHub {
         Ok(())
     }
 
-    pub fn get_projects(&self) -> Vec<Project> {
-        self.projects.clone()
+    pub fn get_projects(&self) -> &[Project] {
+        &self.projects
     }
 
     async fn get_cards_by_column(&self, column: &Column) -> Result<Card> {
@@ -373,14 +1672,14 @@ impl GitHub {
         let mut page = 0;
         while all.len() == page * PER_PAGE {
             page += 1;
-            let url = format!("{}/projects/columns/{}/cards?page={}&per_page={}", API_BASE_URL, column_id, page, PER_PAGE);
+            let url = format!("{}/projects/columns/{}/cards?page={}&per_page={}", self.base_url, column_id, page, PER_PAGE);
             let res = self.request(&url[..], vec![
                 Header{
-                    key: "Accept".to_owned(),
-                    value: "application/vnd.github.inertia-preview+json".to_owned(),
+                    key: "Accept",
+                    value: "application/vnd.github.inertia-preview+json",
                 }
             ]).await?;
-            let batch: Vec<Card> = serde_json::from_str(&res[..])?;
+            let batch: Vec<Card> = parse(&url, &res)?;
             all.extend(batch);
         }
         Ok(all)
@@ -388,51 +1687,51 @@ impl GitHub {
 
     async fn get_columns(&self, project: &Project) -> Result<Vec<Column>> {
         if let Some(project_id) = project.id {
-            let url = format!("{}/projects/{}/columns?per_page={}", API_BASE_URL, project_id, PER_PAGE);
+            let url = format!("{}/projects/{}/columns?per_page={}", self.base_url, project_id, PER_PAGE);
             let res = self.request(&url[..], vec![
                 Header{
-                    key: "Accept".to_owned(),
-                    value: "application/vnd.github.inertia-preview+json".to_owned(),
+                    key: "Accept",
+                    value: "application/vnd.github.inertia-preview+json",
                 }
             ]).await?;
-            let mut columns: Vec<Column> = serde_json::from_str(&res[..])?;
-            for column in columns.iter_mut() {
-                (*column).cards = self.get_cards(column.id).await?;
-            }
+            let columns: Vec<Column> = parse(&url, &res)?;
+            // large boards have many columns, fetch their cards at the same time
+            let columns = stream::iter(columns)
+                .map(|mut column| async move {
+                    column.cards = self.get_cards(column.id).await?;
+                    Ok::<_, Error>(column)
+                })
+                .buffered(self.concurrency)
+                .try_collect()
+                .await?;
             Ok(columns)
         } else {
             Err("project id is none".into())
         }
     }
 
-    async fn get_project<'a> (&'a self, project: &'a Project) -> Result<ProjectIssues<'a>> {
+    async fn get_project(&self, project: &Project) -> Result<ProjectIssues> {
         let columns = self.get_columns(project).await?;
 
-        Ok(ProjectIssues{
-            project: project,
+        Ok(ProjectIssues {
+            project: project.clone(),
             columns: columns,
         })
     }
 
-    async fn get_projects_snapshot<'a> (&'a self) -> Result<Vec<ProjectIssues<'a>>> {
+    async fn get_projects_snapshot(&self) -> Result<Vec<ProjectIssues>> {
         let mut projects: Vec<ProjectIssues> = Vec::new();
-        for project in &self.projects {
+        for (i, project) in self.projects.iter().enumerate() {
+            let name = project.to_string();
+            self.progress.set_message(name.clone());
             let project_issues = self.get_project(project).await?;
             projects.push(project_issues);
+            self.progress.inc(1);
+            info!("[{}/{}] fetched {}", i + 1, self.projects.len(), name);
         }
         Ok(projects)
     }
 
-    pub async fn get_snapshot<'a> (&'a self) -> Result<Snapshot<'a>> {
-        let repo_issues = self.get_opened_issues().await?;
-        let projects = self.get_projects_snapshot().await?;
-        Ok(Snapshot{
-            time: &self.time,
-            repo_issues: repo_issues,
-            project_issues: projects,
-        })
-    }
-
     // fn if_filter_by_label(&self, issue: &Issue) -> bool {
     //     for label in &issue.labels {
     //         let lower_label = label.name.to_lowercase();
@@ -444,6 +1743,109 @@ impl GitHub {
     // }
 }
 
+#[async_trait]
+impl IssueProvider for GitHub {
+    fn name(&self) -> &'static str {
+        "github"
+    }
+
+    async fn list_issues(&self) -> Result<Vec<RepoIssues>> {
+        let mut repo_issues = if self.use_graphql() {
+            self.get_opened_issues_graphql().await?
+        } else {
+            self.get_opened_issues().await?
+        };
+        self.retain_branches(&mut repo_issues).await?;
+        for issue in repo_issues.iter_mut().flat_map(|r| r.issues.iter_mut()) {
+            issue.extract_fields();
+        }
+        Ok(repo_issues)
+    }
+
+    async fn list_projects(&self) -> Result<Vec<ProjectIssues>> {
+        if self.use_graphql() {
+            self.get_projects_graphql().await
+        } else {
+            self.get_projects_snapshot().await
+        }
+    }
+
+    async fn get_comments(&self, issue: &Issue) -> Result<Vec<Comment>> {
+        self.get_comments_by_issue(issue).await
+    }
+
+    async fn get_snapshot(&self) -> Result<Snapshot> {
+        self.progress.set_length((self.repos.len() + self.projects.len()) as u64);
+        let repo_issues = self.list_issues().await?;
+        let project_issues = self.list_projects().await?;
+        self.progress.finish_and_clear();
+        Ok(Snapshot {
+            time: self.time,
+            repo_issues,
+            project_issues,
+        })
+    }
+}
+
+fn parse<T: DeserializeOwned>(url: &str, body: &str) -> Result<T> {
+    serde_json::from_str(body).map_err(|source| Error::Deserialize {
+        url: url.to_owned(),
+        source,
+    })
+}
+
+// check_status turns the failed responses into errors of their kind.
+fn check_status(url: &str, res: reqwest::Response) -> Result<reqwest::Response> {
+    usage::record(usage_key(url), res.headers());
+    let header = |key: &str| {
+        res.headers()
+            .get(key)
+            .and_then(|v| v.to_str().ok())
+            .map(str::to_owned)
+    };
+    match res.status() {
+        StatusCode::UNAUTHORIZED => Err(Error::Unauthorized),
+        StatusCode::FORBIDDEN | StatusCode::TOO_MANY_REQUESTS
+            if header("X-RateLimit-Remaining").as_deref() == Some("0") =>
+        {
+            let reset = header("X-RateLimit-Reset")
+                .and_then(|reset| reset.parse().ok())
+                .map_or_else(Utc::now, |reset| Utc.timestamp(reset, 0));
+            Err(Error::RateLimited { reset })
+        }
+        // GitHub hides private repos as 404 if the token can't read them
+        StatusCode::NOT_FOUND => Err(Error::NotFound {
+            repo: repo_of(url),
+            missing_scope: header("X-OAuth-Scopes")
+                .map_or(false, |scopes| !scopes.split(',').any(|s| s.trim() == "repo")),
+        }),
+        _ => Ok(res.error_for_status()?),
+    }
+}
+
+// repo_of returns the owner/repo of an API url, or the url itself.
+fn repo_of(url: &str) -> String {
+    let re = Regex::new(r"/repos/([^/?]+/[^/?]+)").unwrap();
+    re.captures(url)
+        .map_or(url, |c| c.get(1).unwrap().as_str())
+        .to_owned()
+}
+
+// usage_key is the repo an API call is counted for, or "graphql" or "other".
+fn usage_key(url: &str) -> String {
+    if url.ends_with("/graphql") {
+        "graphql".to_owned()
+    } else if url.contains("/repos/") {
+        repo_of(url)
+    } else {
+        "other".to_owned()
+    }
+}
+
+fn empty_str() -> Arc<str> {
+    Arc::from("")
+}
+
 fn if_member(relation: &String) -> bool {
     relation == "OWNER"
         || relation == "COLLABORATOR"
@@ -451,6 +1853,10 @@ fn if_member(relation: &String) -> bool {
         || relation == "CONTRIBUTOR"
 }
 
+fn is_member(relation: &String, login: &str, members: &[String]) -> bool {
+    if_member(relation) || members.iter().any(|m| m == login)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -464,20 +1870,29 @@ mod tests {
             "https://github.com/pingcap/parser/projects/1".to_owned(),
             "https://github.com/pingcap/tidb/projects/40".to_owned(),
         ];
-        GitHub::new("".to_owned(), repos, projects)
+        GitHub::new(reqwest::Client::new(), "".to_owned(), repos, projects).unwrap()
     }
 
-    #[allow(dead_code)]
     fn new_issue_with_labels(labels: Vec<String>) -> Issue {
         Issue {
             number: 0,
             title: "title".to_owned(),
             assignee: None,
-            owner: "".to_owned(),
-            repo: "".to_owned(),
+            assignees: vec![],
+            milestone: None,
+            comments: 0,
+            state: "open".to_owned(),
+            html_url: "".to_owned(),
+            owner: empty_str(),
+            repo: empty_str(),
             pull_request: None,
+            draft: false,
             created_at: Utc::now(),
             author_association: "".to_owned(),
+            updated_at: None,
+            user: None,
+            body: None,
+            fields: BTreeMap::new(),
             labels: labels
                 .into_iter()
                 .map(|name| Label {
@@ -489,20 +1904,61 @@ mod tests {
         }
     }
 
+    fn new_snapshot(numbers: Vec<i32>) -> Snapshot {
+        let issues = numbers
+            .into_iter()
+            .map(|number| {
+                let mut issue = new_issue_with_labels(vec![]);
+                issue.number = number;
+                issue
+            })
+            .collect();
+        Snapshot {
+            time: Utc::now(),
+            repo_issues: vec![RepoIssues {
+                repo: Arc::new(Repo::try_from("pingcap/parser".to_owned()).unwrap()),
+                issues,
+            }],
+            project_issues: vec![],
+        }
+    }
+
+    #[test]
+    fn error_kinds() {
+        assert_eq!(
+            repo_of("https://api.github.com/repos/pingcap/tidbx/issues?page=1"),
+            "pingcap/tidbx"
+        );
+        assert_eq!(
+            repo_of("https://api.github.com/user"),
+            "https://api.github.com/user"
+        );
+        let err = parse::<User>("https://api.github.com/user", "Not Found").unwrap_err();
+        assert!(matches!(err, Error::Deserialize { .. }));
+        let err = Error::NotFound {
+            repo: "pingcap/tidbx".to_owned(),
+            missing_scope: true,
+        };
+        assert_eq!(
+            err.to_string(),
+            "pingcap/tidbx not found or private, the token lacks the `repo` scope to read private repos"
+        );
+    }
+
     #[test]
     fn create_client() {
         let client = new_client();
         assert_eq!(
             client.repos,
             vec![
-                Repo {
+                Arc::new(Repo {
                     owner: "pingcap".to_owned(),
                     repo: "parser".to_owned()
-                },
-                Repo {
+                }),
+                Arc::new(Repo {
                     owner: "you06".to_owned(),
                     repo: "issues-watcher".to_owned()
-                },
+                }),
             ]
         );
         assert_eq!(
@@ -515,4 +1971,92 @@ mod tests {
             },]
         );
     }
+
+    #[test]
+    fn diff_snapshots() {
+        let old = new_snapshot(vec![1, 2, 3]);
+        let new = new_snapshot(vec![2, 3, 4, 5]);
+        let diff = new.diff(&old);
+        assert_eq!(diff.len(), 1);
+        assert_eq!(diff[0].repo, "pingcap/parser");
+        let numbers = |issues: &Vec<Issue>| issues.iter().map(|i| i.number).collect::<Vec<i32>>();
+        assert_eq!(numbers(&diff[0].opened), vec![4, 5]);
+        assert_eq!(numbers(&diff[0].closed), vec![1]);
+    }
+
+    #[test]
+    fn filter_stale_issues() {
+        let rule = StaleRule {
+            days: 3,
+            ignore_labels: vec!["question".to_owned()],
+            members: vec!["you06".to_owned()],
+        };
+        let before = Utc::now() - chrono::Duration::days(3);
+        let mut issue = new_issue_with_labels(vec![]);
+        assert!(!issue.may_be_stale(&before, &rule));
+
+        issue.created_at = Utc::now() - chrono::Duration::days(4);
+        assert!(issue.may_be_stale(&before, &rule));
+
+        let mut labeled = issue.clone();
+        labeled.labels = new_issue_with_labels(vec!["question".to_owned()]).labels;
+        assert!(!labeled.may_be_stale(&before, &rule));
+
+        let mut by_member = issue.clone();
+        by_member.user = Some(User {
+            login: "you06".to_owned(),
+        });
+        assert!(!by_member.may_be_stale(&before, &rule));
+
+        let mut by_owner = issue.clone();
+        by_owner.author_association = "OWNER".to_owned();
+        assert!(!by_owner.may_be_stale(&before, &rule));
+    }
+
+    #[test]
+    fn parse_repo() {
+        assert!(Repo::try_from("pingcap/parser".to_owned()).is_ok());
+        for invalid in vec!["pingcap", "pingcap/", "/parser", "pingcap/tidb/parser"] {
+            assert!(Repo::try_from(invalid.to_owned()).is_err());
+        }
+    }
+
+    #[test]
+    fn parse_project() {
+        let project = Project::try_from("https://github.com/pingcap/tidb/projects/40/".to_owned());
+        assert_eq!(project.unwrap().to_string(), "pingcap/tidb project 40");
+        for invalid in vec![
+            "https://github.com/pingcap/tidb/project/40",
+            "https://github.com/pingcap/projects/40",
+            "https://github.com/pingcap/tidb/projects/99999999999",
+            "github.com/pingcap/tidb/projects/40",
+        ] {
+            assert!(Project::try_from(invalid.to_owned()).is_err());
+        }
+    }
+
+    #[test]
+    fn build_github_client() {
+        let github = GitHub::builder("token".to_owned())
+            .repos(vec!["pingcap/tidb".to_owned()])
+            .base_url("https://github.example.com/api/v3/")
+            .user_agent("watcher")
+            .timeout(Duration::from_secs(5))
+            .build()
+            .unwrap();
+        assert_eq!(github.base_url, "https://github.example.com/api/v3");
+        assert_eq!(
+            github.graphql_url(),
+            "https://github.example.com/api/graphql"
+        );
+        assert_eq!(github.http.headers()["authorization"], "token token");
+        assert_eq!(github.repos.len(), 1);
+
+        let github = GitHub::builder("".to_owned())
+            .base_url("http://127.0.0.1:8080")
+            .build()
+            .unwrap();
+        assert_eq!(github.graphql_url(), "http://127.0.0.1:8080/graphql");
+        assert_eq!(github.http.headers()["user-agent"], USER_AGENT);
+    }
 }
@@ -0,0 +1,546 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    convert::TryFrom,
+    sync::Arc,
+};
+
+use chrono::{DateTime, Utc};
+use reqwest;
+use serde::{de::DeserializeOwned, Deserialize};
+use serde_json::{json, Value};
+use tokio::time::delay_for;
+use tracing::{info, warn};
+
+use super::{
+    check_status, parse, Assignee, Card, Column, GitHub, Issue, Label, Milestone, Project,
+    ProjectIssues, Pull, Repo, RepoIssues, Result, User, PER_PAGE,
+};
+
+// repos in one query, each of them is an alias of the query
+const REPOS_PER_QUERY: usize = 10;
+
+const ISSUE_FIELDS: &str = "number title body url state createdAt updatedAt authorAssociation \
+                            author { login } milestone { number title dueOn } comments { totalCount } \
+                            assignees(first: 10) { nodes { databaseId login } } \
+                            labels(first: 20) { nodes { name description } }";
+
+#[derive(Deserialize)]
+struct Response<T> {
+    data: Option<T>,
+    #[serde(default)]
+    errors: Vec<ResponseError>,
+}
+
+#[derive(Deserialize)]
+struct ResponseError {
+    #[serde(rename = "type")]
+    kind: Option<String>,
+    message: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Connection<T> {
+    #[serde(default)]
+    page_info: Option<PageInfo>,
+    nodes: Vec<T>,
+}
+
+impl<T> Connection<T> {
+    // next_cursor is the cursor of the next page, none if this is the last one.
+    fn next_cursor(&self) -> Option<String> {
+        self.page_info
+            .as_ref()
+            .filter(|page| page.has_next_page)
+            .and_then(|page| page.end_cursor.clone())
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PageInfo {
+    has_next_page: bool,
+    end_cursor: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RepoNode {
+    issues: Connection<IssueNode>,
+    pull_requests: Connection<IssueNode>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct IssueNode {
+    number: i32,
+    title: String,
+    #[serde(default)]
+    body: Option<String>,
+    url: String,
+    state: String,
+    created_at: DateTime<Utc>,
+    updated_at: DateTime<Utc>,
+    author_association: String,
+    author: Option<Login>,
+    milestone: Option<Milestone>,
+    comments: Count,
+    assignees: Connection<AssigneeNode>,
+    labels: Connection<LabelNode>,
+    // only asked for pull requests
+    #[serde(default)]
+    is_draft: bool,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Count {
+    total_count: u32,
+}
+
+#[derive(Deserialize)]
+struct Login {
+    login: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct AssigneeNode {
+    database_id: Option<i64>,
+    login: String,
+}
+
+#[derive(Deserialize)]
+struct LabelNode {
+    name: String,
+    description: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct RepoProject {
+    repository: Option<ProjectRepoNode>,
+}
+
+#[derive(Deserialize)]
+struct ProjectRepoNode {
+    project: Option<ProjectNode>,
+}
+
+#[derive(Deserialize)]
+struct ProjectNode {
+    columns: Connection<ColumnNode>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ColumnNode {
+    database_id: i64,
+    name: String,
+    cards: Connection<CardNode>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct CardNode {
+    database_id: Option<i64>,
+    note: Option<String>,
+    updated_at: Option<DateTime<Utc>>,
+    content: Option<ContentNode>,
+}
+
+#[derive(Deserialize)]
+struct ContentNode {
+    url: Option<String>,
+}
+
+impl IssueNode {
+    fn into_issue(self, owner: &Arc<str>, repo: &Arc<str>, pull: bool) -> Issue {
+        let assignees: Vec<Assignee> = self
+            .assignees
+            .nodes
+            .into_iter()
+            .map(|a| Assignee {
+                id: a.database_id.unwrap_or(0),
+                login: a.login,
+            })
+            .collect();
+        Issue {
+            number: self.number,
+            title: self.title,
+            assignee: assignees.first().cloned(),
+            assignees,
+            milestone: self.milestone,
+            comments: self.comments.total_count,
+            state: self.state.to_lowercase(),
+            html_url: self.url.clone(),
+            owner: Arc::clone(owner),
+            repo: Arc::clone(repo),
+            pull_request: if pull {
+                Some(Pull { html_url: self.url })
+            } else {
+                None
+            },
+            draft: self.is_draft,
+            created_at: self.created_at,
+            updated_at: Some(self.updated_at),
+            author_association: self.author_association,
+            labels: self
+                .labels
+                .nodes
+                .into_iter()
+                .map(|l| Label {
+                    id: 0,
+                    name: l.name,
+                    description: l.description,
+                })
+                .collect(),
+            user: self.author.map(|a| User { login: a.login }),
+            body: self.body,
+            fields: BTreeMap::new(),
+        }
+    }
+}
+
+impl CardNode {
+    // the REST API gives the API url of the issue, keep the same in snapshots.
+    fn into_card(self) -> Card {
+        let content_url = self.content.and_then(|c| c.url).map(|url| {
+            url.replace("https://github.com/", "https://api.github.com/repos/")
+                .replace("/pull/", "/issues/")
+        });
+        Card {
+            id: self.database_id.unwrap_or_default(),
+            note: self.note,
+            content_url,
+            updated_at: self.updated_at,
+        }
+    }
+}
+
+fn connection(field: &str, after: Option<&str>) -> String {
+    let after = after.map_or("".to_owned(), |cursor| format!(", after: {:?}", cursor));
+    let draft = if field == "pullRequests" {
+        " isDraft"
+    } else {
+        ""
+    };
+    format!(
+        "{}(first: {}, states: OPEN, orderBy: {{field: CREATED_AT, direction: DESC}}{}) \
+         {{ pageInfo {{ hasNextPage endCursor }} nodes {{ {}{} }} }}",
+        field, PER_PAGE, after, ISSUE_FIELDS, draft
+    )
+}
+
+// repos_query asks for the first page of issues and pull requests of the repos,
+// the repo at index i is aliased as `r{i}`.
+fn repos_query(repos: &[Arc<Repo>]) -> String {
+    let mut query = "query {".to_owned();
+    for (i, repo) in repos.iter().enumerate() {
+        query.push_str(&format!(
+            " r{}: repository(owner: {:?}, name: {:?}) {{ {} {} }}",
+            i,
+            repo.owner,
+            repo.repo,
+            connection("issues", None),
+            connection("pullRequests", None)
+        ));
+    }
+    query.push_str(" }");
+    query
+}
+
+fn project_query(project: &Project) -> String {
+    format!(
+        "query {{ repository(owner: {:?}, name: {:?}) {{ project(number: {}) {{ \
+         columns(first: {}) {{ nodes {{ databaseId name \
+         cards(first: {}) {{ pageInfo {{ hasNextPage endCursor }} nodes {{ databaseId note \
+         updatedAt content {{ ... on Issue {{ url }} ... on PullRequest {{ url }} }} }} }} \
+         }} }} }} }} }}",
+        project.owner, project.repo, project.number, PER_PAGE, PER_PAGE
+    )
+}
+
+// into_data returns the data unless there are errors, missing repos are
+// null in the data and the callers decide what to do with them.
+fn into_data<T>(res: Response<T>) -> Result<T> {
+    let not_found = |e: &&ResponseError| e.kind.as_deref() == Some("NOT_FOUND");
+    if let Some(e) = res
+        .errors
+        .iter()
+        .find(|e| res.data.is_none() || !not_found(e))
+    {
+        return Err(format!("GraphQL: {}", e.message).into());
+    }
+    res.data
+        .ok_or_else(|| "GraphQL: response without data".into())
+}
+
+impl GitHub {
+    async fn query<T: DeserializeOwned>(&self, query: &str) -> Result<T> {
+        self.query_with(query, json!({})).await
+    }
+
+    // query_with passes the variables of the query, for values which are
+    // not safe to format into the query, like the body of comments.
+    async fn query_with<T: DeserializeOwned>(&self, query: &str, variables: Value) -> Result<T> {
+        if self.interval > std::time::Duration::from_millis(0) {
+            delay_for(self.interval).await;
+        }
+        let url = self.graphql_url();
+        let body = json!({ "query": query, "variables": variables });
+        let res = self
+            .http
+            .send(reqwest::Method::POST, &url, |req| req.json(&body))
+            .await?;
+        let res = check_status(&url, res)?.text().await?;
+        into_data(parse(&url, &res)?)
+    }
+
+    // next_pages fetches the pages of `field` after the first one.
+    async fn next_pages(
+        &self,
+        repo: &Repo,
+        field: &str,
+        mut cursor: Option<String>,
+    ) -> Result<Vec<IssueNode>> {
+        #[derive(Deserialize)]
+        struct Data {
+            repository: HashMap<String, Connection<IssueNode>>,
+        }
+        let mut all = vec![];
+        while let Some(after) = cursor {
+            let query = format!(
+                "query {{ repository(owner: {:?}, name: {:?}) {{ {} }} }}",
+                repo.owner,
+                repo.repo,
+                connection(field, Some(&after))
+            );
+            let mut data: Data = self.query(&query).await?;
+            let page = data
+                .repository
+                .remove(field)
+                .ok_or_else(|| format!("GraphQL: {} missing", field))?;
+            cursor = page.next_cursor();
+            all.extend(page.nodes);
+        }
+        Ok(all)
+    }
+
+    /// comment_discussion posts a comment to a discussion of `repo`, and returns
+    /// the url of the comment. Discussions are only in the GraphQL API.
+    pub async fn comment_discussion(&self, repo: &str, number: i32, body: &str) -> Result<String> {
+        #[derive(Deserialize)]
+        struct Data {
+            repository: Option<DiscussionRepo>,
+        }
+        #[derive(Deserialize)]
+        struct DiscussionRepo {
+            discussion: Option<Node>,
+        }
+        #[derive(Deserialize)]
+        struct Node {
+            id: String,
+        }
+        #[derive(Deserialize)]
+        #[serde(rename_all = "camelCase")]
+        struct Added {
+            add_discussion_comment: AddedComment,
+        }
+        #[derive(Deserialize)]
+        struct AddedComment {
+            comment: ContentNode,
+        }
+
+        let repo = Repo::try_from(repo.to_owned())?;
+        let query = format!(
+            "query {{ repository(owner: {:?}, name: {:?}) {{ discussion(number: {}) {{ id }} }} }}",
+            repo.owner, repo.repo, number
+        );
+        let data: Data = self.query(&query).await?;
+        let id = data
+            .repository
+            .and_then(|r| r.discussion)
+            .ok_or_else(|| format!("discussion {}#{} is not found", repo, number))?
+            .id;
+        let mutation = "mutation($id: ID!, $body: String!) { \
+                        addDiscussionComment(input: {discussionId: $id, body: $body}) \
+                        { comment { url } } }";
+        let added: Added = self
+            .query_with(mutation, json!({ "id": id, "body": body }))
+            .await?;
+        added
+            .add_discussion_comment
+            .comment
+            .url
+            .ok_or_else(|| "GraphQL: comment url missing".into())
+    }
+
+    pub(super) async fn get_opened_issues_graphql(&self) -> Result<Vec<RepoIssues>> {
+        let mut all = vec![];
+        for batch in self.repos.chunks(REPOS_PER_QUERY) {
+            let mut data: HashMap<String, Option<RepoNode>> =
+                self.query(&repos_query(batch)).await?;
+            for (i, repo) in batch.iter().enumerate() {
+                let node = match data.remove(&format!("r{}", i)).flatten() {
+                    Some(node) => node,
+                    None => {
+                        warn!("skipped repo {}, it's not found or private", repo);
+                        continue;
+                    }
+                };
+                let issues_cursor = node.issues.next_cursor();
+                let pulls_cursor = node.pull_requests.next_cursor();
+                let owner: Arc<str> = Arc::from(&repo.owner[..]);
+                let name: Arc<str> = Arc::from(&repo.repo[..]);
+                let mut issues: Vec<Issue> = node
+                    .issues
+                    .nodes
+                    .into_iter()
+                    .chain(self.next_pages(repo, "issues", issues_cursor).await?)
+                    .map(|issue| issue.into_issue(&owner, &name, false))
+                    .filter(|issue| self.keep(issue))
+                    .collect();
+                issues.extend(
+                    node.pull_requests
+                        .nodes
+                        .into_iter()
+                        .chain(self.next_pages(repo, "pullRequests", pulls_cursor).await?)
+                        .map(|pull| pull.into_issue(&owner, &name, true))
+                        .filter(|pull| self.keep(pull)),
+                );
+                issues.sort_by(|a, b| b.created_at.cmp(&a.created_at));
+                self.progress.set_message(repo.to_string());
+                self.progress.inc(1);
+                info!("fetched repo {} by GraphQL", repo);
+                all.push(RepoIssues {
+                    repo: Arc::clone(repo),
+                    issues,
+                });
+            }
+        }
+        Ok(all)
+    }
+
+    pub(super) async fn get_projects_graphql(&self) -> Result<Vec<ProjectIssues>> {
+        let mut all = vec![];
+        for project in self.projects.iter() {
+            let data: RepoProject = self.query(&project_query(project)).await?;
+            let node = data
+                .repository
+                .and_then(|r| r.project)
+                .ok_or_else(|| format!("{} not found", project))?;
+            let mut columns = vec![];
+            for column in node.columns.nodes {
+                // columns with more cards than a page are rare, fall back to REST
+                let cards = if column.cards.next_cursor().is_some() {
+                    self.get_cards(column.database_id).await?
+                } else {
+                    column
+                        .cards
+                        .nodes
+                        .into_iter()
+                        .map(CardNode::into_card)
+                        .collect()
+                };
+                columns.push(Column {
+                    id: column.database_id,
+                    name: column.name,
+                    cards,
+                });
+            }
+            self.progress.set_message(project.to_string());
+            self.progress.inc(1);
+            info!("fetched {} by GraphQL", project);
+            all.push(ProjectIssues {
+                project: project.clone(),
+                columns,
+            });
+        }
+        Ok(all)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn convert_graphql_nodes() {
+        let repo = Repo::try_from("pingcap/parser".to_owned()).unwrap();
+        let query = repos_query(&[Arc::new(repo)]);
+        assert!(query.starts_with("query { r0: repository(owner: \"pingcap\", name: \"parser\")"));
+        assert!(query.contains("pullRequests(first: 100, states: OPEN"));
+
+        let node: RepoNode = serde_json::from_str(
+            r#"{
+                "issues": {
+                    "pageInfo": {"hasNextPage": false, "endCursor": null},
+                    "nodes": [{
+                        "number": 1,
+                        "title": "issue",
+                        "url": "https://github.com/pingcap/parser/issues/1",
+                        "state": "OPEN",
+                        "createdAt": "2020-04-01T00:00:00Z",
+                        "updatedAt": "2020-04-02T00:00:00Z",
+                        "authorAssociation": "NONE",
+                        "author": {"login": "you06"},
+                        "milestone": {"number": 1, "title": "v4.0"},
+                        "comments": {"totalCount": 3},
+                        "assignees": {"nodes": [
+                            {"databaseId": 1, "login": "you06"},
+                            {"databaseId": 2, "login": "lysu"}
+                        ]},
+                        "labels": {"nodes": [{"name": "bug", "description": null}]}
+                    }]
+                },
+                "pullRequests": {
+                    "pageInfo": {"hasNextPage": true, "endCursor": "abc"},
+                    "nodes": []
+                }
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(node.pull_requests.next_cursor(), Some("abc".to_owned()));
+        assert_eq!(node.issues.next_cursor(), None);
+        let issue = node.issues.nodes.into_iter().next().unwrap();
+        let issue = issue.into_issue(&Arc::from("pingcap"), &Arc::from("parser"), false);
+        assert_eq!(
+            issue.to_string(),
+            "https://github.com/pingcap/parser/issues/1"
+        );
+        assert_eq!(issue.assignee(), Some("you06"));
+        assert_eq!(issue.assignees().collect::<Vec<_>>(), vec!["you06", "lysu"]);
+        assert_eq!(issue.milestone(), Some("v4.0"));
+        assert_eq!(issue.comments(), 3);
+        assert_eq!(issue.state(), "open");
+        assert_eq!(issue.labels().collect::<Vec<_>>(), vec!["bug"]);
+        assert!(!issue.is_pull());
+
+        let res: Response<HashMap<String, Option<RepoNode>>> = serde_json::from_str(
+            r#"{
+                "data": {"r0": null},
+                "errors": [{"type": "NOT_FOUND", "message": "Could not resolve to a Repository"}]
+            }"#,
+        )
+        .unwrap();
+        assert!(into_data(res).unwrap()["r0"].is_none());
+        let res: Response<HashMap<String, Option<RepoNode>>> =
+            serde_json::from_str(r#"{"data": null, "errors": [{"message": "Bad credentials"}]}"#)
+                .unwrap();
+        assert!(into_data(res).is_err());
+
+        let card = CardNode {
+            database_id: Some(1),
+            note: None,
+            updated_at: None,
+            content: Some(ContentNode {
+                url: Some("https://github.com/pingcap/parser/pull/2".to_owned()),
+            }),
+        }
+        .into_card();
+        assert_eq!(
+            card.html_url().as_deref(),
+            Some("https://github.com/pingcap/parser/issues/2")
+        );
+    }
+}
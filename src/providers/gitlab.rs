@@ -0,0 +1,454 @@
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{Arc, Mutex},
+};
+
+use async_trait::async_trait;
+use chrono::{DateTime, NaiveDate, TimeZone, Utc};
+use regex::Regex;
+use reqwest::{header::HeaderName, Method, Url};
+use serde::{de::DeserializeOwned, Deserialize};
+use tracing::{info, warn};
+
+use super::github::{
+    Assignee, Card, Column, Comment, Issue, Label, Milestone, Project, ProjectIssues, Repo,
+    RepoIssues, User, USER_AGENT,
+};
+use super::{check_status, Error, IssueProvider, Result};
+use crate::cache::Cache;
+use crate::http::Http;
+
+const PER_PAGE: usize = 100;
+
+pub struct GitLab {
+    // adds the token and the user agent, and keeps the cache
+    http: Http,
+    // e.g. https://gitlab.com, or a self-hosted instance
+    base_url: String,
+    projects: Vec<Arc<Repo>>,
+    boards: Vec<Project>,
+    // members of each project, fetched once
+    members: Mutex<HashMap<String, Arc<HashSet<String>>>>,
+}
+
+#[derive(Deserialize)]
+struct GitLabIssue {
+    iid: i32,
+    title: String,
+    state: String,
+    web_url: String,
+    created_at: DateTime<Utc>,
+    updated_at: DateTime<Utc>,
+    #[serde(default)]
+    user_notes_count: u32,
+    author: Option<GitLabUser>,
+    #[serde(default)]
+    assignees: Vec<GitLabUser>,
+    milestone: Option<GitLabMilestone>,
+    #[serde(default)]
+    labels: Vec<String>,
+}
+
+#[derive(Deserialize)]
+struct GitLabUser {
+    id: i64,
+    username: String,
+}
+
+#[derive(Deserialize)]
+struct GitLabMilestone {
+    iid: i32,
+    title: String,
+    due_date: Option<NaiveDate>,
+}
+
+#[derive(Deserialize)]
+struct Note {
+    id: i64,
+    author: GitLabUser,
+    // notes like "changed the label" are made by GitLab, not replies
+    #[serde(default)]
+    system: bool,
+}
+
+#[derive(Deserialize)]
+struct Board {
+    #[serde(default)]
+    lists: Vec<BoardList>,
+}
+
+#[derive(Deserialize)]
+struct BoardList {
+    id: i64,
+    label: Option<BoardLabel>,
+}
+
+#[derive(Deserialize)]
+struct BoardLabel {
+    name: String,
+}
+
+// split_path splits `group/subgroup/project` into the namespace and the project name.
+fn split_path(path: &str) -> Result<Repo> {
+    match path.trim_matches('/').rsplitn(2, '/').collect::<Vec<_>>()[..] {
+        [repo, owner] if !repo.is_empty() && !owner.is_empty() => Ok(Repo {
+            owner: owner.to_owned(),
+            repo: repo.to_owned(),
+        }),
+        _ => Err(format!("gitlab project {:?} is not in group/project format", path).into()),
+    }
+}
+
+/// parse_board parses a board url like `https://gitlab.com/group/project/-/boards/1`.
+pub fn parse_board(url: &str) -> Result<Project> {
+    let re = Regex::new(r"^https?://[^/]+/(.+)/-/boards/(\d+)/?$").unwrap();
+    let m = re.captures(url.trim()).ok_or_else(|| {
+        format!(
+            "board {:?} is not like https://gitlab.com/group/project/-/boards/1",
+            url
+        )
+    })?;
+    let repo = split_path(&m[1])?;
+    let number = m[2]
+        .parse::<i32>()
+        .map_err(|e| format!("board {:?} has an invalid number: {}", url, e))?;
+    Ok(Project {
+        owner: repo.owner,
+        repo: repo.repo,
+        number,
+        id: None,
+    })
+}
+
+impl GitLab {
+    pub fn new(
+        client: reqwest::Client,
+        base_url: &str,
+        token: String,
+        projects: Vec<String>,
+        boards: Vec<String>,
+    ) -> Result<Self> {
+        let projects = projects
+            .iter()
+            .map(|path| split_path(path).map(Arc::new))
+            .collect::<Result<Vec<_>>>()?;
+        let boards = boards
+            .iter()
+            .map(|url| parse_board(url))
+            .collect::<Result<Vec<_>>>()?;
+        let http = Http::new(client, USER_AGENT)?
+            .header(HeaderName::from_static("private-token"), &token)?;
+        Ok(GitLab {
+            http,
+            base_url: base_url.trim_end_matches('/').to_owned(),
+            projects,
+            boards,
+            members: Mutex::new(HashMap::new()),
+        })
+    }
+
+    pub fn set_cache(&mut self, cache: Option<Cache>) {
+        self.http.set_cache(cache);
+    }
+
+    fn project_url(&self, owner: &str, repo: &str) -> String {
+        format!(
+            "{}/api/v4/projects/{}%2F{}",
+            self.base_url,
+            owner.replace('/', "%2F"),
+            repo
+        )
+    }
+
+    // get_all fetches all the pages of a list.
+    async fn get_all<T: DeserializeOwned>(
+        &self,
+        url: &str,
+        query: &[(&str, &str)],
+    ) -> Result<Vec<T>> {
+        let mut all = vec![];
+        let mut page = 0;
+        while all.len() == page * PER_PAGE {
+            page += 1;
+            all.extend(self.get::<Vec<T>>(url, query, page).await?);
+        }
+        Ok(all)
+    }
+
+    async fn get<T: DeserializeOwned>(
+        &self,
+        url: &str,
+        query: &[(&str, &str)],
+        page: usize,
+    ) -> Result<T> {
+        let (page, per_page) = (page.to_string(), PER_PAGE.to_string());
+        let paging = [("page", &page[..]), ("per_page", &per_page[..])];
+        let page_url = Url::parse_with_params(url, query.iter().chain(&paging))
+            .map_err(|e| format!("invalid url {}: {}", url, e))?;
+        let body = match self.http.cached(page_url.as_str(), &[]) {
+            Some(body) => body,
+            None => {
+                let res = self
+                    .http
+                    .send(Method::GET, page_url.as_str(), |req| req)
+                    .await?;
+                let body = check_status(url, res)?.text().await?;
+                self.http.store(page_url.as_str(), &[], &body);
+                body
+            }
+        };
+        serde_json::from_str(&body).map_err(|source| Error::Deserialize {
+            url: url.to_owned(),
+            source,
+        })
+    }
+
+    // get_members returns the usernames of the project members, including the
+    // inherited ones, their comments are replies to the issues.
+    async fn get_members(&self, owner: &str, repo: &str) -> Result<Arc<HashSet<String>>> {
+        let url = format!("{}/members/all", self.project_url(owner, repo));
+        let cached = self.members.lock().unwrap().get(&url).cloned();
+        if let Some(members) = cached {
+            return Ok(members);
+        }
+        let members: Vec<GitLabUser> = self.get_all(&url, &[]).await?;
+        let members: Arc<HashSet<String>> =
+            Arc::new(members.into_iter().map(|m| m.username).collect());
+        self.members
+            .lock()
+            .unwrap()
+            .insert(url, Arc::clone(&members));
+        Ok(members)
+    }
+
+    async fn get_issues(&self, repo: &Repo, labels: Option<&str>) -> Result<Vec<Issue>> {
+        let url = format!("{}/issues", self.project_url(&repo.owner, &repo.repo));
+        let mut query = vec![("state", "opened")];
+        if let Some(labels) = labels {
+            query.push(("labels", labels));
+        }
+        let issues: Vec<GitLabIssue> = self.get_all(&url, &query).await?;
+        let members = self.get_members(&repo.owner, &repo.repo).await?;
+        let owner: Arc<str> = Arc::from(&repo.owner[..]);
+        let name: Arc<str> = Arc::from(&repo.repo[..]);
+        Ok(issues
+            .into_iter()
+            .map(|issue| issue.into_issue(&owner, &name, &members))
+            .collect())
+    }
+
+    async fn get_board(&self, board: &Project) -> Result<ProjectIssues> {
+        let url = format!(
+            "{}/boards/{}",
+            self.project_url(&board.owner, &board.repo),
+            board.number
+        );
+        let detail: Board = self.get(&url, &[], 1).await?;
+        let repo = Repo {
+            owner: board.owner.clone(),
+            repo: board.repo.clone(),
+        };
+        let mut columns = vec![];
+        // the lists of a board are labels, the open and closed lists are implicit
+        for list in detail.lists {
+            let label = match list.label {
+                Some(label) => label.name,
+                None => continue,
+            };
+            let cards = self
+                .get_issues(&repo, Some(&label))
+                .await?
+                .into_iter()
+                .map(|issue| Card {
+                    // cards of GitLab boards are moved by labels, not by ids
+                    id: 0,
+                    note: None,
+                    content_url: Some(issue.html_url()),
+                    updated_at: issue.updated_at,
+                })
+                .collect();
+            columns.push(Column {
+                id: list.id,
+                name: label,
+                cards,
+            });
+        }
+        Ok(ProjectIssues {
+            project: board.clone(),
+            columns,
+        })
+    }
+}
+
+impl GitLabIssue {
+    fn into_issue(self, owner: &Arc<str>, repo: &Arc<str>, members: &HashSet<String>) -> Issue {
+        let assignees: Vec<Assignee> = self
+            .assignees
+            .into_iter()
+            .map(|a| Assignee {
+                id: a.id,
+                login: a.username,
+            })
+            .collect();
+        let author = self.author.map(|a| a.username);
+        let association = match &author {
+            Some(login) if members.contains(login) => "MEMBER",
+            _ => "NONE",
+        };
+        Issue {
+            number: self.iid,
+            title: self.title,
+            assignee: assignees.first().cloned(),
+            assignees,
+            milestone: self.milestone.map(|m| Milestone {
+                number: m.iid,
+                title: m.title,
+                due_on: m
+                    .due_date
+                    .map(|d| Utc.from_utc_datetime(&d.and_hms(0, 0, 0))),
+            }),
+            comments: self.user_notes_count,
+            // GitLab says "opened" where GitHub says "open"
+            state: match &self.state[..] {
+                "opened" => "open".to_owned(),
+                _ => self.state,
+            },
+            html_url: self.web_url,
+            owner: Arc::clone(owner),
+            repo: Arc::clone(repo),
+            pull_request: None,
+            draft: false,
+            created_at: self.created_at,
+            updated_at: Some(self.updated_at),
+            author_association: association.to_owned(),
+            labels: self
+                .labels
+                .into_iter()
+                .map(|name| Label {
+                    id: 0,
+                    name,
+                    description: None,
+                })
+                .collect(),
+            user: author.map(|login| User { login }),
+            body: None,
+            fields: Default::default(),
+        }
+    }
+}
+
+#[async_trait]
+impl IssueProvider for GitLab {
+    fn name(&self) -> &'static str {
+        "gitlab"
+    }
+
+    async fn list_issues(&self) -> Result<Vec<RepoIssues>> {
+        let mut all = vec![];
+        for (i, repo) in self.projects.iter().enumerate() {
+            // one missing project shouldn't fail the whole snapshot
+            let issues = match self.get_issues(repo, None).await {
+                Ok(issues) => issues,
+                Err(e @ Error::NotFound { .. }) => {
+                    warn!("skipped gitlab project {}: {}", repo, e);
+                    continue;
+                }
+                Err(e) => return Err(e),
+            };
+            info!(
+                "[{}/{}] fetched gitlab project {}",
+                i + 1,
+                self.projects.len(),
+                repo
+            );
+            all.push(RepoIssues {
+                repo: Arc::clone(repo),
+                issues,
+            });
+        }
+        Ok(all)
+    }
+
+    async fn list_projects(&self) -> Result<Vec<ProjectIssues>> {
+        let mut all = vec![];
+        for board in &self.boards {
+            all.push(self.get_board(board).await?);
+            info!("fetched gitlab board {}", board);
+        }
+        Ok(all)
+    }
+
+    async fn get_comments(&self, issue: &Issue) -> Result<Vec<Comment>> {
+        let url = format!(
+            "{}/issues/{}/notes",
+            self.project_url(&issue.owner, &issue.repo),
+            issue.number
+        );
+        let notes: Vec<Note> = self.get_all(&url, &[]).await?;
+        let members = self.get_members(&issue.owner, &issue.repo).await?;
+        Ok(notes
+            .into_iter()
+            .filter(|note| !note.system)
+            .map(|note| Comment {
+                html_url: format!("{}#note_{}", issue.html_url(), note.id),
+                author_association: if members.contains(&note.author.username) {
+                    "MEMBER".to_owned()
+                } else {
+                    "NONE".to_owned()
+                },
+                user: User {
+                    login: note.author.username,
+                },
+            })
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn convert_gitlab_issues() {
+        assert_eq!(
+            split_path("group/sub/project").unwrap().to_string(),
+            "group/sub/project"
+        );
+        assert!(split_path("project").is_err());
+        let board = parse_board("https://gitlab.example.com/group/sub/project/-/boards/3").unwrap();
+        assert_eq!(board.to_string(), "group/sub/project project 3");
+        assert!(parse_board("https://gitlab.com/group/project/boards/3").is_err());
+
+        let issue: GitLabIssue = serde_json::from_str(
+            r#"{
+                "iid": 7,
+                "title": "crash",
+                "state": "opened",
+                "web_url": "https://gitlab.com/group/project/-/issues/7",
+                "created_at": "2020-04-01T00:00:00Z",
+                "updated_at": "2020-04-02T00:00:00Z",
+                "user_notes_count": 2,
+                "author": {"id": 1, "username": "you06"},
+                "assignees": [{"id": 2, "username": "lysu"}],
+                "milestone": {"iid": 1, "title": "v1.0", "due_date": "2020-06-01"},
+                "labels": ["bug"]
+            }"#,
+        )
+        .unwrap();
+        let members = vec!["you06".to_owned()].into_iter().collect();
+        let issue = issue.into_issue(&Arc::from("group"), &Arc::from("project"), &members);
+        assert_eq!(
+            issue.to_string(),
+            "https://gitlab.com/group/project/-/issues/7"
+        );
+        assert_eq!(issue.state(), "open");
+        assert_eq!(issue.assignees().collect::<Vec<_>>(), vec!["lysu"]);
+        assert_eq!(issue.milestone(), Some("v1.0"));
+        assert_eq!(
+            issue.milestone_due_on().map(|d| d.to_rfc3339()),
+            Some("2020-06-01T00:00:00+00:00".to_owned())
+        );
+        assert_eq!(issue.labels().collect::<Vec<_>>(), vec!["bug"]);
+        assert_eq!(issue.author_association, "MEMBER");
+    }
+}
@@ -0,0 +1,356 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use chrono::{DateTime, NaiveDate, TimeZone, Utc};
+use serde::{Deserialize, Deserializer};
+use tracing::{debug, info, warn};
+
+use super::github::{
+    Assignee, Comment, Issue, Label, Milestone, ProjectIssues, Repo, RepoIssues, User,
+};
+use super::{check_status, Error, IssueProvider, Result};
+
+const PER_PAGE: usize = 50;
+// the fields of issues used in snapshots, the others are not fetched
+const FIELDS: &str = "summary,status,created,updated,reporter,assignee,labels,fixVersions,comment";
+
+// Jira has no author association like GitHub, the replies are only
+// counted by the `members` in config. Each JQL filter is watched like a repo
+// named "jira/<name>".
+pub struct Jira {
+    email: String,
+    token: String,
+    client: reqwest::Client,
+    base_url: String,
+    filters: Vec<(Arc<Repo>, String)>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SearchPage {
+    start_at: usize,
+    total: usize,
+    issues: Vec<JiraIssue>,
+}
+
+#[derive(Deserialize)]
+struct JiraIssue {
+    id: String,
+    key: String,
+    fields: Fields,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Fields {
+    summary: String,
+    status: Status,
+    #[serde(deserialize_with = "jira_time")]
+    created: DateTime<Utc>,
+    #[serde(deserialize_with = "jira_time")]
+    updated: DateTime<Utc>,
+    reporter: Option<JiraUser>,
+    assignee: Option<JiraUser>,
+    #[serde(default)]
+    labels: Vec<String>,
+    #[serde(default)]
+    fix_versions: Vec<Version>,
+    comment: Option<Comments>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Status {
+    status_category: StatusCategory,
+}
+
+#[derive(Deserialize)]
+struct StatusCategory {
+    key: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct JiraUser {
+    // Jira Server and Data Center only, Cloud hides it
+    #[serde(default)]
+    name: Option<String>,
+    display_name: String,
+}
+
+impl JiraUser {
+    fn login(self) -> String {
+        self.name.unwrap_or(self.display_name)
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Version {
+    id: String,
+    name: String,
+    release_date: Option<NaiveDate>,
+}
+
+#[derive(Deserialize)]
+struct Comments {
+    total: u32,
+    comments: Vec<JiraComment>,
+}
+
+#[derive(Deserialize)]
+struct JiraComment {
+    id: String,
+    author: Option<JiraUser>,
+}
+
+// jira_time parses times like "2020-04-01T10:00:00.000+0800", whose offset
+// has no colon and is not RFC 3339.
+fn jira_time<'de, D: Deserializer<'de>>(d: D) -> std::result::Result<DateTime<Utc>, D::Error> {
+    let s = String::deserialize(d)?;
+    DateTime::parse_from_str(&s, "%Y-%m-%dT%H:%M:%S%.f%z")
+        .map(|t| t.with_timezone(&Utc))
+        .map_err(serde::de::Error::custom)
+}
+
+impl Jira {
+    /// new creates a Jira client, `filters` are pairs of names and JQL queries.
+    pub fn new(
+        client: reqwest::Client,
+        base_url: &str,
+        email: String,
+        token: String,
+        filters: Vec<(String, String)>,
+    ) -> Self {
+        let filters = filters
+            .into_iter()
+            .map(|(name, jql)| {
+                let repo = Repo {
+                    owner: "jira".to_owned(),
+                    repo: name,
+                };
+                (Arc::new(repo), jql)
+            })
+            .collect();
+        Jira {
+            email,
+            token,
+            client,
+            base_url: base_url.trim_end_matches('/').to_owned(),
+            filters,
+        }
+    }
+
+    fn request(&self, url: &str) -> reqwest::RequestBuilder {
+        let req = self.client.get(url);
+        // Cloud takes the email with an API token, Server a personal access token
+        if self.email.is_empty() {
+            req.bearer_auth(&self.token)
+        } else {
+            req.basic_auth(&self.email, Some(&self.token))
+        }
+    }
+
+    // search fetches all the pages of a JQL query.
+    async fn search(&self, jql: &str) -> Result<Vec<JiraIssue>> {
+        let url = format!("{}/rest/api/2/search", self.base_url);
+        let mut all = vec![];
+        loop {
+            debug!("GET {} at {}", url, all.len());
+            let res = self
+                .request(&url)
+                .query(&[("jql", jql), ("fields", FIELDS)])
+                .query(&[("startAt", all.len()), ("maxResults", PER_PAGE)])
+                .send()
+                .await?;
+            let body = check_status(&url, res)?.text().await?;
+            let page: SearchPage =
+                serde_json::from_str(&body).map_err(|source| Error::Deserialize {
+                    url: url.clone(),
+                    source,
+                })?;
+            let done = page.issues.is_empty() || page.start_at + page.issues.len() >= page.total;
+            all.extend(page.issues);
+            if done {
+                return Ok(all);
+            }
+        }
+    }
+}
+
+impl JiraIssue {
+    fn into_issue(self, base_url: &str, filter: &Arc<str>) -> Result<Issue> {
+        let fields = self.fields;
+        let assignees: Vec<Assignee> = fields
+            .assignee
+            .map(|a| Assignee {
+                id: 0,
+                login: a.login(),
+            })
+            .into_iter()
+            .collect();
+        Ok(Issue {
+            number: self
+                .id
+                .parse()
+                .map_err(|_| format!("jira issue {} has a bad id {:?}", self.key, self.id))?,
+            title: format!("{} {}", self.key, fields.summary),
+            assignee: assignees.first().cloned(),
+            assignees,
+            milestone: fields.fix_versions.into_iter().next().map(|v| Milestone {
+                number: v.id.parse().unwrap_or_default(),
+                title: v.name,
+                due_on: v
+                    .release_date
+                    .map(|d| Utc.from_utc_datetime(&d.and_hms(0, 0, 0))),
+            }),
+            comments: fields.comment.map_or(0, |c| c.total),
+            state: match &fields.status.status_category.key[..] {
+                "done" => "closed".to_owned(),
+                _ => "open".to_owned(),
+            },
+            html_url: format!("{}/browse/{}", base_url, self.key),
+            owner: Arc::from("jira"),
+            repo: Arc::clone(filter),
+            pull_request: None,
+            draft: false,
+            created_at: fields.created,
+            updated_at: Some(fields.updated),
+            author_association: "NONE".to_owned(),
+            labels: fields
+                .labels
+                .into_iter()
+                .map(|name| Label {
+                    id: 0,
+                    name,
+                    description: None,
+                })
+                .collect(),
+            user: fields.reporter.map(|r| User { login: r.login() }),
+            body: None,
+            fields: Default::default(),
+        })
+    }
+}
+
+#[async_trait]
+impl IssueProvider for Jira {
+    fn name(&self) -> &'static str {
+        "jira"
+    }
+
+    async fn list_issues(&self) -> Result<Vec<RepoIssues>> {
+        let mut all = vec![];
+        for (i, (repo, jql)) in self.filters.iter().enumerate() {
+            let issues = match self.search(jql).await {
+                Ok(issues) => issues,
+                Err(e @ Error::NotFound { .. }) => {
+                    warn!("skipped jira filter {}: {}", repo, e);
+                    continue;
+                }
+                Err(e) => return Err(e),
+            };
+            info!(
+                "[{}/{}] fetched jira filter {}",
+                i + 1,
+                self.filters.len(),
+                repo
+            );
+            let name: Arc<str> = Arc::from(&repo.repo[..]);
+            all.push(RepoIssues {
+                repo: Arc::clone(repo),
+                issues: issues
+                    .into_iter()
+                    .map(|issue| issue.into_issue(&self.base_url, &name))
+                    .collect::<Result<_>>()?,
+            });
+        }
+        Ok(all)
+    }
+
+    // boards are made of JQL filters too, watch them by `jira-filter`
+    async fn list_projects(&self) -> Result<Vec<ProjectIssues>> {
+        Ok(vec![])
+    }
+
+    async fn get_comments(&self, issue: &Issue) -> Result<Vec<Comment>> {
+        let url = format!(
+            "{}/rest/api/2/issue/{}/comment",
+            self.base_url, issue.number
+        );
+        let mut all = vec![];
+        loop {
+            let res = self
+                .request(&url)
+                .query(&[("startAt", all.len()), ("maxResults", PER_PAGE)])
+                .send()
+                .await?;
+            let body = check_status(&url, res)?.text().await?;
+            let page: Comments =
+                serde_json::from_str(&body).map_err(|source| Error::Deserialize {
+                    url: url.clone(),
+                    source,
+                })?;
+            let done =
+                page.comments.is_empty() || all.len() + page.comments.len() >= page.total as usize;
+            all.extend(page.comments.into_iter().map(|c| Comment {
+                html_url: format!("{}?focusedCommentId={}", issue.html_url(), c.id),
+                author_association: "NONE".to_owned(),
+                user: User {
+                    login: c.author.map_or_else(String::new, JiraUser::login),
+                },
+            }));
+            if done {
+                return Ok(all);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn convert_jira_issues() {
+        let page: SearchPage = serde_json::from_str(
+            r#"{
+                "startAt": 0,
+                "maxResults": 50,
+                "total": 1,
+                "issues": [{
+                    "id": "10002",
+                    "key": "TIDB-12",
+                    "fields": {
+                        "summary": "crash on startup",
+                        "status": {"name": "In Progress", "statusCategory": {"key": "indeterminate"}},
+                        "created": "2020-04-01T10:00:00.000+0800",
+                        "updated": "2020-04-02T10:00:00.000+0800",
+                        "reporter": {"displayName": "You06"},
+                        "assignee": null,
+                        "labels": ["bug"],
+                        "fixVersions": [{"id": "3", "name": "v4.0"}],
+                        "comment": {"total": 2, "comments": []}
+                    }
+                }]
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(page.total, 1);
+        let issue = page.issues.into_iter().next().unwrap();
+        let issue = issue
+            .into_issue("https://example.atlassian.net", &Arc::from("bugs"))
+            .unwrap();
+        assert_eq!(issue.number, 10002);
+        assert_eq!(issue.title, "TIDB-12 crash on startup");
+        assert_eq!(
+            issue.to_string(),
+            "https://example.atlassian.net/browse/TIDB-12"
+        );
+        assert_eq!(issue.state(), "open");
+        assert_eq!(issue.milestone(), Some("v4.0"));
+        assert_eq!(issue.comments(), 2);
+        assert_eq!(issue.created_at.to_rfc3339(), "2020-04-01T02:00:00+00:00");
+        assert_eq!(issue.user.unwrap().login, "You06");
+    }
+}
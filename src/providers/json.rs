@@ -0,0 +1,81 @@
+use std::io::{self, Read};
+
+use bytes::Bytes;
+use futures::{
+    channel::mpsc::{channel, Receiver},
+    executor::block_on,
+    SinkExt, StreamExt,
+};
+use serde::de::DeserializeOwned;
+
+// chunks buffered between the response and the parser
+const BUFFERED_CHUNKS: usize = 8;
+
+// ChunkReader reads the chunks of a response body on the parser thread,
+// it ends when the sender is dropped.
+struct ChunkReader {
+    chunks: Receiver<Bytes>,
+    current: Bytes,
+}
+
+impl Read for ChunkReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.current.is_empty() {
+            match block_on(self.chunks.next()) {
+                Some(chunk) => self.current = chunk,
+                None => return Ok(0),
+            }
+        }
+        let n = buf.len().min(self.current.len());
+        buf[..n].copy_from_slice(&self.current.split_to(n));
+        Ok(n)
+    }
+}
+
+/// from_response parses the body while it's received, so a large page is never
+/// held as a whole string and the skipped fields are dropped as they come.
+pub async fn from_response<T>(mut res: reqwest::Response) -> Result<T, serde_json::Error>
+where
+    T: DeserializeOwned + Send + 'static,
+{
+    let (mut sender, receiver) = channel(BUFFERED_CHUNKS);
+    let parser = tokio::task::spawn_blocking(move || {
+        serde_json::from_reader::<_, T>(ChunkReader {
+            chunks: receiver,
+            current: Bytes::new(),
+        })
+    });
+    while let Some(chunk) = res.chunk().await.map_err(io_error)? {
+        // the parser has stopped on invalid JSON, its error is returned below
+        if sender.send(chunk).await.is_err() {
+            break;
+        }
+    }
+    drop(sender);
+    parser.await.map_err(io_error)?
+}
+
+fn io_error(err: impl std::error::Error + Send + Sync + 'static) -> serde_json::Error {
+    serde_json::Error::io(io::Error::new(io::ErrorKind::Other, err))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn read_chunks() {
+        let (mut sender, receiver) = channel(BUFFERED_CHUNKS);
+        for chunk in &["[{\"a\"", ": 1}, {\"a\": ", "2}]"] {
+            sender.try_send(Bytes::from(chunk.to_string())).unwrap();
+        }
+        drop(sender);
+        let reader = ChunkReader {
+            chunks: receiver,
+            current: Bytes::new(),
+        };
+        let values: Vec<serde_json::Value> = serde_json::from_reader(reader).unwrap();
+        assert_eq!(values.len(), 2);
+        assert_eq!(values[1]["a"], 2);
+    }
+}
@@ -1,2 +1,204 @@
+use async_trait::async_trait;
+use chrono::{DateTime, TimeZone, Utc};
+use reqwest::StatusCode;
+
+use github::{Comment, Issue, ProjectIssues, RepoIssues, Snapshot, StaleIssues, StaleRule};
+
+#[cfg(feature = "bitbucket")]
+pub mod bitbucket;
+#[cfg(feature = "gitea")]
+pub mod gitea;
 pub mod github;
+#[cfg(feature = "gitlab")]
+pub mod gitlab;
+#[cfg(feature = "jira")]
+pub mod jira;
+mod json;
+pub mod opsgenie;
 pub mod slack;
+pub mod wecom;
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// Error tells the kind of failure, so callers can tell a missing repo or
+/// an exhausted rate limit from other failures.
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("rate limited until {reset}")]
+    RateLimited { reset: DateTime<Utc> },
+    #[error("{repo} not found or private{}", scope_hint(.missing_scope))]
+    NotFound { repo: String, missing_scope: bool },
+    #[error("bad credentials, the token is invalid, expired or revoked")]
+    Unauthorized,
+    #[error("invalid response of {url}: {source}")]
+    Deserialize {
+        url: String,
+        source: serde_json::Error,
+    },
+    #[error(transparent)]
+    Http(#[from] reqwest::Error),
+    #[error("{0}")]
+    Other(String),
+}
+
+impl From<&str> for Error {
+    fn from(err: &str) -> Self {
+        Error::Other(err.to_owned())
+    }
+}
+
+impl From<String> for Error {
+    fn from(err: String) -> Self {
+        Error::Other(err)
+    }
+}
+
+fn scope_hint(missing_scope: &bool) -> &'static str {
+    if *missing_scope {
+        ", the token lacks the `repo` scope to read private repos"
+    } else {
+        ""
+    }
+}
+
+// check_status maps the failed responses of the providers other than GitHub,
+// the rate limit reset time is in the `RateLimit-Reset` header of GitLab.
+fn check_status(url: &str, res: reqwest::Response) -> Result<reqwest::Response> {
+    match res.status() {
+        StatusCode::UNAUTHORIZED => Err(Error::Unauthorized),
+        StatusCode::NOT_FOUND => Err(Error::NotFound {
+            repo: url.to_owned(),
+            missing_scope: false,
+        }),
+        StatusCode::TOO_MANY_REQUESTS => {
+            let reset = res
+                .headers()
+                .get("RateLimit-Reset")
+                .and_then(|v| v.to_str().ok())
+                .and_then(|v| v.parse().ok())
+                .map_or_else(Utc::now, |reset| Utc.timestamp(reset, 0));
+            Err(Error::RateLimited { reset })
+        }
+        _ => Ok(res.error_for_status()?),
+    }
+}
+
+/// IssueProvider is a backend where issues are watched, the snapshots and
+/// reports only talk to the backends through it.
+#[async_trait]
+pub trait IssueProvider: Send + Sync {
+    /// name is the backend, e.g. "github".
+    fn name(&self) -> &'static str;
+
+    /// list_issues returns the open issues of each watched repo.
+    async fn list_issues(&self) -> Result<Vec<RepoIssues>>;
+
+    /// list_projects returns the columns and cards of each watched project.
+    async fn list_projects(&self) -> Result<Vec<ProjectIssues>>;
+
+    /// get_comments returns all the comments of the issue.
+    async fn get_comments(&self, issue: &Issue) -> Result<Vec<Comment>>;
+
+    /// get_snapshot returns the watched issues and projects at this time.
+    async fn get_snapshot(&self) -> Result<Snapshot> {
+        let time = Utc::now();
+        let repo_issues = self.list_issues().await?;
+        let project_issues = self.list_projects().await?;
+        Ok(Snapshot::new(time, repo_issues, project_issues))
+    }
+
+    /// get_stale_issues returns the issues in the repo without reply from members,
+    /// comments are only fetched for the issues old enough.
+    async fn get_stale_issues(
+        &self,
+        repo_issues: &RepoIssues,
+        rule: &StaleRule,
+    ) -> Result<StaleIssues> {
+        let before = Utc::now() - chrono::Duration::days(rule.days);
+        let mut issues = vec![];
+        for issue in repo_issues.issues() {
+            if !issue.may_be_stale(&before, rule) {
+                continue;
+            }
+            let comments = self.get_comments(issue).await?;
+            if !comments.iter().any(|c| c.by_member(&rule.members)) {
+                issues.push(issue.clone());
+            }
+        }
+        Ok(StaleIssues {
+            repo: repo_issues.name(),
+            days: rule.days,
+            issues,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ISSUES: &str = r#"[{
+        "repo": {"owner": "pingcap", "repo": "tidb"},
+        "issues": [{
+            "number": 1,
+            "title": "no reply",
+            "created_at": "2020-03-01T00:00:00Z",
+            "author_association": "NONE",
+            "labels": []
+        }, {
+            "number": 2,
+            "title": "replied",
+            "created_at": "2020-03-01T00:00:00Z",
+            "author_association": "NONE",
+            "labels": []
+        }]
+    }]"#;
+
+    struct Fake;
+
+    #[async_trait]
+    impl IssueProvider for Fake {
+        fn name(&self) -> &'static str {
+            "fake"
+        }
+
+        async fn list_issues(&self) -> Result<Vec<RepoIssues>> {
+            Ok(serde_json::from_str(ISSUES).unwrap())
+        }
+
+        async fn list_projects(&self) -> Result<Vec<ProjectIssues>> {
+            Ok(vec![])
+        }
+
+        async fn get_comments(&self, issue: &Issue) -> Result<Vec<Comment>> {
+            let association = if issue.number() == 1 {
+                "NONE"
+            } else {
+                "MEMBER"
+            };
+            let comment = serde_json::json!({
+                "html_url": "",
+                "author_association": association,
+                "user": {"login": "someone"}
+            });
+            Ok(vec![serde_json::from_value(comment).unwrap()])
+        }
+    }
+
+    #[tokio::test]
+    async fn provide_stale_issues() {
+        let snapshot = Fake.get_snapshot().await.unwrap();
+        let rule = StaleRule {
+            days: 3,
+            ignore_labels: vec![],
+            members: vec![],
+        };
+        let stale = Fake
+            .get_stale_issues(&snapshot.repo_issues()[0], &rule)
+            .await
+            .unwrap();
+        assert_eq!(stale.repo, "pingcap/tidb");
+        let numbers: Vec<_> = stale.issues.iter().map(|i| i.number()).collect();
+        assert_eq!(numbers, vec![1]);
+    }
+}
@@ -0,0 +1,157 @@
+use std::collections::BTreeMap;
+
+use reqwest::{header::AUTHORIZATION, Method};
+use serde::Serialize;
+
+use super::github::USER_AGENT;
+use super::{check_status, Error, Result};
+use crate::http::Http;
+
+// Opsgenie truncates longer messages
+const MESSAGE_LIMIT: usize = 130;
+const SOURCE: &str = "issues-watcher";
+
+/// Alert is an Opsgenie alert, the alerts of an issue are deduplicated by
+/// their alias.
+#[derive(Serialize, Debug, PartialEq)]
+pub struct Alert {
+    pub message: String,
+    pub alias: String,
+    pub description: String,
+    pub tags: Vec<String>,
+    /// shown as extra properties, like the url of the issue
+    pub details: BTreeMap<String, String>,
+    pub entity: String,
+    pub priority: String,
+    pub source: String,
+}
+
+impl Alert {
+    /// new makes the alert of an issue of `repo`, aliased by the repo and the
+    /// issue number.
+    pub fn new(repo: &str, number: i32, title: &str, url: &str, priority: &str) -> Self {
+        let message: String = format!("{}#{} {}", repo, number, title)
+            .chars()
+            .take(MESSAGE_LIMIT)
+            .collect();
+        let mut details = BTreeMap::new();
+        details.insert("url".to_owned(), url.to_owned());
+        Alert {
+            message,
+            alias: alias(repo, number),
+            description: format!("{}\n{}", title, url),
+            tags: vec![SOURCE.to_owned(), repo.to_owned()],
+            details,
+            entity: repo.to_owned(),
+            priority: priority.to_owned(),
+            source: SOURCE.to_owned(),
+        }
+    }
+}
+
+/// alias identifies the alert of an issue, e.g. "pingcap/tidb#123".
+pub fn alias(repo: &str, number: i32) -> String {
+    format!("{}#{}", repo, number)
+}
+
+#[derive(Serialize)]
+struct Close<'a> {
+    source: &'a str,
+    note: &'a str,
+}
+
+pub struct Opsgenie {
+    // adds the API key and the user agent
+    http: Http,
+    // e.g. https://api.opsgenie.com, or the EU instance
+    base_url: String,
+}
+
+impl Opsgenie {
+    pub fn new(client: reqwest::Client, base_url: &str, api_key: &str) -> Result<Self> {
+        let http = Http::new(client, USER_AGENT)?
+            .header(AUTHORIZATION, &format!("GenieKey {}", api_key))?;
+        Ok(Opsgenie {
+            http,
+            base_url: base_url.trim_end_matches('/').to_owned(),
+        })
+    }
+
+    /// check_key lists the heartbeats, which only checks the API key can read.
+    pub async fn check_key(&self) -> Result<()> {
+        let url = format!("{}/v2/heartbeats", self.base_url);
+        let res = self.http.send(Method::GET, &url, |req| req).await?;
+        check_status(&url, res)?;
+        Ok(())
+    }
+
+    /// create_alert opens the alert, Opsgenie only counts it again if an open
+    /// alert has the same alias.
+    pub async fn create_alert(&self, alert: &Alert) -> Result<()> {
+        let url = format!("{}/v2/alerts", self.base_url);
+        let res = self
+            .http
+            .send(Method::POST, &url, |req| req.json(alert))
+            .await?;
+        check_status(&url, res)?;
+        Ok(())
+    }
+
+    /// close_alert closes the alert with the alias, with the note why. An alert
+    /// which isn't found is closed already.
+    pub async fn close_alert(&self, alias: &str, note: &str) -> Result<()> {
+        let url = format!("{}/v2/alerts/{}/close", self.base_url, encode(alias));
+        let close = Close {
+            source: SOURCE,
+            note,
+        };
+        let res = self
+            .http
+            .send(Method::POST, &url, |req| {
+                req.query(&[("identifierType", "alias")]).json(&close)
+            })
+            .await?;
+        match check_status(&url, res) {
+            Ok(_) | Err(Error::NotFound { .. }) => Ok(()),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+// encode escapes the alias as a path segment, e.g. the '/' and '#' of it.
+fn encode(alias: &str) -> String {
+    alias
+        .bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                (b as char).to_string()
+            }
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn make_alert() {
+        let alert = Alert::new(
+            "pingcap/tidb",
+            123,
+            "panic in the planner",
+            "https://github.com/pingcap/tidb/issues/123",
+            "P3",
+        );
+        assert_eq!(alert.alias, "pingcap/tidb#123");
+        assert_eq!(alert.message, "pingcap/tidb#123 panic in the planner");
+        assert_eq!(
+            alert.details["url"],
+            "https://github.com/pingcap/tidb/issues/123"
+        );
+        assert_eq!(encode(&alert.alias), "pingcap%2Ftidb%23123");
+        let long = Alert::new("pingcap/tidb", 1, &"a".repeat(200), "", "P3");
+        assert_eq!(long.message.chars().count(), MESSAGE_LIMIT);
+    }
+}
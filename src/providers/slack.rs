@@ -1,10 +1,19 @@
 use std::fmt;
 
-use reqwest;
+use reqwest::{
+    self,
+    header::{AUTHORIZATION, CONTENT_TYPE},
+    Method,
+};
 use serde::{Deserialize, Serialize};
 use serde_json;
 
+use super::github::USER_AGENT;
+use crate::http::Http;
+
 const API_BASE_URL: &str = "https://slack.com/api";
+// Slack truncates the text longer than 40000 characters, leave some room
+const MESSAGE_LIMIT: usize = 39000;
 
 pub type Result<T> = std::result::Result<T, Error>;
 
@@ -48,8 +57,8 @@ impl From<String> for Error {
 }
 
 pub struct Slack {
-    token: String,
-    client: reqwest::Client,
+    // adds the token and the user agent
+    http: Http,
 }
 
 struct Header {
@@ -61,46 +70,193 @@ struct Header {
 struct Message {
     text: String,
     channel: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    thread_ts: Option<String>,
+}
+
+/// UserGroup is a Slack user group, mentioned like "@sig-planner".
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct UserGroup {
+    pub id: String,
+    pub handle: String,
+}
+
+impl UserGroup {
+    /// mention notifies the members of the group in a message.
+    pub fn mention(&self) -> String {
+        format!("<!subteam^{}>", self.id)
+    }
+}
+
+#[derive(Deserialize)]
+struct UserGroups {
+    ok: bool,
+    error: Option<String>,
+    #[serde(default)]
+    usergroups: Vec<UserGroup>,
+}
+
+/// Channel is a Slack channel the token can see.
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct Channel {
+    pub id: String,
+    pub name: String,
+    /// whether the user or the bot of the token is in the channel
+    #[serde(default)]
+    pub is_member: bool,
+}
+
+#[derive(Deserialize)]
+struct Channels {
+    ok: bool,
+    error: Option<String>,
+    #[serde(default)]
+    channels: Vec<Channel>,
+    response_metadata: Option<Metadata>,
+}
+
+#[derive(Deserialize)]
+struct Metadata {
+    #[serde(default)]
+    next_cursor: String,
+}
+
+#[derive(Deserialize)]
+struct Auth {
+    ok: bool,
+    error: Option<String>,
+    user: Option<String>,
 }
 
 #[derive(Deserialize, Serialize)]
 struct Response {
     ok: bool,
     error: Option<String>,
+    ts: Option<String>,
 }
 
 impl Slack {
-    pub fn new(token: String) -> Self {
-        let mut auth_header = "Bearer ".to_owned();
-        auth_header.push_str(&token);
-        Slack {
-            token: auth_header,
-            client: reqwest::Client::new(),
-        }
+    pub fn new(client: reqwest::Client, token: String) -> Result<Self> {
+        let http = Http::new(client, USER_AGENT)?
+            .header(AUTHORIZATION, &format!("Bearer {}", token))?
+            .header(CONTENT_TYPE, "application/json")?;
+        Ok(Slack { http })
     }
 
     async fn request(&self, url: &str, headers: Vec<Header>, body: String) -> Result<String> {
-        let mut req = self
-            .client
-            .post(url)
-            .header(reqwest::header::USER_AGENT, "pingbot")
-            .header(reqwest::header::AUTHORIZATION, &self.token[..])
-            .header(reqwest::header::CONTENT_TYPE, "application/json");
-        for header in headers {
-            req = req.header(&header.key[..], &header.value[..]);
-        }
-        let res = req.body(body).send().await?.text().await?;
+        let res = self
+            .http
+            .send(Method::POST, url, |req| {
+                headers
+                    .iter()
+                    .fold(req, |req, header| {
+                        req.header(&header.key[..], &header.value[..])
+                    })
+                    .body(body.clone())
+            })
+            .await?
+            .text()
+            .await?;
         Ok(res)
     }
 
+    /// send_message splits a long text into several messages,
+    /// the rest of them are replies in the thread of the first one.
     pub async fn send_message(&self, channel: String, text: String) -> Result<()> {
+        let mut thread_ts = None;
+        for text in split_message(&text, MESSAGE_LIMIT) {
+            let ts = self
+                .post_message(channel.clone(), text, thread_ts.clone())
+                .await?;
+            if thread_ts.is_none() {
+                thread_ts = ts;
+            }
+        }
+        Ok(())
+    }
+
+    /// user_groups lists the user groups of the workspace, which requires the
+    /// `usergroups:read` scope.
+    pub async fn user_groups(&self) -> Result<Vec<UserGroup>> {
+        let url = format!("{}/{}", API_BASE_URL, "usergroups.list");
+        let res_text = self.request(&url[..], vec![], "{}".to_owned()).await?;
+        let res: UserGroups = serde_json::from_str(&res_text[..])?;
+        match res.ok {
+            true => Ok(res.usergroups),
+            false => match res.error {
+                Some(e) => Err(e.into()),
+                None => Err("unknown error".to_owned().into()),
+            },
+        }
+    }
+
+    /// auth_test returns the user or the bot of the token.
+    pub async fn auth_test(&self) -> Result<String> {
+        let url = format!("{}/{}", API_BASE_URL, "auth.test");
+        let res_text = self.request(&url[..], vec![], "{}".to_owned()).await?;
+        let res: Auth = serde_json::from_str(&res_text[..])?;
+        match res.ok {
+            true => Ok(res.user.unwrap_or_default()),
+            false => match res.error {
+                Some(e) => Err(e.into()),
+                None => Err("unknown error".to_owned().into()),
+            },
+        }
+    }
+
+    /// channels lists the public and private channels the token can see, which
+    /// requires the `channels:read` and `groups:read` scopes.
+    pub async fn channels(&self) -> Result<Vec<Channel>> {
+        let url = format!("{}/{}", API_BASE_URL, "conversations.list");
+        let mut all = vec![];
+        let mut cursor = String::new();
+        loop {
+            let query = [
+                ("types", "public_channel,private_channel"),
+                ("exclude_archived", "true"),
+                ("limit", "1000"),
+                ("cursor", &cursor[..]),
+            ];
+            let res_text = self
+                .http
+                .send(Method::GET, &url, |req| req.query(&query))
+                .await?
+                .text()
+                .await?;
+            let res: Channels = serde_json::from_str(&res_text[..])?;
+            if !res.ok {
+                return match res.error {
+                    Some(e) => Err(e.into()),
+                    None => Err("unknown error".to_owned().into()),
+                };
+            }
+            all.extend(res.channels);
+            cursor = res
+                .response_metadata
+                .map_or_else(String::new, |m| m.next_cursor);
+            if cursor.is_empty() {
+                return Ok(all);
+            }
+        }
+    }
+
+    async fn post_message(
+        &self,
+        channel: String,
+        text: String,
+        thread_ts: Option<String>,
+    ) -> Result<Option<String>> {
         let url = format!("{}/{}", API_BASE_URL, "chat.postMessage");
-        let message = Message { text, channel };
+        let message = Message {
+            text,
+            channel,
+            thread_ts,
+        };
         let body = serde_json::to_string(&message)?;
         let res_text = self.request(&url[..], vec![], body).await?;
         let res: Response = serde_json::from_str(&res_text[..])?;
         match res.ok {
-            true => Ok(()),
+            true => Ok(res.ts),
             false => match res.error {
                 Some(e) => Err(e.into()),
                 None => Err("unknown error".to_owned().into()),
@@ -108,3 +264,75 @@ impl Slack {
         }
     }
 }
+
+// split_message keeps the sections separated by blank lines together if they fit,
+// then the lines, a line longer than the limit is cut at characters.
+pub(super) fn split_message(text: &str, limit: usize) -> Vec<String> {
+    let mut messages = vec![];
+    let mut current = String::new();
+    for section in text.split("\n\n") {
+        let pieces: Vec<String> = if section.chars().count() <= limit {
+            vec![section.to_owned()]
+        } else {
+            section
+                .lines()
+                .flat_map(|line| {
+                    let chars: Vec<char> = line.chars().collect();
+                    chars
+                        .chunks(limit)
+                        .map(|chunk| chunk.iter().collect())
+                        .collect::<Vec<String>>()
+                })
+                .collect()
+        };
+        for (i, piece) in pieces.into_iter().enumerate() {
+            let separator = if i == 0 { "\n\n" } else { "\n" };
+            if current.is_empty() {
+                current = piece;
+            } else if current.chars().count() + separator.len() + piece.chars().count() <= limit {
+                current.push_str(separator);
+                current.push_str(&piece);
+            } else {
+                messages.push(std::mem::replace(&mut current, piece));
+            }
+        }
+    }
+    if !current.is_empty() || messages.is_empty() {
+        messages.push(current);
+    }
+    messages
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn split_long_message() {
+        assert_eq!(split_message("short", 10), vec!["short"]);
+        assert_eq!(
+            split_message("aaaa\n\nbbbb\n\ncccc", 10),
+            vec!["aaaa\n\nbbbb", "cccc"]
+        );
+        assert_eq!(
+            split_message("aaaa\nbbbb\ncccc", 10),
+            vec!["aaaa\nbbbb", "cccc"]
+        );
+        assert_eq!(
+            split_message("abcdefghijkl", 5),
+            vec!["abcde", "fghij", "kl"]
+        );
+    }
+
+    #[test]
+    fn parse_user_groups() {
+        let res: UserGroups = serde_json::from_str(
+            r#"{"ok": true, "usergroups": [
+                {"id": "S0614TZR7", "team_id": "T060RNRCH", "handle": "sig-planner", "name": "SIG Planner"}
+            ]}"#,
+        )
+        .unwrap();
+        assert_eq!(res.usergroups[0].handle, "sig-planner");
+        assert_eq!(res.usergroups[0].mention(), "<!subteam^S0614TZR7>");
+    }
+}
@@ -0,0 +1,141 @@
+use reqwest::Method;
+use serde::{Deserialize, Serialize};
+
+use super::github::USER_AGENT;
+use super::slack::split_message;
+use super::{check_status, Error, Result};
+use crate::http::{redact, Http};
+
+// markdown messages are at most 4096 bytes, a Chinese character takes 3
+const MESSAGE_LIMIT: usize = 1365;
+// the errcode of a webhook with a wrong key
+const INVALID_WEBHOOK: i64 = 93000;
+
+#[derive(Serialize)]
+struct Message<'a> {
+    msgtype: &'static str,
+    markdown: Markdown<'a>,
+}
+
+#[derive(Serialize)]
+struct Markdown<'a> {
+    content: &'a str,
+}
+
+#[derive(Deserialize)]
+struct Response {
+    errcode: i64,
+    #[serde(default)]
+    errmsg: String,
+}
+
+/// WeCom is a group robot of WeCom (WeChat Work), which posts to the group it's
+/// added to by its webhook.
+pub struct WeCom {
+    // adds the user agent, the key is in the webhook
+    http: Http,
+    webhook: String,
+}
+
+impl WeCom {
+    pub fn new(client: reqwest::Client, webhook: &str) -> Result<Self> {
+        Ok(WeCom {
+            http: Http::new(client, USER_AGENT)?,
+            webhook: webhook.to_owned(),
+        })
+    }
+
+    /// check_key uploads nothing to the webhook, which fails for any key but
+    /// tells a wrong one apart, and posts nothing to the group.
+    pub async fn check_key(&self) -> Result<()> {
+        let url = self
+            .webhook
+            .replacen("/webhook/send", "/webhook/upload_media", 1);
+        if url == self.webhook {
+            return Err("the webhook isn't like .../cgi-bin/webhook/send?key=...".into());
+        }
+        let res = self
+            .http
+            .send(Method::POST, &url, |req| req.query(&[("type", "file")]))
+            .await
+            .map_err(without_key)?;
+        let res: Response = check_status("WeCom webhook", res)
+            .map_err(without_key)?
+            .json()
+            .await
+            .map_err(without_key)?;
+        if res.errcode == INVALID_WEBHOOK {
+            return Err(Error::Other(format!(
+                "WeCom error {}: {}",
+                res.errcode, res.errmsg
+            )));
+        }
+        Ok(())
+    }
+
+    /// send_markdown splits a long text into several markdown messages.
+    pub async fn send_markdown(&self, text: &str) -> Result<()> {
+        for content in split_message(text, MESSAGE_LIMIT) {
+            let message = Message {
+                msgtype: "markdown",
+                markdown: Markdown { content: &content },
+            };
+            let res = self
+                .http
+                .send(Method::POST, &self.webhook, |req| req.json(&message))
+                .await
+                .map_err(without_key)?;
+            // the key of the webhook is a secret
+            let res: Response = check_status("WeCom webhook", res)
+                .map_err(without_key)?
+                .json()
+                .await
+                .map_err(without_key)?;
+            if res.errcode != 0 {
+                return Err(Error::Other(format!(
+                    "WeCom error {}: {}",
+                    res.errcode, res.errmsg
+                )));
+            }
+        }
+        Ok(())
+    }
+}
+
+// without_key drops the query of the url from an HTTP error, the key of the
+// webhook is in it.
+fn without_key(e: impl Into<Error>) -> Error {
+    match e.into() {
+        Error::Http(e) => {
+            let text = e.to_string();
+            match e.url() {
+                Some(url) => Error::Other(text.replace(url.as_str(), redact(url.as_str()))),
+                None => Error::Other(text),
+            }
+        }
+        e => e,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn markdown_message() {
+        let message = Message {
+            msgtype: "markdown",
+            markdown: Markdown {
+                content: "**pingcap/tidb** 2 stale issues",
+            },
+        };
+        assert_eq!(
+            serde_json::to_string(&message).unwrap(),
+            r#"{"msgtype":"markdown","markdown":{"content":"**pingcap/tidb** 2 stale issues"}}"#
+        );
+        let res: Response =
+            serde_json::from_str(r#"{"errcode": 93000, "errmsg": "invalid webhook url"}"#).unwrap();
+        assert_eq!(res.errcode, INVALID_WEBHOOK);
+        assert_eq!(res.errmsg, "invalid webhook url");
+    }
+}
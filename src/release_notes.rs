@@ -0,0 +1,82 @@
+use crate::i18n::{fill, Messages};
+use crate::providers::github::Issue;
+
+/// render groups the closed issues and pull requests by the first of `groups`
+/// they are labeled with, the others go to the last section, and renders a
+/// Markdown draft of the release notes. Empty sections are left out.
+pub fn render(messages: &Messages, since: &str, groups: &[String], issues: &[Issue]) -> String {
+    let mut sections: Vec<(&str, Vec<&Issue>)> = groups
+        .iter()
+        .map(|group| (&group[..], vec![]))
+        .chain(std::iter::once((messages.release_notes_other, vec![])))
+        .collect();
+    for issue in issues {
+        let i = groups
+            .iter()
+            .position(|group| issue.labels().any(|label| label == group))
+            .unwrap_or(groups.len());
+        sections[i].1.push(issue);
+    }
+    let mut text = format!("# {}\n", fill(messages.release_notes_title, &[&since]));
+    for (title, issues) in sections.iter().filter(|(_, issues)| !issues.is_empty()) {
+        text.push_str(&format!("\n## {}\n\n", title));
+        for issue in issues {
+            text.push_str(&format!(
+                "- {} ([#{}]({}))\n",
+                issue.title(),
+                issue.number(),
+                issue
+            ));
+        }
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::i18n::Locale;
+
+    #[test]
+    fn render_release_notes() {
+        let issues: Vec<Issue> = serde_json::from_str(
+            r#"[{
+                "number": 1,
+                "title": "fix a panic",
+                "html_url": "https://github.com/pingcap/parser/issues/1",
+                "created_at": "2020-05-01T00:00:00Z",
+                "author_association": "NONE",
+                "labels": [{"id": 1, "name": "type/bug", "description": null}]
+            }, {
+                "number": 2,
+                "title": "support window functions",
+                "html_url": "https://github.com/pingcap/parser/pull/2",
+                "created_at": "2020-05-01T00:00:00Z",
+                "author_association": "MEMBER",
+                "labels": [
+                    {"id": 1, "name": "type/bug", "description": null},
+                    {"id": 2, "name": "type/feature", "description": null}
+                ]
+            }, {
+                "number": 3,
+                "title": "update docs",
+                "html_url": "https://github.com/pingcap/parser/pull/3",
+                "created_at": "2020-05-01T00:00:00Z",
+                "author_association": "MEMBER",
+                "labels": []
+            }]"#,
+        )
+        .unwrap();
+        let groups = vec!["type/feature".to_owned(), "type/bug".to_owned()];
+        assert_eq!(
+            render(Locale::En.messages(), "v4.0.0", &groups, &issues),
+            "# Release notes since v4.0.0\n\
+             \n## type/feature\n\n\
+             - support window functions ([#2](https://github.com/pingcap/parser/pull/2))\n\
+             \n## type/bug\n\n\
+             - fix a panic ([#1](https://github.com/pingcap/parser/issues/1))\n\
+             \n## Other\n\n\
+             - update docs ([#3](https://github.com/pingcap/parser/pull/3))\n"
+        );
+    }
+}
@@ -0,0 +1,923 @@
+use std::collections::BTreeMap;
+use std::fs;
+
+use chrono::{DateTime, Duration, SecondsFormat, Utc};
+use chrono_tz::Tz;
+use crossterm::style::{Color, Stylize};
+use handlebars::{no_escape, Handlebars};
+use serde::Serialize;
+
+use crate::ages::AgeHistogram;
+use crate::board::{CardTransition, WipViolation};
+use crate::checks::FailingChecks;
+use crate::config::Config;
+use crate::conflicts::ConflictedPulls;
+use crate::drafts::AgingDrafts;
+use crate::filter::QueryIssues;
+use crate::i18n::{fill, Messages};
+use crate::label_stats::LabelGroupStats;
+use crate::needs_info::NeedsInfo;
+use crate::providers::github::{Issue, RepoDiff, Snapshot, StaleIssues};
+use crate::Result;
+
+const TEXT_TEMPLATE: &str = include_str!("report/text.hbs");
+const MARKDOWN_TEMPLATE: &str = include_str!("report/markdown.hbs");
+const HTML_TEMPLATE: &str = include_str!("report/html.hbs");
+const DIGEST_TEMPLATE: &str = include_str!("report/digest.hbs");
+const ATOM_TEMPLATE: &str = include_str!("report/atom.hbs");
+
+/// Templates are the handlebars templates of the reports, the values they can
+/// use are listed in the README.
+#[derive(Clone)]
+pub struct Templates {
+    pub text: String,
+    pub markdown: String,
+    pub html: String,
+    pub digest: String,
+    pub atom: String,
+}
+
+impl Default for Templates {
+    fn default() -> Self {
+        Templates {
+            text: TEXT_TEMPLATE.to_owned(),
+            markdown: MARKDOWN_TEMPLATE.to_owned(),
+            html: HTML_TEMPLATE.to_owned(),
+            digest: DIGEST_TEMPLATE.to_owned(),
+            atom: ATOM_TEMPLATE.to_owned(),
+        }
+    }
+}
+
+impl Templates {
+    /// from_config reads the templates set by `text-template`, `markdown-template`,
+    /// `html-template`, `digest-template` and `atom-template`, the built-in ones
+    /// are used for the others.
+    pub fn from_config(conf: &Config) -> Result<Templates> {
+        Ok(Templates {
+            text: read_template(&conf.text_template, TEXT_TEMPLATE)?,
+            markdown: read_template(&conf.markdown_template, MARKDOWN_TEMPLATE)?,
+            html: read_template(&conf.html_template, HTML_TEMPLATE)?,
+            digest: read_template(&conf.digest_template, DIGEST_TEMPLATE)?,
+            atom: read_template(&conf.atom_template, ATOM_TEMPLATE)?,
+        })
+    }
+
+    /// for_notifier replaces the text and digest templates by the ones of the
+    /// `[notifier-template]` of the notifier, e.g. "slack", if they're set.
+    pub fn for_notifier(&self, conf: &Config, notifier: &str) -> Result<Templates> {
+        let mut templates = self.clone();
+        if let Some(template) = conf.notifier_templates.get(notifier) {
+            templates.text = read_template(&template.report_template, &self.text)?;
+            templates.digest = read_template(&template.digest_template, &self.digest)?;
+        }
+        Ok(templates)
+    }
+}
+
+fn read_template(path: &Option<String>, default: &str) -> Result<String> {
+    match path {
+        Some(path) => Ok(fs::read_to_string(path).map_err(|e| format!("read {}: {}", path, e))?),
+        None => Ok(default.to_owned()),
+    }
+}
+
+// Context is what the templates see, the messages are filled already.
+#[derive(Serialize)]
+struct Context {
+    title: String,
+    summary: Vec<String>,
+    wip: Vec<String>,
+    changes: Vec<Changes>,
+    board: Vec<Board>,
+    stale: Vec<Stale>,
+    conflicts: Vec<Stale>,
+    checks: Vec<Checks>,
+    drafts: Vec<Stale>,
+    needs_info: Vec<Checks>,
+    ages_title: String,
+    ages: Vec<String>,
+    labels_title: String,
+    labels: Vec<String>,
+    queries: Vec<Stale>,
+    repos: Vec<RepoTable>,
+}
+
+#[derive(Serialize)]
+struct Changes {
+    title: String,
+    opened: Vec<IssueContext>,
+    closed: Vec<IssueContext>,
+}
+
+#[derive(Serialize)]
+struct Board {
+    title: String,
+    moves: Vec<String>,
+}
+
+#[derive(Serialize)]
+struct Stale {
+    title: String,
+    issues: Vec<IssueContext>,
+}
+
+#[derive(Serialize)]
+struct Checks {
+    title: String,
+    pulls: Vec<PullChecks>,
+}
+
+#[derive(Serialize)]
+struct PullChecks {
+    title: String,
+    url: String,
+    // the failed checks, and whether it's approved, or the missing sections
+    status: String,
+}
+
+#[derive(Serialize)]
+struct RepoTable {
+    summary: String,
+    columns: Columns,
+    issues: Vec<IssueContext>,
+}
+
+#[derive(Serialize, Clone, Copy)]
+struct Columns {
+    title: &'static str,
+    age: &'static str,
+    labels: &'static str,
+}
+
+#[derive(Serialize)]
+struct Feed {
+    title: &'static str,
+    updated: String,
+    entries: Vec<Entry>,
+}
+
+#[derive(Serialize)]
+struct Entry {
+    title: String,
+    url: String,
+    // when the issue became stale
+    updated: String,
+    summary: String,
+}
+
+#[derive(Serialize)]
+pub(crate) struct IssueContext {
+    number: i32,
+    title: String,
+    url: String,
+    // days since created, at the time of the snapshot
+    age: i64,
+    pull: bool,
+    labels: Vec<String>,
+    // fields of the issue form
+    fields: BTreeMap<String, String>,
+}
+
+/// Report is a snapshot with the changes since the previous snapshot
+/// and the stale issues, the diffs are empty if there is nothing to compare.
+#[derive(Clone)]
+pub struct Report {
+    pub snapshot: Snapshot,
+    pub diffs: Vec<RepoDiff>,
+    pub stale: Vec<StaleIssues>,
+    /// columns over their `wip-limit`
+    pub wip: Vec<WipViolation>,
+    /// cards moved to another column since the previous snapshot
+    pub transitions: Vec<CardTransition>,
+    /// long open pull requests with merge conflicts
+    pub conflicts: Vec<ConflictedPulls>,
+    /// open pull requests failing their checks
+    pub checks: Vec<FailingChecks>,
+    /// draft pull requests open for more than `draft-days`
+    pub drafts: Vec<AgingDrafts>,
+    /// new issues missing the sections of the issue templates
+    pub needs_info: Vec<NeedsInfo>,
+    /// open issues by age, if `age-histogram` is set
+    pub ages: Vec<AgeHistogram>,
+    /// counts of the `label-groups`
+    pub labels: Vec<LabelGroupStats>,
+    /// issues of the saved `queries`
+    pub queries: Vec<QueryIssues>,
+}
+
+impl Report {
+    /// new makes the report of the snapshot with every section empty.
+    pub fn new(snapshot: Snapshot) -> Self {
+        Report {
+            snapshot,
+            diffs: vec![],
+            stale: vec![],
+            wip: vec![],
+            transitions: vec![],
+            conflicts: vec![],
+            checks: vec![],
+            drafts: vec![],
+            needs_info: vec![],
+            ages: vec![],
+            labels: vec![],
+            queries: vec![],
+        }
+    }
+
+    /// retain_repos keeps the sections of the repos matching `f`, the board
+    /// isn't of a repo and is kept.
+    pub fn retain_repos(&mut self, f: impl Fn(&str) -> bool) {
+        self.snapshot.retain_repos(&f);
+        self.diffs.retain(|diff| f(&diff.repo));
+        self.stale.retain(|stale| f(&stale.repo));
+        self.conflicts.retain(|conflicts| f(&conflicts.repo));
+        self.checks.retain(|checks| f(&checks.repo));
+        self.drafts.retain(|drafts| f(&drafts.repo));
+        self.needs_info.retain(|needs_info| f(&needs_info.repo));
+        self.ages.retain(|ages| f(&ages.repo));
+        self.labels.retain(|labels| f(&labels.repo));
+        for query in self.queries.iter_mut() {
+            query.issues.retain(|issue| f(&issue.repo_name()));
+        }
+    }
+
+    pub fn stale_count(&self) -> usize {
+        self.stale.iter().map(|s| s.issues.len()).sum()
+    }
+
+    /// issue_urls returns the urls of the opened, closed and stale issues in
+    /// the report.
+    pub fn issue_urls(&self) -> Vec<String> {
+        let mut urls: Vec<String> = Vec::new();
+        let diffs = self
+            .diffs
+            .iter()
+            .flat_map(|d| d.opened.iter().chain(&d.closed));
+        let stale = self.stale.iter().flat_map(|s| &s.issues);
+        for issue in diffs.chain(stale) {
+            let url = issue.to_string();
+            if !urls.contains(&url) {
+                urls.push(url);
+            }
+        }
+        urls
+    }
+
+    /// render builds the plain text report, which is sent to Slack.
+    pub fn render(&self, templates: &Templates, messages: &Messages, tz: &Tz) -> Result<String> {
+        self.render_template(&templates.text, no_escape, messages, tz)
+    }
+
+    /// render_markdown builds the report in GitHub flavored Markdown.
+    pub fn render_markdown(
+        &self,
+        templates: &Templates,
+        messages: &Messages,
+        tz: &Tz,
+    ) -> Result<String> {
+        self.render_template(&templates.markdown, escape_markdown, messages, tz)
+    }
+
+    /// render_html builds a standalone page, the tables are sorted by clicking
+    /// the headers.
+    pub fn render_html(
+        &self,
+        templates: &Templates,
+        messages: &Messages,
+        tz: &Tz,
+    ) -> Result<String> {
+        self.render_template(&templates.html, escape_html, messages, tz)
+    }
+
+    /// render_atom builds an Atom feed of the stale issues. Each entry is updated
+    /// when the issue became stale, and identified by its url, so feed readers
+    /// only show the newly stale ones.
+    pub fn render_atom(&self, templates: &Templates, messages: &Messages) -> Result<String> {
+        let rfc3339 = |time: &DateTime<Utc>| time.to_rfc3339_opts(SecondsFormat::Secs, true);
+        let mut entries: Vec<(DateTime<Utc>, Entry)> = self
+            .stale_repos()
+            .flat_map(|repo| {
+                repo.issues.iter().map(move |issue| {
+                    let stale_at = *issue.created_at() + Duration::days(repo.days);
+                    let entry = Entry {
+                        title: issue.title().to_owned(),
+                        url: issue.to_string(),
+                        updated: rfc3339(&stale_at),
+                        summary: fill(messages.feed_entry, &[&repo.repo, &repo.days]),
+                    };
+                    (stale_at, entry)
+                })
+            })
+            .collect();
+        entries.sort_by(|a, b| b.0.cmp(&a.0));
+        let feed = Feed {
+            title: messages.feed_title,
+            updated: rfc3339(self.snapshot.time()),
+            entries: entries.into_iter().map(|(_, entry)| entry).collect(),
+        };
+        render_template(&templates.atom, escape_html, &feed)
+    }
+
+    /// render_link builds a short text report with the totals and a link to
+    /// the full report, for reports too long to read in chat.
+    pub fn render_link(&self, messages: &Messages, tz: &Tz, url: &str) -> String {
+        let opened: usize = self.diffs.iter().map(|d| d.opened.len()).sum();
+        let closed: usize = self.diffs.iter().map(|d| d.closed.len()).sum();
+        let repos = self.snapshot.repo_issues().len();
+        format!(
+            "{}\n{}\n{}\n",
+            fill(
+                messages.snapshot_time,
+                &[&self.snapshot.time().with_timezone(tz)]
+            ),
+            fill(
+                messages.report_totals,
+                &[&repos, &opened, &closed, &self.stale_count()]
+            ),
+            fill(messages.full_report, &[&url]),
+        )
+    }
+
+    fn render_template(
+        &self,
+        template: &str,
+        escape: fn(&str) -> String,
+        messages: &Messages,
+        tz: &Tz,
+    ) -> Result<String> {
+        render_template(template, escape, &self.context(messages, tz))
+    }
+
+    fn context(&self, messages: &Messages, tz: &Tz) -> Context {
+        let issue = |issue: &Issue| IssueContext::new(issue, self.snapshot.time());
+        let summary = self.summary(messages);
+        let columns = Columns {
+            title: messages.column_title,
+            age: messages.column_age,
+            labels: messages.column_labels,
+        };
+        Context {
+            title: fill(
+                messages.snapshot_time,
+                &[&self.snapshot.time().with_timezone(tz)],
+            ),
+            changes: self
+                .changed_repos()
+                .map(|diff| Changes {
+                    title: fill(
+                        messages.repo_changes,
+                        &[&diff.repo, &diff.opened.len(), &diff.closed.len()],
+                    ),
+                    opened: diff.opened.iter().map(issue).collect(),
+                    closed: diff.closed.iter().map(issue).collect(),
+                })
+                .collect(),
+            board: self.board(messages),
+            stale: self
+                .stale_repos()
+                .map(|repo| Stale {
+                    title: fill(
+                        messages.stale_issues,
+                        &[&repo.repo, &repo.issues.len(), &repo.days],
+                    ),
+                    issues: repo.issues.iter().map(issue).collect(),
+                })
+                .collect(),
+            conflicts: self
+                .conflicts
+                .iter()
+                .map(|repo| Stale {
+                    title: fill(
+                        messages.merge_conflicts,
+                        &[&repo.repo, &repo.issues.len(), &repo.days],
+                    ),
+                    issues: repo.issues.iter().map(issue).collect(),
+                })
+                .collect(),
+            checks: self
+                .checks
+                .iter()
+                .map(|repo| Checks {
+                    title: fill(messages.failing_checks, &[&repo.repo, &repo.pulls.len()]),
+                    pulls: repo
+                        .pulls
+                        .iter()
+                        .map(|pull| PullChecks {
+                            title: pull.issue.title().to_owned(),
+                            url: pull.issue.to_string(),
+                            status: fill(
+                                if pull.approved {
+                                    messages.checks_approved
+                                } else {
+                                    messages.checks_failed
+                                },
+                                &[&pull.checks.join(", ")],
+                            ),
+                        })
+                        .collect(),
+                })
+                .collect(),
+            drafts: self
+                .drafts
+                .iter()
+                .map(|repo| Stale {
+                    title: fill(
+                        messages.aging_drafts,
+                        &[&repo.repo, &repo.issues.len(), &repo.days],
+                    ),
+                    issues: repo.issues.iter().map(issue).collect(),
+                })
+                .collect(),
+            needs_info: self
+                .needs_info
+                .iter()
+                .map(|repo| Checks {
+                    title: fill(
+                        messages.needs_info,
+                        &[&repo.repo, &repo.issues.len(), &repo.days],
+                    ),
+                    pulls: repo
+                        .issues
+                        .iter()
+                        .map(|incomplete| PullChecks {
+                            title: incomplete.issue.title().to_owned(),
+                            url: incomplete.issue.to_string(),
+                            status: fill(
+                                messages.needs_info_missing,
+                                &[&incomplete.missing.join(", ")],
+                            ),
+                        })
+                        .collect(),
+                })
+                .collect(),
+            ages_title: messages.age_histogram.to_owned(),
+            ages: self
+                .ages
+                .iter()
+                .map(|h| {
+                    let [new, recent, old, older] = h.counts;
+                    fill(
+                        messages.age_buckets,
+                        &[&h.repo, &new, &recent, &old, &older],
+                    )
+                })
+                .collect(),
+            labels_title: messages.label_stats.to_owned(),
+            labels: self.labels.iter().map(LabelGroupStats::render).collect(),
+            queries: self
+                .queries
+                .iter()
+                .map(|query| Stale {
+                    title: fill(messages.query_issues, &[&query.name, &query.issues.len()]),
+                    issues: query.issues.iter().map(issue).collect(),
+                })
+                .collect(),
+            repos: self
+                .snapshot
+                .repo_issues()
+                .iter()
+                .zip(summary.iter())
+                .map(|(repo, summary)| RepoTable {
+                    summary: summary.clone(),
+                    columns,
+                    issues: repo.issues().iter().map(issue).collect(),
+                })
+                .collect(),
+            summary,
+            wip: self
+                .wip
+                .iter()
+                .map(|v| {
+                    fill(
+                        messages.wip_violation,
+                        &[&v.project, &v.column, &v.cards, &v.max],
+                    )
+                })
+                .collect(),
+        }
+    }
+
+    // board groups the card transitions by project.
+    fn board(&self, messages: &Messages) -> Vec<Board> {
+        let mut projects: Vec<&str> = self.transitions.iter().map(|t| &t.project[..]).collect();
+        projects.dedup();
+        projects
+            .into_iter()
+            .map(|project| {
+                let moves: Vec<String> = self
+                    .transitions
+                    .iter()
+                    .filter(|t| t.project == project)
+                    .map(|t| fill(messages.card_moved, &[&t.card, &t.from, &t.to]))
+                    .collect();
+                Board {
+                    title: fill(messages.board_moves, &[&project, &moves.len()]),
+                    moves,
+                }
+            })
+            .collect()
+    }
+
+    /// render_terminal builds the report for reading in a terminal, issues are
+    /// grouped by repo and the oldest come first, colors are optional.
+    pub fn render_terminal(&self, messages: &Messages, tz: &Tz, color: bool) -> String {
+        let painter = Painter { color };
+        let mut report = painter.bold(&fill(
+            messages.snapshot_time,
+            &[&self.snapshot.time().with_timezone(tz)],
+        ));
+        report.push('\n');
+        for (repo, summary) in self
+            .snapshot
+            .repo_issues()
+            .iter()
+            .zip(self.summary(messages))
+        {
+            report.push('\n');
+            report.push_str(&painter.bold(&summary));
+            report.push('\n');
+            let mut issues: Vec<_> = repo.issues().iter().collect();
+            issues.sort_by_key(|issue| *issue.created_at());
+            for issue in issues {
+                let age = (*self.snapshot.time() - *issue.created_at()).num_days();
+                report.push_str(&format!(
+                    "  {} {} ",
+                    painter.paint(&format!("#{:<6}", issue.number()), Color::Cyan),
+                    painter.paint(&format!("{:>4}d", age), Color::Yellow),
+                ));
+                if issue.is_pull() {
+                    report.push_str(&painter.paint("PR ", Color::Green));
+                }
+                report.push_str(issue.title());
+                for label in issue.labels() {
+                    report.push(' ');
+                    report.push_str(&painter.chip(label));
+                }
+                report.push('\n');
+            }
+        }
+        report
+    }
+
+    /// github_annotations are workflow commands which show stale issues
+    /// as warnings in GitHub Actions.
+    pub fn github_annotations(&self, messages: &Messages) -> Vec<String> {
+        let mut annotations = vec![];
+        for repo in self.stale_repos() {
+            let title = fill(
+                messages.stale_issues,
+                &[&repo.repo, &repo.issues.len(), &repo.days],
+            );
+            for issue in &repo.issues {
+                annotations.push(format!(
+                    "::warning title={}::{}",
+                    escape_property(&title),
+                    escape_data(&format!("{} {}", issue.title(), issue))
+                ));
+            }
+        }
+        annotations
+    }
+
+    fn summary(&self, messages: &Messages) -> Vec<String> {
+        self.snapshot
+            .repo_issues()
+            .iter()
+            .map(|repo| {
+                let pulls = repo.issues().iter().filter(|i| i.is_pull()).count();
+                let issues = repo.issues().len() - pulls;
+                fill(messages.repo_summary, &[&repo.name(), &issues, &pulls])
+            })
+            .collect()
+    }
+
+    fn changed_repos(&self) -> impl Iterator<Item = &RepoDiff> {
+        self.diffs
+            .iter()
+            .filter(|diff| !diff.opened.is_empty() || !diff.closed.is_empty())
+    }
+
+    fn stale_repos(&self) -> impl Iterator<Item = &StaleIssues> {
+        self.stale.iter().filter(|repo| !repo.issues.is_empty())
+    }
+}
+
+impl IssueContext {
+    pub(crate) fn new(issue: &Issue, now: &DateTime<Utc>) -> IssueContext {
+        IssueContext {
+            number: issue.number(),
+            title: issue.title().to_owned(),
+            url: issue.to_string(),
+            age: (*now - *issue.created_at()).num_days(),
+            pull: issue.is_pull(),
+            labels: issue.labels().map(str::to_owned).collect(),
+            fields: issue.fields().clone(),
+        }
+    }
+}
+
+// render_template renders a template with the values escaped by `escape`.
+pub(crate) fn render_template<T: Serialize>(
+    template: &str,
+    escape: fn(&str) -> String,
+    context: &T,
+) -> Result<String> {
+    let mut handlebars = Handlebars::new();
+    handlebars.register_escape_fn(escape);
+    Ok(handlebars.render_template(template, context)?)
+}
+
+// Painter adds ANSI colors to text if color is enabled.
+struct Painter {
+    color: bool,
+}
+
+impl Painter {
+    fn paint(&self, text: &str, color: Color) -> String {
+        if self.color {
+            text.with(color).to_string()
+        } else {
+            text.to_owned()
+        }
+    }
+
+    fn bold(&self, text: &str) -> String {
+        if self.color {
+            text.bold().to_string()
+        } else {
+            text.to_owned()
+        }
+    }
+
+    fn chip(&self, label: &str) -> String {
+        if self.color {
+            format!(" {} ", label).black().on(Color::Grey).to_string()
+        } else {
+            format!("[{}]", label)
+        }
+    }
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn escape_markdown(text: &str) -> String {
+    text.replace('[', "\\[").replace(']', "\\]")
+}
+
+fn escape_data(text: &str) -> String {
+    text.replace('%', "%25")
+        .replace('\r', "%0D")
+        .replace('\n', "%0A")
+}
+
+fn escape_property(text: &str) -> String {
+    escape_data(text).replace(':', "%3A").replace(',', "%2C")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::i18n::Locale;
+
+    fn new_report() -> Report {
+        let snapshot: Snapshot = serde_json::from_str(
+            r#"{
+                "time": "2020-05-01T00:00:00Z",
+                "repo_issues": [{
+                    "repo": {"owner": "pingcap", "repo": "parser"},
+                    "issues": [{
+                        "number": 1,
+                        "title": "issue",
+                        "created_at": "2020-04-01T00:00:00Z",
+                        "owner": "pingcap",
+                        "repo": "parser",
+                        "author_association": "NONE",
+                        "labels": [{"id": 1, "name": "bug", "description": null}]
+                    }, {
+                        "number": 2,
+                        "title": "pull",
+                        "pull_request": {"html_url": "https://github.com/pingcap/parser/pull/2"},
+                        "created_at": "2020-04-01T00:00:00Z",
+                        "owner": "pingcap",
+                        "repo": "parser",
+                        "author_association": "MEMBER",
+                        "labels": []
+                    }]
+                }],
+                "project_issues": []
+            }"#,
+        )
+        .unwrap();
+        Report::new(snapshot)
+    }
+
+    #[test]
+    fn render_report() {
+        let mut report = new_report();
+        report.transitions = vec![CardTransition {
+            project: "pingcap/parser project 1".to_owned(),
+            card: "pingcap/parser/issues/1".to_owned(),
+            from: "To Do".to_owned(),
+            to: "In Progress".to_owned(),
+        }];
+        let report = report
+            .render(
+                &Templates::default(),
+                Locale::En.messages(),
+                &Tz::Asia__Shanghai,
+            )
+            .unwrap();
+        assert_eq!(
+            report,
+            "Snapshot at 2020-05-01 08:00:00 CST\n\
+             pingcap/parser: 1 open issues, 1 open pull requests\n\
+             \n\
+             pingcap/parser project 1: 1 cards moved\n\
+             pingcap/parser/issues/1 moved To Do → In Progress\n"
+        );
+
+        let link =
+            new_report().render_link(Locale::En.messages(), &Tz::UTC, "https://gist.github.com/1");
+        assert_eq!(
+            link,
+            "Snapshot at 2020-05-01 00:00:00 UTC\n\
+             1 repos: 0 opened, 0 closed, 0 issues without reply\n\
+             Full report: https://gist.github.com/1\n"
+        );
+    }
+
+    #[test]
+    fn render_markdown_report() {
+        let mut report = new_report();
+        report.wip = vec![WipViolation {
+            project: "pingcap/parser project 1".to_owned(),
+            column: "In Progress".to_owned(),
+            cards: 3,
+            max: 2,
+        }];
+        let pulls = report.snapshot.repo_issues()[0].issues()[1..].to_vec();
+        report.conflicts = vec![ConflictedPulls {
+            repo: "pingcap/parser".to_owned(),
+            days: 14,
+            issues: pulls,
+        }];
+        report.ages = vec![AgeHistogram {
+            repo: "pingcap/parser".to_owned(),
+            counts: [1, 0, 0, 2],
+        }];
+        let report = report
+            .render_markdown(&Templates::default(), Locale::En.messages(), &Tz::UTC)
+            .unwrap();
+        assert_eq!(
+            report,
+            "## Snapshot at 2020-05-01 00:00:00 UTC\n\n\
+             - pingcap/parser: 1 open issues, 1 open pull requests\n\
+             - **pingcap/parser project 1 In Progress: 3 cards, over the WIP limit of 2**\n\
+             \n\
+             ### Issue ages\n\
+             \n\
+             - pingcap/parser: 1 under 7 days, 0 in 7-30 days, 0 in 30-90 days, 2 over 90 days\n\
+             \n\
+             ### pingcap/parser: 1 pull requests open for more than 14 days have merge conflicts\n\
+             \n\
+             - [pull](https://github.com/pingcap/parser/issues/2)\n"
+        );
+    }
+
+    #[test]
+    fn render_terminal_report() {
+        let report = new_report().render_terminal(Locale::En.messages(), &Tz::UTC, false);
+        assert_eq!(
+            report,
+            "Snapshot at 2020-05-01 00:00:00 UTC\n\n\
+             pingcap/parser: 1 open issues, 1 open pull requests\n  \
+             #1        30d issue [bug]\n  \
+             #2        30d PR pull\n"
+        );
+    }
+
+    #[test]
+    fn render_html_report() {
+        let report = new_report()
+            .render_html(&Templates::default(), Locale::En.messages(), &Tz::UTC)
+            .unwrap();
+        assert!(report.starts_with("<!DOCTYPE html>"));
+        assert!(report.contains("<h1>Snapshot at 2020-05-01 00:00:00 UTC</h1>"));
+        assert!(report.contains(
+            "<tr><td>1</td><td><a href=\"https://github.com/pingcap/parser/issues/1\">issue</a></td>\
+             <td>30</td><td><span class=\"label\">bug</span></td></tr>"
+        ));
+        assert_eq!(escape_html("<a & \"b\">"), "&lt;a &amp; &quot;b&quot;&gt;");
+    }
+
+    #[test]
+    fn render_atom_feed() {
+        let mut report = new_report();
+        let issues = report.snapshot.repo_issues()[0].issues()[..1].to_vec();
+        report.stale = vec![StaleIssues {
+            repo: "pingcap/parser".to_owned(),
+            days: 3,
+            issues,
+        }];
+        let feed = report
+            .render_atom(&Templates::default(), Locale::En.messages())
+            .unwrap();
+        assert!(feed.starts_with("<?xml version=\"1.0\" encoding=\"utf-8\"?>\n"));
+        assert!(feed.contains("<updated>2020-05-01T00:00:00Z</updated>\n"));
+        assert!(feed.contains(
+            "<entry>\n<id>https://github.com/pingcap/parser/issues/1</id>\n<title>issue</title>\n\
+             <link href=\"https://github.com/pingcap/parser/issues/1\"/>\n\
+             <updated>2020-04-04T00:00:00Z</updated>\n\
+             <summary>pingcap/parser: no reply from members in 3 days</summary>\n</entry>\n"
+        ));
+        assert!(feed.ends_with("</feed>\n"));
+        assert_eq!(
+            report.issue_urls(),
+            vec!["https://github.com/pingcap/parser/issues/1"]
+        );
+    }
+
+    #[test]
+    fn render_custom_template() {
+        let mut report = new_report();
+        let issue: Issue = serde_json::from_str(
+            r#"{
+                "number": 1,
+                "title": "[parser] crash",
+                "created_at": "2020-04-01T00:00:00Z",
+                "owner": "pingcap",
+                "repo": "parser",
+                "author_association": "NONE",
+                "labels": []
+            }"#,
+        )
+        .unwrap();
+        report.diffs = vec![RepoDiff {
+            repo: "pingcap/parser".to_owned(),
+            opened: vec![issue],
+            closed: vec![],
+        }];
+        let markdown = report
+            .render_markdown(&Templates::default(), Locale::En.messages(), &Tz::UTC)
+            .unwrap();
+        assert!(markdown.ends_with(
+            "\n### pingcap/parser: 1 opened, 0 closed\n\n\
+             - [\\[parser\\] crash](https://github.com/pingcap/parser/issues/1)\n"
+        ));
+
+        let templates = Templates {
+            text: "{{#each repos}}{{#each issues}}#{{number}} {{age}}d {{/each}}{{/each}}"
+                .to_owned(),
+            ..Templates::default()
+        };
+        let text = report
+            .render(&templates, Locale::En.messages(), &Tz::UTC)
+            .unwrap();
+        assert_eq!(text, "#1 30d #2 30d ");
+
+        let path = std::env::temp_dir().join("issues-watcher-slack-template.hbs");
+        fs::write(&path, "{{title}}").unwrap();
+        let conf: Config = toml::from_str(&format!(
+            "slack-token = \"\"\nslack-channel = \"\"\n\
+             [notifier-template.slack]\nreport-template = {:?}",
+            path
+        ))
+        .unwrap();
+        let render = |notifier: &str| {
+            let templates = templates.for_notifier(&conf, notifier).unwrap();
+            report.render(&templates, Locale::En.messages(), &Tz::UTC)
+        };
+        assert!(render("slack").unwrap().starts_with("Snapshot at"));
+        assert_eq!(render("github").unwrap(), "#1 30d #2 30d ");
+    }
+
+    #[test]
+    fn retain_report_repos() {
+        let mut report = new_report();
+        let issues = report.snapshot.repo_issues()[0].issues()[..1].to_vec();
+        report.stale = vec![StaleIssues {
+            repo: "pingcap/parser".to_owned(),
+            days: 7,
+            issues,
+        }];
+        let mut routed = report.clone();
+        routed.retain_repos(|repo| repo == "pingcap/parser");
+        assert_eq!(routed.stale_count(), 1);
+        assert_eq!(routed.snapshot.repo_issues().len(), 1);
+        report.retain_repos(|repo| repo != "pingcap/parser");
+        assert_eq!(report.stale_count(), 0);
+        assert!(report.snapshot.repo_issues().is_empty());
+    }
+
+    #[test]
+    fn escape_annotation() {
+        assert_eq!(escape_data("100%\nok"), "100%25%0Aok");
+        assert_eq!(escape_property("a: b, c"), "a%3A b%2C c");
+    }
+}
@@ -0,0 +1,216 @@
+use std::{
+    convert::Infallible,
+    net::SocketAddr,
+    sync::{Arc, RwLock},
+};
+
+use chrono::{DateTime, Utc};
+use hyper::{
+    body, header,
+    service::{make_service_fn, service_fn},
+    Body, Method, Request, Response, Server, StatusCode,
+};
+use serde::Serialize;
+use serde_json::json;
+use tracing::{debug, info};
+
+use crate::providers::github::{Snapshot, StaleIssues};
+
+mod grafana;
+
+const JSON: &str = "application/json";
+const ATOM: &str = "application/atom+xml";
+const ICS: &str = "text/calendar";
+// the reports whose stale issues are kept for the trend, a month of reports
+// every 5 minutes
+const STALE_HISTORY: usize = 8640;
+
+// Reply is the status, the content type and the body of a response.
+type Reply = (StatusCode, &'static str, String);
+
+/// State is what the API serves, `serve` updates it after each report.
+#[derive(Default)]
+pub struct State {
+    /// `github-data`, where the trends are read from
+    pub data_dir: String,
+    pub snapshot: Option<Snapshot>,
+    pub stale: Vec<StaleIssues>,
+    /// the number of stale issues found by the latest reports since started
+    pub stale_history: Vec<(DateTime<Utc>, usize)>,
+    /// Atom feed of the stale issues found by the latest report
+    pub feed: Option<String>,
+    /// iCalendar of the milestone due dates and SLA deadlines
+    pub calendar: Option<String>,
+}
+
+impl State {
+    /// push_stale records the number of stale issues found by a report, the
+    /// oldest are dropped after `STALE_HISTORY` reports.
+    pub fn push_stale(&mut self, time: DateTime<Utc>, count: usize) {
+        self.stale_history.push((time, count));
+        if self.stale_history.len() > STALE_HISTORY {
+            let dropped = self.stale_history.len() - STALE_HISTORY;
+            self.stale_history.drain(..dropped);
+        }
+    }
+}
+
+pub type SharedState = Arc<RwLock<State>>;
+
+/// run serves the read-only API on `addr` until the process exits:
+///
+/// - `/api/snapshot` is the latest snapshot
+/// - `/api/repos/:owner/:repo/issues` are the open issues of a repo in it
+/// - `/api/stale` are the stale issues found by the latest report
+/// - `/grafana` is a simple JSON datasource of Grafana
+/// - `/feed.atom` is an Atom feed of the stale issues
+/// - `/calendar.ics` is an iCalendar of the milestone due dates and SLA deadlines
+pub async fn run(addr: SocketAddr, state: SharedState) -> hyper::Result<()> {
+    let make_service = make_service_fn(move |_| {
+        let state = Arc::clone(&state);
+        async move {
+            Ok::<_, Infallible>(service_fn(move |req| {
+                let state = Arc::clone(&state);
+                async move { Ok::<_, Infallible>(handle(req, &state).await) }
+            }))
+        }
+    });
+    let server = Server::try_bind(&addr)?.serve(make_service);
+    info!("serve the API on http://{}", addr);
+    server.await
+}
+
+async fn handle(req: Request<Body>, state: &SharedState) -> Response<Body> {
+    debug!("{} {}", req.method(), req.uri());
+    let (parts, body) = req.into_parts();
+    let (status, content_type, body) = match body::to_bytes(body).await {
+        Ok(body) => {
+            let state = match state.read() {
+                Ok(state) => state,
+                // a panic while updating the state, the old one is still whole
+                Err(poisoned) => poisoned.into_inner(),
+            };
+            route(&parts.method, parts.uri.path(), &body, &state)
+        }
+        Err(e) => error(StatusCode::BAD_REQUEST, &e.to_string()),
+    };
+    Response::builder()
+        .status(status)
+        .header(header::CONTENT_TYPE, content_type)
+        .body(Body::from(body))
+        .unwrap()
+}
+
+fn route(method: &Method, path: &str, body: &[u8], state: &State) -> Reply {
+    let segments: Vec<&str> = path.trim_matches('/').split('/').collect();
+    if let ["grafana", rest @ ..] = &segments[..] {
+        return grafana::route(method.as_str(), rest, body, state);
+    }
+    if method != Method::GET {
+        return error(StatusCode::METHOD_NOT_ALLOWED, "only GET is allowed");
+    }
+    let snapshot = match &state.snapshot {
+        Some(snapshot) => snapshot,
+        None => return error(StatusCode::SERVICE_UNAVAILABLE, "no snapshot yet"),
+    };
+    match &segments[..] {
+        ["api", "snapshot"] => ok(snapshot),
+        ["api", "repos", owner, repo, "issues"] => {
+            let name = format!("{}/{}", owner, repo);
+            match snapshot.repo_issues().iter().find(|r| r.name() == name) {
+                Some(repo) => ok(repo.issues()),
+                None => error(StatusCode::NOT_FOUND, "repo is not watched"),
+            }
+        }
+        ["api", "stale"] => ok(&state.stale),
+        ["feed.atom"] => match &state.feed {
+            Some(feed) => (StatusCode::OK, ATOM, feed.clone()),
+            None => error(StatusCode::SERVICE_UNAVAILABLE, "no report yet"),
+        },
+        ["calendar.ics"] => match &state.calendar {
+            Some(calendar) => (StatusCode::OK, ICS, calendar.clone()),
+            None => error(StatusCode::SERVICE_UNAVAILABLE, "no report yet"),
+        },
+        _ => error(StatusCode::NOT_FOUND, "not found"),
+    }
+}
+
+fn ok<T: Serialize + ?Sized>(value: &T) -> Reply {
+    match serde_json::to_string(value) {
+        Ok(body) => (StatusCode::OK, JSON, body),
+        Err(e) => error(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()),
+    }
+}
+
+fn error(status: StatusCode, message: &str) -> Reply {
+    (status, JSON, json!({ "error": message }).to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::Value;
+
+    #[test]
+    fn route_requests() {
+        let mut state = State::default();
+        let (status, _, _) = route(&Method::GET, "/api/snapshot", b"", &state);
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+
+        state.snapshot = Some(
+            serde_json::from_str(
+                r#"{
+                    "time": "2020-05-01T00:00:00Z",
+                    "repo_issues": [{
+                        "repo": {"owner": "pingcap", "repo": "parser"},
+                        "issues": [{
+                            "number": 1,
+                            "title": "issue",
+                            "created_at": "2020-04-01T00:00:00Z",
+                            "author_association": "NONE",
+                            "labels": []
+                        }]
+                    }],
+                    "project_issues": []
+                }"#,
+            )
+            .unwrap(),
+        );
+        let (status, _, body) = route(&Method::GET, "/api/snapshot", b"", &state);
+        assert_eq!(status, StatusCode::OK);
+        let snapshot: Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(snapshot["time"], "2020-05-01T00:00:00Z");
+
+        let (status, _, body) = route(
+            &Method::GET,
+            "/api/repos/pingcap/parser/issues/",
+            b"",
+            &state,
+        );
+        assert_eq!(status, StatusCode::OK);
+        let issues: Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(issues[0]["number"], 1);
+
+        let (status, _, _) = route(&Method::GET, "/api/repos/pingcap/tidb/issues", b"", &state);
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        let (status, _, body) = route(&Method::GET, "/api/stale", b"", &state);
+        assert_eq!((status, &body[..]), (StatusCode::OK, "[]"));
+        let (status, _, _) = route(&Method::GET, "/feed.atom", b"", &state);
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+        state.feed = Some("<feed/>".to_owned());
+        let reply = route(&Method::GET, "/feed.atom", b"", &state);
+        assert_eq!(reply, (StatusCode::OK, ATOM, "<feed/>".to_owned()));
+        let (status, _, _) = route(&Method::POST, "/api/stale", b"", &state);
+        assert_eq!(status, StatusCode::METHOD_NOT_ALLOWED);
+    }
+
+    #[test]
+    fn cap_stale_history() {
+        let mut state = State::default();
+        for count in 0..STALE_HISTORY + 2 {
+            state.push_stale(Utc::now(), count);
+        }
+        assert_eq!(state.stale_history.len(), STALE_HISTORY);
+        assert_eq!(state.stale_history[0].1, 2);
+    }
+}
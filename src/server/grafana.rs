@@ -0,0 +1,193 @@
+// The simple JSON datasource of Grafana posts to `/search` for the metric names
+// and to `/query` for the time series, and checks the datasource by `/`.
+
+use chrono::{DateTime, Utc};
+use hyper::StatusCode;
+use serde::{Deserialize, Serialize};
+
+use super::{error, ok, Reply, State};
+use crate::providers::github::Snapshot;
+use crate::storage;
+use crate::Result;
+
+const METRICS: &[&str] = &["open_issues", "open_pulls"];
+// only counted by the reports of this process, comments are not stored
+const STALE: &str = "stale";
+
+#[derive(Deserialize)]
+struct SearchRequest {
+    #[serde(default)]
+    target: String,
+}
+
+#[derive(Deserialize)]
+struct QueryRequest {
+    range: Range,
+    targets: Vec<Target>,
+}
+
+#[derive(Deserialize)]
+struct Range {
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+}
+
+#[derive(Deserialize)]
+struct Target {
+    #[serde(default)]
+    target: String,
+}
+
+#[derive(Serialize, Debug, PartialEq)]
+struct TimeSeries {
+    target: String,
+    // pairs of the value and the time in milliseconds
+    datapoints: Vec<(usize, i64)>,
+}
+
+pub(super) fn route(method: &str, segments: &[&str], body: &[u8], state: &State) -> Reply {
+    match (method, segments) {
+        ("GET", []) => ok(&serde_json::json!({})),
+        ("POST", ["search"]) => match serde_json::from_slice(body) {
+            Ok(req) => ok(&search(&req, state)),
+            Err(e) => error(StatusCode::BAD_REQUEST, &e.to_string()),
+        },
+        ("POST", ["query"]) => match serde_json::from_slice(body) {
+            Ok(req) => match query(&req, state) {
+                Ok(series) => ok(&series),
+                Err(e) => error(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()),
+            },
+            Err(e) => error(StatusCode::BAD_REQUEST, &e.to_string()),
+        },
+        _ => error(StatusCode::NOT_FOUND, "not found"),
+    }
+}
+
+// search lists the metrics, and the metrics of each repo like
+// "open_issues:pingcap/parser".
+fn search(req: &SearchRequest, state: &State) -> Vec<String> {
+    let repos: Vec<String> = state.snapshot.as_ref().map_or(vec![], |snapshot| {
+        snapshot.repo_issues().iter().map(|r| r.name()).collect()
+    });
+    METRICS
+        .iter()
+        .map(|m| m.to_string())
+        .chain(std::iter::once(STALE.to_owned()))
+        .chain(
+            METRICS
+                .iter()
+                .flat_map(|m| repos.iter().map(move |r| format!("{}:{}", m, r))),
+        )
+        .filter(|m| m.contains(&req.target))
+        .collect()
+}
+
+// query counts the targets in each stored snapshot in the range.
+fn query(req: &QueryRequest, state: &State) -> Result<Vec<TimeSeries>> {
+    let mut series: Vec<TimeSeries> = req
+        .targets
+        .iter()
+        .map(|t| TimeSeries {
+            target: t.target.clone(),
+            datapoints: vec![],
+        })
+        .collect();
+    for path in storage::since(&state.data_dir, &req.range.from)? {
+        let snapshot = storage::load(&path)?;
+        if snapshot.time() > &req.range.to {
+            break;
+        }
+        for s in series.iter_mut() {
+            if let Some(count) = count(&snapshot, &s.target) {
+                s.datapoints
+                    .push((count, snapshot.time().timestamp_millis()));
+            }
+        }
+    }
+    for s in series.iter_mut().filter(|s| s.target == STALE) {
+        s.datapoints = state
+            .stale_history
+            .iter()
+            .filter(|(time, _)| time >= &req.range.from && time <= &req.range.to)
+            .map(|(time, count)| (*count, time.timestamp_millis()))
+            .collect();
+    }
+    Ok(series)
+}
+
+// count returns the value of a metric in the snapshot, none if it's unknown.
+fn count(snapshot: &Snapshot, target: &str) -> Option<usize> {
+    let mut parts = target.splitn(2, ':');
+    let metric = parts.next()?;
+    let repo = parts.next();
+    let pulls = match metric {
+        "open_issues" => false,
+        "open_pulls" => true,
+        _ => return None,
+    };
+    Some(
+        snapshot
+            .repo_issues()
+            .iter()
+            .filter(|r| repo.map_or(true, |repo| r.name() == repo))
+            .flat_map(|r| r.issues())
+            .filter(|i| i.is_pull() == pulls)
+            .count(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn query_metrics() {
+        let dir = std::env::temp_dir().join("issues-watcher-grafana-test");
+        let _ = std::fs::remove_dir_all(&dir);
+        let snapshot: Snapshot = serde_json::from_str(
+            r#"{
+                "time": "2020-05-01T00:00:00Z",
+                "repo_issues": [{
+                    "repo": {"owner": "pingcap", "repo": "parser"},
+                    "issues": [{
+                        "number": 1,
+                        "title": "issue",
+                        "created_at": "2020-04-01T00:00:00Z",
+                        "author_association": "NONE",
+                        "labels": []
+                    }]
+                }],
+                "project_issues": []
+            }"#,
+        )
+        .unwrap();
+        let mut state = State {
+            data_dir: dir.to_str().unwrap().to_owned(),
+            ..State::default()
+        };
+        storage::save(&state.data_dir, &snapshot).unwrap();
+        state.stale_history = vec![(*snapshot.time(), 3)];
+        state.snapshot = Some(snapshot);
+
+        let (_, _, body) = route("POST", &["search"], br#"{"target": "parser"}"#, &state);
+        assert_eq!(
+            body,
+            r#"["open_issues:pingcap/parser","open_pulls:pingcap/parser"]"#
+        );
+
+        let (status, _, body) = route(
+            "POST",
+            &["query"],
+            br#"{
+                "range": {"from": "2020-04-30T00:00:00Z", "to": "2020-05-02T00:00:00Z"},
+                "targets": [{"target": "open_issues:pingcap/parser"}, {"target": "stale"}]
+            }"#,
+            &state,
+        );
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(
+            body,
+            r#"[{"target":"open_issues:pingcap/parser","datapoints":[[1,1588291200000]]},{"target":"stale","datapoints":[[3,1588291200000]]}]"#
+        );
+    }
+}
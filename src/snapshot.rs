@@ -0,0 +1,252 @@
+use std::time::Duration;
+
+use tracing::info;
+
+use crate::ages;
+use crate::board;
+use crate::cache::Cache;
+use crate::checks;
+use crate::config::Config;
+use crate::conflicts;
+use crate::drafts;
+use crate::filter;
+use crate::label_stats;
+use crate::needs_info;
+#[cfg(feature = "bitbucket")]
+use crate::providers::bitbucket::Bitbucket;
+#[cfg(feature = "gitea")]
+use crate::providers::gitea::Gitea;
+use crate::providers::github::{GitHub, Snapshot, StaleIssues, StaleRule};
+#[cfg(feature = "gitlab")]
+use crate::providers::gitlab::GitLab;
+#[cfg(feature = "jira")]
+use crate::providers::jira::Jira;
+use crate::providers::IssueProvider;
+use crate::report::Report;
+use crate::storage;
+use crate::Result;
+
+/// new_github creates a GitHub client for the repos and projects of the config,
+/// `progress` draws a progress bar on stderr while fetching.
+pub async fn new_github(conf: &Config, client: &reqwest::Client, progress: bool) -> Result<GitHub> {
+    let mut github_client = GitHub::new(
+        client.clone(),
+        conf.github_token.to_owned(),
+        conf.repo_names(),
+        conf.projects.clone(),
+    )?;
+    github_client.set_request_interval(Duration::from_millis(conf.request_interval));
+    github_client.set_concurrency(conf.concurrency);
+    github_client.set_graphql(conf.graphql);
+    github_client.set_pull_branches(
+        conf.repo_rules()
+            .into_iter()
+            .map(|rule| (rule.name, rule.branches))
+            .collect(),
+    );
+    if conf.cache_ttl > 0 {
+        github_client.set_cache(Some(Cache::new(&conf.github_data, conf.cache_ttl)));
+    }
+    if progress {
+        github_client.show_progress();
+    }
+    github_client.get_projects_id().await?;
+    Ok(github_client)
+}
+
+/// extra_providers are the configured providers other than GitHub, it fails if
+/// one of them is not built in.
+// nothing is pushed when no optional provider is built in
+#[allow(unused_mut, unused_variables)]
+pub fn extra_providers(
+    conf: &Config,
+    client: &reqwest::Client,
+) -> Result<Vec<Box<dyn IssueProvider>>> {
+    if let Some(e) = conf.disabled_providers().into_iter().next() {
+        return Err(e.into());
+    }
+    let mut providers: Vec<Box<dyn IssueProvider>> = vec![];
+    #[cfg(feature = "gitlab")]
+    if !conf.gitlab_projects.is_empty() || !conf.gitlab_boards.is_empty() {
+        let mut gitlab = GitLab::new(
+            client.clone(),
+            &conf.gitlab_url,
+            conf.gitlab_token.clone(),
+            conf.gitlab_projects.clone(),
+            conf.gitlab_boards.clone(),
+        )?;
+        if conf.cache_ttl > 0 {
+            gitlab.set_cache(Some(Cache::new(&conf.github_data, conf.cache_ttl)));
+        }
+        providers.push(Box::new(gitlab));
+    }
+    #[cfg(feature = "gitea")]
+    if let Some(url) = &conf.gitea_url {
+        if !conf.gitea_repos.is_empty() {
+            let mut gitea = Gitea::new(
+                client.clone(),
+                url,
+                conf.gitea_token.clone(),
+                conf.gitea_repos.clone(),
+            )?;
+            if conf.cache_ttl > 0 {
+                gitea.set_cache(Some(Cache::new(&conf.github_data, conf.cache_ttl)));
+            }
+            providers.push(Box::new(gitea));
+        }
+    }
+    #[cfg(feature = "bitbucket")]
+    if !conf.bitbucket_repos.is_empty() {
+        providers.push(Box::new(Bitbucket::new(
+            client.clone(),
+            conf.bitbucket_username.clone(),
+            conf.bitbucket_app_password.clone(),
+            conf.bitbucket_repos.clone(),
+        )?));
+    }
+    #[cfg(feature = "jira")]
+    if let Some(url) = &conf.jira_url {
+        if !conf.jira_filters.is_empty() {
+            let filters = conf
+                .jira_filters
+                .iter()
+                .map(|f| (f.name.clone(), f.jql.clone()))
+                .collect();
+            providers.push(Box::new(Jira::new(
+                client.clone(),
+                url,
+                conf.jira_email.clone(),
+                conf.jira_token.clone(),
+                filters,
+            )));
+        }
+    }
+    Ok(providers)
+}
+
+/// providers are GitHub and the other configured providers.
+pub async fn providers(
+    conf: &Config,
+    client: &reqwest::Client,
+    progress: bool,
+) -> Result<Vec<Box<dyn IssueProvider>>> {
+    let mut providers: Vec<Box<dyn IssueProvider>> =
+        vec![Box::new(new_github(conf, client, progress).await?)];
+    providers.extend(extra_providers(conf, client)?);
+    Ok(providers)
+}
+
+/// fetch returns a snapshot of the watched repos and projects of all the
+/// providers without storing it.
+pub async fn fetch(conf: &Config, client: &reqwest::Client, progress: bool) -> Result<Snapshot> {
+    let providers = providers(conf, client, progress).await?;
+    let mut snapshot: Option<Snapshot> = None;
+    for provider in &providers {
+        let part = provider.get_snapshot().await?;
+        snapshot = Some(match snapshot {
+            Some(snapshot) => snapshot.merge(part),
+            None => part,
+        });
+    }
+    Ok(snapshot.ok_or("no provider is configured")?)
+}
+
+/// build_report fetches a snapshot from all the providers and builds the report by `report`,
+/// with the long open pull requests having merge conflicts if `conflict-days` is set,
+/// the pull requests failing their checks if `check-runs` is set and the new issues
+/// missing the sections of the issue templates if `needs-info-days` is set.
+pub async fn build_report(conf: &Config, client: &reqwest::Client) -> Result<Report> {
+    let providers = providers(conf, client, false).await?;
+    let mut report = report(&providers, conf).await?;
+    report.conflicts = conflicts::detect(conf, client, &report.snapshot).await?;
+    report.checks = checks::detect(conf, client, &report.snapshot).await?;
+    report.needs_info = needs_info::detect(conf, client, &report.snapshot).await?;
+    Ok(report)
+}
+
+/// store stores the snapshot of a delivered report unless `dry_run`, a run
+/// failing before is compared with the previous snapshot again by the next one.
+pub fn store(conf: &Config, snapshot: &Snapshot, dry_run: bool) -> Result<()> {
+    if !dry_run {
+        let path = storage::save(&conf.github_data, snapshot)?;
+        info!("snapshot is stored at {}", path.display());
+    }
+    Ok(())
+}
+
+/// report fetches a snapshot from the providers and compares it with the last
+/// stored one, the stale issues are fetched by the rules of each repo from the
+/// provider it belongs to. The snapshot is stored by `store` once the report is
+/// delivered.
+pub async fn report(providers: &[Box<dyn IssueProvider>], conf: &Config) -> Result<Report> {
+    let rules: Vec<_> = conf
+        .repo_rules()
+        .into_iter()
+        .chain(conf.extra_rules())
+        .collect();
+    let mut snapshot: Option<Snapshot> = None;
+    let mut stale = vec![];
+    for provider in providers {
+        let part = provider.get_snapshot().await?;
+        for rule in &rules {
+            let repo_issues = part.repo_issues().iter().find(|r| r.name() == rule.name);
+            if let Some(repo_issues) = repo_issues {
+                let stale_rule = StaleRule {
+                    days: rule.stale_days,
+                    ignore_labels: rule.ignore_labels.clone(),
+                    members: rule.members.clone(),
+                };
+                stale.push(provider.get_stale_issues(repo_issues, &stale_rule).await?);
+            }
+        }
+        snapshot = Some(match snapshot {
+            Some(snapshot) => snapshot.merge(part),
+            None => part,
+        });
+    }
+    let snapshot = snapshot.ok_or("no provider is configured")?;
+    // snoozed issues are left out until the snoozes expire
+    let snoozes: Vec<_> = storage::snoozes(&conf.github_data)?
+        .into_iter()
+        .filter(|s| &s.until > snapshot.time())
+        .collect();
+    for StaleIssues { repo, issues, .. } in stale.iter_mut() {
+        issues.retain(|issue| {
+            !snoozes
+                .iter()
+                .any(|s| &s.repo == repo && s.number == issue.number())
+        });
+    }
+    let (diffs, transitions) = match storage::latest(&conf.github_data, 1)?.first() {
+        Some(path) => {
+            let previous = storage::load(path)?;
+            (
+                snapshot.diff(&previous),
+                board::transitions(&previous, &snapshot),
+            )
+        }
+        None => (vec![], vec![]),
+    };
+    let labels = label_stats::build(conf, &snapshot)?;
+    let wip = board::wip_violations(&conf.wip_limits, &snapshot);
+    let drafts = conf
+        .draft_days
+        .map_or(vec![], |days| drafts::aging(&snapshot, days));
+    let ages = if conf.age_histogram {
+        ages::histogram(&snapshot)
+    } else {
+        vec![]
+    };
+    let queries = filter::queries(&conf.queries, &snapshot);
+    Ok(Report {
+        diffs,
+        stale,
+        wip,
+        transitions,
+        drafts,
+        ages,
+        labels,
+        queries,
+        ..Report::new(snapshot)
+    })
+}
@@ -0,0 +1,330 @@
+use std::{
+    collections::BTreeMap,
+    fs::{self, OpenOptions},
+    io::{Error, ErrorKind, Result, Write},
+    path::{Path, PathBuf},
+};
+
+use chrono::{DateTime, NaiveDate, TimeZone, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::providers::github::Snapshot;
+
+const SNAPSHOT_DIR: &str = "snapshots";
+const NAME_FORMAT: &str = "%Y%m%dT%H%M%SZ";
+const NOTIFICATIONS_FILE: &str = "notifications.jsonl";
+const SNOOZES_FILE: &str = "snoozes.json";
+const OPENED_FILE: &str = "opened.json";
+const PINGED_DRAFTS_FILE: &str = "pinged_drafts.json";
+const ASKED_INFO_FILE: &str = "asked_info.json";
+const COMMENTS_FILE: &str = "comments.json";
+const TEAM_MEMBERS_FILE: &str = "team_members.json";
+const ALERTS_FILE: &str = "alerts.json";
+
+/// Notification is a record of a sent or failed notification.
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct Notification {
+    pub time: DateTime<Utc>,
+    pub notifier: String,
+    /// e.g. "#channel" of Slack or "owner/repo#123" of GitHub
+    pub channel: String,
+    /// urls of the issues in the notification
+    pub issues: Vec<String>,
+    /// none if it's sent
+    pub error: Option<String>,
+}
+
+/// Snooze keeps an issue out of the stale issues of reports until the time.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Snooze {
+    pub repo: String,
+    pub number: i32,
+    pub until: DateTime<Utc>,
+}
+
+/// OpenedCounts are the issues opened in a repo each day, the baseline of
+/// spike alerts.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct OpenedCounts {
+    pub repo: String,
+    pub days: BTreeMap<NaiveDate, usize>,
+    /// the last day a spike was alerted
+    pub alerted: Option<NaiveDate>,
+}
+
+/// StoredComment is a comment of an issue or a pull request, kept for the
+/// contributor statistics of digests.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct StoredComment {
+    pub repo: String,
+    pub url: String,
+    pub login: String,
+    /// by a member of the repo
+    pub member: bool,
+    /// of a pull request rather than an issue
+    pub pull: bool,
+    pub created_at: DateTime<Utc>,
+}
+
+/// TeamMembers are the logins of the members of a GitHub team at the time
+/// they're fetched.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct TeamMembers {
+    /// like "pingcap/tidb-team"
+    pub team: String,
+    pub time: DateTime<Utc>,
+    pub logins: Vec<String>,
+}
+
+/// Snapshots are stored as JSON files in `<github-data>/snapshots`,
+/// named by the snapshot time so the names are in time order.
+pub fn save(dir: &str, snapshot: &Snapshot) -> Result<PathBuf> {
+    let dir = Path::new(dir).join(SNAPSHOT_DIR);
+    fs::create_dir_all(&dir)?;
+    let path = dir.join(format!("{}.json", snapshot.time().format(NAME_FORMAT)));
+    let contents = serde_json::to_string(snapshot).map_err(invalid_data)?;
+    fs::write(&path, contents)?;
+    Ok(path)
+}
+
+pub fn load(path: &Path) -> Result<Snapshot> {
+    let contents = fs::read_to_string(path)?;
+    serde_json::from_str(&contents[..]).map_err(invalid_data)
+}
+
+/// latest returns at most `n` stored snapshots, the newest first.
+pub fn latest(dir: &str, n: usize) -> Result<Vec<PathBuf>> {
+    let dir = Path::new(dir).join(SNAPSHOT_DIR);
+    if !dir.exists() {
+        return Ok(vec![]);
+    }
+    let mut paths = fs::read_dir(dir)?
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| path.extension().map_or(false, |ext| ext == "json"))
+        .collect::<Vec<PathBuf>>();
+    paths.sort();
+    paths.reverse();
+    paths.truncate(n);
+    Ok(paths)
+}
+
+/// since returns the stored snapshots taken at or after `time`, the oldest first.
+pub fn since(dir: &str, time: &DateTime<Utc>) -> Result<Vec<PathBuf>> {
+    let mut paths = latest(dir, usize::MAX)?;
+    paths.retain(|path| {
+        let name = path.file_stem().unwrap_or_default().to_string_lossy();
+        Utc.datetime_from_str(&name, NAME_FORMAT)
+            .map_or(false, |t| &t >= time)
+    });
+    paths.reverse();
+    Ok(paths)
+}
+
+/// record appends a notification to `<github-data>/notifications.jsonl`,
+/// one JSON object per line.
+pub fn record(dir: &str, notification: &Notification) -> Result<()> {
+    fs::create_dir_all(dir)?;
+    let mut line = serde_json::to_string(notification).map_err(invalid_data)?;
+    line.push('\n');
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(Path::new(dir).join(NOTIFICATIONS_FILE))?;
+    file.write_all(line.as_bytes())
+}
+
+/// notifications returns the recorded notifications, the oldest first.
+pub fn notifications(dir: &str) -> Result<Vec<Notification>> {
+    let path = Path::new(dir).join(NOTIFICATIONS_FILE);
+    if !path.exists() {
+        return Ok(vec![]);
+    }
+    fs::read_to_string(path)?
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| serde_json::from_str(line).map_err(invalid_data))
+        .collect()
+}
+
+/// snoozes returns the snoozes stored in `<github-data>/snoozes.json`,
+/// including the expired ones.
+pub fn snoozes(dir: &str) -> Result<Vec<Snooze>> {
+    let path = Path::new(dir).join(SNOOZES_FILE);
+    if !path.exists() {
+        return Ok(vec![]);
+    }
+    let contents = fs::read_to_string(path)?;
+    serde_json::from_str(&contents).map_err(invalid_data)
+}
+
+pub fn save_snoozes(dir: &str, snoozes: &[Snooze]) -> Result<()> {
+    fs::create_dir_all(dir)?;
+    let contents = serde_json::to_string_pretty(snoozes).map_err(invalid_data)?;
+    fs::write(Path::new(dir).join(SNOOZES_FILE), contents)
+}
+
+/// opened_counts returns the counts stored in `<github-data>/opened.json`.
+pub fn opened_counts(dir: &str) -> Result<Vec<OpenedCounts>> {
+    let path = Path::new(dir).join(OPENED_FILE);
+    if !path.exists() {
+        return Ok(vec![]);
+    }
+    let contents = fs::read_to_string(path)?;
+    serde_json::from_str(&contents).map_err(invalid_data)
+}
+
+pub fn save_opened_counts(dir: &str, counts: &[OpenedCounts]) -> Result<()> {
+    fs::create_dir_all(dir)?;
+    let contents = serde_json::to_string_pretty(counts).map_err(invalid_data)?;
+    fs::write(Path::new(dir).join(OPENED_FILE), contents)
+}
+
+/// pinged_drafts returns the urls of the draft pull requests whose authors
+/// are pinged, stored in `<github-data>/pinged_drafts.json`.
+pub fn pinged_drafts(dir: &str) -> Result<Vec<String>> {
+    let path = Path::new(dir).join(PINGED_DRAFTS_FILE);
+    if !path.exists() {
+        return Ok(vec![]);
+    }
+    let contents = fs::read_to_string(path)?;
+    serde_json::from_str(&contents).map_err(invalid_data)
+}
+
+pub fn save_pinged_drafts(dir: &str, urls: &[String]) -> Result<()> {
+    fs::create_dir_all(dir)?;
+    let contents = serde_json::to_string_pretty(urls).map_err(invalid_data)?;
+    fs::write(Path::new(dir).join(PINGED_DRAFTS_FILE), contents)
+}
+
+/// asked_info returns the urls of the issues asked for their missing sections,
+/// stored in `<github-data>/asked_info.json`.
+pub fn asked_info(dir: &str) -> Result<Vec<String>> {
+    let path = Path::new(dir).join(ASKED_INFO_FILE);
+    if !path.exists() {
+        return Ok(vec![]);
+    }
+    let contents = fs::read_to_string(path)?;
+    serde_json::from_str(&contents).map_err(invalid_data)
+}
+
+pub fn save_asked_info(dir: &str, urls: &[String]) -> Result<()> {
+    fs::create_dir_all(dir)?;
+    let contents = serde_json::to_string_pretty(urls).map_err(invalid_data)?;
+    fs::write(Path::new(dir).join(ASKED_INFO_FILE), contents)
+}
+
+/// comments returns the comments stored in `<github-data>/comments.json`.
+pub fn comments(dir: &str) -> Result<Vec<StoredComment>> {
+    let path = Path::new(dir).join(COMMENTS_FILE);
+    if !path.exists() {
+        return Ok(vec![]);
+    }
+    let contents = fs::read_to_string(path)?;
+    serde_json::from_str(&contents).map_err(invalid_data)
+}
+
+pub fn save_comments(dir: &str, comments: &[StoredComment]) -> Result<()> {
+    fs::create_dir_all(dir)?;
+    let contents = serde_json::to_string(comments).map_err(invalid_data)?;
+    fs::write(Path::new(dir).join(COMMENTS_FILE), contents)
+}
+
+/// team_members returns the members of the teams fetched before, stored in
+/// `<github-data>/team_members.json`.
+pub fn team_members(dir: &str) -> Result<Vec<TeamMembers>> {
+    let path = Path::new(dir).join(TEAM_MEMBERS_FILE);
+    if !path.exists() {
+        return Ok(vec![]);
+    }
+    let contents = fs::read_to_string(path)?;
+    serde_json::from_str(&contents).map_err(invalid_data)
+}
+
+pub fn save_team_members(dir: &str, teams: &[TeamMembers]) -> Result<()> {
+    fs::create_dir_all(dir)?;
+    let contents = serde_json::to_string_pretty(teams).map_err(invalid_data)?;
+    fs::write(Path::new(dir).join(TEAM_MEMBERS_FILE), contents)
+}
+
+/// alerts returns the aliases of the open Opsgenie alerts, like
+/// "pingcap/tidb#123", stored in `<github-data>/alerts.json`.
+pub fn alerts(dir: &str) -> Result<Vec<String>> {
+    let path = Path::new(dir).join(ALERTS_FILE);
+    if !path.exists() {
+        return Ok(vec![]);
+    }
+    let contents = fs::read_to_string(path)?;
+    serde_json::from_str(&contents).map_err(invalid_data)
+}
+
+pub fn save_alerts(dir: &str, aliases: &[String]) -> Result<()> {
+    fs::create_dir_all(dir)?;
+    let contents = serde_json::to_string_pretty(aliases).map_err(invalid_data)?;
+    fs::write(Path::new(dir).join(ALERTS_FILE), contents)
+}
+
+fn invalid_data(err: serde_json::Error) -> Error {
+    Error::new(ErrorKind::InvalidData, err.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn save_and_load() {
+        let dir = std::env::temp_dir().join("issues-watcher-storage-test");
+        let _ = fs::remove_dir_all(&dir);
+        let dir = dir.to_str().unwrap();
+        assert!(latest(dir, 2).unwrap().is_empty());
+
+        for time in &["2020-05-01T00:00:00Z", "2020-05-02T00:00:00Z"] {
+            let snapshot: Snapshot = serde_json::from_str(&format!(
+                r#"{{"time": "{}", "repo_issues": [], "project_issues": []}}"#,
+                time
+            ))
+            .unwrap();
+            save(dir, &snapshot).unwrap();
+        }
+        let paths = latest(dir, 1).unwrap();
+        assert_eq!(paths.len(), 1);
+        assert!(paths[0].ends_with("20200502T000000Z.json"));
+        let snapshot = load(&paths[0]).unwrap();
+        assert_eq!(snapshot.time().to_rfc3339(), "2020-05-02T00:00:00+00:00");
+
+        let paths = since(dir, &Utc.ymd(2020, 5, 1).and_hms(12, 0, 0)).unwrap();
+        assert_eq!(paths.len(), 1);
+        let paths = since(dir, &Utc.ymd(2020, 4, 1).and_hms(0, 0, 0)).unwrap();
+        assert!(paths[0].ends_with("20200501T000000Z.json"));
+
+        assert!(notifications(dir).unwrap().is_empty());
+        let notification = Notification {
+            time: Utc.ymd(2020, 5, 2).and_hms(0, 0, 0),
+            notifier: "slack".to_owned(),
+            channel: "#tidb-dev".to_owned(),
+            issues: vec!["https://github.com/pingcap/parser/issues/1".to_owned()],
+            error: Some("channel_not_found".to_owned()),
+        };
+        record(dir, &notification).unwrap();
+        record(dir, &notification).unwrap();
+        assert_eq!(notifications(dir).unwrap().len(), 2);
+        assert_eq!(notifications(dir).unwrap()[1], notification);
+
+        assert!(snoozes(dir).unwrap().is_empty());
+        let snooze = Snooze {
+            repo: "pingcap/parser".to_owned(),
+            number: 1,
+            until: Utc.ymd(2020, 5, 9).and_hms(0, 0, 0),
+        };
+        save_snoozes(dir, &[snooze.clone()]).unwrap();
+        assert_eq!(snoozes(dir).unwrap(), vec![snooze]);
+
+        let mut counts = OpenedCounts {
+            repo: "pingcap/parser".to_owned(),
+            ..OpenedCounts::default()
+        };
+        counts.days.insert(NaiveDate::from_ymd(2020, 5, 2), 3);
+        save_opened_counts(dir, &[counts.clone()]).unwrap();
+        assert_eq!(opened_counts(dir).unwrap(), vec![counts]);
+    }
+}
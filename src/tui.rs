@@ -0,0 +1,302 @@
+use std::io::{self, Stdout};
+
+use crossterm::{
+    event::{self, Event, KeyCode, KeyEventKind},
+    execute,
+    terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
+};
+use ratatui::{
+    backend::CrosstermBackend,
+    layout::{Constraint, Direction, Layout},
+    style::{Color, Modifier, Style},
+    widgets::{Block, Borders, List, ListItem, ListState, Paragraph},
+    Frame, Terminal,
+};
+
+use issues_watcher::providers::github::Snapshot;
+
+const HELP: &str = "↑/↓ move  ←/→ switch  enter open in browser  q quit";
+
+// Group is a repo or a project column on the left side of the board,
+// its issues or cards are listed on the right side.
+struct Group {
+    name: String,
+    items: Vec<Item>,
+}
+
+struct Item {
+    title: String,
+    url: Option<String>,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Focus {
+    Groups,
+    Items,
+}
+
+struct App {
+    groups: Vec<Group>,
+    group: ListState,
+    item: ListState,
+    focus: Focus,
+    status: String,
+}
+
+// run shows the snapshot as a board until `q` is pressed.
+pub fn run(snapshot: &Snapshot) -> io::Result<()> {
+    enable_raw_mode()?;
+    let mut stdout = io::stdout();
+    execute!(stdout, EnterAlternateScreen)?;
+    let mut terminal = Terminal::new(CrosstermBackend::new(stdout))?;
+    let result = App::new(groups(snapshot)).run(&mut terminal);
+    disable_raw_mode()?;
+    execute!(terminal.backend_mut(), LeaveAlternateScreen)?;
+    terminal.show_cursor()?;
+    result
+}
+
+fn groups(snapshot: &Snapshot) -> Vec<Group> {
+    let mut groups: Vec<Group> = snapshot
+        .repo_issues()
+        .iter()
+        .map(|repo| Group {
+            name: repo.name(),
+            items: repo
+                .issues()
+                .iter()
+                .map(|issue| Item {
+                    title: format!("#{} {}", issue.number(), issue.title()),
+                    url: Some(issue.to_string()),
+                })
+                .collect(),
+        })
+        .collect();
+    for project in snapshot.project_issues() {
+        for column in project.columns() {
+            groups.push(Group {
+                name: format!("{} / {}", project.name(), column.name()),
+                items: column
+                    .cards()
+                    .iter()
+                    .map(|card| Item {
+                        title: card.title(),
+                        url: card.html_url(),
+                    })
+                    .collect(),
+            });
+        }
+    }
+    groups
+}
+
+impl App {
+    fn new(groups: Vec<Group>) -> Self {
+        let mut app = App {
+            groups,
+            group: ListState::default(),
+            item: ListState::default(),
+            focus: Focus::Groups,
+            status: HELP.to_owned(),
+        };
+        if !app.groups.is_empty() {
+            app.group.select(Some(0));
+            app.reset_item();
+        }
+        app
+    }
+
+    fn run(&mut self, terminal: &mut Terminal<CrosstermBackend<Stdout>>) -> io::Result<()> {
+        loop {
+            terminal.draw(|f| self.draw(f))?;
+            if let Event::Key(key) = event::read()? {
+                if key.kind != KeyEventKind::Press {
+                    continue;
+                }
+                match key.code {
+                    KeyCode::Char('q') | KeyCode::Esc => return Ok(()),
+                    KeyCode::Tab => {
+                        self.focus = match self.focus {
+                            Focus::Groups => Focus::Items,
+                            Focus::Items => Focus::Groups,
+                        }
+                    }
+                    KeyCode::Left | KeyCode::Char('h') => self.focus = Focus::Groups,
+                    KeyCode::Right | KeyCode::Char('l') => self.focus = Focus::Items,
+                    KeyCode::Down | KeyCode::Char('j') => self.step(1),
+                    KeyCode::Up | KeyCode::Char('k') => self.step(-1),
+                    KeyCode::Enter | KeyCode::Char('o') => self.open(),
+                    _ => {}
+                }
+            }
+        }
+    }
+
+    fn items(&self) -> &[Item] {
+        self.group
+            .selected()
+            .and_then(|i| self.groups.get(i))
+            .map_or(&[][..], |g| &g.items[..])
+    }
+
+    fn reset_item(&mut self) {
+        let selected = if self.items().is_empty() {
+            None
+        } else {
+            Some(0)
+        };
+        self.item.select(selected);
+    }
+
+    // step moves the selection of the focused list, wrapping at both ends.
+    fn step(&mut self, delta: isize) {
+        let (len, state) = match self.focus {
+            Focus::Groups => (self.groups.len(), &mut self.group),
+            Focus::Items => (
+                self.group
+                    .selected()
+                    .and_then(|i| self.groups.get(i))
+                    .map_or(0, |g| g.items.len()),
+                &mut self.item,
+            ),
+        };
+        if len == 0 {
+            return;
+        }
+        let current = state.selected().unwrap_or(0) as isize;
+        let next = (current + delta).rem_euclid(len as isize) as usize;
+        state.select(Some(next));
+        if self.focus == Focus::Groups {
+            self.reset_item();
+        }
+    }
+
+    fn open(&mut self) {
+        let url = self
+            .item
+            .selected()
+            .and_then(|i| self.items().get(i))
+            .and_then(|item| item.url.clone());
+        self.status = match url {
+            Some(url) => match webbrowser::open(&url) {
+                Ok(_) => format!("opened {}", url),
+                Err(e) => format!("failed to open {}: {}", url, e),
+            },
+            None => "nothing to open".to_owned(),
+        };
+    }
+
+    fn border(&self, focus: Focus) -> Style {
+        if self.focus == focus {
+            Style::default().fg(Color::Yellow)
+        } else {
+            Style::default()
+        }
+    }
+
+    fn draw(&mut self, f: &mut Frame) {
+        let rows = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Min(1), Constraint::Length(1)])
+            .split(f.size());
+        let columns = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Percentage(30), Constraint::Percentage(70)])
+            .split(rows[0]);
+        let highlight = Style::default().add_modifier(Modifier::REVERSED);
+
+        let groups: Vec<ListItem> = self
+            .groups
+            .iter()
+            .map(|g| ListItem::new(format!("{} ({})", g.name, g.items.len())))
+            .collect();
+        let groups = List::new(groups)
+            .block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .title("Repos / Columns")
+                    .border_style(self.border(Focus::Groups)),
+            )
+            .highlight_style(highlight);
+
+        let items: Vec<ListItem> = self
+            .items()
+            .iter()
+            .map(|item| ListItem::new(item.title.clone()))
+            .collect();
+        let items = List::new(items)
+            .block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .title("Issues / Cards")
+                    .border_style(self.border(Focus::Items)),
+            )
+            .highlight_style(highlight);
+
+        f.render_stateful_widget(groups, columns[0], &mut self.group);
+        f.render_stateful_widget(items, columns[1], &mut self.item);
+        f.render_widget(Paragraph::new(self.status.as_str()), rows[1]);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn navigate_board() {
+        let snapshot: Snapshot = serde_json::from_str(
+            r#"{
+                "time": "2020-05-01T00:00:00Z",
+                "repo_issues": [{
+                    "repo": {"owner": "pingcap", "repo": "parser"},
+                    "issues": [{
+                        "number": 1,
+                        "title": "issue",
+                        "owner": "pingcap",
+                        "repo": "parser",
+                        "created_at": "2020-04-01T00:00:00Z",
+                        "author_association": "NONE",
+                        "labels": []
+                    }]
+                }],
+                "project_issues": [{
+                    "project": {"owner": "pingcap", "repo": "parser", "number": 1, "id": 1},
+                    "columns": [{
+                        "id": 1,
+                        "name": "To do",
+                        "cards": [
+                            {"note": "write docs\nmore lines"},
+                            {"content_url": "https://api.github.com/repos/pingcap/parser/issues/2"}
+                        ]
+                    }]
+                }]
+            }"#,
+        )
+        .unwrap();
+        let mut app = App::new(groups(&snapshot));
+        assert_eq!(app.groups.len(), 2);
+        assert_eq!(app.items()[0].title, "#1 issue");
+        assert_eq!(
+            app.items()[0].url.as_deref(),
+            Some("https://github.com/pingcap/parser/issues/1")
+        );
+
+        app.step(1);
+        assert_eq!(app.groups[1].name, "pingcap/parser project 1 / To do");
+        assert_eq!(app.items()[0].title, "write docs");
+        assert_eq!(app.items()[1].title, "pingcap/parser/issues/2");
+        assert_eq!(
+            app.items()[1].url.as_deref(),
+            Some("https://github.com/pingcap/parser/issues/2")
+        );
+
+        app.focus = Focus::Items;
+        app.step(-1);
+        assert_eq!(app.item.selected(), Some(1));
+        app.focus = Focus::Groups;
+        app.step(1);
+        assert_eq!(app.group.selected(), Some(0));
+        assert_eq!(app.item.selected(), Some(0));
+    }
+}
@@ -0,0 +1,160 @@
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+
+use chrono::{DateTime, TimeZone, Utc};
+use reqwest::header::HeaderMap;
+use tracing::{info, warn};
+
+// USAGE sums up the API calls of all the GitHub clients of a run.
+static USAGE: Mutex<ApiUsage> = Mutex::new(ApiUsage::new());
+
+/// RateLimit is what's left of a GitHub rate limit, like "core" or "graphql".
+#[derive(Debug, Clone, PartialEq)]
+pub struct RateLimit {
+    pub remaining: u64,
+    pub limit: u64,
+    pub reset: DateTime<Utc>,
+}
+
+/// ApiUsage is the GitHub API calls made by repo, and the rate limits left
+/// after the last calls. Cached responses aren't calls.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ApiUsage {
+    /// the calls without a repo are counted as "graphql" or "other"
+    pub calls: BTreeMap<String, usize>,
+    /// by the resource of the rate limit
+    pub limits: BTreeMap<String, RateLimit>,
+}
+
+impl ApiUsage {
+    const fn new() -> Self {
+        ApiUsage {
+            calls: BTreeMap::new(),
+            limits: BTreeMap::new(),
+        }
+    }
+
+    pub fn total(&self) -> usize {
+        self.calls.values().sum()
+    }
+
+    /// top returns at most `n` repos making the most calls, the most first.
+    pub fn top(&self, n: usize) -> Vec<(&str, usize)> {
+        let mut calls: Vec<(&str, usize)> = self.calls.iter().map(|(k, v)| (&k[..], *v)).collect();
+        calls.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+        calls.truncate(n);
+        calls
+    }
+
+    /// low checks whether less than a tenth of any rate limit is left.
+    pub fn low(&self) -> bool {
+        self.limits.values().any(|l| l.remaining * 10 < l.limit)
+    }
+
+    /// summary tells the calls, the rate limits left and the `top` repos by
+    /// calls.
+    pub fn summary(&self, top: usize) -> String {
+        let mut summary = format!("{} GitHub API calls", self.total());
+        for (resource, limit) in &self.limits {
+            summary.push_str(&format!(
+                ", {}/{} {} left until {}",
+                limit.remaining,
+                limit.limit,
+                resource,
+                limit.reset.format("%H:%M UTC")
+            ));
+        }
+        let top: Vec<String> = self
+            .top(top)
+            .into_iter()
+            .map(|(repo, calls)| format!("{} {}", repo, calls))
+            .collect();
+        if !top.is_empty() {
+            summary.push_str(&format!(", most by {}", top.join(", ")));
+        }
+        summary
+    }
+}
+
+/// record counts a call of the repo, or "graphql" or "other", with the rate
+/// limit in the headers of its response.
+pub fn record(repo: String, headers: &HeaderMap) {
+    let header = |key: &str| {
+        headers
+            .get(key)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.parse::<u64>().ok())
+    };
+    let mut usage = USAGE.lock().unwrap();
+    *usage.calls.entry(repo).or_insert(0) += 1;
+    if let (Some(remaining), Some(limit), Some(reset)) = (
+        header("X-RateLimit-Remaining"),
+        header("X-RateLimit-Limit"),
+        header("X-RateLimit-Reset"),
+    ) {
+        let resource = headers
+            .get("X-RateLimit-Resource")
+            .and_then(|v| v.to_str().ok())
+            .unwrap_or("core")
+            .to_owned();
+        let reset = Utc.timestamp(reset as i64, 0);
+        usage.limits.insert(
+            resource,
+            RateLimit {
+                remaining,
+                limit,
+                reset,
+            },
+        );
+    }
+}
+
+/// take returns the usage since the last take, and counts from zero again.
+pub fn take() -> ApiUsage {
+    std::mem::replace(&mut *USAGE.lock().unwrap(), ApiUsage::new())
+}
+
+/// log logs the summary of the usage since the last one, as a warning if a
+/// rate limit is running low. Nothing is logged without calls.
+pub fn log(top: usize) {
+    let usage = take();
+    if usage.total() == 0 {
+        return;
+    }
+    if usage.low() {
+        warn!("{}, the rate limit is running low", usage.summary(top));
+    } else {
+        info!("{}", usage.summary(top));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn summarize_usage() {
+        let mut usage = ApiUsage::new();
+        usage.calls.insert("pingcap/parser".to_owned(), 3);
+        usage.calls.insert("pingcap/tidb".to_owned(), 12);
+        usage.calls.insert("graphql".to_owned(), 3);
+        usage.limits.insert(
+            "core".to_owned(),
+            RateLimit {
+                remaining: 4000,
+                limit: 5000,
+                reset: Utc.ymd(2020, 5, 1).and_hms(12, 30, 0),
+            },
+        );
+        assert_eq!(usage.total(), 18);
+        assert_eq!(usage.top(2), vec![("pingcap/tidb", 12), ("graphql", 3)]);
+        assert_eq!(
+            usage.summary(2),
+            "18 GitHub API calls, 4000/5000 core left until 12:30 UTC, \
+             most by pingcap/tidb 12, graphql 3"
+        );
+        assert!(!usage.low());
+        usage.limits.get_mut("core").unwrap().remaining = 100;
+        assert!(usage.low());
+    }
+}
@@ -0,0 +1,202 @@
+// These tests run the GitHub provider against a local mock server, the
+// responses are recorded from the GitHub API in `tests/fixtures/github`.
+
+use chrono::{TimeZone, Utc};
+use futures::{StreamExt, TryStreamExt};
+use httpmock::{Method::GET, MockServer};
+use serde_json::Value;
+
+use issues_watcher::providers::{
+    github::{GitHub, StaleRule},
+    Error, IssueProvider,
+};
+
+const ISSUES: &str = include_str!("fixtures/github/issues.json");
+const COMMENTS: &str = include_str!("fixtures/github/comments.json");
+
+fn new_github(server: &MockServer, repos: &[&str]) -> GitHub {
+    GitHub::builder("test-token".to_owned())
+        .repos(repos.iter().map(|r| r.to_string()).collect())
+        .base_url(&server.base_url())
+        .user_agent("issues-watcher-test")
+        .build()
+        .unwrap()
+}
+
+// full_page is a page of 100 issues, so the client asks for the next one.
+fn full_page() -> String {
+    let issues: Vec<Value> = serde_json::from_str(ISSUES).unwrap();
+    let page: Vec<Value> = (1..=100)
+        .map(|number| {
+            let mut issue = issues[0].clone();
+            issue["number"] = number.into();
+            issue
+        })
+        .collect();
+    serde_json::to_string(&page).unwrap()
+}
+
+#[tokio::test]
+async fn list_issues_by_pages() {
+    let server = MockServer::start_async().await;
+    let first = server
+        .mock_async(|when, then| {
+            when.method(GET)
+                .path("/repos/pingcap/parser/issues")
+                .query_param("page", "1")
+                .header("authorization", "token test-token")
+                .header("user-agent", "issues-watcher-test");
+            then.status(200)
+                .header("content-type", "application/json")
+                .body(full_page());
+        })
+        .await;
+    let second = server
+        .mock_async(|when, then| {
+            when.method(GET)
+                .path("/repos/pingcap/parser/issues")
+                .query_param("page", "2");
+            then.status(200)
+                .header("content-type", "application/json")
+                .body(ISSUES);
+        })
+        .await;
+
+    let github = new_github(&server, &["pingcap/parser"]);
+    let repos = github.list_issues().await.unwrap();
+    first.assert_async().await;
+    second.assert_async().await;
+    assert_eq!(repos.len(), 1);
+    assert_eq!(repos[0].name(), "pingcap/parser");
+    let issues = repos[0].issues();
+    assert_eq!(issues.len(), 102);
+    let pull = &issues[101];
+    assert_eq!(pull.number(), 845);
+    assert!(pull.is_pull());
+    assert_eq!(pull.assignees().collect::<Vec<_>>(), vec!["you06"]);
+    assert_eq!(pull.milestone(), Some("v4.0.0"));
+}
+
+#[tokio::test]
+async fn stream_issues() {
+    let server = MockServer::start_async().await;
+    server
+        .mock_async(|when, then| {
+            when.method(GET).path("/repos/pingcap/parser/issues");
+            then.status(200)
+                .header("content-type", "application/json")
+                .body(ISSUES);
+        })
+        .await;
+
+    let github = new_github(&server, &[]);
+    let numbers: Vec<i32> = github
+        .issues_stream("pingcap/parser")
+        .map_ok(|issue| issue.number())
+        .try_collect()
+        .await
+        .unwrap();
+    assert_eq!(numbers, vec![843, 845]);
+
+    let mut stream = Box::pin(github.issues_stream("pingcap"));
+    assert!(stream.next().await.unwrap().is_err());
+    assert!(stream.next().await.is_none());
+}
+
+#[tokio::test]
+async fn skip_missing_repos() {
+    let server = MockServer::start_async().await;
+    server
+        .mock_async(|when, then| {
+            when.method(GET).path("/repos/pingcap/private/issues");
+            then.status(404)
+                .header("x-oauth-scopes", "read:org")
+                .body(r#"{"message": "Not Found"}"#);
+        })
+        .await;
+    server
+        .mock_async(|when, then| {
+            when.method(GET).path("/repos/pingcap/parser/issues");
+            then.status(200)
+                .header("content-type", "application/json")
+                .body(ISSUES);
+        })
+        .await;
+
+    let github = new_github(&server, &["pingcap/private", "pingcap/parser"]);
+    let repos = github.list_issues().await.unwrap();
+    assert_eq!(repos.len(), 1);
+    assert_eq!(repos[0].name(), "pingcap/parser");
+    assert_eq!(repos[0].issues().len(), 2);
+}
+
+#[tokio::test]
+async fn rate_limited() {
+    let server = MockServer::start_async().await;
+    server
+        .mock_async(|when, then| {
+            when.method(GET).path("/repos/pingcap/parser/issues");
+            then.status(403)
+                .header("x-ratelimit-remaining", "0")
+                .header("x-ratelimit-reset", "1588000000")
+                .body(r#"{"message": "API rate limit exceeded"}"#);
+        })
+        .await;
+
+    let github = new_github(&server, &["pingcap/parser"]);
+    match github.list_issues().await {
+        Err(Error::RateLimited { reset }) => assert_eq!(reset, Utc.timestamp(1588000000, 0)),
+        other => panic!("expect rate limited, got {:?}", other.map(|r| r.len())),
+    }
+}
+
+#[tokio::test]
+async fn unauthorized() {
+    let server = MockServer::start_async().await;
+    server
+        .mock_async(|when, then| {
+            when.method(GET).path("/user");
+            then.status(401).body(r#"{"message": "Bad credentials"}"#);
+        })
+        .await;
+
+    let github = new_github(&server, &[]);
+    assert!(matches!(
+        github.get_user_result().await,
+        Err(Error::Unauthorized)
+    ));
+}
+
+#[tokio::test]
+async fn stale_issues_by_comments() {
+    let server = MockServer::start_async().await;
+    server
+        .mock_async(|when, then| {
+            when.method(GET).path("/repos/pingcap/parser/issues");
+            then.status(200)
+                .header("content-type", "application/json")
+                .body(ISSUES);
+        })
+        .await;
+    let comments = server
+        .mock_async(|when, then| {
+            when.method(GET)
+                .path("/repos/pingcap/parser/issues/843/comments");
+            then.status(200)
+                .header("content-type", "application/json")
+                .body(COMMENTS);
+        })
+        .await;
+
+    let github = new_github(&server, &["pingcap/parser"]);
+    let repos = github.list_issues().await.unwrap();
+    let rule = StaleRule {
+        days: 3,
+        ignore_labels: vec![],
+        members: vec![],
+    };
+    // a member replied to the issue, and pull requests are not stale
+    let stale = github.get_stale_issues(&repos[0], &rule).await.unwrap();
+    assert!(stale.issues.is_empty());
+    assert_eq!(comments.hits_async().await, 1);
+}
@@ -0,0 +1,25 @@
+// These tests run the WeCom robot against a local mock server.
+
+use httpmock::{Method::POST, MockServer};
+
+use issues_watcher::providers::wecom::WeCom;
+
+#[tokio::test]
+async fn hide_key_of_failed_webhook() {
+    let server = MockServer::start_async().await;
+    let send = server
+        .mock_async(|when, then| {
+            when.method(POST)
+                .path("/cgi-bin/webhook/send")
+                .query_param("key", "secret-key");
+            then.status(500).body("internal error");
+        })
+        .await;
+    let webhook = format!("{}/cgi-bin/webhook/send?key=secret-key", server.base_url());
+    let wecom = WeCom::new(reqwest::Client::new(), &webhook).unwrap();
+    let err = wecom.send_markdown("2 stale issues").await.unwrap_err();
+    send.assert_async().await;
+    let err = err.to_string();
+    assert!(err.contains("500"), "{}", err);
+    assert!(!err.contains("secret-key"), "{}", err);
+}